@@ -1,14 +1,22 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use std::sync::atomic::{AtomicUsize, Ordering};
+mod common;
+
+use common::{measure, print_summary, Tally};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::cell::Cell;
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Mutex;
 
+const REPETITIONS: [usize; 4] = [100, 1_000, 10_000, 100_000];
+// how many increments are accumulated locally before a flush to the shared counter
+const BATCH_SIZES: [usize; 4] = [1, 8, 64, 512];
+
 fn benchmark_batched_increment(repetitions: usize, mut increment: usize, counter: &AtomicUsize) {
     let mut batch = 0;
     for _ in 0..repetitions {
         // avoiding compiler optimizations
         // E.g. go to https://rust.godbolt.org/z/7he65h
         // and try to comment the line #4
-        increment = increment ^ 1;
+        increment ^= 1;
         batch += increment;
     }
     counter.fetch_add(batch, Ordering::Relaxed);
@@ -16,7 +24,7 @@ fn benchmark_batched_increment(repetitions: usize, mut increment: usize, counter
 
 fn benchmark_atomic_increment(repetitions: usize, mut increment: usize, counter: &AtomicUsize) {
     for _ in 0..repetitions {
-        increment = increment ^ 1;
+        increment ^= 1;
         counter.fetch_add(increment, Ordering::Relaxed);
     }
 }
@@ -27,68 +35,295 @@ fn benchmark_mutex_increment(
     counter_mutex: &Mutex<usize>,
 ) {
     for _ in 0..repetitions {
-        increment = increment ^ 1;
+        increment ^= 1;
         let mut lock = counter_mutex.lock().expect("Never fails in this bench");
         *lock += increment;
     }
 }
 
+/// Same as `benchmark_batched_increment`, but flushes the batch every `batch_size` repetitions.
+fn benchmark_periodic_flush_increment(
+    repetitions: usize,
+    mut increment: usize,
+    batch_size: usize,
+    counter: &AtomicUsize,
+) {
+    let mut batch = 0;
+    for i in 1..=repetitions {
+        increment ^= 1;
+        batch += increment;
+        if i % batch_size == 0 {
+            counter.fetch_add(batch, Ordering::Relaxed);
+            batch = 0;
+        }
+    }
+    counter.fetch_add(batch, Ordering::Relaxed);
+}
+
+fn benchmark_ordered_increment(
+    repetitions: usize,
+    mut increment: usize,
+    counter: &AtomicUsize,
+    ordering: Ordering,
+) {
+    for _ in 0..repetitions {
+        increment ^= 1;
+        counter.fetch_add(increment, ordering);
+    }
+}
+
+fn benchmark_fenced_increment(repetitions: usize, mut increment: usize, counter: &AtomicUsize) {
+    for _ in 0..repetitions {
+        increment ^= 1;
+        counter.fetch_add(increment, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+    }
+}
+
+fn baseline_plain_increment(repetitions: usize, mut increment: u64, counter: &mut u64) {
+    for _ in 0..repetitions {
+        increment ^= 1;
+        *counter += increment;
+    }
+}
+
+fn baseline_cell_increment(repetitions: usize, mut increment: u64, counter: &Cell<u64>) {
+    for _ in 0..repetitions {
+        increment ^= 1;
+        counter.set(counter.get() + increment);
+    }
+}
+
+fn baseline_atomic_u64_increment(repetitions: usize, mut increment: u64, counter: &AtomicU64) {
+    for _ in 0..repetitions {
+        increment ^= 1;
+        counter.fetch_add(increment, Ordering::Relaxed);
+    }
+}
+
+/// What a call of an increment adds to the counter.
+fn per_call(repetitions: usize, mut increment: usize) -> usize {
+    let mut sum = 0;
+    for _ in 0..repetitions {
+        increment ^= 1;
+        sum += increment;
+    }
+    sum
+}
+
 fn benchmark_increment(c: &mut Criterion) {
-    let counter_batched: AtomicUsize = AtomicUsize::new(0);
-    let counter_atomic: AtomicUsize = AtomicUsize::new(0);
-    let counter_mutex: Mutex<usize> = Mutex::new(0);
+    let increment = 1;
+
+    let mut group = c.benchmark_group("Increment");
+    for &repetitions in REPETITIONS.iter() {
+        let per_call = per_call(repetitions, increment);
+        let tally_batched = Tally::new("Batched");
+        let tally_atomic = Tally::new("Atomic");
+        let tally_mutex = Tally::new("Mutex");
+        let tallies_flush: Vec<Tally> = BATCH_SIZES
+            .iter()
+            .map(|batch_size| Tally::new(format!("Flush {}", batch_size)))
+            .collect();
+
+        group.throughput(Throughput::Elements(repetitions as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Batched", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_batched,
+                        iters,
+                        AtomicUsize::new(0),
+                        |counter| benchmark_batched_increment(repetitions, increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Atomic", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_atomic,
+                        iters,
+                        AtomicUsize::new(0),
+                        |counter| benchmark_atomic_increment(repetitions, increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Mutex", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_mutex,
+                        iters,
+                        Mutex::new(0),
+                        |counter| benchmark_mutex_increment(repetitions, increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        for (&batch_size, tally) in BATCH_SIZES.iter().zip(tallies_flush.iter()) {
+            group.bench_with_input(
+                BenchmarkId::new(format!("Periodic Flush {}", batch_size), repetitions),
+                &repetitions,
+                |b, &repetitions| {
+                    b.iter_custom(|iters| {
+                        measure(
+                            tally,
+                            iters,
+                            AtomicUsize::new(0),
+                            |counter| {
+                                benchmark_periodic_flush_increment(
+                                    repetitions,
+                                    increment,
+                                    batch_size,
+                                    counter,
+                                )
+                            },
+                            per_call,
+                        )
+                    })
+                },
+            );
+        }
 
+        let mut tallies = vec![tally_batched, tally_atomic, tally_mutex];
+        tallies.extend(tallies_flush);
+        print_summary(&format!("Repetitions: {}", repetitions), &tallies);
+    }
+    group.finish();
+}
+
+/// The cost of an increment without any contention, to compare the contended benches with.
+fn benchmark_baseline(c: &mut Criterion) {
+    let increment = 1;
+
+    let mut group = c.benchmark_group("Baseline Single-threaded");
+    for &repetitions in REPETITIONS.iter() {
+        // the final values are asserted by `measure`, i.e. if the XOR trick (or the compiler)
+        // broke the increments, the bench would fail instead of reporting a meaningless time
+        let per_call = per_call(repetitions, increment);
+        let tallies = [
+            Tally::new("u64"),
+            Tally::new("Cell<u64>"),
+            Tally::new("AtomicU64"),
+            Tally::new("AtomicUsize"),
+        ];
+
+        group.throughput(Throughput::Elements(repetitions as u64));
+        group.bench_with_input(
+            BenchmarkId::new("u64", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tallies[0],
+                        iters,
+                        0u64,
+                        |counter| {
+                            baseline_plain_increment(repetitions, increment as u64, counter);
+                            // otherwise nothing forces the sum out of a register
+                            black_box(counter);
+                        },
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Cell<u64>", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tallies[1],
+                        iters,
+                        Cell::new(0),
+                        |counter| {
+                            baseline_cell_increment(repetitions, increment as u64, counter);
+                            black_box(counter);
+                        },
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("AtomicU64", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tallies[2],
+                        iters,
+                        AtomicU64::new(0),
+                        |counter| {
+                            baseline_atomic_u64_increment(repetitions, increment as u64, counter)
+                        },
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("AtomicUsize", repetitions),
+            &repetitions,
+            |b, &repetitions| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tallies[3],
+                        iters,
+                        AtomicUsize::new(0),
+                        |counter| benchmark_atomic_increment(repetitions, increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+
+        print_summary(&format!("Baseline, repetitions: {}", repetitions), &tallies);
+    }
+    group.finish();
+}
+
+fn benchmark_memory_ordering(c: &mut Criterion) {
     let increment = 1;
     let repetitions = 1000;
 
-    c.bench_function("Increment Batched", |b| {
-        b.iter(|| {
-            black_box(benchmark_batched_increment(
-                repetitions,
-                increment,
-                &counter_batched,
-            ))
-        })
-    });
-    c.bench_function("Increment Atomic", |b| {
-        b.iter(|| {
-            black_box(benchmark_atomic_increment(
-                repetitions,
-                increment,
-                &counter_atomic,
-            ))
-        })
-    });
-    c.bench_function("Increment Mutex", |b| {
-        b.iter(|| {
-            black_box(benchmark_mutex_increment(
-                repetitions,
-                increment,
-                &counter_mutex,
-            ))
-        })
+    let mut group = c.benchmark_group("Memory Ordering Single-threaded");
+    for &(name, ordering) in [
+        ("Relaxed", Ordering::Relaxed),
+        ("AcqRel", Ordering::AcqRel),
+        ("SeqCst", Ordering::SeqCst),
+    ]
+    .iter()
+    {
+        let counter = AtomicUsize::new(0);
+        group.bench_function(name, |b| {
+            b.iter(|| benchmark_ordered_increment(repetitions, increment, &counter, ordering))
+        });
+    }
+    let counter = AtomicUsize::new(0);
+    group.bench_function("Relaxed + fence(SeqCst)", |b| {
+        b.iter(|| benchmark_fenced_increment(repetitions, increment, &counter))
     });
-
-    let batched = counter_batched.load(Ordering::Relaxed);
-    let atomic = counter_atomic.load(Ordering::Relaxed);
-    let mutex = counter_mutex.lock().unwrap();
-    println!(
-        "Batched  {:12} operations, {:.6}",
-        batched / repetitions,
-        batched as f64 / batched as f64
-    );
-    println!(
-        "Atomic   {:12} operations, {:.6}",
-        atomic / repetitions,
-        atomic as f64 / batched as f64
-    );
-    println!(
-        "Mutex    {:12} operations, {:.6}",
-        *mutex / repetitions,
-        *mutex as f64 / batched as f64
-    );
+    group.finish();
 }
 
-criterion_group!(benches, benchmark_increment,);
+criterion_group!(
+    benches,
+    benchmark_baseline,
+    benchmark_increment,
+    benchmark_memory_ordering
+);
 
 criterion_main!(benches);
@@ -0,0 +1,118 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+use tokio::task::{yield_now, JoinHandle};
+
+// increments per task
+const INCREMENTS: usize = 1000;
+const TASKS: [usize; 3] = [4, 64, 1024];
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+/// Spawns `tasks` copies of `task` and waits for all of them.
+async fn run_tasks<F, T>(tasks: usize, task: F)
+where
+    F: Fn() -> T,
+    T: std::future::Future<Output = ()> + Send + 'static,
+{
+    let handles: Vec<JoinHandle<()>> = (0..tasks).map(|_| tokio::spawn(task())).collect();
+    for handle in handles {
+        handle.await.expect("Never fails in this bench");
+    }
+}
+
+async fn atomic_increments(tasks: usize) {
+    let counter = Arc::new(AtomicUsize::new(0));
+    run_tasks(tasks, || {
+        let counter = counter.clone();
+        async move {
+            for _ in 0..INCREMENTS {
+                counter.fetch_add(black_box(1), Ordering::Relaxed);
+            }
+        }
+    })
+    .await;
+    assert_eq!(counter.load(Ordering::Relaxed), tasks * INCREMENTS);
+}
+
+async fn std_mutex_increments(tasks: usize, yielding: bool) {
+    let counter = Arc::new(Mutex::new(0));
+    run_tasks(tasks, || {
+        let counter = counter.clone();
+        async move {
+            for _ in 0..INCREMENTS {
+                *counter.lock().expect("Never fails in this bench") += black_box(1);
+                // the guard is already dropped, a std guard can't be held across an `.await`
+                if yielding {
+                    // `yield_now` is wrongly marked as `must_use` in tokio 0.2
+                    let _ = yield_now().await;
+                }
+            }
+        }
+    })
+    .await;
+    assert_eq!(
+        *counter.lock().expect("Never fails in this bench"),
+        tasks * INCREMENTS
+    );
+}
+
+async fn tokio_mutex_increments(tasks: usize, yielding: bool) {
+    let counter = Arc::new(tokio::sync::Mutex::new(0));
+    run_tasks(tasks, || {
+        let counter = counter.clone();
+        async move {
+            for _ in 0..INCREMENTS {
+                let mut lock = counter.lock().await;
+                *lock += black_box(1);
+                // holding the lock across an `.await`, other tasks wait for it
+                if yielding {
+                    // `yield_now` is wrongly marked as `must_use` in tokio 0.2
+                    let _ = yield_now().await;
+                }
+            }
+        }
+    })
+    .await;
+    assert_eq!(*counter.lock().await, tasks * INCREMENTS);
+}
+
+fn benchmark_async_mutex(c: &mut Criterion) {
+    let mut runtime = runtime();
+    let mut group = c.benchmark_group("Async Mutex");
+    for &tasks in TASKS.iter() {
+        group.throughput(Throughput::Elements((tasks * INCREMENTS) as u64));
+        group.bench_with_input(BenchmarkId::new("Atomic", tasks), &tasks, |b, &tasks| {
+            b.iter(|| runtime.block_on(atomic_increments(tasks)))
+        });
+        group.bench_with_input(BenchmarkId::new("std Mutex", tasks), &tasks, |b, &tasks| {
+            b.iter(|| runtime.block_on(std_mutex_increments(tasks, false)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("tokio Mutex", tasks),
+            &tasks,
+            |b, &tasks| b.iter(|| runtime.block_on(tokio_mutex_increments(tasks, false))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("std Mutex, .await after unlock", tasks),
+            &tasks,
+            |b, &tasks| b.iter(|| runtime.block_on(std_mutex_increments(tasks, true))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("tokio Mutex, .await under lock", tasks),
+            &tasks,
+            |b, &tasks| b.iter(|| runtime.block_on(tokio_mutex_increments(tasks, true))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_async_mutex);
+
+criterion_main!(benches);
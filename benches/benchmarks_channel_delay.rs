@@ -0,0 +1,203 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use hdrhistogram::Histogram;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::task::yield_now;
+use tokio::time::interval;
+
+// the producer sends this many messages every millisecond, i.e. 10k per second
+const PER_TICK: usize = 10;
+const TICK: Duration = Duration::from_millis(1);
+// the tasks competing with the producer and the consumer for the runtime
+const BUSY_TASKS: [usize; 2] = [0, 8];
+// a busy task burns this much CPU between the yields
+const BUSY_SLICE: Duration = Duration::from_micros(50);
+const WORKER_THREADS: usize = 4;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Tokio(usize),
+    AsyncChannel(usize),
+}
+
+impl Kind {
+    fn name(self) -> String {
+        match self {
+            Kind::Tokio(capacity) => format!("tokio mpsc({})", capacity),
+            Kind::AsyncChannel(capacity) => format!("async_channel({})", capacity),
+        }
+    }
+
+    fn channel(self) -> (Sender, Receiver) {
+        match self {
+            Kind::Tokio(capacity) => {
+                let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+                (Sender::Tokio(sender), Receiver::Tokio(receiver))
+            }
+            Kind::AsyncChannel(capacity) => {
+                let (sender, receiver) = async_channel::bounded(capacity);
+                (
+                    Sender::AsyncChannel(sender),
+                    Receiver::AsyncChannel(receiver),
+                )
+            }
+        }
+    }
+}
+
+/// The messages are the timestamps of sending.
+enum Sender {
+    Tokio(tokio::sync::mpsc::Sender<Instant>),
+    AsyncChannel(async_channel::Sender<Instant>),
+}
+
+enum Receiver {
+    Tokio(tokio::sync::mpsc::Receiver<Instant>),
+    AsyncChannel(async_channel::Receiver<Instant>),
+}
+
+impl Sender {
+    async fn send(&mut self, message: Instant) {
+        match self {
+            Sender::Tokio(sender) => sender
+                .send(message)
+                .await
+                .expect("Never fails in this bench"),
+            Sender::AsyncChannel(sender) => sender
+                .send(message)
+                .await
+                .expect("Never fails in this bench"),
+        }
+    }
+}
+
+impl Receiver {
+    async fn recv(&mut self) -> Option<Instant> {
+        match self {
+            Receiver::Tokio(receiver) => receiver.recv().await,
+            Receiver::AsyncChannel(receiver) => receiver.recv().await.ok(),
+        }
+    }
+}
+
+async fn busy_task(stopped: Arc<AtomicBool>) {
+    while !stopped.load(Ordering::Relaxed) {
+        let start = Instant::now();
+        while start.elapsed() < BUSY_SLICE {}
+        // tokio 0.2 doesn't mark it as `must_use`, but later versions do
+        let _ = yield_now().await;
+    }
+}
+
+/// Sends `messages` at the fixed rate, `PER_TICK` every `TICK`.
+async fn produce(mut sender: Sender, messages: usize) {
+    let mut ticks = interval(TICK);
+    let mut sent = 0;
+    while sent < messages {
+        ticks.tick().await;
+        for _ in 0..PER_TICK.min(messages - sent) {
+            sender.send(Instant::now()).await;
+            sent += 1;
+        }
+    }
+}
+
+/// Receives `messages` and records the delay of each (µs).
+/// Returns the sum of the delays.
+async fn consume(mut receiver: Receiver, messages: usize) -> (Duration, Histogram<u64>) {
+    let mut delays = delay_histogram();
+    let mut total = Duration::from_secs(0);
+    for _ in 0..messages {
+        let sent = receiver.recv().await.expect("Never fails in this bench");
+        let delay = sent.elapsed();
+        total += delay;
+        delays.saturating_record(delay.as_micros() as u64);
+    }
+    // the producer is done, so it's the last message
+    assert_eq!(receiver.recv().await, None);
+    (total, delays)
+}
+
+/// The producer and the consumer are tasks of the runtime, as the dispatch of the async simulator.
+async fn measure_delays(
+    kind: Kind,
+    busy_tasks: usize,
+    messages: usize,
+    delays: &mut Histogram<u64>,
+) -> Duration {
+    let stopped = Arc::new(AtomicBool::new(false));
+    for _ in 0..busy_tasks {
+        tokio::spawn(busy_task(stopped.clone()));
+    }
+    let (sender, receiver) = kind.channel();
+    let consumer = tokio::spawn(consume(receiver, messages));
+    tokio::spawn(produce(sender, messages))
+        .await
+        .expect("Never fails in this bench");
+    let (total, consumed) = consumer.await.expect("Never fails in this bench");
+    stopped.store(true, Ordering::Relaxed);
+    delays.add(consumed).expect("The same bounds");
+    total
+}
+
+/// In µs, up to a minute.
+fn delay_histogram() -> Histogram<u64> {
+    Histogram::new_with_max(60_000_000, 3).expect("Valid histogram bounds")
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .core_threads(WORKER_THREADS)
+        .enable_all()
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+/// The time of an iteration is the mean delay of a message, i.e. not the wall time,
+/// which is bound by the rate of the producer.
+fn benchmark_channel_delay(c: &mut Criterion) {
+    let kinds = [
+        Kind::Tokio(1),
+        Kind::Tokio(64),
+        Kind::Tokio(1024),
+        Kind::AsyncChannel(1024),
+    ];
+    let mut runtime = runtime();
+    for &busy_tasks in BUSY_TASKS.iter() {
+        let mut group = c.benchmark_group(format!("Channel Delay {} Busy Tasks", busy_tasks));
+        group.sample_size(10);
+        println!("Busy tasks: {}", busy_tasks);
+        for &kind in kinds.iter() {
+            // over all the measurements of the variant
+            let delays = RefCell::new(delay_histogram());
+            group.bench_function(kind.name(), |b| {
+                b.iter_custom(|iters| {
+                    let mut delays = delays.borrow_mut();
+                    runtime.block_on(measure_delays(
+                        kind,
+                        busy_tasks,
+                        iters as usize,
+                        &mut delays,
+                    ))
+                })
+            });
+            let delays = delays.borrow();
+            println!(
+                "{:20} p50 {:8}µs, p99 {:8}µs, max {:8}µs",
+                kind.name(),
+                delays.value_at_quantile(0.5),
+                delays.value_at_quantile(0.99),
+                delays.max()
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, benchmark_channel_delay);
+
+criterion_main!(benches);
@@ -0,0 +1,345 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+// sent in every iteration of the throughput benchmark, over all the producers
+const MESSAGES: usize = 10_000;
+// `None` is unbounded
+const CAPACITIES: [Option<usize>; 3] = [Some(1), Some(64), None];
+const PRODUCERS: [usize; 2] = [1, 4];
+
+/// A blocking MPSC channel of `usize`.
+trait Channel {
+    type Sender: Clone + Send;
+    type Receiver: Send;
+
+    fn with_capacity(capacity: Option<usize>) -> (Self::Sender, Self::Receiver);
+    fn send(sender: &Self::Sender, message: usize);
+    /// `None` once all the senders are gone.
+    fn recv(receiver: &Self::Receiver) -> Option<usize>;
+}
+
+struct Crossbeam;
+
+impl Channel for Crossbeam {
+    type Sender = crossbeam::channel::Sender<usize>;
+    type Receiver = crossbeam::channel::Receiver<usize>;
+
+    fn with_capacity(capacity: Option<usize>) -> (Self::Sender, Self::Receiver) {
+        match capacity {
+            Some(capacity) => crossbeam::channel::bounded(capacity),
+            None => crossbeam::channel::unbounded(),
+        }
+    }
+
+    fn send(sender: &Self::Sender, message: usize) {
+        sender.send(message).expect("Never fails in this bench");
+    }
+
+    fn recv(receiver: &Self::Receiver) -> Option<usize> {
+        receiver.recv().ok()
+    }
+}
+
+struct Std;
+
+#[derive(Clone)]
+enum StdSender {
+    Bounded(mpsc::SyncSender<usize>),
+    Unbounded(mpsc::Sender<usize>),
+}
+
+impl Channel for Std {
+    type Sender = StdSender;
+    type Receiver = mpsc::Receiver<usize>;
+
+    fn with_capacity(capacity: Option<usize>) -> (Self::Sender, Self::Receiver) {
+        match capacity {
+            Some(capacity) => {
+                let (sender, receiver) = mpsc::sync_channel(capacity);
+                (StdSender::Bounded(sender), receiver)
+            }
+            None => {
+                let (sender, receiver) = mpsc::channel();
+                (StdSender::Unbounded(sender), receiver)
+            }
+        }
+    }
+
+    fn send(sender: &Self::Sender, message: usize) {
+        match sender {
+            StdSender::Bounded(sender) => sender.send(message),
+            StdSender::Unbounded(sender) => sender.send(message),
+        }
+        .expect("Never fails in this bench");
+    }
+
+    fn recv(receiver: &Self::Receiver) -> Option<usize> {
+        receiver.recv().ok()
+    }
+}
+
+struct Flume;
+
+impl Channel for Flume {
+    type Sender = flume::Sender<usize>;
+    type Receiver = flume::Receiver<usize>;
+
+    fn with_capacity(capacity: Option<usize>) -> (Self::Sender, Self::Receiver) {
+        match capacity {
+            Some(capacity) => flume::bounded(capacity),
+            None => flume::unbounded(),
+        }
+    }
+
+    fn send(sender: &Self::Sender, message: usize) {
+        sender.send(message).expect("Never fails in this bench");
+    }
+
+    fn recv(receiver: &Self::Receiver) -> Option<usize> {
+        receiver.recv().ok()
+    }
+}
+
+enum TokioSender {
+    Bounded(tokio::sync::mpsc::Sender<usize>),
+    Unbounded(tokio::sync::mpsc::UnboundedSender<usize>),
+}
+
+enum TokioReceiver {
+    Bounded(tokio::sync::mpsc::Receiver<usize>),
+    Unbounded(tokio::sync::mpsc::UnboundedReceiver<usize>),
+}
+
+impl TokioSender {
+    async fn send(&mut self, message: usize) {
+        match self {
+            TokioSender::Bounded(sender) => sender
+                .send(message)
+                .await
+                .expect("Never fails in this bench"),
+            TokioSender::Unbounded(sender) => {
+                sender.send(message).expect("Never fails in this bench")
+            }
+        }
+    }
+}
+
+impl Clone for TokioSender {
+    fn clone(&self) -> Self {
+        match self {
+            TokioSender::Bounded(sender) => TokioSender::Bounded(sender.clone()),
+            TokioSender::Unbounded(sender) => TokioSender::Unbounded(sender.clone()),
+        }
+    }
+}
+
+impl TokioReceiver {
+    async fn recv(&mut self) -> Option<usize> {
+        match self {
+            TokioReceiver::Bounded(receiver) => receiver.recv().await,
+            TokioReceiver::Unbounded(receiver) => receiver.recv().await,
+        }
+    }
+}
+
+fn tokio_channel(capacity: Option<usize>) -> (TokioSender, TokioReceiver) {
+    match capacity {
+        Some(capacity) => {
+            let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+            (
+                TokioSender::Bounded(sender),
+                TokioReceiver::Bounded(receiver),
+            )
+        }
+        None => {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            (
+                TokioSender::Unbounded(sender),
+                TokioReceiver::Unbounded(receiver),
+            )
+        }
+    }
+}
+
+/// A small runtime, so the producers and the consumer may run in parallel.
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .core_threads(2)
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+fn label(capacity: Option<usize>) -> String {
+    match capacity {
+        Some(capacity) => format!("capacity {}", capacity),
+        None => "unbounded".to_string(),
+    }
+}
+
+/// Every producer sends `0..MESSAGES / producers`.
+fn verify(received: usize, sum: usize, producers: usize) {
+    let per_producer = MESSAGES / producers;
+    assert_eq!(received, producers * per_producer);
+    assert_eq!(sum, producers * per_producer * (per_producer - 1) / 2);
+}
+
+fn transfer<C: Channel>(capacity: Option<usize>, producers: usize) {
+    let (sender, receiver) = C::with_capacity(capacity);
+    let (received, sum) = crossbeam::scope(|scope| {
+        for _ in 0..producers {
+            let sender = sender.clone();
+            scope.spawn(move |_| {
+                for i in 0..MESSAGES / producers {
+                    C::send(&sender, i);
+                }
+            });
+        }
+        // the consumer stops once all the producers are done
+        drop(sender);
+        let (mut received, mut sum) = (0, 0);
+        while let Some(message) = C::recv(&receiver) {
+            received += 1;
+            sum += message;
+        }
+        (received, sum)
+    })
+    .expect("Never fails in this bench");
+    verify(received, sum, producers);
+}
+
+async fn tokio_transfer(capacity: Option<usize>, producers: usize) {
+    let (sender, mut receiver) = tokio_channel(capacity);
+    for _ in 0..producers {
+        let mut sender = sender.clone();
+        tokio::spawn(async move {
+            for i in 0..MESSAGES / producers {
+                sender.send(i).await;
+            }
+        });
+    }
+    drop(sender);
+    let (mut received, mut sum) = (0, 0);
+    while let Some(message) = receiver.recv().await {
+        received += 1;
+        sum += message;
+    }
+    verify(received, sum, producers);
+}
+
+/// `iters` messages sent to an echo thread and back, one at a time.
+fn round_trip<C: Channel>(capacity: Option<usize>, iters: u64) -> Duration {
+    let (ping, pings) = C::with_capacity(capacity);
+    let (pong, pongs) = C::with_capacity(capacity);
+    crossbeam::scope(|scope| {
+        scope.spawn(move |_| {
+            while let Some(message) = C::recv(&pings) {
+                C::send(&pong, message);
+            }
+        });
+        let start = Instant::now();
+        for i in 0..iters as usize {
+            C::send(&ping, i);
+            assert_eq!(C::recv(&pongs), Some(i));
+        }
+        let elapsed = start.elapsed();
+        // stops the echo thread
+        drop(ping);
+        elapsed
+    })
+    .expect("Never fails in this bench")
+}
+
+async fn tokio_round_trip(capacity: Option<usize>, iters: u64) -> Duration {
+    let (mut ping, mut pings) = tokio_channel(capacity);
+    let (mut pong, mut pongs) = tokio_channel(capacity);
+    tokio::spawn(async move {
+        while let Some(message) = pings.recv().await {
+            pong.send(message).await;
+        }
+    });
+    let start = Instant::now();
+    for i in 0..iters as usize {
+        ping.send(i).await;
+        assert_eq!(pongs.recv().await, Some(i));
+    }
+    start.elapsed()
+}
+
+fn bench_throughput<C: Channel>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("Channel Throughput");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    for &capacity in CAPACITIES.iter() {
+        for &producers in PRODUCERS.iter() {
+            group.bench_with_input(
+                BenchmarkId::new(
+                    format!("{} {}", name, label(capacity)),
+                    format!("{} producers", producers),
+                ),
+                &producers,
+                |b, &producers| b.iter(|| transfer::<C>(capacity, producers)),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_round_trip<C: Channel>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("Channel Round-trip");
+    for &capacity in CAPACITIES.iter() {
+        group.bench_with_input(
+            BenchmarkId::new(name, label(capacity)),
+            &capacity,
+            |b, &capacity| b.iter_custom(|iters| round_trip::<C>(capacity, iters)),
+        );
+    }
+    group.finish();
+}
+
+fn benchmark_throughput(c: &mut Criterion) {
+    bench_throughput::<Crossbeam>(c, "crossbeam");
+    bench_throughput::<Std>(c, "std mpsc");
+    bench_throughput::<Flume>(c, "flume");
+
+    let mut runtime = runtime();
+    let mut group = c.benchmark_group("Channel Throughput");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    for &capacity in CAPACITIES.iter() {
+        for &producers in PRODUCERS.iter() {
+            group.bench_with_input(
+                BenchmarkId::new(
+                    format!("tokio mpsc {}", label(capacity)),
+                    format!("{} producers", producers),
+                ),
+                &producers,
+                |b, &producers| b.iter(|| runtime.block_on(tokio_transfer(capacity, producers))),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn benchmark_round_trip(c: &mut Criterion) {
+    bench_round_trip::<Crossbeam>(c, "crossbeam");
+    bench_round_trip::<Std>(c, "std mpsc");
+    bench_round_trip::<Flume>(c, "flume");
+
+    let mut runtime = runtime();
+    let mut group = c.benchmark_group("Channel Round-trip");
+    for &capacity in CAPACITIES.iter() {
+        group.bench_with_input(
+            BenchmarkId::new("tokio mpsc", label(capacity)),
+            &capacity,
+            |b, &capacity| {
+                b.iter_custom(|iters| runtime.block_on(tokio_round_trip(capacity, iters)))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_throughput, benchmark_round_trip);
+
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+mod common;
+
+use common::run_contended;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// timestamps per iteration and thread
+const CALLS: usize = 1000;
+const THREADS: usize = 8;
+// how often the coarse clock is updated, i.e. its resolution
+const RESOLUTION: Duration = Duration::from_millis(1);
+
+/// The time since the start, cached in an atomic by a background thread every `RESOLUTION`.
+struct CoarseClock {
+    start: Instant,
+    nanos: Arc<AtomicU64>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl CoarseClock {
+    fn start() -> Self {
+        let nanos = Arc::new(AtomicU64::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let start = Instant::now();
+        {
+            let (nanos, stopped) = (nanos.clone(), stopped.clone());
+            thread::spawn(move || {
+                while !stopped.load(Ordering::Relaxed) {
+                    nanos.store(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    thread::sleep(RESOLUTION);
+                }
+            });
+        }
+        Self {
+            start,
+            nanos,
+            stopped,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.nanos.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for CoarseClock {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn std_instant() {
+    for _ in 0..CALLS {
+        black_box(Instant::now());
+    }
+}
+
+fn quanta_clock(clock: &quanta::Clock) {
+    for _ in 0..CALLS {
+        black_box(clock.now());
+    }
+}
+
+fn coarse_clock(clock: &CoarseClock) {
+    for _ in 0..CALLS {
+        black_box(clock.now());
+    }
+}
+
+/// How far the coarse clock lags behind `Instant::now`, i.e. the error of a timestamp.
+fn print_coarse_lag(clock: &CoarseClock) {
+    let start = Instant::now();
+    let mut max_lag = Duration::from_secs(0);
+    let mut total_lag = Duration::from_secs(0);
+    let mut samples = 0;
+    while start.elapsed() < Duration::from_millis(200) {
+        let coarse = Duration::from_nanos(clock.now());
+        let lag = clock.start.elapsed() - coarse;
+        max_lag = max_lag.max(lag);
+        total_lag += lag;
+        samples += 1;
+    }
+    println!(
+        "Coarse clock lag: {:?} mean, {:?} max (resolution {:?})",
+        total_lag / samples,
+        max_lag,
+        RESOLUTION
+    );
+}
+
+fn benchmark_clock(c: &mut Criterion) {
+    let quanta = quanta::Clock::new();
+    let coarse = CoarseClock::start();
+    // the first update
+    thread::sleep(RESOLUTION * 2);
+    print_coarse_lag(&coarse);
+
+    let mut group = c.benchmark_group("Timestamp");
+    group.throughput(Throughput::Elements(CALLS as u64));
+    group.bench_function("Instant::now", |b| b.iter(std_instant));
+    group.bench_function("quanta::Clock::now", |b| b.iter(|| quanta_clock(&quanta)));
+    group.bench_function("Coarse", |b| b.iter(|| coarse_clock(&coarse)));
+    group.finish();
+
+    let mut group = c.benchmark_group(format!("Timestamp {} Threads", THREADS));
+    group.throughput(Throughput::Elements((THREADS * CALLS) as u64));
+    group.bench_function("Instant::now", |b| {
+        b.iter_custom(|iters| run_contended(THREADS, iters, |_| std_instant()))
+    });
+    group.bench_function("quanta::Clock::now", |b| {
+        b.iter_custom(|iters| run_contended(THREADS, iters, |_| quanta_clock(&quanta)))
+    });
+    group.bench_function("Coarse", |b| {
+        b.iter_custom(|iters| run_contended(THREADS, iters, |_| coarse_clock(&coarse)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_clock);
+
+criterion_main!(benches);
@@ -0,0 +1,239 @@
+mod common;
+
+use arc_swap::ArcSwap;
+use common::{print_summary, run_contended, thread_counts, Tally};
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
+};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// reads per iteration and reader thread
+const REPETITIONS: usize = 1000;
+// the writer updates the config this often, while the readers run
+const WRITE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// E.g. a rate or a feature flag, changed in the middle of a run.
+struct Config {
+    rate: usize,
+    // always `2 * rate`, so a torn read is detected
+    burst: usize,
+    name: String,
+}
+
+impl Config {
+    fn new(version: usize) -> Self {
+        Self {
+            rate: version,
+            burst: 2 * version,
+            name: format!("config-{}", version),
+        }
+    }
+}
+
+/// A config shared between the workers, read on every request and updated rarely.
+trait SharedConfig: Sync {
+    fn new(config: Config) -> Self;
+    fn read<R>(&self, f: impl FnOnce(&Config) -> R) -> R;
+    fn update(&self, config: Config);
+}
+
+impl SharedConfig for RwLock<Config> {
+    fn new(config: Config) -> Self {
+        RwLock::new(config)
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&Config) -> R) -> R {
+        f(&self.read().expect("Never fails in this bench"))
+    }
+
+    fn update(&self, config: Config) {
+        *self.write().expect("Never fails in this bench") = config;
+    }
+}
+
+impl SharedConfig for ArcSwap<Config> {
+    fn new(config: Config) -> Self {
+        ArcSwap::from_pointee(config)
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&Config) -> R) -> R {
+        f(&self.load())
+    }
+
+    fn update(&self, config: Config) {
+        self.store(Arc::new(config));
+    }
+}
+
+/// Clone-on-read: the lock is held only to clone the `Arc`.
+impl SharedConfig for Mutex<Arc<Config>> {
+    fn new(config: Config) -> Self {
+        Mutex::new(Arc::new(config))
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&Config) -> R) -> R {
+        let config = self.lock().expect("Never fails in this bench").clone();
+        f(&config)
+    }
+
+    fn update(&self, config: Config) {
+        *self.lock().expect("Never fails in this bench") = Arc::new(config);
+    }
+}
+
+/// The latencies of the updates of a variant over all its measurements.
+#[derive(Clone, Copy, Default)]
+struct Updates {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+impl Updates {
+    fn add(&mut self, latency: Duration) {
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    fn merge(&mut self, other: Updates) {
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    fn mean(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+}
+
+fn read_config<C: SharedConfig>(config: &C) {
+    for _ in 0..REPETITIONS {
+        let rate = config.read(|config| {
+            assert_eq!(
+                config.burst,
+                2 * config.rate,
+                "Torn read of {}",
+                config.name
+            );
+            config.rate
+        });
+        black_box(rate);
+    }
+}
+
+/// Updates the config every `WRITE_INTERVAL` until `done`.
+fn write_config<C: SharedConfig>(config: &C, done: &AtomicBool) -> Updates {
+    let mut updates = Updates::default();
+    let mut version = 0;
+    while !done.load(Ordering::Relaxed) {
+        thread::sleep(WRITE_INTERVAL);
+        version += 1;
+        // allocated outside the measured time, the same for every variant
+        let new_config = Config::new(version);
+        let start = Instant::now();
+        config.update(new_config);
+        updates.add(start.elapsed());
+    }
+    updates
+}
+
+fn bench_config<C: SharedConfig>(
+    group: &mut BenchmarkGroup<WallTime>,
+    readers: usize,
+    tally: &Tally,
+    updates: &Cell<Updates>,
+) {
+    group.bench_with_input(
+        BenchmarkId::new(tally.name(), readers),
+        &readers,
+        |b, &readers| {
+            b.iter_custom(|iters| {
+                let config = C::new(Config::new(0));
+                let done = AtomicBool::new(false);
+                let (elapsed, written) = crossbeam::scope(|scope| {
+                    let writer = scope.spawn(|_| write_config(&config, &done));
+                    let elapsed = run_contended(readers, iters, |_| read_config(&config));
+                    done.store(true, Ordering::Relaxed);
+                    (elapsed, writer.join().expect("Never fails in this bench"))
+                })
+                .expect("Never fails in this bench");
+                tally.add(iters * (readers * REPETITIONS) as u64, elapsed);
+                let mut total = updates.get();
+                total.merge(written);
+                updates.set(total);
+                elapsed
+            })
+        },
+    );
+}
+
+/// Names the variant with the fastest readers, to build the live reconfiguration on.
+fn print_conclusion(readers: usize, tallies: &[Tally], updates: &[Cell<Updates>]) {
+    let by_rate =
+        |a: &(&Tally, _), b: &(&Tally, _)| a.0.rate().partial_cmp(&b.0.rate()).expect("Not NaN");
+    let variants = || tallies.iter().zip(updates.iter().map(Cell::get));
+    let (fastest, slowest) = match (variants().max_by(by_rate), variants().min_by(by_rate)) {
+        (Some(fastest), Some(slowest)) => (fastest, slowest),
+        _ => return,
+    };
+    let (fastest, fastest_updates) = fastest;
+    let slowest = slowest.0;
+    println!(
+        "Conclusion: with {} readers, {} reads {:.1} times faster than {}, and its updates take {:?} on average ({:?} at most).",
+        readers,
+        fastest.name(),
+        fastest.rate() / slowest.rate(),
+        slowest.name(),
+        fastest_updates.mean(),
+        fastest_updates.max
+    );
+    println!(
+        "A config, which is read on every request, should be shared as {}.",
+        fastest.name()
+    );
+}
+
+fn benchmark_config(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Shared Config");
+    let thread_counts = thread_counts();
+    for &readers in thread_counts.iter() {
+        let tallies = [
+            Tally::new("RwLock<Config>"),
+            Tally::new("ArcSwap<Config>"),
+            Tally::new("Mutex<Arc<Config>>"),
+        ];
+        let updates: Vec<Cell<Updates>> = tallies.iter().map(|_| Cell::default()).collect();
+
+        group.throughput(Throughput::Elements((readers * REPETITIONS) as u64));
+        bench_config::<RwLock<Config>>(&mut group, readers, &tallies[0], &updates[0]);
+        bench_config::<ArcSwap<Config>>(&mut group, readers, &tallies[1], &updates[1]);
+        bench_config::<Mutex<Arc<Config>>>(&mut group, readers, &tallies[2], &updates[2]);
+
+        print_summary(&format!("Readers: {}", readers), &tallies);
+        for (tally, updates) in tallies.iter().zip(updates.iter()) {
+            let updates = updates.get();
+            println!(
+                "{:16} {:14} updates, {:?} mean, {:?} max",
+                tally.name(),
+                updates.count,
+                updates.mean(),
+                updates.max
+            );
+        }
+        // the most readers is the case the choice matters for
+        if Some(&readers) == thread_counts.last() {
+            print_conclusion(readers, &tallies, &updates);
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_config);
+
+criterion_main!(benches);
@@ -0,0 +1,319 @@
+mod common;
+
+use common::{run_contended, thread_counts};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::utils::{Backoff, CachePadded};
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const REPETITIONS: usize = 1000;
+// the sweep flushes every 1, 2, 4, ..., 4096 increments, on this many threads
+const SWEEP_BATCH_SIZES: usize = 13;
+const SWEEP_THREADS: usize = 4;
+// a multiple of the largest batch size
+const SWEEP_REPETITIONS: usize = 8192;
+
+type AtomicIncrement = fn(&AtomicUsize);
+
+fn batched_increment(counter: &AtomicUsize) {
+    let mut batch = 0;
+    for _ in 0..REPETITIONS {
+        // avoiding compiler optimizations
+        batch += black_box(1);
+    }
+    counter.fetch_add(batch, Ordering::Relaxed);
+}
+
+fn atomic_increment(counter: &AtomicUsize) {
+    for _ in 0..REPETITIONS {
+        counter.fetch_add(black_box(1), Ordering::Relaxed);
+    }
+}
+
+fn ordered_increment(counter: &AtomicUsize, ordering: Ordering) {
+    for _ in 0..REPETITIONS {
+        counter.fetch_add(black_box(1), ordering);
+    }
+}
+
+fn fenced_increment(counter: &AtomicUsize) {
+    for _ in 0..REPETITIONS {
+        counter.fetch_add(black_box(1), Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+    }
+}
+
+fn cas_increment(counter: &AtomicUsize) {
+    for _ in 0..REPETITIONS {
+        let mut current = counter.load(Ordering::Relaxed);
+        while let Err(actual) = counter.compare_exchange_weak(
+            current,
+            current + black_box(1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            current = actual;
+        }
+    }
+}
+
+/// Same as `cas_increment`, but spins for exponentially (up to a bound) longer after every failure.
+fn cas_backoff_increment(counter: &AtomicUsize) {
+    for _ in 0..REPETITIONS {
+        let backoff = Backoff::new();
+        let mut current = counter.load(Ordering::Relaxed);
+        while let Err(actual) = counter.compare_exchange_weak(
+            current,
+            current + black_box(1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            current = actual;
+            backoff.spin();
+        }
+    }
+}
+
+fn mutex_increment(counter_mutex: &Mutex<usize>) {
+    for _ in 0..REPETITIONS {
+        let mut lock = counter_mutex.lock().expect("Never fails in this bench");
+        *lock += black_box(1);
+    }
+}
+
+/// A counter per thread, each on its own cache line, summed on read.
+struct ShardedCounter {
+    shards: Vec<CachePadded<AtomicUsize>>,
+}
+
+impl ShardedCounter {
+    fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards).map(|_| Default::default()).collect(),
+        }
+    }
+
+    fn add(&self, shard: usize, value: usize) {
+        self.shards[shard].fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn sum(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+fn sharded_increment(counter: &ShardedCounter, shard: usize) {
+    for _ in 0..REPETITIONS {
+        counter.add(shard, black_box(1));
+    }
+}
+
+/// Accumulates locally and flushes to the shared counter every `batch_size` increments.
+fn periodic_flush_increment(counter: &AtomicUsize, batch_size: usize) {
+    let mut batch = 0;
+    for i in 1..=SWEEP_REPETITIONS {
+        batch += black_box(1);
+        if i % batch_size == 0 {
+            counter.fetch_add(batch, Ordering::Relaxed);
+            batch = 0;
+        }
+    }
+    counter.fetch_add(batch, Ordering::Relaxed);
+}
+
+fn benchmark_contended_increment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Contended Increment");
+    for threads in thread_counts() {
+        group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Batched", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed = run_contended(threads, iters, |_| batched_increment(&counter));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * REPETITIONS
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Atomic", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed = run_contended(threads, iters, |_| atomic_increment(&counter));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * REPETITIONS
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Mutex", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = Mutex::new(0);
+                    let elapsed = run_contended(threads, iters, |_| mutex_increment(&counter));
+                    assert_eq!(
+                        *counter.lock().expect("Never fails in this bench"),
+                        threads * iters as usize * REPETITIONS
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Sharded", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = ShardedCounter::new(threads);
+                    let elapsed =
+                        run_contended(threads, iters, |thread| sharded_increment(&counter, thread));
+                    assert_eq!(counter.sum(), threads * iters as usize * REPETITIONS);
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn benchmark_compare_exchange(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Compare-and-swap Increment");
+    let increments: [(&str, AtomicIncrement); 3] = [
+        ("fetch_add", atomic_increment),
+        ("CAS loop", cas_increment),
+        ("CAS loop with backoff", cas_backoff_increment),
+    ];
+    for threads in thread_counts() {
+        group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+        for &(name, increment) in increments.iter() {
+            group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed = run_contended(threads, iters, |_| increment(&counter));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * REPETITIONS
+                    );
+                    elapsed
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// A group per thread count, so every report compares the orderings under the same contention.
+fn benchmark_memory_ordering(c: &mut Criterion) {
+    for threads in thread_counts() {
+        let mut group = c.benchmark_group(format!("Memory Ordering {} Threads", threads));
+        group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+        for &(name, ordering) in [
+            ("Relaxed", Ordering::Relaxed),
+            ("AcqRel", Ordering::AcqRel),
+            ("SeqCst", Ordering::SeqCst),
+        ]
+        .iter()
+        {
+            group.bench_function(name, |b| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed =
+                        run_contended(threads, iters, |_| ordered_increment(&counter, ordering));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * REPETITIONS
+                    );
+                    elapsed
+                })
+            });
+        }
+        group.bench_function("Relaxed + fence(SeqCst)", |b| {
+            b.iter_custom(|iters| {
+                let counter = AtomicUsize::new(0);
+                let elapsed = run_contended(threads, iters, |_| fenced_increment(&counter));
+                assert_eq!(
+                    counter.load(Ordering::Relaxed),
+                    threads * iters as usize * REPETITIONS
+                );
+                elapsed
+            })
+        });
+        group.finish();
+    }
+}
+
+/// The other side of sharding: a read sums as many shards as there are threads.
+fn benchmark_counter_read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Counter Read");
+    for threads in thread_counts() {
+        let atomic = AtomicUsize::new(threads);
+        group.bench_with_input(BenchmarkId::new("Atomic", threads), &threads, |b, _| {
+            b.iter(|| atomic.load(Ordering::Relaxed))
+        });
+        let mutex = Mutex::new(threads);
+        group.bench_with_input(BenchmarkId::new("Mutex", threads), &threads, |b, _| {
+            b.iter(|| *mutex.lock().expect("Never fails in this bench"))
+        });
+        let sharded = ShardedCounter::new(threads);
+        (0..threads).for_each(|shard| sharded.add(shard, 1));
+        group.bench_with_input(BenchmarkId::new("Sharded", threads), &threads, |b, _| {
+            b.iter(|| sharded.sum())
+        });
+        assert_eq!(atomic.load(Ordering::Relaxed), sharded.sum());
+    }
+    group.finish();
+}
+
+/// The throughput over the batch size shows where amortizing the synchronization saturates,
+/// e.g. `bench-report --sweep "Batch Size Sweep"` plots it.
+fn benchmark_batch_size_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Batch Size Sweep");
+    group.throughput(Throughput::Elements(
+        (SWEEP_THREADS * SWEEP_REPETITIONS) as u64,
+    ));
+    for batch_size in (0..SWEEP_BATCH_SIZES).map(|i| 1 << i) {
+        group.bench_with_input(
+            BenchmarkId::new(format!("{} Threads", SWEEP_THREADS), batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed = run_contended(SWEEP_THREADS, iters, |_| {
+                        periodic_flush_increment(&counter, batch_size)
+                    });
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        SWEEP_THREADS * iters as usize * SWEEP_REPETITIONS
+                    );
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_contended_increment,
+    benchmark_batch_size_sweep,
+    benchmark_counter_read,
+    benchmark_memory_ordering,
+    benchmark_compare_exchange
+);
+
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{run_contended, thread_counts};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::utils::CachePadded;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const REPETITIONS: usize = 1000;
+
+fn increment(counter: &AtomicUsize) {
+    for _ in 0..REPETITIONS {
+        counter.fetch_add(black_box(1), Ordering::Relaxed);
+    }
+}
+
+fn benchmark_false_sharing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("False Sharing");
+    for threads in thread_counts() {
+        group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+        // every thread increments its own counter, so the work is the same in both cases
+        let expected = |iters: u64| vec![iters as usize * REPETITIONS; threads];
+        group.bench_with_input(
+            BenchmarkId::new("Packed", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    // adjacent counters share cache lines
+                    let counters: Vec<AtomicUsize> =
+                        (0..threads).map(|_| AtomicUsize::new(0)).collect();
+                    let elapsed =
+                        run_contended(threads, iters, |thread| increment(&counters[thread]));
+                    let totals: Vec<usize> = counters
+                        .iter()
+                        .map(|counter| counter.load(Ordering::Relaxed))
+                        .collect();
+                    assert_eq!(totals, expected(iters));
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("CachePadded", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counters: Vec<CachePadded<AtomicUsize>> =
+                        (0..threads).map(|_| Default::default()).collect();
+                    let elapsed =
+                        run_contended(threads, iters, |thread| increment(&counters[thread]));
+                    let totals: Vec<usize> = counters
+                        .iter()
+                        .map(|counter| counter.load(Ordering::Relaxed))
+                        .collect();
+                    assert_eq!(totals, expected(iters));
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_false_sharing);
+
+criterion_main!(benches);
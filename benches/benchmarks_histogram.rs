@@ -0,0 +1,140 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use hdrhistogram::Histogram;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const SAMPLES: usize = 1_000_000;
+// 1ms-wide, the last one takes everything above 10s
+const BUCKETS: usize = 10_000;
+const PERCENTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+// the median of the latencies, ms, and the standard deviation of their logarithm
+const MEDIAN: f64 = 20.;
+const SIGMA: f64 = 1.;
+
+/// Lognormal latencies in ms, the same for every run.
+fn latencies() -> Vec<f64> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..SAMPLES)
+        .map(|_| {
+            // Box-Muller, `1 - u` is never 0
+            let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+            let normal = (-2. * (1. - u).ln()).sqrt() * (2. * std::f64::consts::PI * v).cos();
+            MEDIAN * (SIGMA * normal).exp()
+        })
+        .collect()
+}
+
+/// In µs, as the aggregator records them, up to an hour.
+fn record_hdr(latencies: &[f64]) -> Histogram<u64> {
+    let mut histogram = Histogram::new_with_max(3_600_000_000, 3).expect("Valid histogram bounds");
+    for latency in latencies {
+        histogram.saturating_record((latency * 1000.) as u64);
+    }
+    histogram
+}
+
+/// The sort is a part of the recording, the percentiles can't be queried without it.
+fn record_sorted(latencies: &[f64]) -> Vec<f64> {
+    let mut sorted = vec![];
+    for &latency in latencies {
+        sorted.push(latency);
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).expect("Not NaN"));
+    sorted
+}
+
+fn record_buckets(latencies: &[f64]) -> Vec<u64> {
+    let mut buckets = vec![0; BUCKETS];
+    for &latency in latencies {
+        buckets[(latency as usize).min(BUCKETS - 1)] += 1;
+    }
+    buckets
+}
+
+fn query_hdr(histogram: &Histogram<u64>) -> Vec<f64> {
+    PERCENTILES
+        .iter()
+        .map(|&p| histogram.value_at_quantile(p) as f64 / 1000.)
+        .collect()
+}
+
+/// The nearest rank.
+fn query_sorted(sorted: &[f64]) -> Vec<f64> {
+    PERCENTILES
+        .iter()
+        .map(|&p| sorted[((p * sorted.len() as f64).ceil() as usize).max(1) - 1])
+        .collect()
+}
+
+/// The upper bound of the bucket of the nearest rank.
+fn query_buckets(buckets: &[u64]) -> Vec<f64> {
+    let total: u64 = buckets.iter().sum();
+    PERCENTILES
+        .iter()
+        .map(|&p| {
+            let rank = ((p * total as f64).ceil() as u64).max(1);
+            let mut seen = 0;
+            let bucket = buckets
+                .iter()
+                .position(|&count| {
+                    seen += count;
+                    seen >= rank
+                })
+                .expect("The rank is within the total");
+            (bucket + 1) as f64
+        })
+        .collect()
+}
+
+/// Compares the percentiles of the histograms with the exact ones.
+fn check_accuracy(latencies: &[f64]) {
+    let exact = query_sorted(&record_sorted(latencies));
+    let hdr = query_hdr(&record_hdr(latencies));
+    let buckets = query_buckets(&record_buckets(latencies));
+    println!("Percentile        exact, ms          HDR, ms      buckets, ms");
+    for (i, p) in PERCENTILES.iter().enumerate() {
+        println!(
+            "p{:<8} {:16.3} {:16.3} {:16.3}",
+            p * 100.,
+            exact[i],
+            hdr[i],
+            buckets[i]
+        );
+        // 3 significant digits, and the truncation to µs
+        assert!((hdr[i] - exact[i]).abs() <= exact[i] * 0.001 + 0.001);
+        // unless it's above the last bucket
+        assert!(buckets[i] - exact[i] <= 1. && buckets[i] >= exact[i]);
+    }
+}
+
+fn benchmark_histogram(c: &mut Criterion) {
+    let latencies = latencies();
+    check_accuracy(&latencies);
+
+    let mut group = c.benchmark_group("Histogram Record");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    // an iteration records 1M samples
+    group.sample_size(10);
+    group.bench_function("HDR", |b| b.iter_with_large_drop(|| record_hdr(&latencies)));
+    group.bench_function("Sorted Vec", |b| {
+        b.iter_with_large_drop(|| record_sorted(&latencies))
+    });
+    group.bench_function("1ms Buckets", |b| {
+        b.iter_with_large_drop(|| record_buckets(&latencies))
+    });
+    group.finish();
+
+    let hdr = record_hdr(&latencies);
+    let sorted = record_sorted(&latencies);
+    let buckets = record_buckets(&latencies);
+    let mut group = c.benchmark_group("Histogram Query");
+    group.throughput(Throughput::Elements(PERCENTILES.len() as u64));
+    group.bench_function("HDR", |b| b.iter(|| query_hdr(&hdr)));
+    group.bench_function("Sorted Vec", |b| b.iter(|| query_sorted(&sorted)));
+    group.bench_function("1ms Buckets", |b| b.iter(|| query_buckets(&buckets)));
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_histogram);
+
+criterion_main!(benches);
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{run_contended, thread_counts};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::utils::CachePadded;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+
+// operations per iteration and thread
+const REPETITIONS: usize = 1000;
+// the keys present from the start, e.g. the seconds of a run
+const KEYS: u64 = 64;
+// every 10th operation inserts a new key, the rest increment an existing one
+const INSERT_EVERY: usize = 10;
+const SHARDS: usize = 16;
+
+/// A concurrent map of counters.
+trait CounterMap: Sync {
+    fn new() -> Self;
+    /// Adds 1 to the counter of `key`, inserting it if it's not there.
+    fn increment(&self, key: u64);
+    /// The number of the keys and the sum of the counters.
+    fn totals(&self) -> (usize, u64);
+}
+
+fn initial_keys() -> impl Iterator<Item = u64> {
+    0..KEYS
+}
+
+impl CounterMap for Mutex<HashMap<u64, u64>> {
+    fn new() -> Self {
+        Mutex::new(initial_keys().map(|key| (key, 0)).collect())
+    }
+
+    fn increment(&self, key: u64) {
+        let mut map = self.lock().expect("Never fails in this bench");
+        *map.entry(key).or_insert(0) += 1;
+    }
+
+    fn totals(&self) -> (usize, u64) {
+        let map = self.lock().expect("Never fails in this bench");
+        (map.len(), map.values().sum())
+    }
+}
+
+/// The counters are atomic, so only an insert takes the write lock.
+impl CounterMap for RwLock<HashMap<u64, AtomicU64>> {
+    fn new() -> Self {
+        RwLock::new(initial_keys().map(|key| (key, AtomicU64::new(0))).collect())
+    }
+
+    fn increment(&self, key: u64) {
+        if let Some(counter) = self.read().expect("Never fails in this bench").get(&key) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.write()
+            .expect("Never fails in this bench")
+            .entry(key)
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn totals(&self) -> (usize, u64) {
+        let map = self.read().expect("Never fails in this bench");
+        let sum = map
+            .values()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum();
+        (map.len(), sum)
+    }
+}
+
+impl CounterMap for DashMap<u64, u64> {
+    fn new() -> Self {
+        initial_keys().map(|key| (key, 0)).collect()
+    }
+
+    fn increment(&self, key: u64) {
+        *self.entry(key).or_insert(0) += 1;
+    }
+
+    fn totals(&self) -> (usize, u64) {
+        (self.len(), self.iter().map(|entry| *entry.value()).sum())
+    }
+}
+
+/// A `Mutex<HashMap>` per `key % SHARDS`.
+impl CounterMap for Vec<Mutex<HashMap<u64, u64>>> {
+    fn new() -> Self {
+        let shards: Vec<Mutex<HashMap<u64, u64>>> =
+            (0..SHARDS).map(|_| Default::default()).collect();
+        for key in initial_keys() {
+            shards[key as usize % SHARDS]
+                .lock()
+                .expect("Never fails in this bench")
+                .insert(key, 0);
+        }
+        shards
+    }
+
+    fn increment(&self, key: u64) {
+        let mut shard = self[key as usize % SHARDS]
+            .lock()
+            .expect("Never fails in this bench");
+        *shard.entry(key).or_insert(0) += 1;
+    }
+
+    fn totals(&self) -> (usize, u64) {
+        self.iter().fold((0, 0), |(len, sum), shard| {
+            let shard = shard.lock().expect("Never fails in this bench");
+            (len + shard.len(), sum + shard.values().sum::<u64>())
+        })
+    }
+}
+
+/// Increments the existing keys round robin,
+/// and inserts a key unique to the thread every `INSERT_EVERY` operations.
+fn mixed_workload<M: CounterMap>(map: &M, thread: usize, inserted: &AtomicU64) {
+    for i in 0..REPETITIONS {
+        if i % INSERT_EVERY == 0 {
+            // the thread is the only writer of its counter
+            let n = inserted.fetch_add(1, Ordering::Relaxed);
+            map.increment(KEYS + (n << 8) + thread as u64);
+        } else {
+            map.increment(i as u64 % KEYS);
+        }
+    }
+}
+
+fn bench_map<M: CounterMap>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("Concurrent Map");
+    for threads in thread_counts() {
+        assert!(
+            threads < 256,
+            "The new keys are unique for up to 256 threads"
+        );
+        group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+        group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, &threads| {
+            b.iter_custom(|iters| {
+                let map = M::new();
+                let inserted: Vec<CachePadded<AtomicU64>> =
+                    (0..threads).map(|_| Default::default()).collect();
+                let elapsed = run_contended(threads, iters, |thread| {
+                    mixed_workload(&map, thread, &inserted[thread])
+                });
+                // the same for every implementation
+                let operations = threads * iters as usize * REPETITIONS;
+                let inserts = threads * iters as usize * REPETITIONS.div_ceil(INSERT_EVERY);
+                assert_eq!(map.totals(), (KEYS as usize + inserts, operations as u64));
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_concurrent_map(c: &mut Criterion) {
+    bench_map::<Mutex<HashMap<u64, u64>>>(c, "Mutex<HashMap>");
+    bench_map::<RwLock<HashMap<u64, AtomicU64>>>(c, "RwLock<HashMap>");
+    bench_map::<DashMap<u64, u64>>(c, "DashMap");
+    bench_map::<Vec<Mutex<HashMap<u64, u64>>>>(c, "Sharded Mutex<HashMap>");
+}
+
+criterion_group!(benches, benchmark_concurrent_map);
+
+criterion_main!(benches);
@@ -0,0 +1,197 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+const RECORDS: usize = 5_000_000;
+// the records of the serial and the parallel post-processing
+const PARALLEL_RECORDS: usize = 10_000_000;
+// the records bucketed by a thread at once, as `TIMELINE_CHUNK` of the simulator
+const CHUNK: usize = 1 << 16;
+// 100k tasks per second, i.e. a run of 50 seconds
+const RATE: usize = 100_000;
+// the share of the failed tasks, %
+const FAILED: f64 = 1.;
+// the median of the overheads, ms, and the standard deviation of their logarithm
+const MEDIAN: f64 = 1.;
+const SIGMA: f64 = 1.;
+const PERCENTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// The records, the copies of the successful ones, and the timeline, all of them are dropped outside the measurement.
+type Processed = (Vec<TaskStats>, Vec<TaskStats>, Vec<[f64; 3]>);
+
+/// The fields of `TaskStats` of the simulator, so the records are of the same size.
+#[allow(dead_code)]
+#[derive(Clone)]
+struct TaskStats {
+    failed: bool,
+    start_time: Instant,
+    service_start_time: Instant,
+    completion_time: Instant,
+    worker: Option<usize>,
+    overhead: f64,
+    clamped: bool,
+}
+
+/// Lognormal overheads in s, the same for every run, in the order of the completions.
+fn records(n: usize) -> Vec<TaskStats> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let start = Instant::now();
+    (0..n)
+        .map(|i| {
+            // Box-Muller, `1 - u` is never 0
+            let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+            let normal = (-2. * (1. - u).ln()).sqrt() * (2. * std::f64::consts::PI * v).cos();
+            let overhead = MEDIAN * (SIGMA * normal).exp() / 1000.;
+            let start_time = start + Duration::from_micros((i * 1_000_000 / RATE) as u64);
+            TaskStats {
+                failed: rng.gen::<f64>() < FAILED / 100.,
+                start_time,
+                service_start_time: start_time,
+                completion_time: start_time + Duration::from_secs_f64(overhead),
+                worker: None,
+                overhead,
+                clamped: false,
+            }
+        })
+        .collect()
+}
+
+/// The nearest rank percentile of the sorted `values`.
+fn percentile(values: &[f64], p: f64) -> f64 {
+    values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1]
+}
+
+/// p50/p90/p99 of the tasks started within each second.
+fn timeline(latencies: &[TaskStats], sort: bool) -> Vec<[f64; 3]> {
+    let first = latencies.iter().map(|s| s.start_time).min().unwrap();
+    let mut batches: Vec<Vec<f64>> = vec![];
+    for task in latencies {
+        let second = task.start_time.duration_since(first).as_secs() as usize;
+        if batches.len() <= second {
+            batches.resize_with(second + 1, Vec::new);
+        }
+        batches[second].push(task.overhead);
+    }
+    batches
+        .iter_mut()
+        .map(|batch| {
+            if sort {
+                batch.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            }
+            let mut ps = [0.; 3];
+            for (p, value) in PERCENTILES.iter().zip(ps.iter_mut()) {
+                *value = percentile(batch, *p);
+            }
+            ps
+        })
+        .collect()
+}
+
+/// Before: the successful records are copied, sorted by overhead, and every second is sorted again.
+fn copied(samples: Vec<TaskStats>) -> Processed {
+    let mut latencies: Vec<TaskStats> = samples.iter().filter(|s| !s.failed).cloned().collect();
+    latencies.sort_by(|a, b| a.overhead.partial_cmp(&b.overhead).unwrap());
+    let timeline = timeline(&latencies, true);
+    (samples, latencies, timeline)
+}
+
+/// After: the records are sorted in place, the successful ones first, so the seconds are filled in order.
+fn in_place(mut samples: Vec<TaskStats>) -> Processed {
+    samples.sort_unstable_by(|a, b| {
+        a.failed
+            .cmp(&b.failed)
+            .then_with(|| a.overhead.partial_cmp(&b.overhead).unwrap())
+    });
+    let successes = samples.partition_point(|s| !s.failed);
+    let timeline = timeline(&samples[..successes], false);
+    (samples, vec![], timeline)
+}
+
+/// `timeline` of the sorted records by the threads: the chunks of the records are bucketed by the seconds,
+/// the buckets of a second are joined in the order of the chunks, so they stay sorted.
+fn timeline_parallel(latencies: &[TaskStats]) -> Vec<[f64; 3]> {
+    let first = latencies.par_iter().map(|s| s.start_time).min().unwrap();
+    let batches: Vec<Vec<f64>> = latencies
+        .par_chunks(CHUNK)
+        .map(|chunk| {
+            let mut batches: Vec<Vec<f64>> = vec![];
+            for task in chunk {
+                let second = task.start_time.duration_since(first).as_secs() as usize;
+                if batches.len() <= second {
+                    batches.resize_with(second + 1, Vec::new);
+                }
+                batches[second].push(task.overhead);
+            }
+            batches
+        })
+        .reduce(Vec::new, |mut joined, batches| {
+            if joined.len() < batches.len() {
+                joined.resize_with(batches.len(), Vec::new);
+            }
+            for (joined, batch) in joined.iter_mut().zip(batches) {
+                joined.extend(batch);
+            }
+            joined
+        });
+    batches
+        .par_iter()
+        .map(|batch| PERCENTILES.map(|p| percentile(batch, p)))
+        .collect()
+}
+
+/// `in_place` by the threads of rayon.
+fn parallel(mut samples: Vec<TaskStats>) -> Processed {
+    samples.par_sort_unstable_by(|a, b| {
+        a.failed
+            .cmp(&b.failed)
+            .then_with(|| a.overhead.partial_cmp(&b.overhead).unwrap())
+    });
+    let successes = samples.partition_point(|s| !s.failed);
+    let timeline = timeline_parallel(&samples[..successes]);
+    (samples, vec![], timeline)
+}
+
+fn benchmark_post_processing(c: &mut Criterion) {
+    let samples = records(RECORDS);
+    // the same percentiles both ways
+    assert_eq!(copied(samples.clone()).2, in_place(samples.clone()).2);
+
+    let mut group = c.benchmark_group("Post-processing");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(RECORDS as u64));
+    group.bench_function("Copied", |b| {
+        b.iter_batched(|| samples.clone(), copied, BatchSize::PerIteration)
+    });
+    group.bench_function("In place", |b| {
+        b.iter_batched(|| samples.clone(), in_place, BatchSize::PerIteration)
+    });
+    group.finish();
+}
+
+fn benchmark_parallel_post_processing(c: &mut Criterion) {
+    let samples = records(PARALLEL_RECORDS);
+    // the same percentiles both ways
+    assert_eq!(in_place(samples.clone()).2, parallel(samples.clone()).2);
+
+    let mut group = c.benchmark_group("Post-processing (parallel)");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(PARALLEL_RECORDS as u64));
+    group.bench_function("Serial", |b| {
+        b.iter_batched(|| samples.clone(), in_place, BatchSize::PerIteration)
+    });
+    group.bench_function(
+        format!("Parallel ({} threads)", rayon::current_num_threads()),
+        |b| b.iter_batched(|| samples.clone(), parallel, BatchSize::PerIteration),
+    );
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_post_processing,
+    benchmark_parallel_post_processing
+);
+
+criterion_main!(benches);
@@ -0,0 +1,150 @@
+mod common;
+
+use common::run_contended;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::queue::{ArrayQueue, SegQueue};
+use crossbeam::utils::{Backoff, CachePadded};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// pushed and popped in every iteration, over all the producers and the consumers
+const MESSAGES: usize = 10_000;
+// producers:consumers
+const RATIOS: [(usize, usize); 3] = [(1, 1), (4, 1), (1, 4)];
+
+/// An MPMC queue of `usize`, which doesn't block.
+trait Queue: Sync {
+    /// The capacity is ignored by the unbounded queues.
+    fn new(capacity: usize) -> Self;
+    /// Gives the message back, if the queue is full.
+    fn push(&self, message: usize) -> Result<(), usize>;
+    fn pop(&self) -> Option<usize>;
+}
+
+impl Queue for SegQueue<usize> {
+    fn new(_capacity: usize) -> Self {
+        SegQueue::new()
+    }
+
+    fn push(&self, message: usize) -> Result<(), usize> {
+        SegQueue::push(self, message);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<usize> {
+        SegQueue::pop(self)
+    }
+}
+
+impl Queue for ArrayQueue<usize> {
+    fn new(capacity: usize) -> Self {
+        ArrayQueue::new(capacity)
+    }
+
+    fn push(&self, message: usize) -> Result<(), usize> {
+        ArrayQueue::push(self, message)
+    }
+
+    fn pop(&self) -> Option<usize> {
+        ArrayQueue::pop(self)
+    }
+}
+
+impl Queue for Mutex<VecDeque<usize>> {
+    fn new(_capacity: usize) -> Self {
+        Mutex::new(VecDeque::new())
+    }
+
+    fn push(&self, message: usize) -> Result<(), usize> {
+        self.lock()
+            .expect("Never fails in this bench")
+            .push_back(message);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<usize> {
+        self.lock().expect("Never fails in this bench").pop_front()
+    }
+}
+
+/// The popped messages of a consumer, over all the iterations.
+#[derive(Default)]
+struct Consumed {
+    count: AtomicUsize,
+    sum: AtomicUsize,
+}
+
+/// Pushes the messages `[producer * share, (producer + 1) * share)`,
+/// so the producers push every message of `0..MESSAGES` once per iteration.
+fn produce<Q: Queue>(queue: &Q, producer: usize, share: usize) {
+    let backoff = Backoff::new();
+    for mut message in producer * share..(producer + 1) * share {
+        // a bounded queue is full, until a consumer pops
+        while let Err(rejected) = queue.push(message) {
+            message = rejected;
+            backoff.snooze();
+        }
+    }
+}
+
+fn consume<Q: Queue>(queue: &Q, share: usize, consumed: &Consumed) {
+    let backoff = Backoff::new();
+    let (mut count, mut sum) = (0, 0);
+    while count < share {
+        match queue.pop() {
+            Some(message) => {
+                count += 1;
+                sum += message;
+                backoff.reset();
+            }
+            None => backoff.snooze(),
+        }
+    }
+    consumed.count.fetch_add(count, Ordering::Relaxed);
+    consumed.sum.fetch_add(sum, Ordering::Relaxed);
+}
+
+fn bench_queue<Q: Queue>(c: &mut Criterion, name: &str, capacity: usize) {
+    for &(producers, consumers) in RATIOS.iter() {
+        let mut group = c.benchmark_group(format!("Queue {}:{}", producers, consumers));
+        group.throughput(Throughput::Elements(MESSAGES as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter_custom(|iters| {
+                let queue = Q::new(capacity);
+                let consumed: Vec<CachePadded<Consumed>> =
+                    (0..consumers).map(|_| Default::default()).collect();
+                // the first threads produce, the rest consume
+                let elapsed = run_contended(producers + consumers, iters, |thread| {
+                    if thread < producers {
+                        produce(&queue, thread, MESSAGES / producers);
+                    } else {
+                        consume(&queue, MESSAGES / consumers, &consumed[thread - producers]);
+                    }
+                });
+                // every message is popped exactly once
+                let count: usize = consumed
+                    .iter()
+                    .map(|c| c.count.load(Ordering::Relaxed))
+                    .sum();
+                let sum: usize = consumed.iter().map(|c| c.sum.load(Ordering::Relaxed)).sum();
+                assert_eq!(count, iters as usize * MESSAGES);
+                assert_eq!(sum, iters as usize * MESSAGES * (MESSAGES - 1) / 2);
+                assert_eq!(queue.pop(), None);
+                elapsed
+            })
+        });
+        group.finish();
+    }
+}
+
+fn benchmark_queue(c: &mut Criterion) {
+    bench_queue::<SegQueue<usize>>(c, "SegQueue", 0);
+    bench_queue::<ArrayQueue<usize>>(c, "ArrayQueue(64)", 64);
+    bench_queue::<ArrayQueue<usize>>(c, "ArrayQueue(4096)", 4096);
+    bench_queue::<Mutex<VecDeque<usize>>>(c, "Mutex<VecDeque>", 0);
+}
+
+criterion_group!(benches, benchmark_queue);
+
+criterion_main!(benches);
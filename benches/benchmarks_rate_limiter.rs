@@ -0,0 +1,88 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rate_limiter::RateLimiter;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+
+// permits per second
+const RATES: [usize; 3] = [1_000, 10_000, 100_000];
+// the window to compare the achieved rate with the configured one
+const WINDOW: Duration = Duration::from_secs(2);
+// the achieved rate may be off the configured one by this fraction at most
+const MAX_ERROR: f64 = 0.1;
+
+/// Refills at the same interval as the simulator.
+fn rate_limiter(rate: usize) -> RateLimiter {
+    let (refill, interval) = rate_limiter::refill(rate);
+    RateLimiter::new(refill, Duration::from_millis(interval))
+}
+
+async fn acquire(rate_limiter: &mut RateLimiter, permits: u64) {
+    for _ in 0..permits {
+        rate_limiter.acquire_one().await;
+    }
+}
+
+/// Acquires permits for `WINDOW`, and asserts the achieved rate is within `MAX_ERROR` of `rate`.
+async fn check_accuracy(rate: usize) {
+    let mut rate_limiter = rate_limiter(rate);
+    let start = Instant::now();
+    let mut permits = 0;
+    while start.elapsed() < WINDOW {
+        rate_limiter.acquire_one().await;
+        permits += 1;
+    }
+    let achieved = permits as f64 / start.elapsed().as_secs_f64();
+    let error = achieved / rate as f64 - 1.;
+    println!(
+        "Rate {:>7}/s: achieved {:10.0}/s over {:?}, error {:+.3}%",
+        rate,
+        achieved,
+        WINDOW,
+        error * 100.
+    );
+    assert!(
+        error.abs() <= MAX_ERROR,
+        "The rate limiter is inaccurate at {}/s, the simulator doesn't get this rate either",
+        rate
+    );
+}
+
+fn current_thread_runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+/// The time per permit above `1 / rate` is the overhead of `acquire_one`,
+/// i.e. how much later than intended the simulator sends a request.
+fn benchmark_rate_limiter(c: &mut Criterion) {
+    let mut runtime = current_thread_runtime();
+    for &rate in RATES.iter() {
+        runtime.block_on(check_accuracy(rate));
+    }
+
+    let mut group = c.benchmark_group("Rate Limiter Acquire");
+    // every permit takes `1 / rate` at least, so fewer samples keep the bench short
+    group.sample_size(10);
+    for &rate in RATES.iter() {
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("acquire_one", rate), &rate, |b, &rate| {
+            b.iter_custom(|iters| {
+                let mut rate_limiter = rate_limiter(rate);
+                let start = Instant::now();
+                runtime.block_on(acquire(&mut rate_limiter, iters));
+                start.elapsed()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_rate_limiter);
+
+criterion_main!(benches);
@@ -0,0 +1,171 @@
+mod common;
+
+use common::{run_contended, thread_counts};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::{Mutex, RwLock};
+
+const REPETITIONS: usize = 1000;
+// the share of writes in the workload, %; the rest are reads of the counter
+const WRITE_PERCENTAGES: [usize; 4] = [1, 5, 20, 50];
+
+/// A counter behind a lock, read and written under the lock.
+trait LockedCounter: Default + Sync {
+    fn read(&self) -> usize;
+    fn add(&self, value: usize);
+}
+
+impl LockedCounter for Mutex<usize> {
+    fn read(&self) -> usize {
+        *self.lock().expect("Never fails in this bench")
+    }
+
+    fn add(&self, value: usize) {
+        *self.lock().expect("Never fails in this bench") += value;
+    }
+}
+
+impl LockedCounter for RwLock<usize> {
+    fn read(&self) -> usize {
+        *self.read().expect("Never fails in this bench")
+    }
+
+    fn add(&self, value: usize) {
+        *self.write().expect("Never fails in this bench") += value;
+    }
+}
+
+impl LockedCounter for parking_lot::Mutex<usize> {
+    fn read(&self) -> usize {
+        *self.lock()
+    }
+
+    fn add(&self, value: usize) {
+        *self.lock() += value;
+    }
+}
+
+impl LockedCounter for parking_lot::RwLock<usize> {
+    fn read(&self) -> usize {
+        *self.read()
+    }
+
+    fn add(&self, value: usize) {
+        *self.write() += value;
+    }
+}
+
+fn is_write(i: usize, write_percentage: usize) -> bool {
+    i % 100 < write_percentage
+}
+
+fn writes_per_call(write_percentage: usize) -> usize {
+    (0..REPETITIONS)
+        .filter(|&i| is_write(i, write_percentage))
+        .count()
+}
+
+fn batched_read_mostly<C: LockedCounter>(counter: &C, write_percentage: usize) {
+    let mut batch = 0;
+    for i in 0..REPETITIONS {
+        if is_write(i, write_percentage) {
+            // avoiding compiler optimizations
+            batch += black_box(1);
+        } else {
+            black_box(counter.read());
+        }
+    }
+    counter.add(batch);
+}
+
+fn naive_read_mostly<C: LockedCounter>(counter: &C, write_percentage: usize) {
+    for i in 0..REPETITIONS {
+        if is_write(i, write_percentage) {
+            counter.add(black_box(1));
+        } else {
+            black_box(counter.read());
+        }
+    }
+}
+
+fn bench_single_threaded<C: LockedCounter>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(format!("Read-mostly {}", name));
+    group.throughput(Throughput::Elements(REPETITIONS as u64));
+    for &write_percentage in WRITE_PERCENTAGES.iter() {
+        let expected = writes_per_call(write_percentage);
+        group.bench_with_input(
+            BenchmarkId::new("Batched", format!("{}% writes", write_percentage)),
+            &write_percentage,
+            |b, &write_percentage| {
+                b.iter_custom(|iters| {
+                    let counter = C::default();
+                    let elapsed = run_contended(1, iters, |_| {
+                        batched_read_mostly(&counter, write_percentage)
+                    });
+                    assert_eq!(counter.read(), iters as usize * expected);
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Naive", format!("{}% writes", write_percentage)),
+            &write_percentage,
+            |b, &write_percentage| {
+                b.iter_custom(|iters| {
+                    let counter = C::default();
+                    let elapsed =
+                        run_contended(1, iters, |_| naive_read_mostly(&counter, write_percentage));
+                    assert_eq!(counter.read(), iters as usize * expected);
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_contended<C: LockedCounter>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(format!("Contended Read-mostly {}", name));
+    for &write_percentage in WRITE_PERCENTAGES.iter() {
+        let expected = writes_per_call(write_percentage);
+        for threads in thread_counts() {
+            group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}% writes", write_percentage), threads),
+                &threads,
+                |b, &threads| {
+                    b.iter_custom(|iters| {
+                        let counter = C::default();
+                        let elapsed = run_contended(threads, iters, |_| {
+                            naive_read_mostly(&counter, write_percentage)
+                        });
+                        assert_eq!(counter.read(), threads * iters as usize * expected);
+                        elapsed
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn benchmark_read_mostly(c: &mut Criterion) {
+    bench_single_threaded::<Mutex<usize>>(c, "Mutex");
+    bench_single_threaded::<RwLock<usize>>(c, "RwLock");
+    bench_single_threaded::<parking_lot::Mutex<usize>>(c, "parking_lot Mutex");
+    bench_single_threaded::<parking_lot::RwLock<usize>>(c, "parking_lot RwLock");
+}
+
+fn benchmark_contended_read_mostly(c: &mut Criterion) {
+    bench_contended::<Mutex<usize>>(c, "Mutex");
+    bench_contended::<RwLock<usize>>(c, "RwLock");
+    bench_contended::<parking_lot::Mutex<usize>>(c, "parking_lot Mutex");
+    bench_contended::<parking_lot::RwLock<usize>>(c, "parking_lot RwLock");
+}
+
+criterion_group!(
+    benches,
+    benchmark_read_mostly,
+    benchmark_contended_read_mostly
+);
+
+criterion_main!(benches);
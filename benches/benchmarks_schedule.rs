@@ -0,0 +1,128 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use std::time::{Duration, Instant};
+use std::vec;
+
+// costs taken per iteration, i.e. the sends of 1 ms at 1M sends per second
+const SENDS: usize = 1000;
+// the runs of up to this many tasks are expanded up front, the same as the simulator
+const MATERIALIZED_MAX: usize = 1 << 20;
+// the latencies of the slow-db preset: 20ms*9,30s
+const LATENCIES: [u64; 10] = [20, 20, 20, 20, 20, 20, 20, 20, 20, 30_000];
+
+/// The send loop before the schedule: the spec is indexed per send.
+struct Indexed {
+    latencies: Vec<u64>,
+    n_jobs: usize,
+    sent: usize,
+}
+
+impl Indexed {
+    fn next(&mut self) -> Option<u64> {
+        if self.sent == self.n_jobs {
+            return None;
+        }
+        let cost = self.latencies[self.sent % self.latencies.len()];
+        self.sent += 1;
+        Some(cost)
+    }
+}
+
+/// A copy of `TaskSchedule` of the simulator.
+enum TaskSchedule {
+    Materialized(vec::IntoIter<u64>),
+    Lazy {
+        latencies: Vec<u64>,
+        next: usize,
+        n_jobs: usize,
+    },
+}
+
+impl TaskSchedule {
+    fn new(latencies: &[u64], n_jobs: usize) -> Self {
+        if n_jobs <= MATERIALIZED_MAX {
+            let costs: Vec<u64> = latencies.iter().copied().cycle().take(n_jobs).collect();
+            TaskSchedule::Materialized(costs.into_iter())
+        } else {
+            TaskSchedule::Lazy {
+                latencies: latencies.to_vec(),
+                next: 0,
+                n_jobs,
+            }
+        }
+    }
+}
+
+impl Iterator for TaskSchedule {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        match self {
+            TaskSchedule::Materialized(costs) => costs.next(),
+            TaskSchedule::Lazy {
+                latencies,
+                next,
+                n_jobs,
+            } => {
+                if *next == *n_jobs {
+                    return None;
+                }
+                let cost = latencies[*next % latencies.len()];
+                *next += 1;
+                Some(cost)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            TaskSchedule::Materialized(costs) => costs.size_hint(),
+            TaskSchedule::Lazy { next, n_jobs, .. } => (n_jobs - next, Some(n_jobs - next)),
+        }
+    }
+}
+
+/// Takes `SENDS` costs, one at a time, as the send loop does.
+fn take(next: &mut impl FnMut() -> Option<u64>) {
+    for _ in 0..SENDS {
+        black_box(next());
+    }
+}
+
+/// The indexed and the lazy runs don't end within the measurement, the materialized one is rebuilt
+/// once it's exhausted, outside of the measured time.
+fn benchmark_schedule(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Task schedule");
+    group.throughput(Throughput::Elements(SENDS as u64));
+    group.bench_function("Indexed", |b| {
+        let mut indexed = Indexed {
+            latencies: LATENCIES.to_vec(),
+            n_jobs: usize::MAX,
+            sent: 0,
+        };
+        b.iter(|| take(&mut || indexed.next()))
+    });
+    group.bench_function("Materialized", |b| {
+        b.iter_custom(|iters| {
+            let mut schedule = TaskSchedule::new(&LATENCIES, MATERIALIZED_MAX);
+            let mut elapsed = Duration::from_secs(0);
+            for _ in 0..iters {
+                if schedule.size_hint().0 < SENDS {
+                    schedule = TaskSchedule::new(&LATENCIES, MATERIALIZED_MAX);
+                }
+                let start = Instant::now();
+                take(&mut || schedule.next());
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+    group.bench_function("Lazy", |b| {
+        let mut schedule = TaskSchedule::new(&LATENCIES, usize::MAX);
+        b.iter(|| take(&mut || schedule.next()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_schedule);
+
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::runtime::Runtime;
+
+// spawned and joined in every iteration
+const UNITS: [usize; 3] = [100, 1_000, 10_000];
+
+fn spawn_threads(units: usize) {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..units)
+        .map(|_| {
+            let counter = counter.clone();
+            thread::spawn(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().expect("Never fails in this bench");
+    }
+    assert_eq!(counter.load(Ordering::Relaxed), units);
+}
+
+async fn spawn_tasks(units: usize) {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..units)
+        .map(|_| {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.expect("Never fails in this bench");
+    }
+    assert_eq!(counter.load(Ordering::Relaxed), units);
+}
+
+fn spawn_rayon(units: usize) {
+    let counter = AtomicUsize::new(0);
+    // the scope joins everything spawned in it
+    rayon::scope(|scope| {
+        for _ in 0..units {
+            scope.spawn(|_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+    });
+    assert_eq!(counter.load(Ordering::Relaxed), units);
+}
+
+fn current_thread_runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+fn multi_thread_runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+fn benchmark_spawn(c: &mut Criterion) {
+    let mut current_thread = current_thread_runtime();
+    let mut multi_thread = multi_thread_runtime();
+    let mut group = c.benchmark_group("Spawn and Join");
+    for &units in UNITS.iter() {
+        group.throughput(Throughput::Elements(units as u64));
+        group.bench_with_input(BenchmarkId::new("thread", units), &units, |b, &units| {
+            b.iter(|| spawn_threads(units))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("tokio current_thread", units),
+            &units,
+            |b, &units| b.iter(|| current_thread.block_on(spawn_tasks(units))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("tokio multi_thread", units),
+            &units,
+            |b, &units| b.iter(|| multi_thread.block_on(spawn_tasks(units))),
+        );
+        group.bench_with_input(BenchmarkId::new("rayon", units), &units, |b, &units| {
+            b.iter(|| spawn_rayon(units))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_spawn);
+
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+mod common;
+mod spinlock;
+
+use common::{run_contended, thread_counts};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use spinlock::Spinlock;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// critical sections per iteration and thread
+const REPETITIONS: usize = 100;
+const HOLD_TIMES: [Duration; 3] = [
+    Duration::from_nanos(0),
+    Duration::from_nanos(100),
+    Duration::from_micros(10),
+];
+
+/// A counter, incremented in a critical section which takes (at least) `hold`.
+trait LockedCounter: Sync {
+    fn new() -> Self;
+    fn increment(&self, hold: Duration);
+    fn get(&self) -> usize;
+}
+
+/// Emulates the work in the critical section.
+fn busy_wait(hold: Duration) {
+    if hold > Duration::from_nanos(0) {
+        let start = Instant::now();
+        while start.elapsed() < hold {}
+    }
+}
+
+struct StdMutex(Mutex<usize>);
+struct ParkingLotMutex(parking_lot::Mutex<usize>);
+struct TasSpinlock(Spinlock<usize>);
+struct BackoffSpinlock(Spinlock<usize>);
+
+impl LockedCounter for StdMutex {
+    fn new() -> Self {
+        Self(Mutex::new(0))
+    }
+
+    fn increment(&self, hold: Duration) {
+        let mut lock = self.0.lock().expect("Never fails in this bench");
+        busy_wait(hold);
+        *lock += 1;
+    }
+
+    fn get(&self) -> usize {
+        *self.0.lock().expect("Never fails in this bench")
+    }
+}
+
+impl LockedCounter for ParkingLotMutex {
+    fn new() -> Self {
+        Self(parking_lot::Mutex::new(0))
+    }
+
+    fn increment(&self, hold: Duration) {
+        let mut lock = self.0.lock();
+        busy_wait(hold);
+        *lock += 1;
+    }
+
+    fn get(&self) -> usize {
+        *self.0.lock()
+    }
+}
+
+impl LockedCounter for TasSpinlock {
+    fn new() -> Self {
+        Self(Spinlock::new(0))
+    }
+
+    fn increment(&self, hold: Duration) {
+        let mut lock = self.0.lock();
+        busy_wait(hold);
+        *lock += 1;
+    }
+
+    fn get(&self) -> usize {
+        *self.0.lock()
+    }
+}
+
+impl LockedCounter for BackoffSpinlock {
+    fn new() -> Self {
+        Self(Spinlock::with_backoff(0))
+    }
+
+    fn increment(&self, hold: Duration) {
+        let mut lock = self.0.lock();
+        busy_wait(hold);
+        *lock += 1;
+    }
+
+    fn get(&self) -> usize {
+        *self.0.lock()
+    }
+}
+
+fn bench_lock<C: LockedCounter>(
+    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+    name: &str,
+    threads: usize,
+    hold: Duration,
+) {
+    group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, &threads| {
+        b.iter_custom(|iters| {
+            let counter = C::new();
+            let elapsed = run_contended(threads, iters, |_| {
+                for _ in 0..REPETITIONS {
+                    counter.increment(hold);
+                }
+            });
+            assert_eq!(counter.get(), threads * iters as usize * REPETITIONS);
+            elapsed
+        })
+    });
+}
+
+/// A group per hold time, so every report shows where spinning stops paying off.
+fn benchmark_spinlock(c: &mut Criterion) {
+    for &hold in HOLD_TIMES.iter() {
+        let mut group = c.benchmark_group(format!("Spinlock Hold {:?}", hold));
+        for threads in thread_counts() {
+            group.throughput(Throughput::Elements((threads * REPETITIONS) as u64));
+            bench_lock::<StdMutex>(&mut group, "std Mutex", threads, hold);
+            bench_lock::<ParkingLotMutex>(&mut group, "parking_lot Mutex", threads, hold);
+            bench_lock::<TasSpinlock>(&mut group, "Spinlock", threads, hold);
+            bench_lock::<BackoffSpinlock>(&mut group, "Spinlock with backoff", threads, hold);
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, benchmark_spinlock);
+
+criterion_main!(benches);
@@ -0,0 +1,209 @@
+mod common;
+
+use common::{print_summary, thread_counts, Tally};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::queue::SegQueue;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// recorded by every thread in every iteration
+const RECORDS: usize = 100_000;
+
+/// Counts the allocations (and reallocations) of the bench, e.g. of a growing `Vec`.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The same fields as `TaskStats` of the simulator.
+#[derive(Clone)]
+struct Record {
+    failure: Option<u8>,
+    start_time: Instant,
+    completion_time: Instant,
+    overhead: f64,
+    clamped: bool,
+}
+
+impl Record {
+    fn new(i: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            failure: None,
+            start_time: now,
+            completion_time: now,
+            overhead: i as f64,
+            clamped: false,
+        }
+    }
+}
+
+type Strategy = fn(usize) -> Vec<Record>;
+
+/// Every record of every thread is there, and intact.
+fn check(name: &str, records: &[Record], threads: usize) {
+    assert_eq!(records.len(), threads * RECORDS, "{}: records lost", name);
+    let overhead: f64 = records.iter().map(|record| record.overhead).sum();
+    assert_eq!(
+        overhead,
+        (threads * RECORDS * (RECORDS - 1) / 2) as f64,
+        "{}: records corrupted",
+        name
+    );
+    assert!(records.iter().all(|record| record.failure.is_none()
+        && !record.clamped
+        && record.completion_time >= record.start_time));
+}
+
+/// Every thread records `RECORDS` into a `Vec` of its own, the vectors are joined at the end.
+fn per_thread_vecs(threads: usize) -> Vec<Record> {
+    let handles: Vec<_> = (0..threads)
+        .map(|_| thread::spawn(|| (0..RECORDS).map(Record::new).collect::<Vec<_>>()))
+        .collect();
+    let mut records = vec![];
+    for handle in handles {
+        records.extend(handle.join().expect("Never fails in this bench"));
+    }
+    records
+}
+
+fn shared_mutex_vec(threads: usize) -> Vec<Record> {
+    let records = Mutex::new(vec![]);
+    crossbeam::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|_| {
+                for i in 0..RECORDS {
+                    let record = Record::new(i);
+                    records
+                        .lock()
+                        .expect("Never fails in this bench")
+                        .push(record);
+                }
+            });
+        }
+    })
+    .expect("Never fails in this bench");
+    records.into_inner().expect("Never fails in this bench")
+}
+
+/// The threads send the records to a collector thread.
+fn channel_to_collector(threads: usize) -> Vec<Record> {
+    let (sender, receiver) = mpsc::channel();
+    // stops once all the senders are gone
+    let collector = thread::spawn(move || receiver.into_iter().collect::<Vec<_>>());
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for i in 0..RECORDS {
+                    sender
+                        .send(Record::new(i))
+                        .expect("Never fails in this bench");
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+    for handle in handles {
+        handle.join().expect("Never fails in this bench");
+    }
+    collector.join().expect("Never fails in this bench")
+}
+
+/// The threads push the records into a lock-free queue, which is drained at the end.
+fn lock_free_queue(threads: usize) -> Vec<Record> {
+    let queue = SegQueue::new();
+    crossbeam::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|_| {
+                for i in 0..RECORDS {
+                    queue.push(Record::new(i));
+                }
+            });
+        }
+    })
+    .expect("Never fails in this bench");
+    let mut records = Vec::with_capacity(queue.len());
+    while let Some(record) = queue.pop() {
+        records.push(record);
+    }
+    records
+}
+
+fn benchmark_stats_collection(c: &mut Criterion) {
+    let strategies: [(&str, Strategy); 4] = [
+        ("Per-thread Vec", per_thread_vecs),
+        ("Mutex<Vec>", shared_mutex_vec),
+        ("mpsc to collector", channel_to_collector),
+        ("SegQueue", lock_free_queue),
+    ];
+
+    let mut group = c.benchmark_group("Stats Collection");
+    // an iteration records 100k per thread
+    group.sample_size(10);
+    for threads in thread_counts() {
+        let tallies: Vec<Tally> = strategies
+            .iter()
+            .map(|(name, _)| Tally::new(*name))
+            .collect();
+        let allocations: Vec<Cell<usize>> = strategies.iter().map(|_| Cell::new(0)).collect();
+
+        group.throughput(Throughput::Elements((threads * RECORDS) as u64));
+        for (i, (name, collect)) in strategies.iter().enumerate() {
+            group.bench_with_input(BenchmarkId::new(*name, threads), &threads, |b, &threads| {
+                b.iter_custom(|iters| {
+                    let mut elapsed = Duration::from_secs(0);
+                    for _ in 0..iters {
+                        let allocated = ALLOCATIONS.load(Ordering::Relaxed);
+                        let start = Instant::now();
+                        let records = collect(threads);
+                        elapsed += start.elapsed();
+                        allocations[i].set(
+                            allocations[i].get() + ALLOCATIONS.load(Ordering::Relaxed) - allocated,
+                        );
+                        check(name, &records, threads);
+                    }
+                    tallies[i].add(iters * (threads * RECORDS) as u64, elapsed);
+                    elapsed
+                })
+            });
+        }
+
+        print_summary(&format!("Threads: {}", threads), &tallies);
+        for (tally, allocations) in tallies.iter().zip(allocations.iter()) {
+            println!(
+                "{:16} {:14.4} allocations per record",
+                tally.name(),
+                // the calls of the tally are the records
+                allocations.get() as f64 / tally.calls().max(1) as f64
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_stats_collection);
+
+criterion_main!(benches);
@@ -1,7 +1,21 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+mod common;
+
+use common::{
+    measure, print_summary, run_contended, run_contended_and_flush, thread_counts, Tally,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::cell::Cell;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
+const REPETITIONS: [usize; 4] = [100, 1_000, 10_000, 100_000];
+// how many observations are accumulated locally before a flush to the shared counter
+const BATCH_SIZES: [usize; 4] = [1, 8, 64, 512];
+
+thread_local! {
+    static ACCUMULATOR: Cell<usize> = const { Cell::new(0) };
+}
+
 fn sum_batched(observations: &[usize], counter: &AtomicUsize) {
     let mut batch = 0;
     for i in observations {
@@ -23,46 +37,199 @@ fn sum_naive_mutex(observations: &[usize], counter_mutex: &Mutex<usize>) {
     }
 }
 
+/// Same as `sum_batched`, but flushes the batch every `batch_size` observations.
+fn sum_periodic_flush(observations: &[usize], batch_size: usize, counter: &AtomicUsize) {
+    for chunk in observations.chunks(batch_size) {
+        let mut batch = 0;
+        for i in chunk {
+            batch += i;
+        }
+        counter.fetch_add(batch, Ordering::Relaxed);
+    }
+}
+
+fn sum_thread_local(observations: &[usize]) {
+    ACCUMULATOR.with(|accumulator| {
+        for i in observations {
+            accumulator.set(accumulator.get() + i);
+        }
+    });
+}
+
+fn flush_thread_local(counter: &AtomicUsize) {
+    counter.fetch_add(
+        ACCUMULATOR.with(|accumulator| accumulator.take()),
+        Ordering::Relaxed,
+    );
+}
+
 fn benchmark_increment(c: &mut Criterion) {
-    let counter_batched: AtomicUsize = AtomicUsize::new(0);
-    let counter_atomic: AtomicUsize = AtomicUsize::new(0);
-    let counter_mutex: Mutex<usize> = Mutex::new(0);
+    let mut group = c.benchmark_group("Sum");
+    for &repetitions in REPETITIONS.iter() {
+        // filling the array with values 0,1,2
+        let vec = (0..repetitions).map(|i| i % 3).collect::<Vec<usize>>();
+        let increment = vec.as_slice();
+        let per_call: usize = increment.iter().sum();
+        let tally_batched = Tally::new("Sum Batched");
+        let tally_atomic = Tally::new("Sum Atomic");
+        let tally_mutex = Tally::new("Sum Mutex");
+        let tallies_flush: Vec<Tally> = BATCH_SIZES
+            .iter()
+            .map(|batch_size| Tally::new(format!("Sum Flush {}", batch_size)))
+            .collect();
 
-    // filling the array with values 0,1,2
+        group.throughput(Throughput::Elements(repetitions as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Batched", repetitions),
+            increment,
+            |b, increment| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_batched,
+                        iters,
+                        AtomicUsize::new(0),
+                        |counter| sum_batched(increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Naive Atomic", repetitions),
+            increment,
+            |b, increment| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_atomic,
+                        iters,
+                        AtomicUsize::new(0),
+                        |counter| sum_naive_atomic(increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Naive Mutex", repetitions),
+            increment,
+            |b, increment| {
+                b.iter_custom(|iters| {
+                    measure(
+                        &tally_mutex,
+                        iters,
+                        Mutex::new(0),
+                        |counter| sum_naive_mutex(increment, counter),
+                        per_call,
+                    )
+                })
+            },
+        );
+        for (&batch_size, tally) in BATCH_SIZES.iter().zip(tallies_flush.iter()) {
+            group.bench_with_input(
+                BenchmarkId::new(format!("Periodic Flush {}", batch_size), repetitions),
+                increment,
+                |b, increment| {
+                    b.iter_custom(|iters| {
+                        measure(
+                            tally,
+                            iters,
+                            AtomicUsize::new(0),
+                            |counter| sum_periodic_flush(increment, batch_size, counter),
+                            per_call,
+                        )
+                    })
+                },
+            );
+        }
+
+        let mut tallies = vec![tally_batched, tally_atomic, tally_mutex];
+        tallies.extend(tallies_flush);
+        print_summary(&format!("Repetitions: {}", repetitions), &tallies);
+    }
+    group.finish();
+}
+
+fn benchmark_contended_sum(c: &mut Criterion) {
     let repetitions = 1_002;
     let vec = (0..repetitions).map(|i| i % 3).collect::<Vec<usize>>();
-    let increment = vec.as_slice();
-
-    c.bench_function("Sum Batched", |b| {
-        b.iter(|| black_box(sum_batched(increment, &counter_batched)))
-    });
-    c.bench_function("Sum Naive Atomic", |b| {
-        b.iter(|| black_box(sum_naive_atomic(increment, &counter_atomic)))
-    });
-    c.bench_function("Sum Naive Mutex", |b| {
-        b.iter(|| black_box(sum_naive_mutex(increment, &counter_mutex)))
-    });
+    let observations = vec.as_slice();
+    let total: usize = observations.iter().sum();
 
-    let batched = counter_batched.load(Ordering::Relaxed);
-    let atomic = counter_atomic.load(Ordering::Relaxed);
-    let mutex = counter_mutex.lock().unwrap();
-    println!(
-        "Sum Batched  {:12} operations, {:.6}",
-        batched / repetitions,
-        batched as f64 / batched as f64
-    );
-    println!(
-        "Sum Atomic   {:12} operations, {:.6}",
-        atomic / repetitions,
-        atomic as f64 / batched as f64
-    );
-    println!(
-        "Sum Mutex    {:12} operations, {:.6}",
-        *mutex / repetitions,
-        *mutex as f64 / batched as f64
-    );
+    let mut group = c.benchmark_group("Contended Sum");
+    for threads in thread_counts() {
+        group.throughput(Throughput::Elements((threads * repetitions) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Batched", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed =
+                        run_contended(threads, iters, |_| sum_batched(observations, &counter));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * total
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Naive Atomic", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed =
+                        run_contended(threads, iters, |_| sum_naive_atomic(observations, &counter));
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * total
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Naive Mutex", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = Mutex::new(0);
+                    let elapsed =
+                        run_contended(threads, iters, |_| sum_naive_mutex(observations, &counter));
+                    assert_eq!(
+                        *counter.lock().expect("Never fails in this bench"),
+                        threads * iters as usize * total
+                    );
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Thread-local", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let counter = AtomicUsize::new(0);
+                    let elapsed = run_contended_and_flush(
+                        threads,
+                        iters,
+                        |_| sum_thread_local(observations),
+                        |_| flush_thread_local(&counter),
+                    );
+                    assert_eq!(
+                        counter.load(Ordering::Relaxed),
+                        threads * iters as usize * total
+                    );
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
 }
 
-criterion_group!(benches, benchmark_increment,);
+criterion_group!(benches, benchmark_increment, benchmark_contended_sum);
 
 criterion_main!(benches);
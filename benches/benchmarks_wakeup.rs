@@ -0,0 +1,165 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::Notify;
+
+// whose turn it is in the ping-pong of the threads
+const PING: usize = 0;
+const PONG: usize = 1;
+const STOP: usize = 2;
+
+/// `iters` round trips: wakes up a blocked thread, which wakes up the current one back.
+fn park_unpark(iters: u64) -> Duration {
+    let turn = Arc::new(AtomicUsize::new(PING));
+    let current = thread::current();
+    let echo = {
+        let turn = turn.clone();
+        thread::spawn(move || {
+            let mut pongs = 0;
+            loop {
+                match turn.load(Ordering::Acquire) {
+                    PONG => {
+                        pongs += 1;
+                        turn.store(PING, Ordering::Release);
+                        current.unpark();
+                    }
+                    STOP => return pongs,
+                    // `park` may return spuriously, the turn is checked again
+                    _ => thread::park(),
+                }
+            }
+        })
+    };
+    let start = Instant::now();
+    for _ in 0..iters {
+        turn.store(PONG, Ordering::Release);
+        echo.thread().unpark();
+        while turn.load(Ordering::Acquire) != PING {
+            thread::park();
+        }
+    }
+    let elapsed = start.elapsed();
+    turn.store(STOP, Ordering::Release);
+    echo.thread().unpark();
+    assert_eq!(echo.join().expect("Never fails in this bench"), iters);
+    elapsed
+}
+
+fn condvar(iters: u64) -> Duration {
+    let pair = Arc::new((Mutex::new(PING), Condvar::new()));
+    let echo = {
+        let pair = pair.clone();
+        thread::spawn(move || {
+            let (turn, condvar) = &*pair;
+            let mut pongs = 0;
+            let mut turn = turn.lock().expect("Never fails in this bench");
+            loop {
+                turn = condvar
+                    .wait_while(turn, |turn| *turn == PING)
+                    .expect("Never fails in this bench");
+                if *turn == STOP {
+                    return pongs;
+                }
+                pongs += 1;
+                *turn = PING;
+                condvar.notify_one();
+            }
+        })
+    };
+    let (turn, condvar) = &*pair;
+    let start = Instant::now();
+    for _ in 0..iters {
+        let mut turn = turn.lock().expect("Never fails in this bench");
+        *turn = PONG;
+        condvar.notify_one();
+        let _turn = condvar
+            .wait_while(turn, |turn| *turn == PONG)
+            .expect("Never fails in this bench");
+    }
+    let elapsed = start.elapsed();
+    *turn.lock().expect("Never fails in this bench") = STOP;
+    condvar.notify_one();
+    assert_eq!(echo.join().expect("Never fails in this bench"), iters);
+    elapsed
+}
+
+fn channel(iters: u64) -> Duration {
+    let (ping, pings) = crossbeam::channel::bounded(1);
+    let (pong, pongs) = crossbeam::channel::bounded(1);
+    // stops once `ping` is dropped
+    let echo = thread::spawn(move || {
+        let mut count = 0;
+        for message in pings {
+            count += 1;
+            pong.send(message).expect("Never fails in this bench");
+        }
+        count
+    });
+    let start = Instant::now();
+    for i in 0..iters {
+        ping.send(i).expect("Never fails in this bench");
+        assert_eq!(pongs.recv(), Ok(i));
+    }
+    let elapsed = start.elapsed();
+    drop(ping);
+    assert_eq!(echo.join().expect("Never fails in this bench"), iters);
+    elapsed
+}
+
+async fn notify(iters: u64) -> Duration {
+    let ping = Arc::new(Notify::new());
+    let pong = Arc::new(Notify::new());
+    let stop = Arc::new(AtomicBool::new(false));
+    let echo = {
+        let (ping, pong, stop) = (ping.clone(), pong.clone(), stop.clone());
+        tokio::spawn(async move {
+            let mut pongs = 0;
+            loop {
+                ping.notified().await;
+                if stop.load(Ordering::Acquire) {
+                    return pongs;
+                }
+                pongs += 1;
+                pong.notify();
+            }
+        })
+    };
+    let start = Instant::now();
+    for _ in 0..iters {
+        ping.notify();
+        pong.notified().await;
+    }
+    let elapsed = start.elapsed();
+    stop.store(true, Ordering::Release);
+    ping.notify();
+    assert_eq!(echo.await.expect("Never fails in this bench"), iters);
+    elapsed
+}
+
+/// Two worker threads, so the ping-pong crosses the threads, as with the OS primitives.
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .core_threads(2)
+        .build()
+        .expect("Failed to build the runtime")
+}
+
+fn benchmark_wakeup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Wakeup Round-trip");
+    group.bench_function("park/unpark", |b| b.iter_custom(park_unpark));
+    group.bench_function("Condvar", |b| b.iter_custom(condvar));
+    group.bench_function("crossbeam bounded(1)", |b| b.iter_custom(channel));
+    let mut runtime = runtime();
+    group.bench_function("tokio Notify", |b| {
+        b.iter_custom(|iters| runtime.block_on(notify(iters)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_wakeup);
+
+criterion_main!(benches);
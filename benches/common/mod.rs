@@ -0,0 +1,170 @@
+//! The multi-threaded harness and the reporting shared by the benches.
+// every bench uses only a part of it
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Barrier, Mutex};
+use std::time::{Duration, Instant};
+
+/// 1, 2, 4, 8 and the number of cores,
+/// or a comma-separated list of the `BENCH_THREADS` environment variable, e.g. `BENCH_THREADS=2,4`.
+pub fn thread_counts() -> Vec<usize> {
+    let mut counts = match std::env::var("BENCH_THREADS") {
+        Ok(threads) => threads
+            .split(',')
+            .map(|n| {
+                n.trim()
+                    .parse()
+                    .expect("BENCH_THREADS must be a comma-separated list of numbers")
+            })
+            .collect(),
+        Err(_) => vec![1, 2, 4, 8, num_cpus::get()],
+    };
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+/// Runs `work` `iters` times on each of the `threads` threads, which are released simultaneously.
+/// `work` gets the index of the thread, `0..threads`.
+/// Returns the time from the release to the last thread done.
+pub fn run_contended<F: Fn(usize) + Sync>(threads: usize, iters: u64, work: F) -> Duration {
+    run_contended_and_flush(threads, iters, work, |_| {})
+}
+
+/// Same as `run_contended`, but every thread calls `flush` once it's done with `work`,
+/// e.g. to publish what it has accumulated locally. The flush is a part of the measured time.
+pub fn run_contended_and_flush<F, G>(threads: usize, iters: u64, work: F, flush: G) -> Duration
+where
+    F: Fn(usize) + Sync,
+    G: Fn(usize) + Sync,
+{
+    let barrier = Barrier::new(threads + 1);
+    let start = crossbeam::scope(|scope| {
+        for thread in 0..threads {
+            let (barrier, work, flush) = (&barrier, &work, &flush);
+            scope.spawn(move |_| {
+                barrier.wait();
+                for _ in 0..iters {
+                    work(thread);
+                }
+                flush(thread);
+            });
+        }
+        barrier.wait();
+        Instant::now()
+    })
+    .expect("Never fails in this bench");
+    start.elapsed()
+}
+
+/// The counter of a single-threaded bench, shared (e.g. an atomic) or not (the baseline).
+pub trait Counter {
+    fn value(&self) -> usize;
+}
+
+impl Counter for AtomicUsize {
+    fn value(&self) -> usize {
+        self.load(Ordering::Relaxed)
+    }
+}
+
+impl Counter for u64 {
+    fn value(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Counter for Cell<u64> {
+    fn value(&self) -> usize {
+        self.get() as usize
+    }
+}
+
+impl Counter for AtomicU64 {
+    fn value(&self) -> usize {
+        self.load(Ordering::Relaxed) as usize
+    }
+}
+
+impl Counter for Mutex<usize> {
+    fn value(&self) -> usize {
+        *self.lock().expect("Never fails in this bench")
+    }
+}
+
+/// The calls and the time of a variant of a bench over all its measurements, e.g. of `Batched`.
+pub struct Tally {
+    name: String,
+    calls: Cell<u64>,
+    elapsed: Cell<Duration>,
+}
+
+impl Tally {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            calls: Cell::new(0),
+            elapsed: Cell::new(Duration::from_secs(0)),
+        }
+    }
+
+    /// Adds a measurement of `calls` which took `elapsed`.
+    pub fn add(&self, calls: u64, elapsed: Duration) {
+        self.calls.set(self.calls.get() + calls);
+        self.elapsed.set(self.elapsed.get() + elapsed);
+    }
+
+    /// Calls per second.
+    pub fn rate(&self) -> f64 {
+        self.calls.get() as f64 / self.elapsed.get().as_secs_f64()
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.get()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Times `iters` calls of `work` on a fresh `counter` (so nothing carries over between measurements),
+/// asserts that every call added `per_call` to it, and adds the measurement to `tally`.
+/// To be used in `iter_custom`.
+pub fn measure<C: Counter>(
+    tally: &Tally,
+    iters: u64,
+    mut counter: C,
+    mut work: impl FnMut(&mut C),
+    per_call: usize,
+) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iters {
+        work(&mut counter);
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(
+        counter.value(),
+        iters as usize * per_call,
+        "{}: unexpected final value of the counter",
+        tally.name
+    );
+    tally.add(iters, elapsed);
+    elapsed
+}
+
+/// Prints the calls per second of every variant, and the ratio to the first one.
+pub fn print_summary(title: &str, tallies: &[Tally]) {
+    println!("{}", title);
+    let baseline = tallies.first().map_or(f64::NAN, Tally::rate);
+    for tally in tallies {
+        println!(
+            "{:16} {:14.0} operations/s, {:.6}",
+            tally.name,
+            tally.rate(),
+            tally.rate() / baseline
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! A test-and-set spinlock, to compare with the locks which park the waiting threads.
+
+use crossbeam::utils::Backoff;
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub struct Spinlock<T> {
+    locked: AtomicBool,
+    // spin for exponentially (up to a bound) longer after every failed attempt
+    backoff: bool,
+    value: UnsafeCell<T>,
+}
+
+// the access to `value` is serialized by `locked`, so it's enough for `T` to be `Send`
+unsafe impl<T: Send> Sync for Spinlock<T> {}
+
+impl<T> Spinlock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            backoff: false,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn with_backoff(value: T) -> Self {
+        Self {
+            backoff: true,
+            ..Self::new(value)
+        }
+    }
+
+    pub fn lock(&self) -> SpinlockGuard<'_, T> {
+        let backoff = Backoff::new();
+        // `Acquire` pairs with the `Release` of the guard, so the writes of the previous owner are visible
+        while self.locked.swap(true, Ordering::Acquire) {
+            // waiting for the lock to be released with plain loads, so the cache line isn't bounced by writes
+            while self.locked.load(Ordering::Relaxed) {
+                if self.backoff {
+                    backoff.spin();
+                } else {
+                    std::hint::spin_loop();
+                }
+            }
+        }
+        SpinlockGuard { lock: self }
+    }
+}
+
+/// Releases the lock when dropped.
+pub struct SpinlockGuard<'a, T> {
+    lock: &'a Spinlock<T>,
+}
+
+impl<T> Deref for SpinlockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // the guard exists only while the lock is held
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for SpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // the guard exists only while the lock is held, and it's borrowed mutably
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for SpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // the module is built into the binary's tests, as `benches/benchmarks_spinlock.rs` has no
+    // test harness, so the tests import within their bodies
+
+    #[test]
+    fn excludes_the_contending_threads() {
+        use super::Spinlock;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        const THREADS: u64 = 8;
+        const ITERATIONS: u64 = 10_000;
+        for lock in [Spinlock::new((0, 0)), Spinlock::with_backoff((0, 0))] {
+            let lock = Arc::new(lock);
+            let inside = Arc::new(AtomicBool::new(false));
+            let threads: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let (lock, inside) = (lock.clone(), inside.clone());
+                    thread::spawn(move || {
+                        for _ in 0..ITERATIONS {
+                            let mut pair = lock.lock();
+                            assert!(!inside.swap(true, Ordering::Relaxed), "Two owners at once");
+                            // the halves are updated apart, another owner would see them differ
+                            pair.0 += 1;
+                            std::hint::spin_loop();
+                            assert_eq!(pair.0, pair.1 + 1);
+                            pair.1 += 1;
+                            inside.store(false, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().expect("A contending thread panicked");
+            }
+            assert_eq!(*lock.lock(), (THREADS * ITERATIONS, THREADS * ITERATIONS));
+        }
+    }
+
+    #[test]
+    fn dropping_the_guard_unlocks() {
+        use super::Spinlock;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        let lock = Arc::new(Spinlock::new(0));
+        let guard = lock.lock();
+        assert!(lock.locked.load(Ordering::Relaxed));
+        let waiter = {
+            let lock = lock.clone();
+            thread::spawn(move || *lock.lock() += 1)
+        };
+        // the waiter spins while the guard is held
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*guard, 0);
+        assert!(!waiter.is_finished());
+        drop(guard);
+        waiter.join().expect("The waiter panicked");
+        assert!(!lock.locked.load(Ordering::Relaxed));
+        assert_eq!(*lock.lock(), 1);
+        // and the same thread can take it again
+        drop(lock.lock());
+        assert_eq!(*lock.lock(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+use core_affinity::CoreId;
+use tracing::warn;
+
+/// The core of each sync worker of `--pin-threads`: consecutive ones of `cores`, or of all the cores
+/// available to the process, round robin if there are more workers than cores.
+/// None, with a warning, if the platform doesn't support the affinity.
+pub fn plan(n_workers: usize, cores: Option<&[usize]>) -> Option<Vec<usize>> {
+    let available: Vec<usize> = match core_affinity::get_core_ids() {
+        Some(ids) if !ids.is_empty() => ids.into_iter().map(|core| core.id).collect(),
+        _ => {
+            warn!("CPU affinity is not supported on this platform, the workers are not pinned");
+            return None;
+        }
+    };
+    let cores = match cores {
+        Some(cores) => {
+            let (usable, missing): (Vec<usize>, Vec<usize>) =
+                cores.iter().partition(|core| available.contains(core));
+            if !missing.is_empty() {
+                warn!(
+                    "The cores {:?} of --cores are not available to the process, the available ones are {:?}",
+                    missing, available
+                );
+            }
+            if usable.is_empty() {
+                warn!("None of --cores is available, the workers are not pinned");
+                return None;
+            }
+            usable
+        }
+        None => available,
+    };
+    if n_workers > cores.len() {
+        warn!(
+            "{} workers are pinned to {} cores, so they share them",
+            n_workers,
+            cores.len()
+        );
+    }
+    Some((0..n_workers).map(|i| cores[i % cores.len()]).collect())
+}
+
+/// Pins the current thread to the core, returns whether it succeeded.
+pub fn pin(core: usize) -> bool {
+    core_affinity::set_for_current(CoreId { id: core })
+}
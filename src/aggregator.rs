@@ -0,0 +1,751 @@
+use crate::clock::Clock;
+use crate::spill::Spill;
+use crate::stats::{self, PercentileMethod, Reservoir, LOG_BUCKETS};
+use crate::{Failure, ModelConfig, TaskStats};
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::iter;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// the latencies are recorded in µs, longer than an hour are clamped
+const MAX_LATENCY_MICROS: u64 = 3_600_000_000;
+// the sends of the send loop are added to the window in batches of up to this many
+const SENDS_BATCH: usize = 64;
+
+/// Aggregates of a single second of the run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SecondStats {
+    pub second: u64,
+    pub sent: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+    pub max: Option<f64>,
+    /// The mean over the events (sent, started, completed) within the second.
+    pub queue_depth: f64,
+    /// Started, but not completed tasks at the end of the second.
+    pub in_flight: usize,
+}
+
+/// A point-in-time view of the run, as seen by the ticker.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub elapsed: u64,
+    pub sent: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub queue_depth: usize,
+    pub last_second: Option<SecondStats>,
+}
+
+/// The index of the label of a task, see `StreamingAggregator::class`.
+pub type Class = usize;
+
+/// The tasks of a class, labeled by `--stdin-tasks`, or by their costs, see `source::cost_classes`.
+#[derive(Clone)]
+pub struct ClassStats {
+    pub completed: usize,
+    pub failed: usize,
+    /// Latencies (overhead) of successful tasks in µs.
+    pub histogram: Histogram<u64>,
+}
+
+/// Everything collected over the run.
+pub struct RunResults {
+    pub seconds: Vec<SecondStats>,
+    /// Per-task records: all of them, or a uniform sample if `--max-samples` is exceeded.
+    pub samples: Vec<TaskStats>,
+    pub sent: usize,
+    pub completed: usize,
+    pub failed: usize,
+    /// Failed tasks, which would be in time if they hadn't waited in the queue.
+    pub queueing_failures: usize,
+    /// Failed requests to the target, e.g. refused connections.
+    pub io_failures: usize,
+    /// The number of negative overheads clamped to zero.
+    pub clamped: usize,
+    /// Latencies (overhead) of successful tasks in µs, exact up to 3 significant digits.
+    pub histogram: Histogram<u64>,
+    /// The setup times of the requests to the target in µs, e.g. of establishing connections.
+    /// They are not kept in the checkpoints.
+    pub setups: Histogram<u64>,
+    /// By the label, if the tasks have any. They are not kept in the checkpoints either.
+    pub classes: Vec<(String, ClassStats)>,
+    /// The latencies (overhead) of the successful tasks of each second of `seconds` by `stats::log_bucket`.
+    /// They are not kept in the checkpoints, the seconds of a resumed run have none.
+    pub heatmap: Vec<[u32; LOG_BUCKETS]>,
+    /// The time of recording a completion on average, its share of a batch, if any were completed in this run.
+    pub collection: Option<Duration>,
+}
+
+impl RunResults {
+    pub fn is_sampled(&self) -> bool {
+        self.samples.len() < self.completed
+    }
+
+    /// Folds the state of a previous run in, e.g. from a checkpoint.
+    /// Its seconds go first, the per-task records are not kept in the state.
+    pub fn resume(&mut self, state: AggregatorState) {
+        let offset = state.seconds.last().map_or(0, |s| s.second + 1);
+        for s in &mut self.seconds {
+            s.second += offset;
+        }
+        self.heatmap
+            .splice(0..0, vec![[0; LOG_BUCKETS]; state.seconds.len()]);
+        self.seconds.splice(0..0, state.seconds);
+        self.sent += state.sent;
+        self.completed += state.completed;
+        self.failed += state.failed;
+        self.queueing_failures += state.queueing_failures;
+        self.io_failures += state.io_failures;
+        self.clamped += state.clamped;
+        for (value, count) in state.histogram {
+            self.histogram.saturating_record_n(value, count);
+        }
+    }
+}
+
+/// The counters, the closed seconds and the histogram of the aggregator, e.g. for a checkpoint.
+#[derive(Serialize, Deserialize)]
+pub struct AggregatorState {
+    pub sent: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub queueing_failures: usize,
+    // the checkpoints before `--target` had none
+    #[serde(default)]
+    pub io_failures: usize,
+    pub clamped: usize,
+    pub seconds: Vec<SecondStats>,
+    /// (µs, count) of the latency histogram.
+    pub histogram: Vec<(u64, u64)>,
+}
+
+impl AggregatorState {
+    /// Appends the state of a later run, e.g. of the next session of a soak test:
+    /// its seconds follow the ones of this state, the counters and the histograms add up.
+    pub fn append(&mut self, other: AggregatorState) {
+        let offset = self.seconds.last().map_or(0, |s| s.second + 1);
+        self.seconds
+            .extend(other.seconds.into_iter().map(|s| SecondStats {
+                second: s.second + offset,
+                ..s
+            }));
+        self.sent += other.sent;
+        self.completed += other.completed;
+        self.failed += other.failed;
+        self.queueing_failures += other.queueing_failures;
+        self.io_failures += other.io_failures;
+        self.clamped += other.clamped;
+        let mut histogram: BTreeMap<u64, u64> = self.histogram.drain(..).collect();
+        for (value, count) in other.histogram {
+            *histogram.entry(value).or_insert(0) += count;
+        }
+        self.histogram = histogram.into_iter().collect();
+    }
+
+    /// The latency histogram in µs.
+    pub fn histogram(&self) -> Histogram<u64> {
+        let mut histogram =
+            Histogram::new_with_max(MAX_LATENCY_MICROS, 3).expect("Valid histogram bounds");
+        for (value, count) in &self.histogram {
+            histogram.saturating_record_n(*value, *count);
+        }
+        histogram
+    }
+}
+
+/// Collects statistics while the run is in progress, so they can be observed
+/// before all the tasks are completed.
+/// Counters are lock-free, latencies are grouped by the second of completion.
+pub struct StreamingAggregator {
+    start_time: Instant,
+    clock: Clock,
+    sent: AtomicUsize,
+    started: AtomicUsize,
+    completed: AtomicUsize,
+    failed: AtomicUsize,
+    queueing_failures: AtomicUsize,
+    io_failures: AtomicUsize,
+    clamped: AtomicUsize,
+    /// The time spent in `record_completions`.
+    collection_nanos: AtomicU64,
+    method: PercentileMethod,
+    window: Mutex<Window>,
+    records: Mutex<Records>,
+}
+
+struct Records {
+    reservoir: Reservoir<TaskStats>,
+    histogram: Histogram<u64>,
+    setups: Histogram<u64>,
+    /// By the class, the labels are registered by `StreamingAggregator::class`.
+    classes: Vec<(String, ClassStats)>,
+    /// All the records, if `--spill-dir` is set, while the reservoir keeps a sample of them.
+    spill: Option<Spill>,
+}
+
+impl Records {
+    fn keep(&mut self, stats: TaskStats, class: Option<Class>) {
+        let micros = (stats.overhead * 1_000_000.) as u64;
+        if stats.success() {
+            self.histogram.saturating_record(micros);
+        }
+        if let Some(class) = class {
+            let (_, class) = &mut self.classes[class];
+            class.completed += 1;
+            if stats.success() {
+                class.histogram.saturating_record(micros);
+            } else {
+                class.failed += 1;
+            }
+        }
+        if let Some(spill) = &mut self.spill {
+            spill.push(&stats);
+        }
+        self.reservoir.push(stats);
+    }
+}
+
+/// An event of a task at its moment, see `StreamingAggregator::record_events`.
+pub enum Event {
+    Sent(Instant),
+    Started(Instant),
+    /// With the label of the task, if any.
+    Completed(TaskStats, Option<Class>),
+}
+
+#[derive(Default)]
+struct Window {
+    second: u64,
+    sent: usize,
+    completed: usize,
+    failed: usize,
+    latencies: Vec<f64>,
+    queue_depth_sum: usize,
+    depth_samples: usize,
+    // the last observed values, carried over seconds without events
+    queue_depth: usize,
+    in_flight: usize,
+    closed: Vec<SecondStats>,
+    heatmap: Vec<[u32; LOG_BUCKETS]>,
+}
+
+impl StreamingAggregator {
+    /// `max_samples` limits the number of kept per-task records, `None` keeps all of them.
+    /// The sample is reproducible by the `seed`. The tasks are timed by the `clock`.
+    pub fn new(
+        start_time: Instant,
+        clock: Clock,
+        method: PercentileMethod,
+        max_samples: Option<usize>,
+        seed: u64,
+    ) -> Self {
+        Self {
+            start_time,
+            clock,
+            sent: AtomicUsize::new(0),
+            started: AtomicUsize::new(0),
+            completed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            queueing_failures: AtomicUsize::new(0),
+            io_failures: AtomicUsize::new(0),
+            clamped: AtomicUsize::new(0),
+            collection_nanos: AtomicU64::new(0),
+            method,
+            window: Mutex::new(Window::default()),
+            records: Mutex::new(Records {
+                reservoir: Reservoir::new(max_samples, stats::rng(seed, "reservoir")),
+                // an auto-resizing `Histogram::new(3)` starts with the max of 2, and `saturating_record`
+                // clamps to it instead of resizing; recording is ~4x faster than pushing to a `Vec`
+                // and sorting it, and the percentiles don't need the sort (`benches/benchmarks_histogram.rs`)
+                histogram: Histogram::new_with_max(MAX_LATENCY_MICROS, 3)
+                    .expect("Valid histogram bounds"),
+                setups: Histogram::new_with_max(MAX_LATENCY_MICROS, 3)
+                    .expect("Valid histogram bounds"),
+                classes: vec![],
+                spill: None,
+            }),
+        }
+    }
+
+    /// Preallocates the records of the `tasks` of the run, up to `max_samples`, and the latencies of a second
+    /// of up to `per_second` completions, so recording a completion doesn't reallocate them.
+    pub fn with_capacity(self, tasks: usize, per_second: usize) -> Self {
+        self.records
+            .lock()
+            .expect("Poisoned aggregator lock")
+            .reservoir
+            .reserve(tasks);
+        self.window
+            .lock()
+            .expect("Poisoned aggregator lock")
+            .latencies
+            .reserve_exact(per_second);
+        self
+    }
+
+    /// Writes all the records to `spill` as well, see `take_spill`.
+    pub fn with_spill(self, spill: Spill) -> Self {
+        self.records.lock().expect("Poisoned aggregator lock").spill = Some(spill);
+        self
+    }
+
+    /// The spill of the records, once the run is over.
+    pub fn take_spill(&self) -> Option<Spill> {
+        self.records
+            .lock()
+            .expect("Poisoned aggregator lock")
+            .spill
+            .take()
+    }
+
+    /// The class of the tasks labeled `label`, it's registered once per label, so a completion only indexes it.
+    pub fn class(&self, label: &str) -> Class {
+        let mut records = self.records.lock().expect("Poisoned aggregator lock");
+        if let Some(class) = records.classes.iter().position(|(known, _)| known == label) {
+            return class;
+        }
+        records.classes.push((
+            label.to_string(),
+            ClassStats {
+                completed: 0,
+                failed: 0,
+                histogram: Histogram::new_with_max(MAX_LATENCY_MICROS, 3)
+                    .expect("Valid histogram bounds"),
+            },
+        ));
+        records.classes.len() - 1
+    }
+
+    /// A task has been sent, it's counted at once, its second is updated by `sends` in a batch.
+    pub fn record_sent(&self, sends: &mut Sends) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        let second = self.second(self.clock.second_of_event());
+        if sends.pending > 0 && second != sends.second {
+            sends.flush(self);
+        }
+        sends.second = second;
+        sends.pending += 1;
+        if sends.pending >= SENDS_BATCH {
+            sends.flush(self);
+        }
+    }
+
+    /// A task has been picked up for execution (i.e. it left the queue). It's only counted,
+    /// the queue depth is sampled by the batches of the sends and of the completions.
+    pub fn record_started(&self) {
+        self.started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// `class` is the label of the task, if any.
+    pub fn record_completion(&self, stats: &TaskStats, class: Option<Class>) {
+        self.record_completions(iter::once((stats, class)));
+    }
+
+    /// The completions of a batch of a worker, the counters are bumped and the locks are taken once for all of them.
+    /// The time it takes is the overhead of collecting the stats.
+    pub fn record_completions<'a, I>(&self, completions: I)
+    where
+        I: IntoIterator<Item = (&'a TaskStats, Option<Class>)>,
+        I::IntoIter: Clone,
+    {
+        let completions = completions.into_iter();
+        self.count_completions(completions.clone().map(|(stats, _)| stats));
+        self.keep_completions(completions);
+    }
+
+    /// The counters and the seconds of the completions, which the live view and the checkpoints need during the run.
+    pub fn count_completions<'a, I>(&self, completions: I)
+    where
+        I: IntoIterator<Item = &'a TaskStats>,
+        I::IntoIter: Clone,
+    {
+        let start = Instant::now();
+        let completions = completions.into_iter();
+        self.count(completions.clone());
+
+        {
+            let mut window = self.window.lock().expect("Poisoned aggregator lock");
+            for stats in completions {
+                window.roll(self.second(stats.completion_time), self.method);
+                window.complete(stats);
+            }
+            self.sample(&mut window);
+        }
+        self.collection_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Bumps the counters of the completions, once per counter.
+    fn count<'a>(&self, completions: impl IntoIterator<Item = &'a TaskStats>) {
+        let (mut completed, mut failed, mut queueing_failures, mut io_failures, mut clamped) =
+            (0, 0, 0, 0, 0);
+        for stats in completions {
+            completed += 1;
+            match stats.failure {
+                Some(Failure::Queueing) => queueing_failures += 1,
+                Some(Failure::Io) => io_failures += 1,
+                _ => {}
+            }
+            failed += usize::from(stats.failure.is_some());
+            clamped += usize::from(stats.clamped);
+        }
+        for (counter, value) in [
+            (&self.completed, completed),
+            (&self.failed, failed),
+            (&self.queueing_failures, queueing_failures),
+            (&self.io_failures, io_failures),
+            (&self.clamped, clamped),
+        ] {
+            if value > 0 {
+                counter.fetch_add(value, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The records of the completions, i.e. the histograms and the sample, which are needed only at the end.
+    pub fn keep_completions<'a>(
+        &self,
+        completions: impl IntoIterator<Item = (&'a TaskStats, Option<Class>)>,
+    ) {
+        let start = Instant::now();
+        let mut records = self.records.lock().expect("Poisoned aggregator lock");
+        for (stats, class) in completions {
+            records.keep(stats.clone(), class);
+        }
+        drop(records);
+        self.collection_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The events of the tasks in their order, the locks are taken once for all of them, e.g. by `--engine des`,
+    /// which records the events of a single thread. The moments don't go back, as the seconds are closed in turn.
+    pub fn record_events(&self, events: impl IntoIterator<Item = Event>) {
+        let mut window = self.window.lock().expect("Poisoned aggregator lock");
+        let mut records = self.records.lock().expect("Poisoned aggregator lock");
+        for event in events {
+            match event {
+                Event::Sent(moment) => {
+                    self.sent.fetch_add(1, Ordering::Relaxed);
+                    window.roll(self.second(moment), self.method);
+                    window.sent += 1;
+                }
+                Event::Started(moment) => {
+                    self.started.fetch_add(1, Ordering::Relaxed);
+                    window.roll(self.second(moment), self.method);
+                }
+                Event::Completed(stats, class) => {
+                    self.count(iter::once(&stats));
+                    window.roll(self.second(stats.completion_time), self.method);
+                    window.complete(&stats);
+                    records.keep(stats, class);
+                }
+            }
+            self.sample(&mut window);
+        }
+    }
+
+    /// A request had to establish a connection or to spawn a process, it's a part of its overhead.
+    pub fn record_setup(&self, setup: Duration) {
+        let mut records = self.records.lock().expect("Poisoned aggregator lock");
+        records.setups.saturating_record(setup.as_micros() as u64);
+    }
+
+    /// The second of the run of `moment`.
+    fn second(&self, moment: Instant) -> u64 {
+        moment.duration_since(self.start_time).as_secs()
+    }
+
+    fn sample(&self, window: &mut Window) {
+        let sent = self.sent.load(Ordering::Relaxed);
+        let started = self.started.load(Ordering::Relaxed);
+        let completed = self.completed.load(Ordering::Relaxed);
+        window.queue_depth = sent.saturating_sub(started);
+        window.in_flight = started.saturating_sub(completed);
+        window.queue_depth_sum += window.queue_depth;
+        window.depth_samples += 1;
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn sent(&self) -> usize {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let elapsed = Instant::now().duration_since(self.start_time).as_secs();
+        let last_second = {
+            let mut window = self.window.lock().expect("Poisoned aggregator lock");
+            window.roll(elapsed, self.method);
+            window.closed.last().cloned()
+        };
+        let sent = self.sent.load(Ordering::Relaxed);
+        Snapshot {
+            elapsed,
+            sent,
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            queue_depth: sent.saturating_sub(self.started.load(Ordering::Relaxed)),
+            last_second,
+        }
+    }
+}
+
+impl StreamingAggregator {
+    /// A copy of the state taken under the aggregator's own locks, which are held only for the copy.
+    /// The current (incomplete) second is not included.
+    pub fn state(&self) -> AggregatorState {
+        let seconds = self
+            .window
+            .lock()
+            .expect("Poisoned aggregator lock")
+            .closed
+            .clone();
+        let histogram = self
+            .records
+            .lock()
+            .expect("Poisoned aggregator lock")
+            .histogram
+            .iter_recorded()
+            .map(|v| (v.value_iterated_to(), v.count_at_value()))
+            .collect();
+        AggregatorState {
+            sent: self.sent.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            queueing_failures: self.queueing_failures.load(Ordering::Relaxed),
+            io_failures: self.io_failures.load(Ordering::Relaxed),
+            clamped: self.clamped.load(Ordering::Relaxed),
+            seconds,
+            histogram,
+        }
+    }
+
+    /// Closes the last (incomplete) second and returns everything collected over the run.
+    pub fn finish(&self) -> RunResults {
+        let mut window = self.window.lock().expect("Poisoned aggregator lock");
+        let next = window.second + 1;
+        window.roll(next, self.method);
+        let mut records = self.records.lock().expect("Poisoned aggregator lock");
+        let mut classes: Vec<(String, ClassStats)> = records
+            .classes
+            .iter()
+            .filter(|(_, stats)| stats.completed > 0)
+            .cloned()
+            .collect();
+        // the labels of the costs, e.g. `20ms` and `100ms`, by their costs
+        classes.sort_by_cached_key(|(class, _)| (ModelConfig::parse_latency(class), class.clone()));
+        RunResults {
+            seconds: window.closed.clone(),
+            heatmap: window.heatmap.clone(),
+            samples: records.reservoir.take(),
+            sent: self.sent.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            queueing_failures: self.queueing_failures.load(Ordering::Relaxed),
+            io_failures: self.io_failures.load(Ordering::Relaxed),
+            clamped: self.clamped.load(Ordering::Relaxed),
+            histogram: records.histogram.clone(),
+            setups: records.setups.clone(),
+            classes,
+            collection: match self.completed.load(Ordering::Relaxed) {
+                0 => None,
+                completed => Some(Duration::from_nanos(
+                    self.collection_nanos.load(Ordering::Relaxed) / completed as u64,
+                )),
+            },
+        }
+    }
+}
+
+/// The sends of the send loop, which are added to the window of their second together, once there are
+/// `SENDS_BATCH` of them, or once their second is over, so the loop doesn't lock the window per task.
+/// The sends of a second, which is closed by then, are added to it.
+#[derive(Default)]
+pub struct Sends {
+    second: u64,
+    pending: usize,
+}
+
+impl Sends {
+    /// Adds the pending sends to the window, e.g. at the end of the send phase.
+    pub fn flush(&mut self, aggregator: &StreamingAggregator) {
+        if self.pending == 0 {
+            return;
+        }
+        let mut window = aggregator.window.lock().expect("Poisoned aggregator lock");
+        window.roll(self.second, aggregator.method);
+        if self.second < window.second {
+            let second = self.second;
+            if let Some(closed) = window.closed.iter_mut().rev().find(|s| s.second == second) {
+                closed.sent += self.pending;
+            }
+        } else {
+            window.sent += self.pending;
+        }
+        aggregator.sample(&mut window);
+        self.pending = 0;
+    }
+}
+
+/// The completions of a sync worker, counted together once there are `size` of them,
+/// or the first one has waited for `flush_every`, so the aggregator is locked once per batch.
+/// Until then, the live view, the checkpoints and the seconds of the events don't see them.
+/// Their records are kept with them, unless they are `per_worker`, then they are kept by the worker until the end.
+pub struct Batch {
+    completions: Vec<(TaskStats, Option<Class>)>,
+    /// The completions of `per_worker`, which are counted already.
+    counted: usize,
+    size: usize,
+    flush_every: Duration,
+    per_worker: bool,
+    /// When the first completion of the batch was pushed.
+    first: Option<Instant>,
+}
+
+impl Batch {
+    /// A `size` of 1 counts every completion on its own. `per_worker` is the expected number of the completions
+    /// of a `per_worker` one, a quarter more are preallocated, as a worker may serve more than its share.
+    pub fn new(size: usize, flush_every: Duration, per_worker: Option<usize>) -> Self {
+        Self {
+            completions: Vec::with_capacity(per_worker.map_or(size, |n| n + n / 4)),
+            counted: 0,
+            size,
+            flush_every,
+            per_worker: per_worker.is_some(),
+            first: None,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        aggregator: &StreamingAggregator,
+        stats: TaskStats,
+        class: Option<Class>,
+    ) {
+        let first = *self.first.get_or_insert_with(Instant::now);
+        self.completions.push((stats, class));
+        if self.completions.len() - self.counted >= self.size || first.elapsed() >= self.flush_every
+        {
+            self.flush(aggregator);
+        }
+    }
+
+    /// How long the worker may wait for the next task before the batch is to be flushed, `None` if it's empty.
+    pub fn due_in(&self) -> Option<Duration> {
+        self.first
+            .map(|first| self.flush_every.saturating_sub(first.elapsed()))
+    }
+
+    pub fn flush(&mut self, aggregator: &StreamingAggregator) {
+        if self.per_worker {
+            aggregator.count_completions(
+                self.completions[self.counted..]
+                    .iter()
+                    .map(|(stats, _)| stats),
+            );
+            self.counted = self.completions.len();
+        } else if !self.completions.is_empty() {
+            aggregator.record_completions(
+                self.completions
+                    .iter()
+                    .map(|(stats, class)| (stats, *class)),
+            );
+            self.completions.clear();
+        }
+        self.first = None;
+    }
+
+    /// Flushes the tail of the run, and hands the kept records of a `per_worker` one over to the aggregator.
+    pub fn finish(mut self, aggregator: &StreamingAggregator) {
+        self.flush(aggregator);
+        if self.per_worker {
+            aggregator.keep_completions(
+                self.completions
+                    .iter()
+                    .map(|(stats, class)| (stats, *class)),
+            );
+        }
+    }
+}
+
+impl Window {
+    fn complete(&mut self, stats: &TaskStats) {
+        self.completed += 1;
+        if stats.success() {
+            self.latencies.push(stats.overhead);
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    /// Closes the current window if `second` is past it.
+    /// Late completions (from a thread that lost the race for the lock)
+    /// are attributed to the current window.
+    fn roll(&mut self, second: u64, method: PercentileMethod) {
+        while second > self.second {
+            self.latencies
+                .sort_by(|a, b| a.partial_cmp(b).expect("NaN latency"));
+            let closed = SecondStats {
+                second: self.second,
+                sent: self.sent,
+                completed: self.completed,
+                failed: self.failed,
+                p50: method.percentile(&self.latencies, 0.5),
+                p90: method.percentile(&self.latencies, 0.9),
+                p99: method.percentile(&self.latencies, 0.99),
+                max: self.latencies.last().cloned(),
+                queue_depth: if self.depth_samples > 0 {
+                    self.queue_depth_sum as f64 / self.depth_samples as f64
+                } else {
+                    self.queue_depth as f64
+                },
+                in_flight: self.in_flight,
+            };
+            self.closed.push(closed);
+            let mut row = [0; LOG_BUCKETS];
+            for latency in &self.latencies {
+                row[stats::log_bucket(*latency)] += 1;
+            }
+            self.heatmap.push(row);
+            self.second += 1;
+            self.sent = 0;
+            self.completed = 0;
+            self.failed = 0;
+            self.latencies.clear();
+            self.queue_depth_sum = 0;
+            self.depth_samples = 0;
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn format_line(&self) -> String {
+        let format_ms = |v: Option<f64>| match v {
+            Some(v) => format!("{:.3} ms", v * 1000.),
+            None => "-".to_string(),
+        };
+        let (p50, p99) = match &self.last_second {
+            Some(s) => (s.p50, s.p99),
+            None => (None, None),
+        };
+        format!(
+            "[{:>5}s] sent: {}, completed: {}, failed: {}, p50: {}, p99: {}, queue: {}",
+            self.elapsed,
+            self.sent,
+            self.completed,
+            self.failed,
+            format_ms(p50),
+            format_ms(p99),
+            self.queue_depth,
+        )
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(any(test, feature = "count-allocations"))]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Whether `Counting` is the allocator of the process, i.e. of the `count-allocations` feature, or of the tests.
+/// Otherwise, all the counts are zeros.
+pub const ENABLED: bool = cfg!(any(test, feature = "count-allocations"));
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+thread_local! {
+    // a const initializer and no destructor, so it never allocates and is never destroyed
+    static THREAD_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+// the bytes allocated at the moment, and the most of them since `reset_peak`
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(any(test, feature = "count-allocations"))]
+/// The allocator of the process, which counts the allocations (and reallocations), e.g. of a growing `Vec`,
+/// so the run can tell whether its hot path allocates per task. It tracks the allocated bytes too,
+/// so a check can tell whether the memory of a phase is bounded.
+pub struct Counting;
+
+#[cfg(any(test, feature = "count-allocations"))]
+fn count_one() {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    THREAD_ALLOCATIONS.with(|count| count.set(count.get() + 1));
+}
+
+#[cfg(any(test, feature = "count-allocations"))]
+fn grow(bytes: usize) {
+    let allocated = ALLOCATED.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+}
+
+#[cfg(any(test, feature = "count-allocations"))]
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_one();
+        grow(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_one();
+        if new_size > layout.size() {
+            grow(new_size - layout.size());
+        } else {
+            ALLOCATED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// The allocations of all the threads since the start of the process.
+pub fn count() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// The allocations of the current thread since its start, so a check isn't affected by the other threads.
+pub fn count_in_thread() -> usize {
+    THREAD_ALLOCATIONS.with(Cell::get)
+}
+
+/// Starts tracking the peak from the bytes allocated at the moment, which are returned.
+pub fn reset_peak() -> usize {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(allocated, Ordering::Relaxed);
+    allocated
+}
+
+/// The most bytes allocated at once by all the threads since `reset_peak`.
+pub fn peak() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+
+    #[test]
+    fn counts_the_allocations_and_the_reallocations_of_the_thread() {
+        let before = count_in_thread();
+        let mut v: Vec<u64> = black_box(Vec::with_capacity(1));
+        v.extend(0..2);
+        black_box(&v);
+        drop(v);
+        assert_eq!(count_in_thread() - before, 2);
+    }
+
+    #[test]
+    fn a_thread_counts_only_its_own() {
+        let before = count_in_thread();
+        std::thread::spawn(|| black_box(vec![0u8; 64]))
+            .join()
+            .unwrap();
+        // the spawn allocates on this thread, the vector doesn't
+        let spawned = count_in_thread() - before;
+        let before = count_in_thread();
+        std::thread::spawn(|| ()).join().unwrap();
+        assert_eq!(count_in_thread() - before, spawned);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::report::{Chart, Figures};
+use serde::Deserialize;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::warn;
+
+/// The results of a benchmark, as written by criterion to `{criterion_dir}/**/new/`.
+/// Times are in ns.
+pub struct Estimate {
+    /// The group of the benchmark, e.g. `Increment`.
+    pub family: String,
+    /// E.g. `Batched`, the same as the family, if the benchmark is not in a group.
+    pub function: String,
+    /// E.g. `1000`, empty if the benchmark is not parameterized.
+    pub parameter: String,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    /// Elements (or bytes) per iteration, if the throughput of the benchmark is set.
+    pub elements: Option<u64>,
+}
+
+/// `benchmark.json`.
+#[derive(Deserialize)]
+struct BenchmarkId {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    throughput: Option<Throughput>,
+}
+
+#[derive(Deserialize)]
+enum Throughput {
+    Bytes(u64),
+    Elements(u64),
+}
+
+/// `estimates.json`.
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Statistic,
+    median: Statistic,
+    std_dev: Statistic,
+}
+
+#[derive(Deserialize)]
+struct Statistic {
+    point_estimate: f64,
+}
+
+/// Collects the latest results of every benchmark within `dir`, sorted by family, function and parameter.
+/// If `families` is not empty, only the listed ones are collected.
+pub fn collect(dir: &Path, families: &[&str]) -> io::Result<Vec<Estimate>> {
+    let mut estimates = vec![];
+    visit(dir, &mut estimates)?;
+    estimates.retain(|e| families.is_empty() || families.contains(&e.family.as_str()));
+    estimates.sort_by(|a, b| {
+        (&a.family, &a.function)
+            .cmp(&(&b.family, &b.function))
+            .then_with(|| compare_parameters(&a.parameter, &b.parameter))
+    });
+    Ok(estimates)
+}
+
+fn visit(dir: &Path, estimates: &mut Vec<Estimate>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        // `base` and `change` are the previous results, `report` is the HTML report
+        let name = path.file_name().and_then(|name| name.to_str());
+        if name == Some("new") {
+            if let Some(estimate) = read(&path)? {
+                estimates.push(estimate);
+            }
+        } else if name != Some("report") {
+            visit(&path, estimates)?;
+        }
+    }
+    Ok(())
+}
+
+fn read(dir: &Path) -> io::Result<Option<Estimate>> {
+    let (id_path, estimates_path) = (dir.join("benchmark.json"), dir.join("estimates.json"));
+    if !id_path.is_file() || !estimates_path.is_file() {
+        return Ok(None);
+    }
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let id: BenchmarkId = serde_json::from_str(&fs::read_to_string(id_path)?).map_err(invalid)?;
+    let estimates: Estimates =
+        serde_json::from_str(&fs::read_to_string(estimates_path)?).map_err(invalid)?;
+    let family = id.group_id;
+    Ok(Some(Estimate {
+        function: id.function_id.unwrap_or_else(|| family.clone()),
+        family,
+        parameter: id.value_str.unwrap_or_default(),
+        mean: estimates.mean.point_estimate,
+        median: estimates.median.point_estimate,
+        std_dev: estimates.std_dev.point_estimate,
+        elements: id.throughput.map(|throughput| match throughput {
+            Throughput::Bytes(n) | Throughput::Elements(n) => n,
+        }),
+    }))
+}
+
+/// Numerically if both are numbers, e.g. `100` < `1000`.
+fn compare_parameters(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+pub fn write_csv(path: &Path, estimates: &[Estimate]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        "family,function,parameter,mean_ns,median_ns,std_dev_ns"
+    )?;
+    for e in estimates {
+        writeln!(
+            out,
+            "{},{},{},{:.3},{:.3},{:.3}",
+            csv_field(&e.family),
+            csv_field(&e.function),
+            csv_field(&e.parameter),
+            e.mean,
+            e.median,
+            e.std_dev
+        )?;
+    }
+    out.flush()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// A group of bars per family and parameter, a bar (the mean with the standard deviation) per function.
+/// The Y-axis is logarithmic, as the times of the functions differ by orders of magnitude.
+pub fn chart(file_name: String, estimates: &[Estimate]) -> Chart {
+    let mut groups: Vec<(&str, &str)> = vec![];
+    let mut functions: Vec<&str> = vec![];
+    for e in estimates {
+        if !groups.contains(&(&e.family, &e.parameter)) {
+            groups.push((&e.family, &e.parameter));
+        }
+        if !functions.contains(&e.function.as_str()) {
+            functions.push(&e.function);
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(b.0).then_with(|| compare_parameters(a.1, b.1)));
+
+    let width = 0.8 / functions.len() as f64;
+    let x_ticks = groups
+        .iter()
+        .enumerate()
+        .map(|(i, (family, parameter))| (i as f64, format!("{} {}", family, parameter)))
+        .collect();
+    let mut chart = Chart::new(file_name, "Benchmark", "Time, µs")
+        .x_ticks(x_ticks)
+        .log_y();
+    for (j, function) in functions.iter().enumerate() {
+        let offset = (j as f64 - (functions.len() - 1) as f64 / 2.) * width;
+        let (mut x, mut y, mut errors) = (vec![], vec![], vec![]);
+        for e in estimates.iter().filter(|e| e.function == *function) {
+            let group = groups
+                .iter()
+                .position(|g| *g == (e.family.as_str(), e.parameter.as_str()))
+                .expect("Every estimate is in a group");
+            x.push(group as f64 + offset);
+            y.push(e.mean / 1000.);
+            errors.push(e.std_dev / 1000.);
+        }
+        chart = chart.bars(function, x, y, errors, width);
+    }
+    chart
+}
+
+/// A line per function of the throughput (millions of elements, or iterations, per second)
+/// over the numeric parameter of `family`, e.g. a batch size. The X-axis is log2, as the sweeps double the parameter.
+/// `None` if there are no such results of the family.
+pub fn sweep_chart(file_name: String, family: &str, estimates: &[Estimate]) -> Option<Chart> {
+    let sweep: Vec<(&Estimate, f64)> = estimates
+        .iter()
+        .filter(|e| e.family == family)
+        .filter_map(|e| match e.parameter.parse::<f64>() {
+            Ok(parameter) if parameter > 0. => Some((e, parameter)),
+            _ => None,
+        })
+        .collect();
+    if sweep.is_empty() {
+        return None;
+    }
+    let mut x_ticks: Vec<(f64, String)> = vec![];
+    let mut functions: Vec<&str> = vec![];
+    for (e, parameter) in sweep.iter() {
+        if !x_ticks.iter().any(|(_, label)| *label == e.parameter) {
+            x_ticks.push((parameter.log2(), e.parameter.clone()));
+        }
+        if !functions.contains(&e.function.as_str()) {
+            functions.push(&e.function);
+        }
+    }
+    let mut chart = Chart::new(file_name, family, "Throughput, M/s").x_ticks(x_ticks);
+    for function in functions {
+        // `collect` sorted them by the parameter
+        let (x, y) = sweep
+            .iter()
+            .filter(|(e, _)| e.function == function)
+            .map(|(e, parameter)| {
+                (
+                    parameter.log2(),
+                    e.elements.unwrap_or(1) as f64 / e.mean * 1000.,
+                )
+            })
+            .unzip();
+        chart = chart.line(Some(function), x, y);
+    }
+    Some(chart)
+}
+
+/// `Batch Size Sweep` -> `batch_size_sweep`.
+fn file_stem(family: &str) -> String {
+    family
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Writes `bench_summary.csv` and renders `bench_summary.png` of the results in `dir`,
+/// and `bench_sweep_{family}.png` of every family of `sweeps`.
+pub fn run(dir: &Path, families: &[&str], sweeps: &[&str], figures: &Figures) -> io::Result<()> {
+    let estimates = collect(dir, families)?;
+    if estimates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "No benchmark results in {}, run `cargo bench` first",
+                dir.display()
+            ),
+        ));
+    }
+    let csv_path = Path::new(".").join("bench_summary.csv");
+    write_csv(&csv_path, &estimates)?;
+    println!(
+        "{} benchmarks written to {}",
+        estimates.len(),
+        csv_path.display()
+    );
+    figures.render(&chart("bench_summary.png".to_string(), &estimates));
+    // the sweeps may be outside of `families`
+    let swept = if sweeps.is_empty() {
+        vec![]
+    } else {
+        collect(dir, sweeps)?
+    };
+    for family in sweeps {
+        let file_name = format!("bench_sweep_{}.png", file_stem(family));
+        match sweep_chart(file_name, family, &swept) {
+            Some(chart) => figures.render(&chart),
+            None => warn!("No numeric results of {} to plot", family),
+        }
+    }
+    Ok(())
+}
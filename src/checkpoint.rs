@@ -0,0 +1,83 @@
+use crate::aggregator::{AggregatorState, StreamingAggregator};
+use crate::metadata::RunMetadata;
+use crossbeam::channel::{bounded, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// The state of a run in progress, written as `checkpoint_{name}.json`.
+/// The metadata fields are at the top level, so `compare` accepts a checkpoint as well.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(flatten)]
+    pub metadata: RunMetadata,
+    /// Since the start of the run, s.
+    pub elapsed: f64,
+    pub state: AggregatorState,
+}
+
+impl Checkpoint {
+    pub fn path(name: &str) -> PathBuf {
+        Path::new(".").join(format!("checkpoint_{}.json", name))
+    }
+
+    /// Atomically, i.e. a reader sees either the previous checkpoint or the new one.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self).expect("Checkpoint is serializable");
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Periodically writes checkpoints on a separate thread,
+/// so neither serialization nor I/O happen on the hot path.
+pub struct Checkpointer {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Checkpointer {
+    pub fn start(
+        aggregator: Arc<StreamingAggregator>,
+        metadata: RunMetadata,
+        start_time: Instant,
+        every: Duration,
+    ) -> Self {
+        let (stop, stopped) = bounded(1);
+        let path = Checkpoint::path(&metadata.config.name);
+        let mut checkpoint = Checkpoint {
+            metadata,
+            elapsed: 0.,
+            state: aggregator.state(),
+        };
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(every) {
+                checkpoint.elapsed = start_time.elapsed().as_secs_f64();
+                checkpoint.state = aggregator.state();
+                match checkpoint.write(&path) {
+                    Ok(()) => debug!("Checkpoint written to {}", path.display()),
+                    Err(e) => warn!("Failed to write {}: {}", path.display(), e),
+                }
+            }
+        });
+        Self { stop, handle }
+    }
+
+    pub fn stop(self) {
+        // the receiver is gone only if the thread has already stopped
+        self.stop.send(()).unwrap_or_default();
+        self.handle.join().expect("Checkpointer failed");
+    }
+}
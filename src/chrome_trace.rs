@@ -0,0 +1,166 @@
+use crate::TaskStats;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// the processes of the trace, i.e. the groups of tracks in Perfetto
+const SERVICE_PID: u64 = 1;
+const QUEUE_PID: u64 = 2;
+const COUNTERS_PID: u64 = 3;
+
+/// Writes the tasks as the Trace Event format of Chrome, which Perfetto loads.
+/// A task is a slice of its service on the track of its sync worker, or on a lane of
+/// the concurrent async tasks, and a slice of its queue wait on a lane of the queue.
+/// The counters of the in-flight and the queued tasks are of the same tasks.
+/// The timestamps are µs since `start`, the start of the run.
+pub fn write(path: &Path, tasks: &[TaskStats], start: Instant) -> io::Result<()> {
+    let us = |instant: Instant| instant.saturating_duration_since(start).as_micros() as u64;
+    let mut tasks: Vec<&TaskStats> = tasks.iter().collect();
+    tasks.sort_by_key(|task| task.start_time);
+
+    let mut events = vec![
+        process_name(SERVICE_PID, "service"),
+        process_name(QUEUE_PID, "queue"),
+        process_name(COUNTERS_PID, "load"),
+    ];
+    let queue_lanes = lanes(tasks.iter().map(|t| (t.start_time, t.service_start_time)));
+    // the sync workers have tracks of their own, the async tasks are packed into lanes
+    let by_worker = tasks.iter().all(|t| t.worker.is_some());
+    let service_tracks = if by_worker {
+        tasks.iter().map(|t| t.worker.unwrap_or_default()).collect()
+    } else {
+        let mut by_service: Vec<(usize, &TaskStats)> = tasks.iter().copied().enumerate().collect();
+        by_service.sort_by_key(|(_, t)| t.service_start_time);
+        let lanes = lanes(
+            by_service
+                .iter()
+                .map(|(_, t)| (t.service_start_time, t.completion_time)),
+        );
+        let mut tracks = vec![0; tasks.len()];
+        for ((i, _), lane) in by_service.iter().zip(lanes) {
+            tracks[*i] = lane;
+        }
+        tracks
+    };
+    for (tid, name) in track_names(&service_tracks, if by_worker { "worker" } else { "lane" }) {
+        events.push(thread_name(SERVICE_PID, tid, &name));
+    }
+    for (tid, name) in track_names(&queue_lanes, "lane") {
+        events.push(thread_name(QUEUE_PID, tid, &name));
+    }
+
+    for ((task, service_track), queue_lane) in tasks.iter().zip(&service_tracks).zip(&queue_lanes) {
+        let args = json!({
+            "latency_ms": (task.completion_time - task.start_time).as_secs_f64() * 1000.,
+            "overhead_ms": task.overhead * 1000.,
+            "failure": task.failure.map(|failure| failure.name()),
+        });
+        events.push(slice(
+            "queue",
+            QUEUE_PID,
+            *queue_lane,
+            us(task.start_time),
+            us(task.service_start_time),
+            args.clone(),
+        ));
+        events.push(slice(
+            if task.success() {
+                "task"
+            } else {
+                "failed task"
+            },
+            SERVICE_PID,
+            *service_track,
+            us(task.service_start_time),
+            us(task.completion_time),
+            args,
+        ));
+    }
+
+    // (µs, queued, in flight) deltas
+    let mut changes: Vec<(u64, i64, i64)> = Vec::with_capacity(tasks.len() * 3);
+    for task in &tasks {
+        changes.push((us(task.start_time), 1, 0));
+        changes.push((us(task.service_start_time), -1, 1));
+        changes.push((us(task.completion_time), 0, -1));
+    }
+    changes.sort_by_key(|(ts, _, _)| *ts);
+    let (mut queued, mut in_flight) = (0, 0);
+    for (i, (ts, queue_delta, in_flight_delta)) in changes.iter().enumerate() {
+        queued += queue_delta;
+        in_flight += in_flight_delta;
+        // a counter per moment, once all its changes are applied
+        if !matches!(changes.get(i + 1), Some((next, _, _)) if next == ts) {
+            events.push(counter("queue depth", *ts, queued));
+            events.push(counter("in flight", *ts, in_flight));
+        }
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")?;
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            writeln!(out, ",")?;
+        }
+        serde_json::to_writer(&mut out, event)?;
+    }
+    writeln!(out, "]}}")?;
+    out.flush()
+}
+
+/// The lowest lane free at the start of every interval, the intervals are sorted by their starts.
+fn lanes(intervals: impl Iterator<Item = (Instant, Instant)>) -> Vec<usize> {
+    let mut busy: BinaryHeap<Reverse<(Instant, usize)>> = BinaryHeap::new();
+    let mut free: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+    let mut lanes = vec![];
+    for (start, end) in intervals {
+        while let Some(Reverse((until, lane))) = busy.peek().copied() {
+            if until > start {
+                break;
+            }
+            busy.pop();
+            free.push(Reverse(lane));
+        }
+        let lane = free.pop().map_or(busy.len(), |Reverse(lane)| lane);
+        busy.push(Reverse((end, lane)));
+        lanes.push(lane);
+    }
+    lanes
+}
+
+/// (tid, name) of every used track, e.g. `worker 3`.
+fn track_names(tracks: &[usize], name: &str) -> Vec<(usize, String)> {
+    let max = tracks.iter().max().map_or(0, |max| max + 1);
+    (0..max)
+        .map(|tid| (tid, format!("{} {}", name, tid)))
+        .collect()
+}
+
+fn process_name(pid: u64, name: &str) -> Value {
+    json!({"name": "process_name", "ph": "M", "pid": pid, "args": {"name": name}})
+}
+
+fn thread_name(pid: u64, tid: usize, name: &str) -> Value {
+    json!({"name": "thread_name", "ph": "M", "pid": pid, "tid": tid, "args": {"name": name}})
+}
+
+fn slice(name: &str, pid: u64, tid: usize, from: u64, to: u64, args: Value) -> Value {
+    json!({
+        "name": name,
+        "ph": "X",
+        "pid": pid,
+        "tid": tid,
+        "ts": from,
+        "dur": to - from,
+        "args": args,
+    })
+}
+
+fn counter(name: &str, ts: u64, value: i64) -> Value {
+    json!({"name": name, "ph": "C", "pid": COUNTERS_PID, "ts": ts, "args": {"value": value}})
+}
@@ -0,0 +1,120 @@
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the timestamps of the calibration of the cost of a timestamp
+const CALIBRATION_CALLS: u32 = 1_000_000;
+// the lag of the coarse clock is observed for this long
+const LAG_WINDOW: Duration = Duration::from_millis(200);
+
+/// The time since its start, stored in an atomic by a background thread every `resolution`,
+/// so a timestamp is a load, rather than a call of `Instant::now()`.
+pub struct CoarseClock {
+    start: Instant,
+    nanos: AtomicU64,
+    stopped: AtomicBool,
+    resolution: Duration,
+}
+
+impl CoarseClock {
+    pub fn start(resolution: Duration) -> Arc<Self> {
+        let clock = Arc::new(Self {
+            start: Instant::now(),
+            nanos: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+            resolution,
+        });
+        let updated = clock.clone();
+        thread::spawn(move || {
+            while !updated.stopped.load(Ordering::Relaxed) {
+                let nanos = updated.start.elapsed().as_nanos() as u64;
+                updated.nanos.store(nanos, Ordering::Relaxed);
+                thread::sleep(updated.resolution);
+            }
+        });
+        clock
+    }
+
+    /// Up to `resolution` stale, more if the updating thread isn't scheduled in time.
+    pub fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Measures the cost of a timestamp of both clocks, and how far this one lags behind `Instant::now()`.
+    pub fn calibrate(&self) -> Calibration {
+        let cost = |now: &dyn Fn() -> Instant| {
+            let start = Instant::now();
+            for _ in 0..CALIBRATION_CALLS {
+                black_box(now());
+            }
+            start.elapsed() / CALIBRATION_CALLS
+        };
+        let precise_cost = cost(&Instant::now);
+        let coarse_cost = cost(&|| self.now());
+        let start = Instant::now();
+        let (mut total_lag, mut max_lag, mut samples) =
+            (Duration::default(), Duration::default(), 0);
+        while start.elapsed() < LAG_WINDOW {
+            let coarse = self.now();
+            let lag = Instant::now().saturating_duration_since(coarse);
+            total_lag += lag;
+            max_lag = max_lag.max(lag);
+            samples += 1;
+        }
+        Calibration {
+            precise_cost,
+            coarse_cost,
+            mean_lag: total_lag / samples.max(1),
+            max_lag,
+        }
+    }
+}
+
+/// The costs and the accuracy of the coarse clock, measured before the run.
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Of `Instant::now()`.
+    pub precise_cost: Duration,
+    pub coarse_cost: Duration,
+    pub mean_lag: Duration,
+    /// The bound of the error of a coarse timestamp, as observed.
+    pub max_lag: Duration,
+}
+
+/// The clock of the timestamps of the tasks: `Instant::now()`, or the coarse one of `--coarse-clock`,
+/// for the seconds of the events only, or, if `full`, for the latencies too.
+#[derive(Clone)]
+pub enum Clock {
+    Precise,
+    Coarse { clock: Arc<CoarseClock>, full: bool },
+}
+
+impl Clock {
+    /// The timestamp of a latency.
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::Coarse { clock, full: true } => clock.now(),
+            _ => Instant::now(),
+        }
+    }
+
+    /// The timestamp of an event, which only places it in a second of the run, e.g. a sent request.
+    pub fn second_of_event(&self) -> Instant {
+        match self {
+            Clock::Coarse { clock, .. } => clock.now(),
+            Clock::Precise => Instant::now(),
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Clock::Coarse { clock, .. } = self {
+            clock.stop();
+        }
+    }
+}
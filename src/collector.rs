@@ -0,0 +1,227 @@
+use crate::aggregator::{Batch, Class, Sends, StreamingAggregator};
+use crate::clock::Clock;
+use crate::stats::PercentileMethod;
+use crate::{StatsBatch, TaskStats};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// the zero-cost tasks of the calibration of each channel, split between the workers
+const CALIBRATION_TASKS: usize = 100_000;
+
+/// How the sync workers deliver their completions to the aggregator, see `--stats-channel`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum StatsChannel {
+    /// The workers lock the aggregator themselves, once per batch.
+    #[default]
+    MutexVec,
+    /// The workers send every completion to a collector thread, which records them.
+    Mpsc,
+    /// The workers count their completions in batches, but keep the records until the end of the run.
+    PerWorker,
+}
+
+impl StatsChannel {
+    pub const ALL: [StatsChannel; 3] = [
+        StatsChannel::MutexVec,
+        StatsChannel::Mpsc,
+        StatsChannel::PerWorker,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StatsChannel::MutexVec => "mutex-vec",
+            StatsChannel::Mpsc => "mpsc",
+            StatsChannel::PerWorker => "per-worker",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        StatsChannel::ALL
+            .iter()
+            .copied()
+            .find(|channel| channel.name() == name)
+    }
+}
+
+type Completion = (TaskStats, Option<Class>);
+
+/// The end of the channel of a worker.
+pub enum Delivery {
+    Batch(Batch),
+    Mpsc(Sender<Completion>),
+}
+
+impl Delivery {
+    pub fn push(
+        &mut self,
+        aggregator: &StreamingAggregator,
+        stats: TaskStats,
+        class: Option<Class>,
+    ) {
+        match self {
+            Delivery::Batch(batch) => batch.push(aggregator, stats, class),
+            Delivery::Mpsc(sender) => sender
+                .send((stats, class))
+                .expect("The collector outlives the workers"),
+        }
+    }
+
+    /// See `Batch::due_in`, the completions sent to the collector are never due.
+    pub fn due_in(&self) -> Option<Duration> {
+        match self {
+            Delivery::Batch(batch) => batch.due_in(),
+            Delivery::Mpsc(_) => None,
+        }
+    }
+
+    pub fn flush(&mut self, aggregator: &StreamingAggregator) {
+        if let Delivery::Batch(batch) = self {
+            batch.flush(aggregator);
+        }
+    }
+
+    /// At the end of the worker, so the tail of the run isn't lost.
+    pub fn finish(self, aggregator: &StreamingAggregator) {
+        if let Delivery::Batch(batch) = self {
+            batch.finish(aggregator);
+        }
+    }
+}
+
+/// The channel of `--stats-channel`: the deliveries of the workers, and the collector thread of `Mpsc`,
+/// which records whatever has been sent, up to a batch at once.
+pub struct Collector {
+    channel: StatsChannel,
+    size: usize,
+    flush_every: Duration,
+    /// The completions a worker is expected to keep, with `PerWorker`.
+    per_worker: usize,
+    sender: Option<Sender<Completion>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Collector {
+    pub fn start(
+        channel: StatsChannel,
+        batch: Option<StatsBatch>,
+        per_worker: usize,
+        aggregator: Arc<StreamingAggregator>,
+    ) -> Self {
+        let (size, flush_every) = batch.map_or((1, Duration::default()), |batch| {
+            (batch.size, Duration::from_millis(batch.flush_every))
+        });
+        let (sender, thread) = match channel {
+            StatsChannel::Mpsc => {
+                let (sender, receiver) = mpsc::channel::<Completion>();
+                let thread = thread::spawn(move || {
+                    let mut completions = Vec::with_capacity(size);
+                    // the loop ends once the workers are gone and the channel is drained
+                    while let Ok(completion) = receiver.recv() {
+                        completions.push(completion);
+                        completions.extend(receiver.try_iter().take(size - 1));
+                        aggregator.record_completions(
+                            completions.iter().map(|(stats, class)| (stats, *class)),
+                        );
+                        completions.clear();
+                    }
+                });
+                (Some(sender), Some(thread))
+            }
+            _ => (None, None),
+        };
+        Self {
+            channel,
+            size,
+            flush_every,
+            per_worker,
+            sender,
+            thread,
+        }
+    }
+
+    pub fn delivery(&self) -> Delivery {
+        match (&self.sender, self.channel) {
+            (Some(sender), _) => Delivery::Mpsc(sender.clone()),
+            (None, StatsChannel::PerWorker) => Delivery::Batch(Batch::new(
+                self.size,
+                self.flush_every,
+                Some(self.per_worker),
+            )),
+            (None, _) => Delivery::Batch(Batch::new(self.size, self.flush_every, None)),
+        }
+    }
+
+    /// Waits for the collector thread to record the rest, once the deliveries of the workers are finished.
+    pub fn stop(mut self) {
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("Collector failed");
+        }
+    }
+}
+
+/// The time of recording a zero-cost task in a throwaway aggregator by each channel,
+/// from `n_workers` threads at once, per task: its send, its start and the delivery of its completion,
+/// the same calls as in a run.
+pub fn calibrate(
+    n_workers: usize,
+    batch: Option<StatsBatch>,
+    max_samples: Option<usize>,
+    seed: u64,
+) -> Vec<(StatsChannel, Duration)> {
+    let per_worker = (CALIBRATION_TASKS / n_workers).max(1);
+    StatsChannel::ALL
+        .iter()
+        .map(|channel| {
+            let aggregator = Arc::new(StreamingAggregator::new(
+                Instant::now(),
+                Clock::Precise,
+                PercentileMethod::NearestRank,
+                max_samples,
+                seed,
+            ));
+            let collector = Collector::start(*channel, batch, per_worker, aggregator.clone());
+            let start = Instant::now();
+            let workers: Vec<JoinHandle<()>> = (0..n_workers)
+                .map(|_| {
+                    let mut delivery = collector.delivery();
+                    let aggregator = aggregator.clone();
+                    thread::spawn(move || {
+                        let mut sends = Sends::default();
+                        for _ in 0..per_worker {
+                            aggregator.record_sent(&mut sends);
+                            aggregator.record_started();
+                            let now = Instant::now();
+                            let stats =
+                                TaskStats::new(Duration::MAX, now, now, Duration::default(), now);
+                            delivery.push(&aggregator, stats, None);
+                        }
+                        sends.flush(&aggregator);
+                        delivery.finish(&aggregator);
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.join().expect("Worker failed");
+            }
+            collector.stop();
+            let tasks = (per_worker * n_workers) as u64;
+            (
+                *channel,
+                Duration::from_nanos(start.elapsed().as_nanos() as u64 / tasks),
+            )
+        })
+        .collect()
+}
+
+/// E.g. `mutex-vec 0.512 µs, mpsc 0.901 µs, per-worker 0.333 µs per task`.
+pub fn format_costs(costs: &[(StatsChannel, Duration)]) -> String {
+    let costs: Vec<String> = costs
+        .iter()
+        .map(|(channel, cost)| format!("{} {:.3} µs", channel.name(), cost.as_secs_f64() * 1e6))
+        .collect();
+    format!("{} per task", costs.join(", "))
+}
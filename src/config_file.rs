@@ -0,0 +1,640 @@
+use crate::ModelConfig;
+use clap::{App, Arg, ArgMatches, ArgSettings, ErrorKind};
+use std::env;
+use std::fs;
+use toml::Value;
+use tracing::info;
+
+// the subcommand of the model, the other keys are the long names of the arguments, e.g. `num_req`
+const MODE: &str = "mode";
+// the subcommands of `mode`, with the required arguments to probe their options
+const MODES: &[(&str, &[&str])] = &[("sync", &["--threads=1"]), ("async", &[])];
+// the prefix of the variables of the options, e.g. `CDB_RATE`, which override the file
+const ENV_PREFIX: &str = "CDB_";
+// the keys without defaults, which are required
+const EXAMPLES: &[(&str, &str)] = &[
+    ("name", "\"sync_30s\""),
+    ("rate", "1000"),
+    ("num_req", "30000"),
+    ("latency", "\"20ms*9,30s\""),
+];
+
+/// The options of `--config <FILE>` or of the environment.
+#[derive(Default)]
+struct Layer {
+    /// The subcommand, sync or async.
+    mode: Option<String>,
+    options: Vec<String>,
+    /// (the mode, the origin, e.g. `CDB_THREADS`, the tokens) of an option of a mode.
+    mode_options: Vec<(&'static str, String, Vec<String>)>,
+}
+
+/// Where the values set by the command line, the environment or `--config <FILE>` come from,
+/// i.e. all but the defaults. The last source of an argument overrides the previous ones.
+#[derive(Default)]
+pub struct Sources(Vec<Source>);
+
+struct Source {
+    /// The id of the argument, e.g. `NUM_REQUESTS`.
+    name: String,
+    /// E.g. `num_req`.
+    key: String,
+    /// The subcommand of the argument, e.g. `sync` of `threads`.
+    mode: Option<&'static str>,
+    flag: bool,
+    /// E.g. `CDB_RATE` or `run.toml`.
+    origin: String,
+}
+
+impl Sources {
+    fn set(&mut self, arg: &Arg, mode: Option<&'static str>, origin: &str) {
+        let name = arg.get_name().to_string();
+        self.0
+            .retain(|source| source.name != name || source.mode != mode);
+        self.0.push(Source {
+            name,
+            key: key_of(arg),
+            mode,
+            flag: !arg.is_set(ArgSettings::TakesValue),
+            origin: origin.to_string(),
+        });
+    }
+
+    /// Logs the value of every argument with its source, e.g. `rate = 1000 (CDB_RATE)`, and the resolved
+    /// count, if it's not a plain integer, e.g. `num_req = 10M = 10000000 (the command line)`.
+    /// The options of a mode other than the one of the run are left out.
+    pub fn log(&self, matches: &ArgMatches) {
+        let lines: Vec<String> = self
+            .0
+            .iter()
+            .filter_map(|source| {
+                let matches = match source.mode {
+                    Some(mode) => matches.subcommand_matches(mode)?,
+                    None => matches,
+                };
+                let occurrences = matches.occurrences_of(&source.name);
+                let value = match matches.values_of(&source.name) {
+                    // e.g. `verbose = 2`
+                    _ if source.flag && occurrences > 1 => occurrences.to_string(),
+                    _ if source.flag && occurrences == 1 => "true".to_string(),
+                    Some(values) if !source.flag => values.collect::<Vec<_>>().join(","),
+                    _ => return None,
+                };
+                // e.g. `num_req = 10M = 10000000`
+                let resolved = match ModelConfig::parse_count(&value) {
+                    Some(n) if n.to_string() != value => format!(" = {}", n),
+                    _ => String::new(),
+                };
+                Some(format!(
+                    "  {} = {}{} ({})",
+                    source.key, value, resolved, source.origin
+                ))
+            })
+            .collect();
+        if !lines.is_empty() {
+            info!("Non-default values:\n{}", lines.join("\n"));
+        }
+    }
+}
+
+/// The command line with the options of `--config <FILE>` and of the `CDB_` environment variables
+/// inserted before the ones of the command line, so the latter override them, and the variables
+/// override the file. The `mode` of the file, or `CDB_MODE`, is the subcommand, unless the command line
+/// has one. The file is `CDB_CONFIG`, unless `--config` is set.
+pub fn merge(args: Vec<String>) -> (Vec<String>, Sources) {
+    let app = ModelConfig::cli();
+    let (subcommand, path, found) = scan(&app, &args);
+    let mut sources = Sources::default();
+    let file = match path.or_else(|| env::var(var_name("config")).ok()) {
+        Some(path) => read(&app, &path, &mut sources),
+        None => Layer::default(),
+    };
+    let env = environment(&app, &mut sources);
+    for arg in found {
+        sources.set(arg, None, "the command line");
+    }
+
+    let mut merged = vec![args[0].clone()];
+    merged.extend(file.options);
+    merged.extend(env.options);
+    merged.extend_from_slice(&args[1..subcommand]);
+    let command_line = args.get(subcommand);
+    let mode = command_line.cloned().or(env.mode).or(file.mode);
+    let name = match mode {
+        Some(name) => name,
+        None => return (merged, sources),
+    };
+    merged.push(name.clone());
+    // the ones of the file are of its mode, which the command line or `CDB_MODE` may override
+    for (_, _, tokens) in file
+        .mode_options
+        .into_iter()
+        .filter(|(of, _, _)| *of == name)
+    {
+        merged.extend(tokens);
+    }
+    for (of, origin, tokens) in env.mode_options {
+        if of == name {
+            merged.extend(tokens);
+        } else if command_line.is_none() {
+            fail(
+                format!(
+                    "{} is an option of mode = \"{}\", but the mode is {}",
+                    origin, of, name
+                ),
+                ErrorKind::ArgumentConflict,
+            );
+        }
+    }
+    if command_line.is_some() {
+        if let Some((mode, _)) = MODES.iter().find(|(mode, _)| *mode == name) {
+            let (_, _, found) = scan(subcommand_of(&app, mode), &args[subcommand..]);
+            for arg in found {
+                sources.set(arg, Some(mode), "the command line");
+            }
+        }
+        merged.extend_from_slice(&args[subcommand + 1..]);
+    }
+    (merged, sources)
+}
+
+/// The options of the file at `path`, validated.
+fn read(app: &App<'static>, path: &str, sources: &mut Sources) -> Layer {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| fail(format!("Failed to read {}: {}", path, e), ErrorKind::Io));
+    let table = match text.parse::<Value>() {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => unreachable!("A TOML document is a table"),
+        Err(e) => fail(format!("Invalid {}: {}", path, e), ErrorKind::InvalidValue),
+    };
+    let origin = |key: &str| format!("`{}`{} of {}", key, line_of(&text, key), path);
+
+    let mode = table
+        .get(MODE)
+        .map(|mode| parse_mode(mode.as_str(), &origin(MODE), &mode.to_string()));
+    let mut layer = Layer {
+        mode,
+        ..Layer::default()
+    };
+    for (key, value) in &table {
+        if key == MODE {
+            continue;
+        }
+        if let Some(arg) = arg_of(app, key) {
+            let tokens = tokens(arg, value, &origin(key));
+            validate(
+                &[tokens.as_slice(), &["async".to_string()]].concat(),
+                &origin(key),
+            );
+            sources.set(arg, None, path);
+            layer.options.extend(tokens);
+            continue;
+        }
+        let (name, required, arg) = MODES
+            .iter()
+            .find_map(|(name, required)| {
+                Some((*name, *required, arg_of(subcommand_of(app, name), key)?))
+            })
+            .unwrap_or_else(|| {
+                fail(
+                    format!("Unknown key {}", origin(key)),
+                    ErrorKind::UnknownArgument,
+                )
+            });
+        if layer.mode.as_deref() != Some(name) {
+            fail(
+                format!("{} is an option of mode = \"{}\"", origin(key), name),
+                ErrorKind::ArgumentConflict,
+            );
+        }
+        let tokens = tokens(arg, value, &origin(key));
+        validate(&probe(name, required, &tokens), &origin(key));
+        sources.set(arg, Some(name), path);
+        layer.mode_options.push((name, origin(key), tokens));
+    }
+    layer
+}
+
+/// The options of the `CDB_` environment variables, validated, e.g. `CDB_RATE=1000` or `CDB_MODE=async`.
+/// The empty ones are ignored.
+fn environment(app: &App<'static>, sources: &mut Sources) -> Layer {
+    let value_of = |key: &str| {
+        env::var(var_name(key))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    let origin = |key: &str| format!("`{}`", var_name(key));
+    let mut layer = Layer {
+        mode: value_of(MODE).map(|mode| parse_mode(Some(&mode), &origin(MODE), &mode)),
+        ..Layer::default()
+    };
+    for arg in options(app) {
+        let key = key_of(arg);
+        if let Some(value) = value_of(&key) {
+            let tokens = tokens(arg, &value_from(arg, value), &origin(&key));
+            validate(
+                &[tokens.as_slice(), &["async".to_string()]].concat(),
+                &origin(&key),
+            );
+            sources.set(arg, None, &var_name(&key));
+            layer.options.extend(tokens);
+        }
+    }
+    for (name, required) in MODES {
+        for arg in options(subcommand_of(app, name)) {
+            let key = key_of(arg);
+            if let Some(value) = value_of(&key) {
+                let tokens = tokens(arg, &value_from(arg, value), &origin(&key));
+                validate(&probe(name, required, &tokens), &origin(&key));
+                sources.set(arg, Some(name), &var_name(&key));
+                layer.mode_options.push((name, origin(&key), tokens));
+            }
+        }
+    }
+    layer
+}
+
+/// E.g. `CDB_NUM_REQ` of `num_req`.
+fn var_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase())
+}
+
+/// A variable as the value of a key of a file: a string, unless the argument is a flag,
+/// e.g. `CDB_NO_PLOTS=true` or `CDB_VERBOSE=2`.
+fn value_from(arg: &Arg, value: String) -> Value {
+    if arg.is_set(ArgSettings::TakesValue) {
+        return Value::String(value);
+    }
+    match (value.parse::<bool>(), value.parse::<i64>()) {
+        (Ok(set), _) => Value::Boolean(set),
+        (_, Ok(n)) => Value::Integer(n),
+        _ => Value::String(value),
+    }
+}
+
+/// Sync or async.
+fn parse_mode(mode: Option<&str>, origin: &str, value: &str) -> String {
+    match mode {
+        Some(mode @ "sync") | Some(mode @ "async") => mode.to_string(),
+        _ => fail(
+            format!(
+                "Invalid value of {}: `{}` is not sync or async",
+                origin, value
+            ),
+            ErrorKind::InvalidValue,
+        ),
+    }
+}
+
+/// The subcommand of a mode with its required arguments, to validate the tokens of its options.
+fn probe(name: &str, required: &[&str], tokens: &[String]) -> Vec<String> {
+    std::iter::once(name)
+        .chain(required.iter().copied())
+        .map(str::to_string)
+        .chain(tokens.iter().cloned())
+        .collect()
+}
+
+/// The options of the command line, i.e. the arguments before the subcommand,
+/// and the subcommand with its arguments, if any.
+pub fn split(args: &[String]) -> (&[String], &[String]) {
+    let (subcommand, _, _) = scan(&ModelConfig::cli(), args);
+    (&args[1..subcommand], &args[subcommand..])
+}
+
+/// A commented template of a file: a key per option, with its default, if any.
+pub fn template() -> String {
+    let app = ModelConfig::cli();
+    let defaults = ModelConfig::cli().get_matches_from(vec!["template", "init-config"]);
+    let mut out = String::from(
+        "# The options of concurrency-demo-benchmarks: the keys are the long names of the command line ones,\n\
+         # which override the values of the file, e.g. `--config run.toml --rate 2000`, as do the variables\n\
+         # of the keys prefixed with CDB_, e.g. `CDB_RATE=2000`.\n\n",
+    );
+    let modes = MODES
+        .iter()
+        .flat_map(|(name, _)| options(subcommand_of(&app, name)));
+    for arg in options(&app).chain(modes) {
+        let key = key_of(arg);
+        if key == "threads" {
+            out.push_str(
+                "# The mode: sync or async. `threads` and `cancellable_work` are of the sync one, `max_in_flight` of the async one\n",
+            );
+            out.push_str(&format!("{} = \"sync\"\n", MODE));
+        }
+        out.push_str(&format!("# {}\n", arg.get_about().unwrap_or_default()));
+        let line = if key == "threads" {
+            format!("{} = 50", key)
+        } else if let Some((_, example)) = EXAMPLES.iter().find(|(k, _)| *k == key) {
+            format!("{} = {}", key, example)
+        } else if !arg.is_set(ArgSettings::TakesValue) {
+            format!("# {} = true", key)
+        } else {
+            match defaults.value_of(arg.get_name()) {
+                Some(default) if default.parse::<u64>().is_ok() => {
+                    format!("# {} = {}", key, default)
+                }
+                Some(default) => format!("# {} = {}", key, Value::String(default.to_string())),
+                None => format!("# {} = \"\"", key),
+            }
+        };
+        out.push_str(&line);
+        out.push_str("\n\n");
+    }
+    out
+}
+
+fn subcommand_of<'a>(app: &'a App<'static>, name: &str) -> &'a App<'static> {
+    app.get_subcommands()
+        .find(|subcommand| subcommand.get_name() == name)
+        .expect("The modes are subcommands")
+}
+
+/// The arguments of `app` which are keys of a file and variables, i.e. all but `--help`, `--version`
+/// and `--config` itself.
+fn options<'a>(app: &'a App<'static>) -> impl Iterator<Item = &'a Arg<'static>> {
+    app.get_arguments().filter(|arg| {
+        !matches!(
+            arg.get_long(),
+            Some("help") | Some("version") | Some("config") | None
+        )
+    })
+}
+
+/// The key of a file, e.g. `max_in_flight` of `--max-in-flight`.
+fn key_of(arg: &Arg) -> String {
+    arg.get_long()
+        .expect("The options have long names")
+        .replace('-', "_")
+}
+
+fn arg_of<'a>(app: &'a App<'static>, key: &str) -> Option<&'a Arg<'static>> {
+    options(app).find(|arg| key_of(arg) == key)
+}
+
+/// (the index of the subcommand, or the number of the arguments; the file of `--config`, if any;
+/// the arguments found).
+fn scan<'a>(
+    app: &'a App<'static>,
+    args: &[String],
+) -> (usize, Option<String>, Vec<&'a Arg<'static>>) {
+    let takes_value =
+        |found: Option<&Arg>| matches!(found, Some(arg) if arg.is_set(ArgSettings::TakesValue));
+    let mut path = None;
+    let mut found = vec![];
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        if let Some(long) = arg.strip_prefix("--") {
+            let (long, value) = match long.find('=') {
+                Some(at) => (&long[..at], Some(long[at + 1..].to_string())),
+                None => (long, None),
+            };
+            if long.is_empty() {
+                break;
+            }
+            let arg = app.get_arguments().find(|a| a.get_long() == Some(long));
+            found.extend(arg);
+            let value = match value {
+                Some(value) => Some(value),
+                None if takes_value(arg) => {
+                    i += 1;
+                    args.get(i).cloned()
+                }
+                None => None,
+            };
+            if long == "config" {
+                path = value;
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // the value of the last short one may be the next argument, e.g. `-r 1000` or `-r1000`
+            for (at, short) in shorts.char_indices() {
+                let arg = app.get_arguments().find(|a| a.get_short() == Some(short));
+                found.extend(arg);
+                if takes_value(arg) {
+                    if at + short.len_utf8() == shorts.len() {
+                        i += 1;
+                    }
+                    break;
+                }
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    (i.min(args.len()), path, found)
+}
+
+/// The command line of a key of a file or of a variable, `origin` is the key in the messages.
+fn tokens(arg: &Arg, value: &Value, origin: &str) -> Vec<String> {
+    let long = arg.get_long().expect("The options have long names");
+    let invalid = |expected: &str| -> ! {
+        fail(
+            format!(
+                "Invalid value of {}: `{}` is not {}",
+                origin, value, expected
+            ),
+            ErrorKind::InvalidValue,
+        )
+    };
+    match value {
+        Value::Boolean(set) if !arg.is_set(ArgSettings::TakesValue) => {
+            if *set {
+                vec![format!("--{}", long)]
+            } else {
+                vec![]
+            }
+        }
+        // e.g. `verbose = 2`
+        Value::Integer(n)
+            if !arg.is_set(ArgSettings::TakesValue)
+                && arg.is_set(ArgSettings::MultipleOccurrences) =>
+        {
+            vec![format!("--{}", long); (*n).max(0) as usize]
+        }
+        _ if !arg.is_set(ArgSettings::TakesValue) => invalid("true or false"),
+        // e.g. `tag = ["branch=feature-x", "host=bench01"]`
+        Value::Array(values) if arg.is_set(ArgSettings::MultipleOccurrences) => values
+            .iter()
+            .flat_map(|value| tokens(arg, value, origin))
+            .collect(),
+        Value::String(s) => vec![format!("--{}={}", long, s)],
+        Value::Integer(n) => vec![format!("--{}={}", long, n)],
+        Value::Float(x) => vec![format!("--{}={}", long, x)],
+        _ => invalid("a string or a number"),
+    }
+}
+
+/// Exits with the reason, if the parser rejects the value of `origin`, e.g. `10k` of `rate`.
+/// The other errors, e.g. conflicts, are left to the parser of the whole command line.
+fn validate(tokens: &[String], origin: &str) {
+    let probe = std::iter::once("probe".to_string()).chain(tokens.iter().cloned());
+    if let Err(e) = ModelConfig::cli().try_get_matches_from(probe) {
+        let reason = match e.kind {
+            ErrorKind::ValueValidation => e.info.get(2).cloned(),
+            ErrorKind::InvalidValue => e
+                .to_string()
+                .lines()
+                .next()
+                .map(|line| line.trim_start_matches("error: ").to_string()),
+            _ => return,
+        };
+        fail(
+            format!(
+                "Invalid value of {}: {}",
+                origin,
+                reason.unwrap_or_default()
+            ),
+            e.kind,
+        );
+    }
+}
+
+/// E.g. ` at line 3`, if the key is found.
+fn line_of(text: &str, key: &str) -> String {
+    text.lines()
+        .position(|line| {
+            matches!(line.trim_start().strip_prefix(key), Some(rest) if rest.trim_start().starts_with('='))
+        })
+        .map_or_else(String::new, |i| format!(" at line {}", i + 1))
+}
+
+fn fail(message: String, kind: ErrorKind) -> ! {
+    clap::Error::with_description(format!("{}\n", message), kind).exit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard};
+
+    // the tests which read the `CDB_` variables take it, as they are of the process
+    static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+    /// The `CDB_` variables of a test, removed on drop.
+    struct Environment {
+        names: Vec<String>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Environment {
+        fn set(vars: &[(&str, &str)]) -> Self {
+            let lock = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+            for (name, value) in vars {
+                env::set_var(name, value);
+            }
+            Self {
+                names: vars.iter().map(|(name, _)| name.to_string()).collect(),
+                _lock: lock,
+            }
+        }
+    }
+
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            for name in &self.names {
+                env::remove_var(name);
+            }
+        }
+    }
+
+    /// A `--config <FILE>` of a test, removed on drop.
+    struct File(PathBuf);
+
+    impl File {
+        fn new(name: &str, text: &str) -> Self {
+            let path = env::temp_dir().join(format!("cdb-{}-{}.toml", name, std::process::id()));
+            fs::write(&path, text).expect("Failed to write a config file");
+            Self(path)
+        }
+    }
+
+    impl Drop for File {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        std::iter::once("concurrency-demo-benchmarks")
+            .chain(line.split_whitespace())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// The config of a command line merged with its file and the environment, as JSON to compare.
+    fn config_of(line: &str) -> serde_json::Value {
+        let (merged, _) = merge(args(line));
+        let config = ModelConfig::from_matches(&ModelConfig::cli().get_matches_from(merged));
+        serde_json::to_value(&config).expect("Failed to serialize the config")
+    }
+
+    #[test]
+    fn the_example_file_is_its_command_line() {
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/sync_30s.toml");
+        // the command line of the comment of the file
+        let command_line = "--name sync_30s --rate 1000 --num_req 30000 --latency 20ms*9,30s \
+                            --timeout 1s --seed 42 --no-plots --verbose \
+                            sync --threads 50 --cancellable-work granularity=50ms";
+        let _environment = Environment::set(&[]);
+        assert_eq!(
+            config_of(&format!("--config {}", file)),
+            config_of(command_line)
+        );
+    }
+
+    // the seed is random by default
+    const REQUIRED: &str = "--name t --num_req 100 --latency 10ms --seed 42";
+
+    #[test]
+    fn the_file_overrides_the_defaults() {
+        let _environment = Environment::set(&[]);
+        let file = File::new(
+            "file-over-defaults",
+            "rate = 2000\ntimeout = \"2s\"\nmode = \"sync\"\nthreads = 8\n",
+        );
+        let config = config_of(&format!("--config {} {}", file.0.display(), REQUIRED));
+        let expected = format!("{} --rate 2000 --timeout 2s sync --threads 8", REQUIRED);
+        assert_eq!(config, config_of(&expected));
+        let defaults = format!("{} --rate 2000 sync --threads 8", REQUIRED);
+        assert_ne!(config, config_of(&defaults));
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file() {
+        let file = File::new(
+            "environment-over-file",
+            "rate = 2000\nmode = \"sync\"\nthreads = 8\n",
+        );
+        let _environment = Environment::set(&[
+            ("CDB_RATE", "3000"),
+            ("CDB_MODE", "sync"),
+            ("CDB_THREADS", "16"),
+        ]);
+        assert_eq!(
+            config_of(&format!("--config {} {}", file.0.display(), REQUIRED)),
+            config_of(&format!("{} --rate 3000 sync --threads 16", REQUIRED))
+        );
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_environment() {
+        let file = File::new(
+            "command-line-over-environment",
+            "rate = 2000\nmode = \"sync\"\nthreads = 8\n",
+        );
+        let _environment = Environment::set(&[("CDB_RATE", "3000"), ("CDB_THREADS", "16")]);
+        let line = format!("--config {} {} --rate 4000", file.0.display(), REQUIRED);
+        assert_eq!(
+            config_of(&format!("{} sync --threads 32", line)),
+            config_of(&format!("{} --rate 4000 sync --threads 32", REQUIRED))
+        );
+        // the subcommand of the command line overrides the mode, and the options of the other mode are left out
+        assert_eq!(
+            config_of(&format!("{} async", line)),
+            config_of(&format!("{} --rate 4000 async", REQUIRED))
+        );
+    }
+}
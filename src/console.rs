@@ -0,0 +1,34 @@
+use indicatif::ProgressBar;
+use std::sync::{Arc, Mutex};
+
+/// Console output, which goes above the progress bar while it's active,
+/// so the bar and the printed lines don't interleave.
+#[derive(Clone, Default)]
+pub struct Console {
+    bar: Arc<Mutex<Option<ProgressBar>>>,
+}
+
+impl Console {
+    pub fn println<S: Into<String>>(&self, line: S) {
+        match self.bar.lock().expect("Poisoned console lock").as_ref() {
+            Some(bar) => bar.println(line),
+            None => println!("{}", line.into()),
+        }
+    }
+
+    /// Same as `println`, but goes to stderr if the progress bar is not active.
+    pub fn eprintln<S: Into<String>>(&self, line: S) {
+        match self.bar.lock().expect("Poisoned console lock").as_ref() {
+            Some(bar) => bar.println(line),
+            None => eprintln!("{}", line.into()),
+        }
+    }
+
+    pub fn attach(&self, bar: ProgressBar) {
+        *self.bar.lock().expect("Poisoned console lock") = Some(bar);
+    }
+
+    pub fn detach(&self) {
+        *self.bar.lock().expect("Poisoned console lock") = None;
+    }
+}
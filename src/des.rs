@@ -0,0 +1,169 @@
+use crate::aggregator::{Class, Event, StreamingAggregator};
+use crate::interrupt::Interrupt;
+use crate::rate_limiter;
+use crate::source::{self, TaskSchedule};
+use crate::{Mode, ModelConfig, TaskStats};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The simulation keeps a sample of at most this many task records, unless `--max-samples` is set,
+/// so a run of 100M requests fits in memory. The percentiles of the summary still cover all of them.
+pub const MAX_RECORDS: usize = 1 << 20;
+// the interrupt is checked once per this many arrivals
+const INTERRUPT_CHECK: usize = 1 << 16;
+// the events recorded at once
+const BATCH: usize = 4096;
+
+/// A task in service with its class, until its completion, the earliest one is the next event.
+struct InService(TaskStats, Option<Class>);
+
+impl PartialEq for InService {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.completion_time == other.0.completion_time
+    }
+}
+
+impl Eq for InService {}
+
+impl PartialOrd for InService {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InService {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.completion_time.cmp(&other.0.completion_time)
+    }
+}
+
+/// The pending events after the current arrival: the starts of the queued tasks, which are in the order
+/// of their arrivals, and the completions of the tasks in service. The past ones are recorded in batches.
+#[derive(Default)]
+struct Events {
+    starts: VecDeque<Instant>,
+    completions: BinaryHeap<Reverse<InService>>,
+    recorded: Vec<Event>,
+}
+
+impl Events {
+    fn record(&mut self, event: Event, aggregator: &StreamingAggregator) {
+        self.recorded.push(event);
+        if self.recorded.len() >= BATCH {
+            aggregator.record_events(self.recorded.drain(..));
+        }
+    }
+
+    /// Records the events up to `until` in their order, so the aggregator sees the seconds of the run in turn.
+    /// A start goes before a completion at the same moment.
+    fn advance(&mut self, until: Instant, aggregator: &StreamingAggregator) {
+        loop {
+            let start = self.starts.front().copied().filter(|start| *start <= until);
+            let completion = self
+                .completions
+                .peek()
+                .map(|Reverse(task)| task.0.completion_time)
+                .filter(|completion| *completion <= until);
+            match (start, completion) {
+                (Some(start), Some(completion)) if start <= completion => {
+                    self.starts.pop_front();
+                    self.record(Event::Started(start), aggregator);
+                }
+                (Some(start), None) => {
+                    self.starts.pop_front();
+                    self.record(Event::Started(start), aggregator);
+                }
+                (_, Some(_)) => {
+                    let Reverse(InService(stats, class)) =
+                        self.completions.pop().expect("A completion is pending");
+                    self.record(Event::Completed(stats, class), aggregator);
+                }
+                (None, None) => return,
+            }
+        }
+    }
+}
+
+/// The time a task is served for: its cost, or, if the work is cancellable, until the first slice
+/// that ends past the deadline, as `serve` does. A task started past its deadline is abandoned at once.
+fn service_time(
+    cost: Duration,
+    service_start: Instant,
+    deadline: Instant,
+    granularity: Option<Duration>,
+) -> Duration {
+    match granularity {
+        Some(granularity) if service_start + cost > deadline => {
+            let left = deadline.saturating_duration_since(service_start);
+            let slices = left.as_nanos().div_ceil(granularity.as_nanos()) as u32;
+            (granularity * slices).min(cost)
+        }
+        _ => cost,
+    }
+}
+
+/// Runs the model of `--engine des` in virtual time from `start`, no time passes and nothing sleeps.
+/// The tasks arrive as the rate limiter sends them, a refill at once. The sync workers are `n` servers
+/// of a FIFO queue, the async executor is an unlimited number of them, or `--max-in-flight`.
+/// The arrivals, the starts and the completions are recorded by `aggregator` at their virtual moments,
+/// so the results go through the same pipeline as the ones of a real run.
+/// Returns the virtual (send phase, whole run).
+pub fn run(
+    config: &ModelConfig,
+    aggregator: &StreamingAggregator,
+    start: Instant,
+    interrupt: &Interrupt,
+) -> (Duration, Duration) {
+    let timeout = Duration::from_millis(config.timeout);
+    let granularity = config.cancellable_work.map(Duration::from_millis);
+    let (refill, interval) = rate_limiter::refill(config.rps);
+    let interval = Duration::from_millis(interval);
+    // when each server is free next, the earliest one takes the next task
+    let mut free: Option<BinaryHeap<Reverse<Instant>>> = match config.mode {
+        Mode::Sync(n_workers) => Some(n_workers),
+        Mode::Async => config.max_in_flight,
+    }
+    .map(|servers| vec![Reverse(start); servers].into());
+    let classes = source::cost_classes(config, aggregator);
+    let mut events = Events::default();
+    let (mut last_arrival, mut last_completion) = (start, start);
+
+    for (i, cost) in TaskSchedule::new(&config.latency_distribution, config.n_jobs).enumerate() {
+        if i % INTERRUPT_CHECK == 0 && interrupt.is_set() {
+            break;
+        }
+        let arrival = start + interval * (i / refill) as u32;
+        events.advance(arrival, aggregator);
+        events.record(Event::Sent(arrival), aggregator);
+
+        let service_start = match &mut free {
+            Some(free) => {
+                let Reverse(free_at) = free.pop().expect("There is a server");
+                free_at.max(arrival)
+            }
+            None => arrival,
+        };
+        let service = service_time(
+            Duration::from_millis(cost),
+            service_start,
+            arrival + timeout,
+            granularity,
+        );
+        let completion = service_start + service;
+        if let Some(free) = &mut free {
+            free.push(Reverse(completion));
+        }
+        events.starts.push_back(service_start);
+        events.completions.push(Reverse(InService(
+            TaskStats::new(timeout, arrival, service_start, service, completion),
+            classes.as_ref().map(|classes| classes[&cost]),
+        )));
+        last_arrival = arrival;
+        last_completion = last_completion.max(completion);
+    }
+    events.advance(last_completion, aggregator);
+    aggregator.record_events(events.recorded);
+
+    (last_arrival - start, last_completion - start)
+}
@@ -0,0 +1,43 @@
+use crate::aggregator::SecondStats;
+use crate::metadata::RunMetadata;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Writes one row per second of the run. The metadata goes first, as a `#` comment line.
+/// Latencies are in ms, empty if there were no successful completions within the second.
+pub fn write_timeseries(
+    path: &Path,
+    seconds: &[SecondStats],
+    metadata: &RunMetadata,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        "# {}",
+        serde_json::to_string(metadata).expect("Metadata is serializable")
+    )?;
+    writeln!(
+        out,
+        "second,sent,completed,failed,p50_ms,p90_ms,p99_ms,max_ms,queue_depth_mean,in_flight"
+    )?;
+    let format_ms = |v: Option<f64>| v.map_or(String::new(), |v| format!("{:.3}", v * 1000.));
+    for s in seconds {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{:.3},{}",
+            s.second,
+            s.sent,
+            s.completed,
+            s.failed,
+            format_ms(s.p50),
+            format_ms(s.p90),
+            format_ms(s.p99),
+            format_ms(s.max),
+            s.queue_depth,
+            s.in_flight
+        )?;
+    }
+    out.flush()
+}
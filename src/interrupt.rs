@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Set by the first Ctrl-C: no new tasks are sent, the in-flight ones are drained,
+/// and the results are reported as a partial run. The second Ctrl-C aborts immediately.
+#[derive(Clone, Default)]
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+    /// Removed on abort, e.g. the socket file or the directory of a target.
+    paths: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl Interrupt {
+    pub fn install() -> Self {
+        let interrupt = Self::default();
+        let flag = interrupt.flag.clone();
+        let paths = interrupt.paths.clone();
+        tokio::spawn(async move {
+            tokio::signal::ctrl_c()
+                .await
+                .expect("Failed to listen for Ctrl-C");
+            flag.store(true, Ordering::Relaxed);
+            warn!("Interrupted: no new requests are sent, waiting for the in-flight ones. Press Ctrl-C again to abort");
+            tokio::signal::ctrl_c()
+                .await
+                .expect("Failed to listen for Ctrl-C");
+            warn!("Aborted");
+            for path in paths.lock().expect("Poisoned interrupt lock").iter() {
+                let _ = if path.is_dir() {
+                    fs::remove_dir_all(path)
+                } else {
+                    fs::remove_file(path)
+                };
+            }
+            std::process::exit(130);
+        });
+        interrupt
+    }
+
+    /// A file or a directory, otherwise its owner removes it.
+    pub fn remove_on_abort(&self, path: PathBuf) {
+        self.paths
+            .lock()
+            .expect("Poisoned interrupt lock")
+            .push(path);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
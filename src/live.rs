@@ -0,0 +1,64 @@
+use crate::aggregator::StreamingAggregator;
+use crate::console::Console;
+use crate::progress::Progress;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, interval_at, Instant};
+use tracing::debug;
+
+const LIVE_TICK: Duration = Duration::from_secs(1);
+const PROGRESS_TICK: Duration = Duration::from_millis(100);
+
+/// Reports the state of the run while it's in progress:
+/// a line of per-second stats (printed if `live` is set, logged at debug level otherwise)
+/// and/or a progress bar.
+pub struct LiveTicker {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl LiveTicker {
+    pub fn start(
+        aggregator: Arc<StreamingAggregator>,
+        console: Console,
+        live: bool,
+        mut progress: Option<Progress>,
+    ) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let handle = tokio::spawn(async move {
+            let mut live_interval = interval_at(Instant::now() + LIVE_TICK, LIVE_TICK);
+            let mut progress_interval = interval(PROGRESS_TICK);
+            loop {
+                tokio::select! {
+                    _ = live_interval.tick() => {
+                        let line = aggregator.snapshot().format_line();
+                        if live {
+                            console.println(line);
+                        } else {
+                            debug!("{}", line);
+                        }
+                    }
+                    _ = progress_interval.tick(), if progress.is_some() => {
+                        let snapshot = aggregator.snapshot();
+                        if let Some(progress) = progress.as_mut() {
+                            progress.update(&snapshot);
+                        }
+                    }
+                    _ = &mut stopped => break,
+                }
+            }
+            if let Some(progress) = progress {
+                progress.finish();
+            }
+        });
+        Self { stop, handle }
+    }
+
+    pub async fn stop(self) {
+        // the receiver is gone only if the ticker has already stopped
+        self.stop.send(()).unwrap_or_default();
+        self.handle.await.expect("Live ticker failed");
+    }
+}
@@ -0,0 +1,82 @@
+use crate::console::Console;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::time::{ChronoUtc, FormatTime};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Log timestamps: the wall clock, plus the time elapsed since the start
+/// of the execution phase (once it has started).
+#[derive(Clone, Default)]
+pub struct RunClock {
+    start_time: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RunClock {
+    pub fn mark_start(&self, start_time: Instant) {
+        *self.start_time.lock().expect("Poisoned clock lock") = Some(start_time);
+    }
+}
+
+impl FormatTime for RunClock {
+    fn format_time(&self, w: &mut dyn fmt::Write) -> fmt::Result {
+        ChronoUtc::with_format("%Y-%m-%dT%H:%M:%S%.3fZ".to_string()).format_time(w)?;
+        if let Some(start_time) = *self.start_time.lock().expect("Poisoned clock lock") {
+            write!(w, " +{:.3}s", start_time.elapsed().as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes log lines to stderr, or above the progress bar while it's active.
+struct ConsoleWriter {
+    console: Console,
+    buffer: Vec<u8>,
+}
+
+impl io::Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleWriter {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buffer);
+        self.console.eprintln(line.trim_end());
+    }
+}
+
+struct MakeConsoleWriter(Console);
+
+impl MakeWriter for MakeConsoleWriter {
+    type Writer = ConsoleWriter;
+
+    fn make_writer(&self) -> Self::Writer {
+        ConsoleWriter {
+            console: self.0.clone(),
+            buffer: vec![],
+        }
+    }
+}
+
+/// Installs the global subscriber. The returned clock is used to mark
+/// the start of the execution phase.
+pub fn init(level: LevelFilter, console: Console) -> RunClock {
+    let clock = RunClock::default();
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_ansi(atty::is(atty::Stream::Stderr))
+        .with_timer(clock.clone())
+        .with_writer(MakeConsoleWriter(console))
+        .init();
+    clock
+}
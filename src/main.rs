@@ -1,258 +1,2332 @@
-use clap::clap_app;
+mod affinity;
+mod aggregator;
+mod allocations;
+mod bench_report;
+mod checkpoint;
+mod chrome_trace;
+mod clock;
+mod collector;
+mod config_file;
+mod console;
+mod des;
+mod export;
+mod interrupt;
+mod live;
+mod logging;
+mod merge;
+mod metadata;
+#[cfg_attr(not(feature = "otlp"), path = "otel_disabled.rs")]
+mod otel;
+mod plan;
+mod precise_sleep;
+mod preset;
+mod priority;
+#[cfg(all(unix, feature = "profile"))]
+mod profile;
+mod progress;
+mod rate_limiter;
+mod repeat;
+mod report;
+mod schedule;
+mod self_check;
+mod source;
+mod spill;
+// the spinlock of `benches/benchmarks_spinlock.rs`, built here only to run its tests
+#[cfg(test)]
+#[path = "../benches/spinlock/mod.rs"]
+mod spinlock;
+mod stats;
+mod store;
+mod suite;
+mod summary;
+mod sweep;
+mod target;
+
+use crate::aggregator::{Class, ClassStats, RunResults, SecondStats, Sends, StreamingAggregator};
+use crate::checkpoint::{Checkpoint, Checkpointer};
+use crate::clock::{Clock, CoarseClock};
+use crate::collector::{Collector, StatsChannel};
+use crate::console::Console;
+use crate::interrupt::Interrupt;
+use crate::live::LiveTicker;
+use crate::metadata::RunMetadata;
+use crate::otel::{Exporter, TaskTracer};
+use crate::precise_sleep::Sleep;
+use crate::priority::BackgroundLoad;
+#[cfg(all(unix, feature = "profile"))]
+use crate::profile::Profiler;
+use crate::progress::Progress;
+use crate::report::{Binning, Chart, Figures};
+use crate::schedule::ScheduleMonitor;
+use crate::source::TaskSource;
+use crate::spill::{Overheads, Spill};
+use crate::stats::{PercentileMethod, LOG_BUCKETS};
+use crate::summary::{Rate, Summary, Thresholds, PRINTED_PERCENTILES};
+use crate::target::{Endpoint, Served, Server};
+use clap::{clap_app, App, ArgMatches, ErrorKind};
+use crossbeam::channel::RecvTimeoutError;
 use humantime::parse_duration;
-use leaky_bucket::LeakyBucket;
-use matplotrust::{histogram, line_plot, Figure};
-use std::collections::HashMap;
-use std::ops::AddAssign;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
 use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Semaphore;
 use tokio::time::delay_for;
-
-const TIMEOUT: Duration = Duration::from_secs(1);
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, error, info, warn};
 
 #[derive(Clone)]
 struct Task {
+    index: usize,
     start: Instant,
     cost: u64,
+    class: Option<Class>,
+}
+
+/// Why a task has exceeded the timeout.
+#[derive(Clone, Copy, PartialEq)]
+enum Failure {
+    /// The service time alone exceeds the timeout, i.e. a slow backend.
+    ServiceTime,
+    /// The task would be in time if it hadn't waited in the queue, i.e. an overloaded service.
+    Queueing,
+    /// The request to the target failed, e.g. the connection was refused or reset.
+    Io,
+}
+
+impl Failure {
+    fn name(self) -> &'static str {
+        match self {
+            Failure::ServiceTime => "service time",
+            Failure::Queueing => "queueing",
+            Failure::Io => "io",
+        }
+    }
 }
 
 #[derive(Clone)]
 struct TaskStats {
-    success: bool,
+    failure: Option<Failure>,
     start_time: Instant,
+    /// When the task left the queue.
+    service_start_time: Instant,
     completion_time: Instant,
+    /// The sync worker, which has served the task.
+    worker: Option<usize>,
     overhead: f64,
+    /// The measured overhead was negative and has been clamped to zero.
+    clamped: bool,
+}
+
+impl TaskStats {
+    /// A task fails if its measured latency (queueing included) exceeds the timeout.
+    /// The overhead is the latency minus the measured (rather than the nominal) service time,
+    /// as sleeps are never exact. With a target, it's measured by the server, so the round trip is an overhead.
+    /// A negative value may come only from the clock, so it's clamped.
+    fn new(
+        timeout: Duration,
+        start_time: Instant,
+        service_start_time: Instant,
+        service_time: Duration,
+        completion_time: Instant,
+    ) -> Self {
+        let latency = completion_time.duration_since(start_time);
+        let failure = if service_time > timeout {
+            Some(Failure::ServiceTime)
+        } else if latency > timeout {
+            Some(Failure::Queueing)
+        } else {
+            None
+        };
+        let overhead = latency.as_secs_f64() - service_time.as_secs_f64();
+        Self {
+            failure,
+            start_time,
+            service_start_time,
+            completion_time,
+            worker: None,
+            overhead: overhead.max(0.),
+            clamped: overhead < 0.,
+        }
+    }
+
+    /// The request failed regardless of its latency.
+    fn io_error(self) -> Self {
+        Self {
+            failure: Some(Failure::Io),
+            ..self
+        }
+    }
+
+    fn served_by(self, worker: usize) -> Self {
+        Self {
+            worker: Some(worker),
+            ..self
+        }
+    }
+
+    fn success(&self) -> bool {
+        self.failure.is_none()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 enum Mode {
     Sync(usize),
     Async,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Backend {
+    Matplotlib,
+    Gnuplot,
+}
+
+impl Backend {
+    /// The value of `--backend`.
+    fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("gnuplot") => Backend::Gnuplot,
+            _ => Backend::Matplotlib,
+        }
+    }
+}
+
+/// What runs the model, see `--engine`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum Engine {
+    /// The tasks are sent and served in real time.
+    #[default]
+    Real,
+    /// A discrete-event simulation in virtual time, see `des::run`.
+    Des,
+}
+
+/// A figure of the run, selected by `--plots`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Plot {
+    Timeline,
+    Histogram,
+    Percentiles,
+    LogPercentiles,
+    Cdf,
+    Scatter,
+    Heatmap,
+    Boxes,
+    Classes,
+    Rate,
+    Combined,
+}
+
+impl Plot {
+    const ALL: [Plot; 11] = [
+        Plot::Timeline,
+        Plot::Histogram,
+        Plot::Percentiles,
+        Plot::LogPercentiles,
+        Plot::Cdf,
+        Plot::Scatter,
+        Plot::Heatmap,
+        Plot::Boxes,
+        Plot::Classes,
+        Plot::Rate,
+        Plot::Combined,
+    ];
+
+    /// The value of `--plots`.
+    fn name(self) -> &'static str {
+        match self {
+            Plot::Timeline => "timeline",
+            Plot::Histogram => "histogram",
+            Plot::Percentiles => "percentiles",
+            Plot::LogPercentiles => "log-percentiles",
+            Plot::Cdf => "cdf",
+            Plot::Scatter => "scatter",
+            Plot::Heatmap => "heatmap",
+            Plot::Boxes => "boxes",
+            Plot::Classes => "classes",
+            Plot::Rate => "rate",
+            Plot::Combined => "combined",
+        }
+    }
+
+    /// E.g. `latency_histogram_{name}.png`.
+    fn file_name(self, name: &str) -> String {
+        let stem = match self {
+            Plot::Timeline => "latency_timeline",
+            Plot::Histogram => "latency_histogram",
+            Plot::Percentiles => "latency_percentiles",
+            Plot::LogPercentiles => "latency_percentiles_log",
+            Plot::Cdf => "latency_cdf",
+            Plot::Scatter => "latency_scatter",
+            Plot::Heatmap => "latency_heatmap",
+            Plot::Boxes => "latency_boxes",
+            Plot::Classes => "latency_classes",
+            Plot::Rate => "request_rate",
+            Plot::Combined => "combined",
+        };
+        format!("{}_{}.png", stem, name)
+    }
+}
+
+/// What the work of a task is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Target {
+    /// A sleep of the task's cost.
+    Sleep,
+    /// A request to the built-in TCP echo server, which sleeps the cost before answering.
+    /// With `tls`, the connections are TLS ones.
+    Tcp { tls: bool },
+    /// `GET /{cost}` of the built-in HTTP/1.1 server, which sleeps the cost before answering.
+    /// Without keep-alive, every request establishes a connection.
+    Http { keepalive: bool, tls: bool },
+    /// The same as `Tcp`, but over a Unix domain socket, which is removed at the end of the run.
+    Uds { socket: String },
+    /// A datagram to the built-in UDP echo server, which drops `drop` % of them.
+    /// A request without a response within the timeout fails.
+    Udp { drop: f64 },
+    /// A write and a read back of a file of `size` bytes per request, within a subdirectory of `dir`.
+    File {
+        dir: String,
+        size: usize,
+        fsync: bool,
+    },
+    /// A process per request, at most `max_processes` at a time in the async mode.
+    Cmd {
+        command: Vec<String>,
+        max_processes: usize,
+    },
+    /// `GET` of an external HTTP service, the latencies are up to it. A request succeeds
+    /// with a status within one of the `success` ranges.
+    Url {
+        url: String,
+        keepalive: bool,
+        success: Vec<(u16, u16)>,
+    },
+}
+
+impl Target {
+    /// What the setup of a request is, e.g. in the summary.
+    fn setup(&self) -> &'static str {
+        match self {
+            Target::Cmd { .. } => "Spawns",
+            Target::Tcp { tls: true } | Target::Http { tls: true, .. } => {
+                "Connects with TLS handshakes"
+            }
+            _ => "Connects",
+        }
+    }
+}
+
+/// The tasks, which are traced by `--otel-endpoint`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraceExport {
+    /// The OTLP/gRPC collector.
+    endpoint: String,
+    /// The traced share of the tasks, %.
+    sample: f64,
+}
+
+/// The batches of the completions of the sync workers of `--stats-batch`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct StatsBatch {
+    /// The completions recorded at once at most.
+    size: usize,
+    /// The first completion of a batch waits for the others at most this long, ms.
+    flush_every: u64,
+}
+
+/// The clock of `--coarse-clock`, which replaces `Instant::now()` in the hot path.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct CoarseClockConfig {
+    /// How often it's updated, ms.
+    resolution: u64,
+    /// It times the latencies too, not only the seconds of the events.
+    full: bool,
+}
+
+/// Which moment of a task places it on the latency timeline.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum TimelineBasis {
+    /// When it was sent. During a stall, the latencies show up when the requests were queued.
+    Start,
+    /// When it completed. During a stall, the latencies show up when the requests were served.
+    Completion,
+}
+
+/// How much of each edge of the run is excluded from the average rate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum TrimEdges {
+    /// 1s, unless the run is shorter than `AUTO_MIN_RUN` seconds.
+    Auto,
+    Seconds(u64),
+}
+
+impl TrimEdges {
+    const AUTO_MIN_RUN: u64 = 10;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ModelConfig {
     name: String,
+    /// The labels of `--tag`, e.g. `branch = "feature-x"`.
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    /// The scenario of `--preset`, if any.
+    #[serde(default)]
+    preset: Option<String>,
+    /// 0 with `--stdin-tasks`.
     n_jobs: usize,
+    /// 0 with `--stdin-tasks`.
     rps: usize,
+    /// The tasks are read from stdin.
+    #[serde(default)]
+    stdin_tasks: bool,
     latency_distribution: Vec<u64>,
+    /// Requests taking longer are failed, ms.
+    timeout: u64,
+    /// The sync workers abandon timed out requests, checking every this many ms.
+    cancellable_work: Option<u64>,
+    /// The sync workers are pinned to consecutive cores.
+    #[serde(default)]
+    pin_threads: bool,
+    /// The sync workers spin for the end of a sleep, rather than overshoot it by the OS timer.
+    #[serde(default)]
+    precise_sleep: bool,
+    /// The cores of `pin_threads`, all of them by default.
+    #[serde(default)]
+    cores: Option<Vec<usize>>,
+    /// The nice value of the sync workers.
+    #[serde(default)]
+    worker_nice: Option<i32>,
+    /// The number of busy threads at the lowest priority during the run.
+    #[serde(default)]
+    background_load: usize,
+    /// The completions of the sync workers are recorded in batches, rather than one at a time.
+    #[serde(default)]
+    stats_batch: Option<StatsBatch>,
+    /// How the sync workers deliver their completions.
+    #[serde(default)]
+    stats_channel: StatsChannel,
+    /// The async tasks served at once at most, the others wait for them.
+    #[serde(default)]
+    max_in_flight: Option<usize>,
     python_path: Option<String>,
+    backend: Backend,
+    hist_bins: Option<usize>,
+    hist_range: Option<(u64, u64)>,
+    percentile_method: PercentileMethod,
+    trim_edges: TrimEdges,
+    timeline_basis: TimelineBasis,
+    live: bool,
+    progress: bool,
+    export_timeseries: Option<String>,
+    /// The Chrome trace events of the tasks are written to this file.
+    #[serde(default)]
+    chrome_trace: Option<String>,
+    /// The database to append the results to.
+    #[serde(default)]
+    sqlite: Option<String>,
+    /// The per-second aggregates are appended too.
+    #[serde(default)]
+    sqlite_timeseries: bool,
+    /// The max number of kept per-task records, all of them are kept by default.
+    max_samples: Option<usize>,
+    /// All the per-task records are written to this directory, for the exact percentiles.
+    #[serde(default)]
+    spill_dir: Option<String>,
+    /// The records of a sorted chunk of the spill.
+    #[serde(default)]
+    spill_chunk: Option<usize>,
+    /// Controls every random choice of the run, random by default.
+    seed: u64,
+    /// Write a checkpoint every this many ms.
+    checkpoint_every: Option<u64>,
+    /// The checkpoint of a previous run to fold into the results.
+    resume: Option<String>,
+    /// Apdex T, ms.
+    apdex: Option<u64>,
+    thresholds: Thresholds,
+    otel: Option<TraceExport>,
+    /// The run is sampled by a CPU profiler.
+    profile: bool,
+    #[serde(default)]
+    coarse_clock: Option<CoarseClockConfig>,
+    no_color: bool,
+    no_plots: bool,
+    /// The figures of `--plots`, none with `--no-plots`.
+    #[serde(default = "ModelConfig::default_plots")]
+    plots: Vec<Plot>,
+    // the runs before `--target` slept
+    #[serde(default = "ModelConfig::default_target")]
+    target: Target,
+    /// The run is simulated in virtual time, rather than executed.
+    #[serde(default)]
+    engine: Engine,
+    // not a parameter of the model
+    #[serde(skip, default = "ModelConfig::default_log_level")]
+    log_level: LevelFilter,
     mode: Mode,
 }
 
+#[cfg(any(test, feature = "count-allocations"))]
+#[global_allocator]
+static ALLOCATOR: allocations::Counting = allocations::Counting;
+
 #[tokio::main]
 async fn main() {
-    let config = ModelConfig::from_cli();
-    println!("Config: {:#?}", config);
-
-    let mut duration_ms = 1000;
-    let mut refill = config.rps;
-    while duration_ms > 10 && refill % 10 == 0 {
-        duration_ms /= 10;
-        refill /= 10;
-    }
-    println!("Rate limit refill {} per {} ms", refill, duration_ms);
-    let rate_limiter = LeakyBucket::builder()
-        .refill_amount(refill)
-        .refill_interval(Duration::from_millis(duration_ms as u64))
-        .build()
-        .expect("LeakyBucket builder failed");
+    let (args, sources) = config_file::merge(env::args().collect());
+    let matches = ModelConfig::cli().get_matches_from(args);
+    if matches.subcommand_matches("init-config").is_some() {
+        print!("{}", config_file::template());
+        return;
+    }
+    if matches.subcommand_matches("validate").is_some() {
+        let python_path = matches.value_of("PYTHON_PATH").map(|s| s.to_string());
+        if !self_check::run(python_path).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(merge) = matches.subcommand_matches("merge") {
+        logging::init(LevelFilter::INFO, Console::default());
+        let runs: Vec<&str> = merge.values_of("RUNS").expect("RUNS is required").collect();
+        let out = Path::new(merge.value_of("OUT").expect("OUT is required"));
+        let mut python_path = matches.value_of("PYTHON_PATH").map(|s| s.to_string());
+        let figures = figures(
+            &Backend::from_name(matches.value_of("BACKEND")),
+            &mut python_path,
+        );
+        if let Err(e) = merge::run(&runs, out, merge.is_present("FORCE"), &figures) {
+            error!("Failed to merge the runs: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if matches.subcommand_matches("presets").is_some() {
+        preset::print();
+        return;
+    }
+    if let Some(compare) = matches.subcommand_matches("compare") {
+        let a = compare.value_of("A").expect("A is required");
+        let b = compare.value_of("B").expect("B is required");
+        if let Err(e) = metadata::compare(Path::new(a), Path::new(b)) {
+            eprintln!("Failed to compare {} and {}: {}", a, b, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(history) = matches.subcommand_matches("history") {
+        require_feature(cfg!(feature = "sqlite"), "history", "sqlite");
+        let db = history.value_of("DB").expect("DB is required");
+        let name = history.value_of("NAME");
+        let last = history
+            .value_of("LAST")
+            .and_then(ModelConfig::parse_count)
+            .expect("LAST has a default value");
+        let tags: Vec<(String, String)> = history
+            .values_of("TAG")
+            .into_iter()
+            .flatten()
+            .filter_map(ModelConfig::parse_tag)
+            .collect();
+        if let Err(e) = store::history(Path::new(db), name, last, &tags) {
+            eprintln!(
+                "Failed to read the history of {} from {}: {}",
+                name.unwrap_or("the tagged runs"),
+                db,
+                e
+            );
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(run_all) = matches.subcommand_matches("run-all") {
+        require_feature(cfg!(feature = "sqlite"), "run-all", "sqlite");
+        logging::init(LevelFilter::INFO, Console::default());
+        let suite = Path::new(run_all.value_of("SUITE").expect("SUITE is required"));
+        let dir = run_all.value_of("OUT_DIR").map_or_else(
+            || suite.with_extension(""),
+            |dir| Path::new(dir).to_path_buf(),
+        );
+        let mut python_path = matches.value_of("PYTHON_PATH").map(|s| s.to_string());
+        let figures = figures(
+            &Backend::from_name(matches.value_of("BACKEND")),
+            &mut python_path,
+        );
+        match suite::run(suite, &dir, &figures) {
+            Ok(failed) if failed.is_empty() => {}
+            Ok(failed) => {
+                error!("Failed scenarios: {}", failed.join(", "));
+                std::process::exit(1);
+            }
+            Err(e) => {
+                error!("Failed to run the suite {}: {}", suite.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(sweep) = matches.subcommand_matches("sweep-threads") {
+        let threads = sweep
+            .value_of("THREADS")
+            .and_then(ModelConfig::parse_counts)
+            .expect("THREADS is required");
+        let (config, figures) = sweep_config(&matches);
+        let sweep = sweep::Sweep {
+            parameter: "threads",
+            label: "Threads",
+            config: &config,
+            max_p99: None,
+        };
+        let trials = run_sweep(&sweep, &threads, |n| {
+            vec!["sync".to_string(), format!("--threads={}", n)]
+        });
+        figures.render(&sweep.rate_chart(&trials));
+        figures.render(&sweep.curve_chart(&trials));
+        return;
+    }
+    if let Some(sweep) = matches.subcommand_matches("sweep-concurrency") {
+        let max_in_flight = sweep
+            .value_of("MAX_IN_FLIGHT")
+            .and_then(ModelConfig::parse_counts)
+            .expect("MAX_IN_FLIGHT is required");
+        let (config, figures) = sweep_config(&matches);
+        let sweep = sweep::Sweep {
+            parameter: "max_in_flight",
+            label: "Max in flight",
+            config: &config,
+            max_p99: None,
+        };
+        let trials = run_sweep(&sweep, &max_in_flight, |n| {
+            vec!["async".to_string(), format!("--max-in-flight={}", n)]
+        });
+        figures.render(&sweep.rate_chart(&trials));
+        figures.render(&sweep.curve_chart(&trials));
+        return;
+    }
+    if let Some(sweep_rate) = matches.subcommand_matches("sweep-rate") {
+        let count = |arg| {
+            sweep_rate
+                .value_of(arg)
+                .and_then(ModelConfig::parse_count)
+                .expect("The bounds are required")
+        };
+        let (from, to) = (count("FROM"), count("TO"));
+        if from > to {
+            clap::Error::with_description(
+                format!("--from {} is above --to {}\n", from, to),
+                ErrorKind::ValueValidation,
+            )
+            .exit();
+        }
+        let step = sweep_rate
+            .value_of("STEP")
+            .and_then(ModelConfig::parse_step)
+            .expect("STEP is required");
+        let duration = sweep_rate
+            .value_of("DURATION")
+            .and_then(ModelConfig::parse_interval)
+            .expect("DURATION has a default value");
+        let threads = sweep_rate
+            .value_of("THREADS")
+            .and_then(ModelConfig::parse_count);
+        let max_p99 = sweep_rate
+            .value_of("MAX_P99")
+            .and_then(ModelConfig::parse_latency);
+        let (config, figures) = sweep_config(&matches);
+        let sweep = sweep::Sweep {
+            parameter: "rate",
+            label: "Rate",
+            config: &config,
+            max_p99,
+        };
+        let trials = run_sweep(&sweep, &step.values(from, to), |rate| {
+            let mut args = vec![
+                format!("--rate={}", rate),
+                format!("--num_req={}", (rate as u64 * duration / 1000).max(1)),
+            ];
+            match threads {
+                Some(threads) => {
+                    args.push("sync".to_string());
+                    args.push(format!("--threads={}", threads));
+                }
+                None => args.push("async".to_string()),
+            }
+            args
+        });
+        let knee = sweep::knee(sweep.parameter, &trials, max_p99);
+        match knee {
+            Some(knee) => println!(
+                "The knee: {} rps, achieved {:.3} rps, p99 {:.3} ms",
+                knee.value,
+                knee.run.achieved_rate().unwrap_or_default(),
+                knee.run.percentile(99.).unwrap_or_default()
+            ),
+            None => println!("No knee up to {} rps", to),
+        }
+        figures.render(&sweep::latency_chart(&sweep, &trials, knee));
+        figures.render(&sweep.curve_chart(&trials));
+        return;
+    }
+    if let Some(plot_sweep) = matches.subcommand_matches("plot-sweep") {
+        logging::init(LevelFilter::INFO, Console::default());
+        let dir = plot_sweep.value_of("DIR").expect("DIR is required");
+        let mut python_path = matches.value_of("PYTHON_PATH").map(|s| s.to_string());
+        let figures = figures(
+            &Backend::from_name(matches.value_of("BACKEND")),
+            &mut python_path,
+        );
+        match sweep::plot(Path::new(dir), &figures) {
+            Ok(trials) => info!("Charted the curve of {} trials of {}", trials, dir),
+            Err(e) => {
+                error!("Failed to chart the sweep of {}: {}", dir, e);
+                std::process::exit(1);
+            }
+        }
+        if figures.rendered() == 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(bench_report) = matches.subcommand_matches("bench-report") {
+        logging::init(LevelFilter::INFO, Console::default());
+        let dir = bench_report
+            .value_of("CRITERION_DIR")
+            .expect("CRITERION_DIR has a default value");
+        let families: Vec<&str> = bench_report
+            .values_of("FAMILY")
+            .map(|families| families.collect())
+            .unwrap_or_default();
+        let sweeps: Vec<&str> = bench_report
+            .values_of("SWEEP")
+            .map(|sweeps| sweeps.collect())
+            .unwrap_or_default();
+        let mut python_path = matches.value_of("PYTHON_PATH").map(|s| s.to_string());
+        let figures = figures(
+            &Backend::from_name(matches.value_of("BACKEND")),
+            &mut python_path,
+        );
+        if let Err(e) = bench_report::run(Path::new(dir), &families, &sweeps, &figures) {
+            error!("Failed to report the benchmarks in {}: {}", dir, e);
+            std::process::exit(1);
+        }
+        if figures.rendered() == 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
 
+    let repeat = matches
+        .value_of("REPEAT")
+        .and_then(ModelConfig::parse_count)
+        .expect("REPEAT has a default value");
+    if repeat > 1 {
+        run_repeats(&matches, repeat);
+        return;
+    }
+
+    let mut config = ModelConfig::from_matches(&matches);
+    let console = Console::default();
+    let clock = logging::init(config.log_level, console.clone());
+    config.check_saturation();
+    if matches.is_present("URL") && matches.is_present("LATENCY_DISTRIBUTION") {
+        warn!("--latency is ignored with --url, the service takes as long as it takes");
+    }
+    if (config.engine == Engine::Des || config.spill_dir.is_some()) && config.max_samples.is_none()
+    {
+        config.max_samples = Some(des::MAX_RECORDS);
+    }
+    if matches.is_present("DRY_RUN") {
+        plan::print(&config);
+        return;
+    }
+
+    let figures = if config.no_plots {
+        Figures::new(None)
+    } else {
+        figures(&config.backend, &mut config.python_path)
+    };
+    info!("Config: {:#?}", config);
+    sources.log(&matches);
+    let (task_clock, calibration) = match config.coarse_clock {
+        Some(coarse) => {
+            let clock = CoarseClock::start(Duration::from_millis(coarse.resolution));
+            let calibration = clock.calibrate();
+            info!(
+                "Coarse clock: updated every {} ms, a timestamp costs {:.1} ns instead of {:.1} ns of Instant::now(), \
+                 it lags by {:.3} ms on average and {:.3} ms at most, so the seconds of the events are off by as much",
+                coarse.resolution,
+                calibration.coarse_cost.as_secs_f64() * 1e9,
+                calibration.precise_cost.as_secs_f64() * 1e9,
+                calibration.mean_lag.as_secs_f64() * 1000.,
+                calibration.max_lag.as_secs_f64() * 1000.
+            );
+            if coarse.full {
+                warn!(
+                    "The latencies are timed by the coarse clock too, so they are off by up to {:.3} ms, \
+                     twice its max lag, the shorter ones are unreliable",
+                    2. * calibration.max_lag.as_secs_f64() * 1000.
+                );
+            }
+            (
+                Clock::Coarse {
+                    clock,
+                    full: coarse.full,
+                },
+                Some(calibration),
+            )
+        }
+        None => (Clock::Precise, None),
+    };
+    let resumed = config.resume.as_ref().map(|path| {
+        Checkpoint::read(Path::new(path)).unwrap_or_else(|e| {
+            error!("Failed to read the checkpoint {}: {}", path, e);
+            std::process::exit(1);
+        })
+    });
+
+    // nothing sleeps in a simulation
+    let sleep = match config.engine {
+        Engine::Real => calibrate_timer(&config).await,
+        Engine::Des => Sleep::Os,
+    };
+    let channels = match (config.engine, &config.mode) {
+        (Engine::Real, &Mode::Sync(n_workers)) => {
+            let channels = tokio::task::block_in_place(|| {
+                collector::calibrate(
+                    n_workers,
+                    config.stats_batch,
+                    config.max_samples,
+                    config.seed,
+                )
+            });
+            info!(
+                "Stats channels, calibrated with zero-cost tasks: {}",
+                collector::format_costs(&channels)
+            );
+            Some(channels)
+        }
+        _ => None,
+    };
+    let interrupt = Interrupt::install();
+    let server = Server::start(&config).await.unwrap_or_else(|e| {
+        error!("Failed to start the server of the target: {}", e);
+        std::process::exit(1);
+    });
+    match &server {
+        #[cfg(unix)]
+        Some(Server::Uds(server)) => interrupt.remove_on_abort(server.path().to_path_buf()),
+        Some(Server::File(files)) => interrupt.remove_on_abort(files.dir().to_path_buf()),
+        _ => {}
+    }
+    let target = Endpoint::new(&config, server.as_ref()).unwrap_or_else(|e| {
+        error!("Failed to resolve the host of the target: {}", e);
+        std::process::exit(1);
+    });
+    let exporter = config.otel.as_ref().map(|otel| {
+        Exporter::start(&otel.endpoint, &config.name, otel.sample, config.seed).unwrap_or_else(
+            |e| {
+                error!("Failed to start exporting the traces: {}", e);
+                std::process::exit(1);
+            },
+        )
+    });
+    let tracer = exporter.as_ref().map(Exporter::tracer);
+    #[cfg(all(unix, feature = "profile"))]
+    let profiler = if config.profile {
+        Some(Profiler::start().unwrap_or_else(|e| {
+            error!("Failed to start the profiler: {}", e);
+            std::process::exit(1);
+        }))
+    } else {
+        None
+    };
+    let pinned = match config.mode {
+        Mode::Sync(n_workers) if config.pin_threads => {
+            affinity::plan(n_workers, config.cores.as_deref())
+        }
+        _ => None,
+    };
+    if let Some(nice) = config.worker_nice {
+        if let Err(e) = priority::probe(nice) {
+            warn!(
+                "Failed to set the nice value of the workers to {}: {}{}, it's left as is",
+                nice,
+                e,
+                if nice < 0 {
+                    " (a higher priority needs root or CAP_SYS_NICE)"
+                } else {
+                    ""
+                }
+            );
+            config.worker_nice = None;
+        }
+    }
+    let background = if config.background_load > 0 {
+        Some(BackgroundLoad::start(config.background_load))
+    } else {
+        None
+    };
     let start_time = Instant::now();
+    clock.mark_start(start_time);
+    let mut metadata = RunMetadata::new(&config, SystemTime::now());
+    metadata.pinned_cores = pinned.clone();
+    // a second may complete more than the rate of the tasks, e.g. once a stall is over
+    let aggregator = StreamingAggregator::new(
+        start_time,
+        task_clock.clone(),
+        config.percentile_method,
+        config.max_samples,
+        config.seed,
+    )
+    .with_capacity(config.n_jobs, config.rps + config.rps / 4);
+    let aggregator = Arc::new(match &config.spill_dir {
+        Some(dir) => {
+            let chunk = config.spill_chunk.unwrap_or(spill::DEFAULT_CHUNK);
+            match Spill::new(Path::new(dir), &config.name, start_time, chunk) {
+                Ok(spill) => aggregator.with_spill(spill),
+                Err(e) => {
+                    error!("Failed to spill to {}: {}", dir, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => aggregator,
+    });
+    let source = TaskSource::new(&config, &aggregator);
+    let allocated = allocations::count();
+    let checkpointer = config.checkpoint_every.map(|every| {
+        Checkpointer::start(
+            aggregator.clone(),
+            metadata.clone(),
+            start_time,
+            Duration::from_millis(every),
+        )
+    });
+    let progress = if config.progress {
+        Progress::new(console.clone(), config.n_jobs, config.rps)
+    } else {
+        None
+    };
+    let ticker = if config.live || progress.is_some() || config.log_level >= LevelFilter::DEBUG {
+        Some(LiveTicker::start(
+            aggregator.clone(),
+            console.clone(),
+            config.live,
+            progress,
+        ))
+    } else {
+        None
+    };
+    let schedule = ScheduleMonitor::start(
+        aggregator.clone(),
+        start_time,
+        config.rps,
+        config.n_jobs,
+        interrupt.clone(),
+    );
 
-    let stats = match config.mode {
-        Mode::Sync(n_workers) => {
+    let mut simulated = None;
+    let send_phase = match (config.engine, &config.mode) {
+        (Engine::Des, _) => {
+            info!("Simulating the run in virtual time...");
+            let wall = Instant::now();
+            let (send_phase, elapsed) = tokio::task::block_in_place(|| {
+                des::run(&config, &aggregator, start_time, &interrupt)
+            });
+            info!(
+                "Simulated {:.3}s of the run in {:.3}s",
+                elapsed.as_secs_f64(),
+                wall.elapsed().as_secs_f64()
+            );
+            simulated = Some((elapsed, wall.elapsed()));
+            send_phase
+        }
+        (Engine::Real, &Mode::Sync(n_workers)) => {
+            let workers = Workers {
+                cores: match &pinned {
+                    Some(cores) => cores.iter().copied().map(Some).collect(),
+                    None => vec![None; n_workers],
+                },
+                sleep,
+            };
             sync_execution(
-                n_workers,
-                &config.latency_distribution,
-                config.n_jobs,
-                rate_limiter,
+                &config,
+                &workers,
+                target,
+                tracer,
+                source,
+                aggregator.clone(),
+                &interrupt,
+            )
+            .await
+        }
+        (Engine::Real, Mode::Async) => {
+            async_execution(
+                &config,
+                target,
+                tracer,
+                source,
+                aggregator.clone(),
+                &interrupt,
             )
             .await
         }
-        Mode::Async => {
-            async_execution(&config.latency_distribution, config.n_jobs, rate_limiter).await
+    };
+    // all the requests are completed, so the clients are gone
+    if let Some(server) = server {
+        server.stop().await;
+    }
+    if let Some(exporter) = exporter {
+        tokio::task::block_in_place(|| exporter.stop());
+    }
+    if let Some(background) = background {
+        background.stop();
+    }
+    task_clock.stop();
+
+    if let Some(ticker) = ticker {
+        ticker.stop().await;
+    }
+    metadata.generator_limited = schedule.stop().await;
+    if let Some(checkpointer) = checkpointer {
+        checkpointer.stop();
+    }
+    // the virtual time of a simulation
+    let elapsed = simulated.map_or_else(|| start_time.elapsed(), |(elapsed, _)| elapsed);
+    let drain_phase = elapsed - send_phase;
+    info!(
+        "Completed in {:.3}s: send phase {:.3}s, drain phase {:.3}s",
+        elapsed.as_secs_f64(),
+        send_phase.as_secs_f64(),
+        drain_phase.as_secs_f64()
+    );
+    let sent = aggregator.sent();
+    if sent > 0 && allocations::ENABLED {
+        let allocations = allocations::count() - allocated;
+        info!(
+            "Allocations during the run: {}, {:.3} per task",
+            allocations,
+            allocations as f64 / sent as f64
+        );
+    }
+    #[cfg(all(unix, feature = "profile"))]
+    if let Some(profiler) = profiler {
+        let path = format!("flamegraph_{}.svg", config.name);
+        if let Err(e) = profiler.finish(Path::new(&path)) {
+            warn!("Failed to write {}: {}", path, e);
+        }
+    }
+
+    let mut results = aggregator.finish();
+    if let Some(checkpoint) = resumed {
+        info!(
+            "Resumed {} requests ({:.3}s) from {}, the per-task figures cover only this run",
+            checkpoint.state.completed,
+            checkpoint.elapsed,
+            config.resume.as_deref().unwrap_or_default()
+        );
+        results.resume(checkpoint.state);
+    }
+    metadata.interrupted = interrupt.is_set();
+    // the last checkpoint is a complete one, e.g. for `merge`
+    if config.checkpoint_every.is_some() {
+        let checkpoint = Checkpoint {
+            metadata: metadata.clone(),
+            elapsed: elapsed.as_secs_f64(),
+            state: aggregator.state(),
+        };
+        let path = Checkpoint::path(&config.name);
+        if let Err(e) = checkpoint.write(&path) {
+            warn!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+    if metadata.interrupted {
+        if config.stdin_tasks {
+            warn!("Partial run: {} requests were sent", results.sent);
+        } else {
+            warn!(
+                "Partial run: {} of {} requests were sent",
+                results.sent, config.n_jobs
+            );
+        }
+    }
+    if results.is_sampled() {
+        info!(
+            "Sampled {} of {} task records",
+            results.samples.len(),
+            results.completed
+        );
+    }
+    let seconds = &results.seconds;
+    let successes = sort_records(&mut results.samples);
+    let latencies = &results.samples[..successes];
+    let overheads = match aggregator.take_spill() {
+        Some(spill) => {
+            let merge_start = Instant::now();
+            match spill.finish() {
+                Ok(merged) => {
+                    let overheads = Overheads::Spilled(merged);
+                    info!(
+                        "Merged the overheads of {} successful requests from {} in {:.3}s",
+                        overheads.len(),
+                        config.spill_dir.as_deref().unwrap_or_default(),
+                        merge_start.elapsed().as_secs_f64()
+                    );
+                    overheads
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to spill the task records: {}, the percentiles come from the sample",
+                        e
+                    );
+                    Overheads::Records(latencies)
+                }
+            }
+        }
+        None => Overheads::Records(latencies),
+    };
+    let (histogram, rate) = build_figures(&config, &figures, &results, successes, &overheads);
+    metadata.histogram = histogram;
+
+    let mut summary = Summary::new(
+        &config,
+        &overheads,
+        &results,
+        rate,
+        (send_phase, drain_phase),
+        metadata.interrupted,
+        &metadata.generator_limited,
+    );
+    if let Some(cores) = &metadata.pinned_cores {
+        summary = summary.pinned(cores.clone());
+    }
+    if let Some((_, wall)) = simulated {
+        summary = summary.simulated(wall);
+    }
+    if let Some(channels) = channels {
+        summary = summary.stats_channels(config.stats_channel, channels);
+    }
+    if let (Some(coarse), Some(calibration)) = (config.coarse_clock, calibration) {
+        summary = summary.coarse_clock(coarse, calibration);
+    }
+    if !config.no_plots {
+        // the requested ones, which weren't rendered, e.g. without enough data, are skipped
+        let (produced, skipped): (Vec<Plot>, Vec<Plot>) = Plot::ALL
+            .iter()
+            .filter(|plot| config.plots.contains(plot))
+            .partition(|plot| figures.is_rendered(&plot.file_name(&config.name)));
+        summary = summary.plots(
+            produced.into_iter().map(Plot::name).collect(),
+            skipped.into_iter().map(Plot::name).collect(),
+        );
+    }
+    // the summary and the metadata are an output of the run as well as the figures
+    let mut produced = match summary.print(summary::color_choice(config.no_color)) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Failed to print the summary: {}", e);
+            false
+        }
+    };
+
+    if let Some(path) = &config.export_timeseries {
+        if let Err(e) = export::write_timeseries(Path::new(path), seconds, &metadata) {
+            warn!("Failed to write {}: {}", path, e);
+        }
+    }
+    if let Some(path) = &config.chrome_trace {
+        if let Err(e) = chrome_trace::write(Path::new(path), &results.samples, start_time) {
+            warn!("Failed to write {}: {}", path, e);
+        }
+    }
+
+    let metadata_path = RunMetadata::path(&config.name);
+    match metadata.write(&metadata_path) {
+        Ok(()) => produced = true,
+        Err(e) => warn!("Failed to write {}: {}", metadata_path.display(), e),
+    }
+    if let Some(path) = &config.sqlite {
+        let seconds = if config.sqlite_timeseries {
+            Some(seconds.as_slice())
+        } else {
+            None
+        };
+        match store::append(Path::new(path), &metadata, &summary, seconds) {
+            Ok(id) => info!("The run is appended to {} as #{}", path, id),
+            Err(e) => warn!("Failed to append the run to {}: {}", path, e),
+        }
+    }
+
+    if figures.failed() > 0 {
+        warn!(
+            "{} of {} figures failed",
+            figures.failed(),
+            figures.failed() + figures.rendered()
+        );
+    }
+    if !config.no_plots && figures.requested() > 0 && figures.rendered() == 0 {
+        warn!("No figures were generated");
+    }
+    if !produced && figures.rendered() == 0 {
+        error!("No output was produced");
+        std::process::exit(1);
+    }
+    if !summary.failures().is_empty() {
+        std::process::exit(1);
+    }
+    if metadata.interrupted {
+        std::process::exit(130);
+    }
+}
+
+/// The figures of `backend`, none if it's not available.
+fn figures(backend: &Backend, python_path: &mut Option<String>) -> Figures {
+    Figures::new(match report::reporter(backend, python_path) {
+        Ok(reporter) => Some(reporter),
+        Err(e) => {
+            error!("{} No figures will be generated.", e);
+            None
         }
+    })
+}
+
+/// The options of the command line shared by the trials of a sweep, or by the repeats, and the figures.
+fn sweep_config(matches: &ArgMatches) -> (ModelConfig, Figures) {
+    let mut config = ModelConfig::from_matches(matches);
+    // the trials are collected in a database
+    let usage = match matches.subcommand_name() {
+        Some(name) if name.starts_with("sweep-") => name,
+        _ => "--repeat",
+    };
+    require_feature(cfg!(feature = "sqlite"), usage, "sqlite");
+    if matches.occurrences_of("REPEAT") > 0
+        && matches!(matches.subcommand_name(), Some(name) if name.starts_with("sweep-"))
+    {
+        clap::Error::with_description(
+            "--repeat can't be used with a sweep, its trials are single runs\n".to_string(),
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    if matches.is_present("DRY_RUN") {
+        clap::Error::with_description(
+            "--dry-run plans a single run, not the ones of a sweep or of --repeat\n".to_string(),
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    if config.stdin_tasks {
+        clap::Error::with_description(
+            "--stdin-tasks can't be swept, stdin is read once\n".to_string(),
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    logging::init(config.log_level, Console::default());
+    let figures = if config.no_plots {
+        Figures::new(None)
+    } else {
+        figures(&config.backend, &mut config.python_path)
     };
+    (config, figures)
+}
+
+/// Exits if no trial completes.
+fn run_sweep(
+    sweep: &sweep::Sweep,
+    values: &[usize],
+    args: impl Fn(usize) -> Vec<String>,
+) -> Vec<sweep::Trial> {
+    sweep.run(values, args).unwrap_or_else(|e| {
+        error!("Failed to sweep {}: {}", sweep.parameter, e);
+        std::process::exit(1);
+    })
+}
+
+/// Runs the model `repeat` times with the seeds derived from `--seed`, or with the same one,
+/// if it's set, and aggregates the summaries into `repeat_{name}.json` and `repeat_{name}.png`.
+fn run_repeats(matches: &ArgMatches, repeat: usize) {
+    let (config, figures) = sweep_config(matches);
+    let seeds: Vec<u64> = if matches.is_present("SEED") {
+        vec![config.seed; repeat]
+    } else {
+        let mut rng = stats::rng(config.seed, "repeat");
+        (0..repeat).map(|_| rng.gen()).collect()
+    };
+    let command_line: Vec<String> = env::args().collect();
+    let (_, subcommand) = config_file::split(&command_line);
+    let sweep = sweep::Sweep {
+        parameter: "repeat",
+        label: "Repeat",
+        config: &config,
+        max_p99: None,
+    };
+    let values: Vec<usize> = (1..=repeat).collect();
+    let trials = run_sweep(&sweep, &values, |i| {
+        let mut args = vec![format!("--seed={}", seeds[i - 1]), "--repeat=1".to_string()];
+        args.extend_from_slice(subcommand);
+        args
+    });
+    let aggregate = repeat::Aggregate::new(&trials);
+    aggregate.print();
+    let path = format!("repeat_{}.json", config.name);
+    if let Err(e) = aggregate.write_json(Path::new(&path), &trials, &seeds, &config.tags) {
+        warn!("Failed to write {}: {}", path, e);
+    }
+    figures.render(&aggregate.chart(format!("repeat_{}.png", config.name)));
+}
+
+/// Logs how much longer than requested a short sleep takes, i.e. the effective timer resolution
+/// of the mode. Nominal task costs are off by about this much, unless the sleep is precise,
+/// then it's calibrated and checked too.
+async fn calibrate_timer(config: &ModelConfig) -> Sleep {
+    let (mean, max) = measure_sleep(&config.mode).await;
+    info!(
+        "Timer calibration: a {} ms sleep takes {:.3} ms on average, {:.3} ms max{}",
+        CALIBRATION_SLEEP.as_millis(),
+        mean.as_secs_f64() * 1000.,
+        max.as_secs_f64() * 1000.,
+        match config.mode {
+            Mode::Sync(_) => "",
+            Mode::Async =>
+                ", the timer of tokio ticks every 1 ms, so the delays are rounded up to it",
+        }
+    );
+    if !config.precise_sleep {
+        return Sleep::Os;
+    }
+    let sleep = tokio::task::block_in_place(Sleep::calibrate);
+    let (mean_error, max_error) = tokio::task::block_in_place(|| sleep.check());
+    if let Sleep::Precise { margin } = sleep {
+        info!(
+            "Precise sleep: the workers spin for the last {:.3} ms of a sleep, \
+             the sleeps of 1-5 ms are off by {:.2}% on average, {:.2}% max",
+            margin.as_secs_f64() * 1000.,
+            mean_error * 100.,
+            max_error * 100.
+        );
+    }
+    sleep
+}
+
+const CALIBRATION_SLEEP: Duration = Duration::from_millis(1);
+
+/// (the mean, the max) of how long a sleep of `CALIBRATION_SLEEP` takes in the mode.
+async fn measure_sleep(mode: &Mode) -> (Duration, Duration) {
+    const ROUNDS: u32 = 10;
+    let mut total = Duration::default();
+    let mut max = Duration::default();
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        match mode {
+            Mode::Sync(_) => tokio::task::block_in_place(|| sleep(CALIBRATION_SLEEP)),
+            Mode::Async => delay_for(CALIBRATION_SLEEP).await,
+        }
+        let elapsed = start.elapsed();
+        total += elapsed;
+        max = max.max(elapsed);
+    }
+    (total / ROUNDS, max)
+}
+
+/// Turns a parser of an argument into a validator, which shows the value and what's expected.
+fn validate<T>(
+    parse: fn(&str) -> Option<T>,
+    expected: &'static str,
+) -> impl FnMut(&str) -> Result<(), String> + Send {
+    move |s| {
+        parse(s)
+            .map(|_| ())
+            .ok_or_else(|| format!("`{}` is not {}", s, expected))
+    }
+}
+
+/// Exits with a usage error if `usage` needs an optional `feature`, which the binary is built without.
+fn require_feature(enabled: bool, usage: &str, feature: &str) {
+    if !enabled {
+        clap::Error::with_description(
+            format!(
+                "{} needs the `{}` feature, e.g. cargo install concurrency-demo-benchmarks --features {}\n",
+                usage, feature, feature
+            ),
+            ErrorKind::InvalidValue,
+        )
+        .exit();
+    }
+}
+
+/// Sorts the records in place rather than copying them: the successful ones first, by overhead,
+/// they are shared by the figures and the summary, the failed ones are left at the end.
+/// Returns the number of the successful ones.
+fn sort_records(samples: &mut [TaskStats]) -> usize {
+    // the ties are equal overheads, so the order of the sorts of the threads doesn't change the percentiles
+    samples.par_sort_unstable_by(|a, b| {
+        a.failure
+            .is_some()
+            .cmp(&b.failure.is_some())
+            .then_with(|| a.overhead.partial_cmp(&b.overhead).unwrap())
+    });
+    samples.partition_point(TaskStats::success)
+}
 
-    let (latencies, rps_buckets) = process_stats(start_time, stats);
+/// The figures of a run, whose records are sorted by `sort_records`, `successes` of them first.
+/// Returns the binning of the histogram and the rate.
+fn build_figures(
+    config: &ModelConfig,
+    figures: &Figures,
+    results: &RunResults,
+    successes: usize,
+    overheads: &Overheads,
+) -> (Option<Binning>, Option<Rate>) {
+    let latencies = &results.samples[..successes];
+    let rps_buckets = results
+        .seconds
+        .iter()
+        .filter(|s| s.completed > s.failed)
+        .map(|s| (s.second, (s.completed - s.failed) as u64))
+        .collect();
+
+    build_latency_timeline(config, figures, latencies);
+    build_latency_boxes(config, figures, latencies);
+    build_latency_classes(config, figures, &results.classes);
+    build_latency_scatter(config, figures, &results.samples);
+    build_latency_heatmap(config, figures, &results.heatmap);
+    let histogram = build_latency_histogram(config, figures, overheads);
+    let rate = build_rps_graph(config, figures, rps_buckets);
+    build_combined_graph(config, figures, &results.seconds);
+    (histogram, rate)
+}
 
-    build_latency_timeline(&config, latencies.clone());
-    build_latency_histogram(&config, latencies);
-    build_rps_graph(&config, rps_buckets);
+/// The sync worker threads: the core of each one, if they are pinned, and how they sleep for the costs.
+struct Workers {
+    cores: Vec<Option<usize>>,
+    sleep: Sleep,
 }
 
 /// Model multi-thread environment, where each threads can handle
 /// a single connection at a time.
+/// With a `target`, every worker sends the requests over a connection of its own.
 async fn sync_execution(
-    n_workers: usize,
-    latency_distribution: &[u64],
-    n_jobs: usize,
-    rate_limiter: LeakyBucket,
-) -> Vec<TaskStats> {
+    config: &ModelConfig,
+    workers: &Workers,
+    target: Option<Endpoint>,
+    tracer: Option<TaskTracer>,
+    mut source: TaskSource,
+    aggregator: Arc<StreamingAggregator>,
+    interrupt: &Interrupt,
+) -> Duration {
+    let timeout = Duration::from_millis(config.timeout);
+    let granularity = config.cancellable_work.map(Duration::from_millis);
+    let n_workers = workers.cores.len();
+    let nice = config.worker_nice;
+    let collector = Collector::start(
+        config.stats_channel,
+        config.stats_batch,
+        config.n_jobs / n_workers,
+        aggregator.clone(),
+    );
     let mut threads = Vec::with_capacity(n_workers);
-    let (send, recv) = crossbeam::channel::bounded::<Task>(n_jobs);
-    static TASK_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    // the tasks of stdin wait for a thread in a queue of their own number, once it's full,
+    // stdin is not read, so the upstream process is blocked
+    let (send, recv) = if config.stdin_tasks {
+        crossbeam::channel::bounded::<Task>(n_workers)
+    } else {
+        crossbeam::channel::bounded::<Task>(config.n_jobs)
+    };
 
-    for _ in 0..n_workers {
+    for (worker, core) in workers.cores.iter().copied().enumerate() {
+        let sleep = workers.sleep;
         let receiver = recv.clone();
+        let aggregator = aggregator.clone();
+        let mut client = target.as_ref().map(Endpoint::sync_client);
+        let tracer = tracer.clone();
+        let mut delivery = collector.delivery();
 
         threads.push(thread::spawn(move || {
-            let mut thread_stats = vec![];
-            for val in receiver {
-                sleep(Duration::from_millis(val.cost));
-                // report metrics
-                let now = Instant::now();
-                let stats = TaskStats {
-                    start_time: val.start,
-                    success: val.cost < TIMEOUT.as_millis() as u64,
-                    completion_time: now,
-                    overhead: now.duration_since(val.start).as_secs_f64() - val.cost as f64 / 1000.,
+            let clock = aggregator.clock().clone();
+            if let Some(core) = core {
+                if !affinity::pin(core) {
+                    warn!("Failed to pin the worker {} to the core {}", worker, core);
+                }
+            }
+            if let Some(nice) = nice {
+                if let Err(e) = priority::set_nice(nice) {
+                    warn!(
+                        "Failed to set the nice value of the worker {} to {}: {}",
+                        worker, nice, e
+                    );
+                }
+            }
+            // the loop ends once the channel is closed and drained, a due batch is flushed while waiting
+            loop {
+                let val = match delivery.due_in() {
+                    None => match receiver.recv() {
+                        Ok(val) => val,
+                        Err(_) => break,
+                    },
+                    Some(due) => match receiver.recv_timeout(due) {
+                        Ok(val) => val,
+                        Err(RecvTimeoutError::Timeout) => {
+                            delivery.flush(&aggregator);
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    },
+                };
+                aggregator.record_started();
+                let service_start = clock.now();
+                let result = match &mut client {
+                    Some(client) => client.call(val.index, val.cost),
+                    None => {
+                        serve(
+                            Duration::from_millis(val.cost),
+                            val.start + timeout,
+                            granularity,
+                            sleep,
+                        );
+                        Ok(Served {
+                            service_time: clock.now() - service_start,
+                            setup: None,
+                        })
+                    }
                 };
-                thread_stats.push(stats);
-                TASK_COUNTER.fetch_add(1, Ordering::Relaxed);
+                // report metrics
+                // `benches/benchmarks_stats.rs`: per-thread `Vec`s joined at the end are the fastest,
+                // a shared `Mutex<Vec>` is close behind, a channel or a lock-free queue are ~2x slower;
+                // the completions are batched the same way, the live view and the checkpoints see them per batch
+                let completion = clock.now();
+                let stats = match result {
+                    Ok(served) => {
+                        if let Some(setup) = served.setup {
+                            aggregator.record_setup(setup);
+                        }
+                        TaskStats::new(
+                            timeout,
+                            val.start,
+                            service_start,
+                            served.service_time,
+                            completion,
+                        )
+                    }
+                    Err(e) => {
+                        debug!("The request to the target failed: {}", e);
+                        TaskStats::new(
+                            timeout,
+                            val.start,
+                            service_start,
+                            completion - service_start,
+                            completion,
+                        )
+                        .io_error()
+                    }
+                }
+                .served_by(worker);
+                if let Some(tracer) = &tracer {
+                    tracer.record(val.index, val.cost, &stats);
+                }
+                delivery.push(&aggregator, stats, val.class);
             }
-            thread_stats
+            delivery.finish(&aggregator);
         }));
     }
 
-    println!("Starting sending tasks...");
+    info!("Starting sending tasks...");
+    let send_start = Instant::now();
+    let clock = aggregator.clock().clone();
 
-    for i in 0..n_jobs {
-        rate_limiter.acquire_one().await.unwrap_or_default();
-        let cost = latency_distribution[i % latency_distribution.len()];
-        let now = Instant::now();
-        send.send(Task { start: now, cost }).unwrap();
+    let mut sends = Sends::default();
+    let mut i = 0;
+    while let Some(spec) = source.next(interrupt).await {
+        let task = Task {
+            index: i,
+            start: clock.now(),
+            cost: spec.cost,
+            class: spec.class,
+        };
+        aggregator.record_sent(&mut sends);
+        if config.stdin_tasks {
+            // the wait for room in the queue is a part of the latency
+            tokio::task::block_in_place(|| send.send(task)).unwrap();
+        } else {
+            send.send(task).unwrap();
+        }
+        i += 1;
     }
+    sends.flush(&aggregator);
 
-    println!("Waiting for completion...");
+    // close the channel, so the workers exit as soon as the queue is drained
+    drop(send);
 
-    while TASK_COUNTER.load(Ordering::Relaxed) < n_jobs {
-        sleep(Duration::from_secs(1));
-    }
+    let send_phase = send_start.elapsed();
+    info!("Waiting for completion...");
 
-    drop(send);
+    tokio::task::block_in_place(|| {
+        for t in threads {
+            t.join().expect("Worker failed");
+        }
+        collector.stop();
+    });
 
-    let mut combined_stats = vec![];
-    for t in threads {
-        let thread_stats = t.join().unwrap();
-        combined_stats.extend(thread_stats);
-    }
+    send_phase
+}
 
-    combined_stats
+/// Blocks for `cost`. If the work is cancellable, it's done in `granularity` slices
+/// and abandoned once the `deadline` has passed, e.g. as a server checking it between I/O operations.
+fn serve(cost: Duration, deadline: Instant, granularity: Option<Duration>, sleep: Sleep) {
+    let granularity = match granularity {
+        Some(granularity) => granularity,
+        None => return sleep.sleep(cost),
+    };
+    let start = Instant::now();
+    loop {
+        let served = start.elapsed();
+        if served >= cost || Instant::now() >= deadline {
+            return;
+        }
+        sleep.sleep(granularity.min(cost - served));
+    }
 }
 
 /// Model an async environment, where there are several threads
 /// handling up to tens (or hundreds) of thousands of connections simultaneously.
+/// With a `target`, every request is sent over a connection of its own, or a pooled one.
 async fn async_execution(
-    latency_distribution: &[u64],
-    n_jobs: usize,
-    rate_limiter: LeakyBucket,
-) -> Vec<TaskStats> {
-    let mut tasks = Vec::with_capacity(n_jobs);
+    config: &ModelConfig,
+    target: Option<Endpoint>,
+    tracer: Option<TaskTracer>,
+    mut source: TaskSource,
+    aggregator: Arc<StreamingAggregator>,
+    interrupt: &Interrupt,
+) -> Duration {
+    let timeout = Duration::from_millis(config.timeout);
+    let mut tasks = Vec::with_capacity(config.n_jobs);
+    // the waiting tasks are queued, as the ones of the sync workers
+    let in_flight = config
+        .max_in_flight
+        .map(|max| Arc::new(Semaphore::new(max)));
 
-    println!("Starting sending tasks...");
+    info!("Starting sending tasks...");
+    let send_start = Instant::now();
 
-    for i in 0..n_jobs {
-        rate_limiter.acquire_one().await.unwrap_or_default();
-        let cost = latency_distribution[i % latency_distribution.len()];
-        let start = Instant::now();
+    let mut sends = Sends::default();
+    let mut i = 0;
+    while let Some(spec) = source.next(interrupt).await {
+        let index = i;
+        i += 1;
+        let cost = spec.cost;
+        let class = spec.class;
+        let start = aggregator.clock().now();
+        let aggregator = aggregator.clone();
+        let target = target.clone();
+        let tracer = tracer.clone();
+        let in_flight = in_flight.clone();
+        aggregator.record_sent(&mut sends);
         tasks.push(tokio::spawn(async move {
-            delay_for(Duration::from_millis(cost)).await;
+            let _permit = match in_flight {
+                Some(in_flight) => Some(in_flight.acquire_owned().await),
+                None => None,
+            };
+            aggregator.record_started();
+            let service_start = aggregator.clock().now();
+            let result = match target {
+                Some(endpoint) => endpoint.call(index, cost).await,
+                None => {
+                    delay_for(Duration::from_millis(cost)).await;
+                    Ok(Served {
+                        service_time: aggregator.clock().now() - service_start,
+                        setup: None,
+                    })
+                }
+            };
 
-            let now = Instant::now();
-            TaskStats {
-                start_time: start,
-                success: cost < TIMEOUT.as_millis() as u64,
-                completion_time: now,
-                overhead: now.duration_since(start).as_secs_f64() - cost as f64 / 1000.,
+            let completion = aggregator.clock().now();
+            let stats = match result {
+                Ok(served) => {
+                    if let Some(setup) = served.setup {
+                        aggregator.record_setup(setup);
+                    }
+                    TaskStats::new(
+                        timeout,
+                        start,
+                        service_start,
+                        served.service_time,
+                        completion,
+                    )
+                }
+                Err(e) => {
+                    debug!("The request to the target failed: {}", e);
+                    TaskStats::new(
+                        timeout,
+                        start,
+                        service_start,
+                        completion - service_start,
+                        completion,
+                    )
+                    .io_error()
+                }
+            };
+            aggregator.record_completion(&stats, class);
+            if let Some(tracer) = &tracer {
+                tracer.record(index, cost, &stats);
             }
         }));
     }
+    sends.flush(&aggregator);
 
-    println!("Waiting for completion...");
+    let send_phase = send_start.elapsed();
+    info!("Waiting for completion...");
 
-    let mut combined_stats = vec![];
     for t in tasks {
-        combined_stats.push(t.await.expect("Task failed"));
+        t.await.expect("Task failed");
     }
 
-    combined_stats
-}
-
-fn process_stats(
-    start_time: Instant,
-    stats_collection: Vec<TaskStats>,
-) -> (Vec<TaskStats>, HashMap<u64, u64>) {
-    let mut latencies = vec![];
-    let mut rps_buckets = HashMap::new();
-    for stats in stats_collection {
-        if stats.success {
-            latencies.push(stats.clone());
-            rps_buckets
-                .entry(stats.completion_time.duration_since(start_time).as_secs())
-                .or_insert(0)
-                .add_assign(1);
-        }
-    }
-    (latencies, rps_buckets)
+    send_phase
 }
 
 impl ModelConfig {
-    fn from_cli() -> Self {
-        let matches = clap_app!(myapp =>
+    /// The characters of a tag value appended to the name.
+    const TAG_IN_NAME: usize = 12;
+
+    fn cli() -> App<'static> {
+        clap_app!(myapp =>
             (name: "Model Sync/Async execution")
             (version: "0.0.1")
             (author: "Eugene Retunsky")
             (about: "A tool to model sync vs async processing for a network service")
-            (@arg NAME: --name -N +takes_value +required "Name of the test-case")
-            (@arg RATE: --rate -r +takes_value +required "Request rate per second. E.g. 100 or 1000")
-            (@arg NUM_REQUESTS: --num_req -n +takes_value +required "Number of requests. E.g. 1000")
-            (@arg LATENCY_DISTRIBUTION: --latency -l +takes_value +required "Comma separated latency values. E.g. 20ms*9,30s or 10ms,20ms,30ms")
-            (@arg PYTHON_PATH: --python_path -p +takes_value "Optional path to python3, e.g. /usr/bin/python3")
+            // only `async`, `sync` and the sweeps run the model, the required arguments are checked in `from_matches`
+            (@setting SubcommandsNegateReqs)
+            // the options of `--config` go first, so the command line overrides them
+            (@setting AllArgsOverrideSelf)
+            (@arg CONFIG: --config +takes_value "Read the options from a TOML file, e.g. run.toml, a key per long name, e.g. num_req = 1000, and `mode` for sync or async. The CDB_ variables, e.g. CDB_RATE, override the file, the command line overrides both. See init-config")
+            (@arg NAME: --name -N +takes_value "Name of the test-case. Required, unless --preset is used, the files of a preset are named {preset}_{name}")
+            (@arg PRESET: --preset +takes_value {validate(preset::find, "a preset, e.g. slow-db, see presets")} "A scenario: its latencies, rate and duration, the options override them: slow-db, spiky-cache, fanout-backend or uniform-fast, see presets")
+            (@arg RATE: --rate -r +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 1000 or 2.5k")} "Request rate per second. E.g. 100, 1000 or 2.5k. Required, unless --stdin-tasks is used")
+            (@arg NUM_REQUESTS: --num_req -n +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 10000 or 10M")} "Number of requests. E.g. 1000, 500k or 10M. Required, unless --stdin-tasks is used")
+            (@arg LATENCY_DISTRIBUTION: --latency -l +takes_value {validate(ModelConfig::parse_latency_distribution, "a list of latencies, e.g. 20ms*9,30s")} "Comma separated latency values. E.g. 20ms*9,30s or 10ms,20ms,30ms. Required, unless --url or --stdin-tasks is used")
+            (@arg STDIN_TASKS: --("stdin-tasks") conflicts_with[RATE NUM_REQUESTS LATENCY_DISTRIBUTION PROGRESS] "Read the tasks from stdin instead of --rate, --num_req and --latency: a line per task of its latency and an optional class, e.g. `20ms read`, sent as soon as it's read. The end of stdin ends the send phase")
+            (@arg TIMEOUT: --timeout +takes_value default_value("1s") {validate(ModelConfig::parse_latency, "a latency, e.g. 1s")} "Requests with the measured latency (queueing included) above the timeout are failed")
+            (@arg TARGET: --target +takes_value default_value("sleep") {validate(ModelConfig::parse_target, "sleep, tcp, http, uds, udp, file or cmd:<command>, e.g. cmd:./work.sh")} "What serving a request is: a sleep of its latency, a request to a built-in TCP echo, HTTP/1.1, Unix domain socket or UDP echo server, which sleeps the latency before answering, a write and a read back of a file, or a run of a command, e.g. cmd:./work.sh. Over TCP or UDS, the sync workers reuse a connection each, the async mode connects per request")
+            (@arg URL: --url +takes_value {validate(ModelConfig::parse_url, "an http URL, e.g. http://localhost:8080/health")} "Send the requests to an external HTTP service instead of --target, e.g. http://localhost:8080/health. Their timeout is --timeout, https isn't supported")
+            (@arg SUCCESS_STATUS: --("success-status") +takes_value {validate(ModelConfig::parse_statuses, "a list of statuses, e.g. 2xx,304")} "With --url, the statuses of a successful request, e.g. 2xx,304. 2xx by default")
+            (@arg MAX_PROCESSES: --("max-processes") +takes_value default_value("256") {validate(ModelConfig::parse_count, "a positive integer, e.g. 256")} "With --target cmd:..., the max number of running processes of the async mode. The sync one runs a process per thread at most")
+            (@arg DROP: --drop +takes_value {validate(ModelConfig::parse_share, "a percentage within [0, 100], e.g. 1%")} "With --target udp, the share of the datagrams the server drops, e.g. 1%. Their requests time out, as there are no retries yet")
+            (@arg SOCKET: --socket +takes_value "The socket file of --target uds, e.g. /tmp/cdb.sock. A stale one is replaced, it's removed at the end of the run")
+            (@arg FILE_SIZE: --("file-size") +takes_value default_value("64k") {validate(ModelConfig::parse_size, "a size, e.g. 64k, 1m or 512")} "With --target file, the size of the file written and read back by every request, in bytes, KiB (k) or MiB (m)")
+            (@arg DIR: --dir +takes_value "With --target file, where the files are written, e.g. /tmp/cdb. They are kept in a subdirectory of the run, which is removed at the end. The temporary directory by default")
+            (@arg FSYNC: --fsync "With --target file, flush every file to the disk before reading it back")
+            (@arg NO_KEEPALIVE: --("no-keepalive") "With --target http or --url, establish a connection per request. The connection time is reported separately")
+            (@arg TLS: --tls "With --target tcp or http, use TLS with a self-signed certificate generated at startup. The handshakes are a part of the connection time")
+            (@arg PYTHON_PATH: --python_path -p +takes_value "Optional path to python3, e.g. /usr/bin/python3. By default, python3 or python with matplotlib is looked up in PATH")
+            (@arg HIST_BINS: --("hist-bins") +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 50")} "The number of bins of the latency histogram. Freedman–Diaconis rule by default")
+            (@arg HIST_RANGE: --("hist-range") +takes_value {validate(ModelConfig::parse_latency_range, "a range of latencies, e.g. 0:500ms")} "The range of the latency histogram, e.g. 0:500ms. The whole range of latencies by default")
+            (@arg PERCENTILE_METHOD: --("percentile-method") +takes_value possible_values(&["nearest-rank", "linear"]) default_value("nearest-rank") "How percentiles are computed: the nearest observed value, or linear interpolation")
+            (@arg TRIM_EDGES: --("trim-edges") +takes_value default_value("auto") {validate(ModelConfig::parse_trim_edges, "a number of seconds or `auto`, e.g. 1s")} "How much of each edge of the run is excluded from the average rate: e.g. 1s, 0s, or auto (1s, unless the run is shorter than 10s)")
+            (@arg TIMELINE_BASIS: --("timeline-basis") +takes_value possible_values(&["start", "completion"]) default_value("start") "Which moment places a request on the latency timeline. By start, the latencies of a stall show up when the requests were queued, i.e. earlier and sharper. By completion, when they were served")
+            (@arg BACKEND: --backend +takes_value possible_values(&["matplotlib", "gnuplot"]) default_value("matplotlib") "Plotting backend: python3 with matplotlib, or gnuplot. If it can't be used, the other one is the fallback")
+            (@arg LIVE: --live "Print per-second stats (sent, completed, failed, p50/p99, queue depth) during the run")
+            (@arg EXPORT_TIMESERIES: --("export-timeseries") +takes_value "Write per-second aggregates (sent, completed, failed, latency percentiles, queue depth, in-flight) to a CSV file, e.g. ts_{name}.csv")
+            (@arg TAG: --tag +takes_value +multiple number_of_values(1) {validate(ModelConfig::parse_tag, "a tag, e.g. branch=feature-x, of a key of letters, digits, `_`, `-` or `.`, and a value without path separators")} "Tag the run, e.g. --tag branch=feature-x --tag host=bench01. The tags are recorded in the metadata and by --sqlite, and their values, cut to 12 characters, are appended to the name, i.e. to the names of the output files")
+            (@arg SQLITE: --sqlite +takes_value "Append the metadata and the summary of the run to a SQLite database, e.g. results.db. It's created or migrated to the current schema. See the history subcommand")
+            (@arg SQLITE_TIMESERIES: --("sqlite-timeseries") requires[SQLITE] "With --sqlite, append the per-second aggregates of the run too")
+            (@arg CHROME_TRACE: --("chrome-trace") +takes_value "Write the tasks as Chrome trace events to a file to be loaded into Perfetto, e.g. trace_{name}.json: a track per sync worker or per lane of concurrent async tasks, the queue wait of each task, the queue depth and the tasks in flight. With --max-samples, only the sampled tasks")
+            (@arg MAX_SAMPLES: --("max-samples") +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 1_000_000")} "Keep a uniform sample of at most this many per-task records for the figures, e.g. 1_000_000. Counts and summary percentiles still cover all the requests")
+            (@arg SPILL_DIR: --("spill-dir") +takes_value "Write all the per-task records to a temporary directory within this one, e.g. /tmp, in sorted chunks, and merge them after the run, so the percentiles, the histogram and Apdex are exact for any number of requests in bounded memory. The timeline and the trace use a sample of 1M records, unless --max-samples is set")
+            (@arg SPILL_CHUNK: --("spill-chunk") +takes_value requires[SPILL_DIR] {validate(ModelConfig::parse_count, "a positive integer, e.g. 1_000_000")} "With --spill-dir, the records of a chunk sorted in memory, e.g. 100_000. Up to 3 chunks are in memory at once. 1_048_576 by default")
+            (@arg REPEAT: --repeat +takes_value default_value("1") {validate(ModelConfig::parse_count, "a positive integer, e.g. 5")} "Run the model this many times, with the seeds derived from --seed, unless it's set, and aggregate the summaries: the mean and the standard deviation of every percentile and of the achieved rate, into repeat_{name}.json and repeat_{name}.png")
+            (@arg SEED: --seed +takes_value {validate(|s| s.parse::<u64>().ok(), "an unsigned integer, e.g. 42")} "The seed of every random choice of the run, e.g. sampling. Random by default, in any case it's logged and written to the metadata")
+            (@arg CHECKPOINT_EVERY: --("checkpoint-every") +takes_value {validate(ModelConfig::parse_interval, "an interval, e.g. 60s")} "Periodically write the aggregated stats to checkpoint_{name}.json, e.g. every 60s")
+            (@arg RESUME: --resume +takes_value "Fold a checkpoint of a crashed run, e.g. checkpoint_{name}.json, into the results")
+            (@arg APDEX: --apdex +takes_value {validate(ModelConfig::parse_latency, "a latency, e.g. 50ms")} "Report the Apdex score for the target latency, e.g. 50ms")
+            (@arg ASSERT_P50: --("assert-p50") +takes_value {validate(ModelConfig::parse_latency, "a latency, e.g. 10ms")} "Fail the run if p50 latency exceeds the value, e.g. 10ms")
+            (@arg ASSERT_P99: --("assert-p99") +takes_value {validate(ModelConfig::parse_latency, "a latency, e.g. 250ms")} "Fail the run if p99 latency exceeds the value, e.g. 250ms")
+            (@arg ASSERT_ERRORS: --("assert-errors") +takes_value {validate(ModelConfig::parse_percentage, "a percentage within [0, 100], e.g. 0.5")} "Fail the run if the share of failed requests exceeds the value, %")
+            (@arg ASSERT_APDEX: --("assert-apdex") +takes_value requires[APDEX] {validate(ModelConfig::parse_score, "a number within [0, 1], e.g. 0.9")} "Fail the run if the Apdex score is below the value, e.g. 0.9")
+            (@arg OTEL_ENDPOINT: --("otel-endpoint") +takes_value {validate(ModelConfig::parse_url, "an http URL, e.g. http://localhost:4317")} "Export a trace of a sample of the tasks, with spans of their queue wait and execution, to an OTLP/gRPC collector, e.g. http://localhost:4317. Needs the `otlp` feature")
+            (@arg TRACE_SAMPLE: --("trace-sample") +takes_value requires[OTEL_ENDPOINT] {validate(ModelConfig::parse_share, "a percentage within [0, 100], e.g. 1%")} "With --otel-endpoint, the share of the traced tasks, e.g. 1%, chosen by --seed. 1% by default")
+            (@arg PROFILE: --profile "Sample the stacks of all the threads at 100 Hz during the run and write flamegraph_{name}.svg. The latencies include the overhead of sampling. Needs the `profile` feature")
+            (@arg COARSE_CLOCK: --("coarse-clock") +takes_value {validate(ModelConfig::parse_coarse_clock, "an interval, e.g. 1ms, or full=1ms")} "Take the timestamps of the events, which only place them in the seconds of the run, from a clock updated by a background thread every interval, e.g. 1ms, instead of Instant::now(). With full=1ms, the latencies too, they are off by up to twice its lag then")
+            (@arg ENGINE: --engine +takes_value possible_values(&["real", "des"]) default_value("real") "What runs the model: real sends and serves the requests in real time, des simulates the sleeps of the sync workers or the async tasks as a queue in virtual time, so a run of millions of requests takes seconds, without the overheads of the threads and the timers. A simulation keeps a sample of 1M task records, unless --max-samples is set")
+            (@arg BACKGROUND_LOAD: --("background-load") +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 4")} "Spin this many busy threads at the lowest priority (nice 19) during the run, to model a service competing with background work")
+            (@arg PLOTS: --plots +takes_value default_value("all") {validate(ModelConfig::parse_plots, "a list of timeline, histogram, percentiles, log-percentiles, cdf, scatter, heatmap, boxes, classes, rate and combined, or all, or none")} "The figures to generate, e.g. histogram,timeline, or all, or none: timeline, histogram, percentiles, log-percentiles, cdf, scatter, heatmap, boxes, classes, rate, combined")
+            (@arg NO_PLOTS: --("no-plots") "Don't generate figures, only the summary. The same as --plots none")
+            (@arg DRY_RUN: --("dry-run") "Validate the options and print the plan of the run without executing it: the schedule of the tasks, the rate limiter, the expected duration and concurrency, the workers and the output files")
+            (@arg NO_COLOR: --("no-color") "Don't color the summary. Also disabled if NO_COLOR is set or stdout is not a TTY")
+            (@arg PROGRESS: --progress "Show a progress bar with ETA for the send and drain phases (only if stdout is a TTY)")
+            (@arg QUIET: --quiet -q conflicts_with[VERBOSE] "Only log warnings and errors. The summary is always printed")
+            (@arg VERBOSE: --verbose -v +multiple_occurrences "Log per-second diagnostics (-v) or everything (-vv)")
             (@subcommand async =>
                 (about: "Model a service with Async I/O")
                 (version: "0.0.1")
+                (@setting AllArgsOverrideSelf)
+                (@arg MAX_IN_FLIGHT: --("max-in-flight") +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 1000")} "The max number of the tasks served at once, e.g. 1000, the others wait in the queue. Unlimited by default")
             )
             (@subcommand sync =>
                 (about: "Model a service with Blocking I/O")
                 (version: "0.0.1")
-                (@arg THREADS: --threads -t +takes_value +required "The number of worker threads")
+                (@setting AllArgsOverrideSelf)
+                (@arg THREADS: --threads -t +takes_value +required {validate(ModelConfig::parse_count, "a positive integer, e.g. 100")} "The number of worker threads")
+                (@arg CANCELLABLE_WORK: --("cancellable-work") +takes_value {validate(ModelConfig::parse_granularity, "a granularity, e.g. granularity=50ms")} "Work in slices, e.g. granularity=50ms, and abandon requests once they time out")
+                (@arg WORKER_NICE: --("worker-nice") +takes_value +allow_hyphen_values {validate(ModelConfig::parse_nice, "a nice value from -20 to 19, e.g. 10")} "The nice value of the worker threads, e.g. 10. A negative one, i.e. a higher priority, needs root or CAP_SYS_NICE. Linux only")
+                (@arg PRECISE_SLEEP: --("precise-sleep") "Sleep for the bulk of a cost and spin for the rest, calibrated at the start, so the costs of a few ms are not off by the granularity of the OS timer. The spins take up the cores")
+                (@arg PIN_THREADS: --("pin-threads") "Pin each worker thread to a core, consecutive ones, to avoid the noise of the migrations by the scheduler")
+                (@arg CORES: --cores +takes_value requires[PIN_THREADS] {validate(ModelConfig::parse_cores, "a list of cores or their ranges, e.g. 0-7 or 0,2,4-6")} "The cores of --pin-threads, e.g. 0-7. All the cores of the process by default")
+                (@arg STATS_BATCH: --("stats-batch") +takes_value default_value("64") {validate(ModelConfig::parse_count, "a positive integer, e.g. 64")} "Each worker records its completions in batches of up to this many, so the stats are locked once per batch. 1 records every completion on its own")
+                (@arg STATS_CHANNEL: --("stats-channel") +takes_value possible_values(&["mutex-vec", "mpsc", "per-worker"]) default_value("mutex-vec") "How the workers deliver their completions: mutex-vec locks the stats once per batch, mpsc sends every completion to a collector thread, per-worker counts them in batches, but keeps the records until the end. The summary compares their costs per task, calibrated with zero-cost tasks")
+                (@arg STATS_FLUSH: --("stats-flush") +takes_value default_value("10ms") {validate(ModelConfig::parse_interval, "an interval, e.g. 10ms")} "The longest a completion waits in the batch of its worker, e.g. 10ms, so the live view and the seconds lag behind at most by as much")
             )
-        ).get_matches();
-
-        Self {
-            name: matches
-                .value_of("NAME")
-                .expect("Name is required")
-                .to_string(),
-            n_jobs: matches
-                .value_of("NUM_REQUESTS")
-                .expect("Rate is required")
-                .parse()
-                .expect("NUM_REQUESTS must be a positive integer"),
-            rps: matches
-                .value_of("RATE")
-                .expect("Rate is required")
-                .parse()
-                .expect("RATE must be a positive integer"),
+            (@subcommand merge =>
+                (about: "Merge the checkpoints of the sessions of a run, e.g. of a soak test, into one dataset, see README")
+                (version: "0.0.1")
+                (@arg RUNS: +required +multiple "The checkpoints of the sessions in order, e.g. checkpoint_{name}.json of --checkpoint-every")
+                (@arg OUT: --out +takes_value +required "The directory of the merged dataset and its figures, e.g. combined/")
+                (@arg FORCE: --force "Merge the runs of different latencies, modes or targets too")
+            )
+            (@subcommand presets =>
+                (about: "Print the scenarios of --preset")
+                (version: "0.0.1")
+            )
+            (@subcommand validate =>
+                (about: "Check the environment: the plotting backends, the permission to write to the current directory, the timer, and a short run of each mode")
+                (version: "0.0.1")
+            )
+            (@subcommand ("init-config") =>
+                (about: "Print a commented template of --config")
+                (version: "0.0.1")
+            )
+            (@subcommand ("run-all") =>
+                (about: "Run the scenarios of a suite in turn and compare them, see README")
+                (version: "0.0.1")
+                (@arg SUITE: --config +takes_value +required "The suite, e.g. suite.toml: the options of --config shared by the scenarios, and a [[scenario]] table with a name per scenario, which overrides them")
+                (@arg OUT_DIR: --("out-dir") +takes_value "The directory of the outputs of the scenarios and the comparison. The name of the suite by default, e.g. suite")
+            )
+            (@subcommand ("sweep-threads") =>
+                (about: "Run the sync model per number of threads, and chart the achieved rate and p99 over them, see README")
+                (version: "0.0.1")
+                (@arg THREADS: --threads -t +takes_value +required {validate(ModelConfig::parse_counts, "a list of positive integers, e.g. 1,2,4,8")} "The numbers of worker threads, a run per each, e.g. 1,2,4,8,16,32,64")
+            )
+            (@subcommand ("sweep-concurrency") =>
+                (about: "Run the async model per max number of the tasks in flight, and chart the achieved rate and p99 over them, see README")
+                (version: "0.0.1")
+                (@arg MAX_IN_FLIGHT: --("max-in-flight") +takes_value +required {validate(ModelConfig::parse_counts, "a list of positive integers, e.g. 100,1000")} "The max numbers of the tasks in flight, a run per each, e.g. 100,500,1000,5000,10000")
+            )
+            (@subcommand ("sweep-rate") =>
+                (about: "Run short trials per rate to find the saturation point, and chart p99 over the achieved rate, see README")
+                (version: "0.0.1")
+                (@arg FROM: --from +takes_value +required {validate(ModelConfig::parse_count, "a positive integer, e.g. 100")} "The first rate, e.g. 100")
+                (@arg TO: --to +takes_value +required {validate(ModelConfig::parse_count, "a positive integer, e.g. 5000")} "The last rate, at most, e.g. 5000")
+                (@arg STEP: --step +takes_value +required {validate(ModelConfig::parse_step, "a positive integer or a factor, e.g. 250 or x2")} "Added to the rate per trial, e.g. 250, or its factor, e.g. x2")
+                (@arg DURATION: --duration +takes_value default_value("10s") {validate(ModelConfig::parse_interval, "an interval, e.g. 10s")} "The send phase of every trial, i.e. its number of requests is the rate times it")
+                (@arg THREADS: --threads -t +takes_value {validate(ModelConfig::parse_count, "a positive integer, e.g. 100")} "Model the sync service with this many threads. The async one by default")
+                (@arg MAX_P99: --("max-p99") +takes_value {validate(ModelConfig::parse_latency, "a latency, e.g. 250ms")} "The knee is also the first rate with p99 above it, e.g. 250ms")
+            )
+            (@subcommand ("plot-sweep") =>
+                (about: "Chart the throughput-latency curve of a sweep again from its saved trials, see README")
+                (version: "0.0.1")
+                (@arg DIR: +required "The trials of the sweep, e.g. sweep_threads_{name}")
+            )
+            (@subcommand compare =>
+                (about: "Compare the parameters of two runs by their metadata files")
+                (version: "0.0.1")
+                (@arg A: +required "meta_{name}.json of the first run")
+                (@arg B: +required "meta_{name}.json of the second run")
+            )
+            (@subcommand history =>
+                (about: "Print the trend of the runs of a name appended by --sqlite, the oldest first")
+                (version: "0.0.1")
+                (@arg DB: +required "The database, e.g. results.db")
+                (@arg NAME: --name -N +takes_value required_unless_present[TAG] "Name of the test-case. All the names by default, with --tag")
+                (@arg TAG: --tag +takes_value +multiple number_of_values(1) {validate(ModelConfig::parse_tag, "a tag, e.g. branch=feature-x")} "Only the runs with the tag, e.g. branch=feature-x, may be repeated")
+                (@arg LAST: --last +takes_value default_value("10") {validate(ModelConfig::parse_count, "a positive integer, e.g. 10")} "The number of the latest runs")
+            )
+            (@subcommand ("bench-report") =>
+                (about: "Summarize the results of `cargo bench` into bench_summary.csv and bench_summary.png")
+                (version: "0.0.1")
+                (@arg CRITERION_DIR: --("criterion-dir") +takes_value default_value("target/criterion") "The results of criterion")
+                (@arg SWEEP: --sweep +takes_value +multiple number_of_values(1) "Also plot the throughput of this group over its numeric parameter, e.g. \"Batch Size Sweep\"")
+                (@arg FAMILY: +multiple "Only these benchmark groups, e.g. Increment Sum. All by default")
+            )
+        )
+    }
+
+    /// All the values are validated by the parser, see `cli`.
+    fn from_matches(matches: &ArgMatches) -> Self {
+        for (arg, usage) in &[
+            ("NAME", "--name <NAME>"),
+            ("RATE", "--rate <RATE>"),
+            ("NUM_REQUESTS", "--num_req <NUM_REQUESTS>"),
+            ("LATENCY_DISTRIBUTION", "--latency <LATENCY_DISTRIBUTION>"),
+        ] {
+            // the latencies are up to the external service
+            if *arg == "LATENCY_DISTRIBUTION" && matches.is_present("URL") {
+                continue;
+            }
+            // the defaults of the preset
+            if matches.is_present("PRESET") {
+                continue;
+            }
+            // the tasks come as they are read
+            if *arg != "NAME" && matches.is_present("STDIN_TASKS") {
+                continue;
+            }
+            // set per trial
+            if (*arg == "RATE" || *arg == "NUM_REQUESTS")
+                && matches.subcommand_matches("sweep-rate").is_some()
+            {
+                continue;
+            }
+            if !matches.is_present(arg) {
+                clap::Error::with_description(
+                    format!(
+                        "The following required argument was not provided: {}\n",
+                        usage
+                    ),
+                    ErrorKind::MissingRequiredArgument,
+                )
+                .exit();
+            }
+        }
+
+        if matches.is_present("URL") && matches.occurrences_of("TARGET") > 0 {
+            clap::Error::with_description(
+                "--url replaces --target, they can't be used together\n".to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        let target = if matches.is_present("URL") {
+            "url"
+        } else {
+            matches
+                .value_of("TARGET")
+                .and_then(ModelConfig::parse_target)
+                .expect("TARGET has a default value")
+        };
+        if target != "sleep"
+            && matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("CANCELLABLE_WORK"))
+                .is_some()
+        {
+            clap::Error::with_description(
+                "--cancellable-work slices a sleep, it can't be used with another --target\n"
+                    .to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        for (arg, usage, only) in &[
+            ("SOCKET", "--socket", "uds"),
+            ("MAX_PROCESSES", "--max-processes", "cmd:..."),
+            ("FILE_SIZE", "--file-size", "file"),
+            ("DIR", "--dir", "file"),
+            ("FSYNC", "--fsync", "file"),
+            ("DROP", "--drop", "udp"),
+        ] {
+            // the defaults don't count
+            if matches.occurrences_of(arg) > 0 && !only.starts_with(target) {
+                clap::Error::with_description(
+                    format!("{} can be used only with --target {}\n", usage, only),
+                    ErrorKind::ArgumentConflict,
+                )
+                .exit();
+            }
+        }
+        if matches.is_present("CHECKPOINT_EVERY")
+            && matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("STATS_CHANNEL"))
+                == Some("per-worker")
+        {
+            clap::Error::with_description(
+                "--stats-channel per-worker keeps the records until the end, so they can't be checkpointed, \
+                 use --checkpoint-every with another one\n"
+                    .to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        if matches.value_of("ENGINE") == Some("des") {
+            let sync = matches.subcommand_matches("sync");
+            for (present, usage) in [
+                (target != "sleep", "--target other than sleep"),
+                (matches.is_present("URL"), "--url"),
+                (matches.is_present("STDIN_TASKS"), "--stdin-tasks"),
+                (matches.is_present("LIVE"), "--live"),
+                (matches.is_present("PROGRESS"), "--progress"),
+                (matches.is_present("PROFILE"), "--profile"),
+                (matches.is_present("OTEL_ENDPOINT"), "--otel-endpoint"),
+                (matches.is_present("COARSE_CLOCK"), "--coarse-clock"),
+                (matches.is_present("BACKGROUND_LOAD"), "--background-load"),
+                (
+                    sync.is_some_and(|sync| sync.is_present("PRECISE_SLEEP")),
+                    "--precise-sleep",
+                ),
+                (
+                    sync.is_some_and(|sync| sync.is_present("PIN_THREADS")),
+                    "--pin-threads",
+                ),
+                (
+                    sync.is_some_and(|sync| sync.is_present("WORKER_NICE")),
+                    "--worker-nice",
+                ),
+                (
+                    sync.is_some_and(|sync| sync.occurrences_of("STATS_CHANNEL") > 0),
+                    "--stats-channel",
+                ),
+            ] {
+                if present {
+                    clap::Error::with_description(
+                        format!(
+                            "{} can't be used with --engine des, which simulates the sleeps in virtual time\n",
+                            usage
+                        ),
+                        ErrorKind::ArgumentConflict,
+                    )
+                    .exit();
+                }
+            }
+        }
+        if matches.is_present("NO_KEEPALIVE") && target != "http" && target != "url" {
+            clap::Error::with_description(
+                "--no-keepalive can be used only with --target http or --url\n".to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        if matches.is_present("SUCCESS_STATUS") && target != "url" {
+            clap::Error::with_description(
+                "--success-status can be used only with --url\n".to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        if matches.is_present("TLS") && target != "tcp" && target != "http" {
+            clap::Error::with_description(
+                "--tls can be used only with --target tcp or http\n".to_string(),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        for (present, usage) in [
+            (target == "http", "--target http"),
+            (target == "url", "--url"),
+            (matches.is_present("TLS"), "--tls"),
+        ] {
+            if present {
+                require_feature(cfg!(feature = "http"), usage, "http");
+            }
+        }
+        if matches.is_present("SQLITE") {
+            require_feature(cfg!(feature = "sqlite"), "--sqlite", "sqlite");
+        }
+        if matches.is_present("OTEL_ENDPOINT") {
+            require_feature(cfg!(feature = "otlp"), "--otel-endpoint", "otlp");
+        }
+        if matches.is_present("PROFILE") {
+            if cfg!(not(unix)) {
+                clap::Error::with_description(
+                    "--profile is not supported on this platform, the profiler is Unix-only\n"
+                        .to_string(),
+                    ErrorKind::InvalidValue,
+                )
+                .exit();
+            }
+            require_feature(cfg!(feature = "profile"), "--profile", "profile");
+        }
+        if target == "uds" {
+            if cfg!(not(unix)) {
+                clap::Error::with_description(
+                    "--target uds is not supported on this platform, Unix domain sockets are Unix-only\n"
+                        .to_string(),
+                    ErrorKind::InvalidValue,
+                )
+                .exit();
+            }
+            if !matches.is_present("SOCKET") {
+                clap::Error::with_description(
+                    "The following required argument was not provided: --socket <SOCKET>\n"
+                        .to_string(),
+                    ErrorKind::MissingRequiredArgument,
+                )
+                .exit();
+            }
+        }
+
+        let plots = if matches.is_present("NO_PLOTS") {
+            vec![]
+        } else {
+            matches
+                .value_of("PLOTS")
+                .and_then(ModelConfig::parse_plots)
+                .expect("PLOTS has a default value")
+        };
+
+        let preset = matches
+            .value_of("PRESET")
+            .map(|name| preset::find(name).expect("PRESET is validated"));
+        let rps = matches
+            .value_of("RATE")
+            .and_then(ModelConfig::parse_count)
+            .or_else(|| preset.map(|preset| preset.rate))
+            .unwrap_or_default();
+        let mut tags = BTreeMap::new();
+        for tag in matches.values_of("TAG").into_iter().flatten() {
+            let (key, value) = ModelConfig::parse_tag(tag).expect("TAG is validated");
+            if let Some(previous) = tags.insert(key.clone(), value) {
+                clap::Error::with_description(
+                    format!(
+                        "The tag `{}` is set twice: `{}={}` and `{}`, a key may be set once\n",
+                        key, key, previous, tag
+                    ),
+                    ErrorKind::ArgumentConflict,
+                )
+                .exit();
+            }
+        }
+        // the trials of a sweep are named after the preset already
+        let name = match (matches.value_of("NAME"), preset) {
+            (Some(name), Some(preset))
+                if name != preset.name && !name.starts_with(&format!("{}_", preset.name)) =>
+            {
+                format!("{}_{}", preset.name, name)
+            }
+            (Some(name), _) => name.to_string(),
+            (None, preset) => preset.expect("Name is required").name.to_string(),
+        };
+        // e.g. `sync_30s_feature-x_bench01`, the trials of a sweep are named after the tags already
+        let suffix = tags
+            .values()
+            .map(|value| {
+                value
+                    .chars()
+                    .take(ModelConfig::TAG_IN_NAME)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("_");
+        let name = if tags.is_empty() || name.contains(&format!("_{}", suffix)) {
+            name
+        } else {
+            format!("{}_{}", name, suffix)
+        };
+
+        Self {
+            name,
+            // with `--stdin-tasks`, neither is known in advance
+            n_jobs: matches
+                .value_of("NUM_REQUESTS")
+                .and_then(ModelConfig::parse_count)
+                .or_else(|| preset.map(|preset| rps * preset.duration))
+                .unwrap_or_default(),
+            rps,
+            // with `--url`, the requests have no cost of their own
             latency_distribution: matches
                 .value_of("LATENCY_DISTRIBUTION")
-                .expect("Rate is required")
-                .split(',')
-                .map(|s| ModelConfig::parse_latency_item(s))
-                .flatten()
-                .collect(),
+                .or_else(|| preset.map(|preset| preset.latency))
+                .map_or_else(
+                    || vec![0],
+                    |s| {
+                        ModelConfig::parse_latency_distribution(s)
+                            .expect("LATENCY_DISTRIBUTION is validated")
+                    },
+                ),
+            preset: preset.map(|preset| preset.name.to_string()),
+            tags,
+            timeout: matches
+                .value_of("TIMEOUT")
+                .and_then(ModelConfig::parse_latency)
+                .expect("TIMEOUT has a default value"),
+            cancellable_work: matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("CANCELLABLE_WORK"))
+                .and_then(ModelConfig::parse_granularity),
+            worker_nice: matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("WORKER_NICE"))
+                .and_then(ModelConfig::parse_nice),
+            background_load: matches
+                .value_of("BACKGROUND_LOAD")
+                .and_then(ModelConfig::parse_count)
+                .unwrap_or_default(),
+            pin_threads: matches
+                .subcommand_matches("sync")
+                .is_some_and(|sync| sync.is_present("PIN_THREADS")),
+            precise_sleep: matches
+                .subcommand_matches("sync")
+                .is_some_and(|sync| sync.is_present("PRECISE_SLEEP")),
+            cores: matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("CORES"))
+                .and_then(ModelConfig::parse_cores),
+            stats_batch: matches.subcommand_matches("sync").and_then(|sync| {
+                let size = sync
+                    .value_of("STATS_BATCH")
+                    .and_then(ModelConfig::parse_count)
+                    .expect("STATS_BATCH has a default value");
+                let flush_every = sync
+                    .value_of("STATS_FLUSH")
+                    .and_then(ModelConfig::parse_interval)
+                    .expect("STATS_FLUSH has a default value");
+                Some(StatsBatch { size, flush_every }).filter(|batch| batch.size > 1)
+            }),
+            stats_channel: matches
+                .subcommand_matches("sync")
+                .and_then(|sync| sync.value_of("STATS_CHANNEL"))
+                .and_then(StatsChannel::from_name)
+                .unwrap_or_default(),
+            max_in_flight: matches
+                .subcommand_matches("async")
+                .and_then(|config| config.value_of("MAX_IN_FLIGHT"))
+                .and_then(ModelConfig::parse_count),
             python_path: matches.value_of("PYTHON_PATH").map(|s| s.to_string()),
+            hist_bins: matches
+                .value_of("HIST_BINS")
+                .and_then(ModelConfig::parse_count),
+            hist_range: matches
+                .value_of("HIST_RANGE")
+                .and_then(ModelConfig::parse_latency_range),
+            percentile_method: match matches.value_of("PERCENTILE_METHOD") {
+                Some("linear") => PercentileMethod::Linear,
+                _ => PercentileMethod::NearestRank,
+            },
+            trim_edges: matches
+                .value_of("TRIM_EDGES")
+                .and_then(ModelConfig::parse_trim_edges)
+                .expect("TRIM_EDGES has a default value"),
+            timeline_basis: match matches.value_of("TIMELINE_BASIS") {
+                Some("completion") => TimelineBasis::Completion,
+                _ => TimelineBasis::Start,
+            },
+            backend: Backend::from_name(matches.value_of("BACKEND")),
+            live: matches.is_present("LIVE"),
+            progress: matches.is_present("PROGRESS"),
+            stdin_tasks: matches.is_present("STDIN_TASKS"),
+            export_timeseries: matches.value_of("EXPORT_TIMESERIES").map(|s| s.to_string()),
+            chrome_trace: matches.value_of("CHROME_TRACE").map(|s| s.to_string()),
+            sqlite: matches.value_of("SQLITE").map(|s| s.to_string()),
+            sqlite_timeseries: matches.is_present("SQLITE_TIMESERIES"),
+            max_samples: matches
+                .value_of("MAX_SAMPLES")
+                .and_then(ModelConfig::parse_count),
+            spill_dir: matches.value_of("SPILL_DIR").map(|s| s.to_string()),
+            spill_chunk: matches
+                .value_of("SPILL_CHUNK")
+                .and_then(ModelConfig::parse_count),
+            seed: matches
+                .value_of("SEED")
+                .map_or_else(rand::random, |s| s.parse().expect("SEED is validated")),
+            checkpoint_every: matches
+                .value_of("CHECKPOINT_EVERY")
+                .and_then(ModelConfig::parse_interval),
+            resume: matches.value_of("RESUME").map(|s| s.to_string()),
+            apdex: matches
+                .value_of("APDEX")
+                .and_then(ModelConfig::parse_latency),
+            thresholds: Thresholds {
+                p50: matches
+                    .value_of("ASSERT_P50")
+                    .and_then(ModelConfig::parse_latency),
+                p99: matches
+                    .value_of("ASSERT_P99")
+                    .and_then(ModelConfig::parse_latency),
+                errors: matches
+                    .value_of("ASSERT_ERRORS")
+                    .and_then(ModelConfig::parse_percentage),
+                apdex: matches
+                    .value_of("ASSERT_APDEX")
+                    .and_then(ModelConfig::parse_score),
+            },
+            otel: matches
+                .value_of("OTEL_ENDPOINT")
+                .map(|endpoint| TraceExport {
+                    endpoint: endpoint.to_string(),
+                    sample: matches
+                        .value_of("TRACE_SAMPLE")
+                        .and_then(ModelConfig::parse_share)
+                        .unwrap_or(1.),
+                }),
+            profile: matches.is_present("PROFILE"),
+            coarse_clock: matches
+                .value_of("COARSE_CLOCK")
+                .and_then(ModelConfig::parse_coarse_clock),
+            no_color: matches.is_present("NO_COLOR"),
+            no_plots: plots.is_empty(),
+            plots,
+            engine: match matches.value_of("ENGINE") {
+                Some("des") => Engine::Des,
+                _ => Engine::Real,
+            },
+            target: match matches.value_of("TARGET") {
+                _ if target == "url" => Target::Url {
+                    url: matches.value_of("URL").expect("URL is present").to_string(),
+                    keepalive: !matches.is_present("NO_KEEPALIVE"),
+                    success: matches.value_of("SUCCESS_STATUS").map_or_else(
+                        || vec![(200, 299)],
+                        |s| ModelConfig::parse_statuses(s).expect("SUCCESS_STATUS is validated"),
+                    ),
+                },
+                Some("tcp") => Target::Tcp {
+                    tls: matches.is_present("TLS"),
+                },
+                Some("http") => Target::Http {
+                    keepalive: !matches.is_present("NO_KEEPALIVE"),
+                    tls: matches.is_present("TLS"),
+                },
+                Some("uds") => Target::Uds {
+                    socket: matches
+                        .value_of("SOCKET")
+                        .expect("SOCKET is required with uds")
+                        .to_string(),
+                },
+                Some("udp") => Target::Udp {
+                    drop: matches
+                        .value_of("DROP")
+                        .and_then(ModelConfig::parse_share)
+                        .unwrap_or(0.),
+                },
+                Some("file") => Target::File {
+                    dir: matches.value_of("DIR").map_or_else(
+                        || std::env::temp_dir().display().to_string(),
+                        |s| s.to_string(),
+                    ),
+                    size: matches
+                        .value_of("FILE_SIZE")
+                        .and_then(ModelConfig::parse_size)
+                        .expect("FILE_SIZE has a default value"),
+                    fsync: matches.is_present("FSYNC"),
+                },
+                Some(target) if target.starts_with("cmd:") => Target::Cmd {
+                    command: ModelConfig::parse_command(target).expect("TARGET is validated"),
+                    max_processes: matches
+                        .value_of("MAX_PROCESSES")
+                        .and_then(ModelConfig::parse_count)
+                        .expect("MAX_PROCESSES has a default value"),
+                },
+                _ => Target::Sleep,
+            },
+            log_level: if matches.is_present("QUIET") {
+                LevelFilter::WARN
+            } else {
+                match matches.occurrences_of("VERBOSE") {
+                    0 => LevelFilter::INFO,
+                    1 => LevelFilter::DEBUG,
+                    _ => LevelFilter::TRACE,
+                }
+            },
             mode: if let Some(config) = matches.subcommand_matches("sync") {
                 Mode::Sync(
                     config
                         .value_of("THREADS")
-                        .expect("Rate is required")
-                        .parse()
-                        .expect("THREADS must be a positive integer"),
+                        .and_then(ModelConfig::parse_count)
+                        .expect("THREADS is required"),
                 )
             } else {
                 Mode::Async
@@ -260,171 +2334,1622 @@ impl ModelConfig {
         }
     }
 
-    fn parse_latency_item(s: &str) -> Vec<u64> {
+    /// Warns if the configuration obviously saturates the service, i.e. it needs more
+    /// concurrent requests (rate × mean latency) than the sync mode has threads,
+    /// or the async one may have in flight.
+    fn check_saturation(&self) {
+        let (limit, what) = match (&self.mode, self.max_in_flight) {
+            (Mode::Sync(n_workers), _) => (*n_workers, "threads"),
+            (Mode::Async, Some(max_in_flight)) => (max_in_flight, "tasks in flight"),
+            (Mode::Async, None) => return,
+        };
+        let mean_latency = self.latency_distribution.iter().sum::<u64>() as f64
+            / self.latency_distribution.len() as f64;
+        let required = self.rps as f64 * mean_latency / 1000.;
+        if required > limit as f64 {
+            warn!(
+                "The service will saturate: {} rps with the mean latency of {:.3} ms needs {:.0} {}, but there are only {}",
+                self.rps, mean_latency, required.ceil(), what, limit
+            );
+        }
+    }
+
+    /// A positive integer, `_` may be used as a separator, e.g. `1_000_000`, with an optional suffix
+    /// of thousands, millions or billions: `k`, `M` or `G`, e.g. `500k`, `10M` or `2.5k`.
+    fn parse_count(s: &str) -> Option<usize> {
+        let s = s.replace('_', "");
+        let (number, zeros) = match s.chars().last()? {
+            'k' | 'K' => (&s[..s.len() - 1], 3),
+            'M' => (&s[..s.len() - 1], 6),
+            'G' => (&s[..s.len() - 1], 9),
+            _ => (s.as_str(), 0),
+        };
+        // the fraction must be a whole number of the unit, e.g. `2.5k`, but not `2.5` or `1.0005k`
+        let (integer, fraction) = match number.find('.') {
+            Some(at) if zeros > 0 => (&number[..at], &number[at + 1..]),
+            _ => (number, ""),
+        };
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty())
+            || !digits(integer)
+            || !digits(fraction)
+            || fraction.len() > zeros
+        {
+            return None;
+        }
+        let scale = 10usize.pow(zeros as u32);
+        let integer = match integer {
+            "" => 0,
+            integer => integer.parse::<usize>().ok()?.checked_mul(scale)?,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            fraction => {
+                fraction.parse::<usize>().ok()? * 10usize.pow((zeros - fraction.len()) as u32)
+            }
+        };
+        integer.checked_add(fraction).filter(|n| *n > 0)
+    }
+
+    /// A comma separated list of the names of plots, e.g. `histogram,timeline`, or `all`, or `none`.
+    fn parse_plots(s: &str) -> Option<Vec<Plot>> {
+        match s {
+            "all" => Some(Plot::ALL.to_vec()),
+            "none" => Some(vec![]),
+            _ => s
+                .split(',')
+                .map(|name| Plot::ALL.iter().copied().find(|plot| plot.name() == name))
+                .collect(),
+        }
+    }
+
+    /// A tag of `--tag`, e.g. `branch=feature-x`: (key, value). The key is of letters, digits, `_`, `-`
+    /// or `.`, the value has no path separators, as both end up in the names of the files.
+    fn parse_tag(s: &str) -> Option<(String, String)> {
+        let (key, value) = s.split_once('=')?;
+        let key_chars = key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if key.is_empty() || !key_chars || value.is_empty() || value.contains(&['/', '\\'][..]) {
+            return None;
+        }
+        Some((key.to_string(), value.to_string()))
+    }
+
+    /// A Unix nice value, from -20 (the highest priority) to 19 (the lowest one).
+    fn parse_nice(s: &str) -> Option<i32> {
+        s.parse().ok().filter(|nice| (-20..=19).contains(nice))
+    }
+
+    /// A comma separated list of cores or their inclusive ranges, e.g. `0-7` or `0,2,4-6`.
+    fn parse_cores(s: &str) -> Option<Vec<usize>> {
+        let mut cores = vec![];
+        for part in s.split(',') {
+            match part.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to): (usize, usize) = (from.parse().ok()?, to.parse().ok()?);
+                    if from > to {
+                        return None;
+                    }
+                    cores.extend(from..=to);
+                }
+                None => cores.push(part.parse().ok()?),
+            }
+        }
+        Some(cores)
+    }
+
+    /// A comma separated list of counts, e.g. `1,2,4,8`.
+    fn parse_counts(s: &str) -> Option<Vec<usize>> {
+        s.split(',').map(ModelConfig::parse_count).collect()
+    }
+
+    /// An increment, e.g. `250`, or a factor above 1, e.g. `x2`.
+    fn parse_step(s: &str) -> Option<sweep::Step> {
+        match s.strip_prefix('x') {
+            Some(factor) => factor
+                .parse()
+                .ok()
+                .filter(|factor| *factor > 1.)
+                .map(sweep::Step::Multiply),
+            None => ModelConfig::parse_count(s).map(sweep::Step::Add),
+        }
+    }
+
+    /// `sleep`, `tcp`, `http`, `uds`, `udp`, `file`, or a command, see `parse_command`. Returns the kind, e.g. `cmd`.
+    fn parse_target(s: &str) -> Option<&'static str> {
+        ["sleep", "tcp", "http", "uds", "udp", "file"]
+            .iter()
+            .find(|kind| **kind == s)
+            .copied()
+            .or_else(|| ModelConfig::parse_command(s).map(|_| "cmd"))
+    }
+
+    /// An `http` URL with a host, e.g. `http://localhost:8080/health`.
+    fn parse_url(s: &str) -> Option<http::Uri> {
+        s.parse::<http::Uri>()
+            .ok()
+            .filter(|uri| uri.scheme_str() == Some("http") && uri.host().is_some())
+    }
+
+    /// Comma separated HTTP statuses, each either a status or a class, e.g. `2xx,304`.
+    /// Returns inclusive ranges.
+    fn parse_statuses(s: &str) -> Option<Vec<(u16, u16)>> {
+        s.split(',')
+            .map(|status| {
+                let status = status.trim();
+                match status.strip_suffix("xx") {
+                    Some(class) => class
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|class| (1..=5).contains(class))
+                        .map(|class| (class * 100, class * 100 + 99)),
+                    None => status
+                        .parse()
+                        .ok()
+                        .filter(|status| (100..=599).contains(status))
+                        .map(|status| (status, status)),
+                }
+            })
+            .collect()
+    }
+
+    /// `cmd:` and a program with its arguments separated by whitespace, e.g. `cmd:./work.sh --fast`.
+    fn parse_command(s: &str) -> Option<Vec<String>> {
+        let command: Vec<String> = s
+            .strip_prefix("cmd:")?
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        Some(command).filter(|command| !command.is_empty())
+    }
+
+    /// A positive number of bytes, or of KiB or MiB with the `k` or `m` suffix, e.g. `64k`.
+    fn parse_size(s: &str) -> Option<usize> {
+        let lower = s.to_lowercase();
+        let (number, unit) = if let Some(number) = lower.strip_suffix('k') {
+            (number, 1 << 10)
+        } else if let Some(number) = lower.strip_suffix('m') {
+            (number, 1 << 20)
+        } else {
+            (lower.as_str(), 1)
+        };
+        // not a count, `k` is KiB
+        number
+            .replace('_', "")
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .and_then(|n| n.checked_mul(unit))
+    }
+
+    /// E.g. `20ms*9,30s` or `10ms,20ms,30ms`.
+    fn parse_latency_distribution(s: &str) -> Option<Vec<u64>> {
+        let items = s
+            .split(',')
+            .map(ModelConfig::parse_latency_item)
+            .collect::<Option<Vec<_>>>()?;
+        Some(items.concat()).filter(|latencies| !latencies.is_empty())
+    }
+
+    /// E.g. `20ms` or `20ms*9`.
+    fn parse_latency_item(s: &str) -> Option<Vec<u64>> {
         if !s.contains('*') {
-            vec![ModelConfig::parse_latency(s)]
+            ModelConfig::parse_latency(s).map(|latency| vec![latency])
         } else {
             let mut split = s.split('*');
-            let value = split.next().expect("Must be in format `value*count`");
-            let count: usize = split
-                .next()
-                .expect("Must be in format `value*count`")
-                .parse()
-                .expect("Illegal numeric value");
-            (0..count)
-                .map(|_| ModelConfig::parse_latency(value))
-                .collect()
+            let value = ModelConfig::parse_latency(split.next()?)?;
+            let count: usize = split.next()?.parse().ok()?;
+            Some(vec![value; count])
         }
     }
 
-    fn parse_latency(value: &str) -> u64 {
+    /// E.g. `0:500ms` or `100ms:2s`.
+    fn parse_latency_range(s: &str) -> Option<(u64, u64)> {
+        let mut split = s.split(':');
+        let min = ModelConfig::parse_latency(split.next()?)?;
+        let max = ModelConfig::parse_latency(split.next()?)?;
+        Some((min, max)).filter(|(min, max)| min < max)
+    }
+
+    /// A duration, e.g. `20ms`, or a number of ms. Returns ms.
+    fn parse_latency(value: &str) -> Option<u64> {
         match parse_duration(value) {
-            Ok(d) => d.as_millis() as u64,
-            Err(_) => value.parse().expect("Illegal numeric value"),
+            Ok(d) => Some(d.as_millis() as u64),
+            Err(_) => value.parse().ok(),
+        }
+    }
+
+    /// `auto` or whole seconds, e.g. `1s` or `0`.
+    fn parse_trim_edges(s: &str) -> Option<TrimEdges> {
+        if s == "auto" {
+            return Some(TrimEdges::Auto);
         }
+        ModelConfig::parse_latency(s)
+            .filter(|ms| ms % 1000 == 0)
+            .map(|ms| TrimEdges::Seconds(ms / 1000))
     }
 
-    fn get_python_path(&self) -> Option<&str> {
-        let python_path = match self.python_path.as_ref() {
-            None => Some("/usr/bin/python3"),
-            Some(s) => Some(s.as_str()),
+    /// `granularity=50ms`, or just `50ms`. Returns ms.
+    fn parse_granularity(s: &str) -> Option<u64> {
+        ModelConfig::parse_interval(s.strip_prefix("granularity=").unwrap_or(s))
+    }
+
+    /// The resolution of the coarse clock, e.g. `1ms`, or `full=1ms` to time the latencies by it too.
+    fn parse_coarse_clock(s: &str) -> Option<CoarseClockConfig> {
+        let (full, resolution) = match s.strip_prefix("full=") {
+            Some(resolution) => (true, resolution),
+            None => (false, s),
         };
-        python_path
+        ModelConfig::parse_interval(resolution)
+            .map(|resolution| CoarseClockConfig { resolution, full })
+    }
+
+    /// A positive duration, e.g. `60s`. Returns ms.
+    fn parse_interval(s: &str) -> Option<u64> {
+        ModelConfig::parse_latency(s).filter(|ms| *ms > 0)
+    }
+
+    fn parse_percentage(s: &str) -> Option<f64> {
+        s.parse().ok().filter(|v| (0. ..=100.).contains(v))
+    }
+
+    /// A percentage, `%` is optional, e.g. `1%`.
+    fn parse_share(s: &str) -> Option<f64> {
+        ModelConfig::parse_percentage(s.strip_suffix('%').unwrap_or(s))
+    }
+
+    fn parse_score(s: &str) -> Option<f64> {
+        s.parse().ok().filter(|v| (0. ..=1.).contains(v))
+    }
+
+    fn default_log_level() -> LevelFilter {
+        LevelFilter::INFO
+    }
+
+    fn default_target() -> Target {
+        Target::Sleep
+    }
+
+    fn default_plots() -> Vec<Plot> {
+        Plot::ALL.to_vec()
     }
 }
 
-fn build_rps_graph(config: &ModelConfig, rps_buckets: HashMap<u64, u64>) {
-    // ignore the first and the last second as they may be incomplete
-    let start = 1
-        + rps_buckets
-            .iter()
-            .map(|(k, _)| k)
-            .min()
-            .expect("At least single data point must be here")
-        + 1;
-    let end = rps_buckets
-        .iter()
-        .map(|(k, _)| k)
-        .max()
-        .expect("At least single data point must be here")
-        - 1;
-    let mut x = vec![0];
-    let mut y = vec![0];
-    let mut total = 0.;
-    for i in start..end {
-        let value = *rps_buckets.get(&i).unwrap_or(&0);
-        let time_since_start = i - start;
-        x.push(time_since_start);
-        y.push(value);
-        total += value as f64;
-    }
-
-    let data_points_count = (end - start) as f64;
-    let avg = total / data_points_count;
-    let mut deviation = 0.;
-    for i in start..end {
-        let value = *rps_buckets.get(&i).unwrap_or(&0);
-        deviation += (avg - value as f64) * (avg - value as f64);
-    }
-
-    println!(
-        "Avg rate: {:.3}, StdDev: {:.3}",
+/// Returns the average rate and its standard deviation, if there were any successful requests.
+/// The chart shows the whole run, the edges excluded from the average are shaded.
+fn build_rps_graph(
+    config: &ModelConfig,
+    figures: &Figures,
+    rps_buckets: HashMap<u64, u64>,
+) -> Option<Rate> {
+    let (min, max) = match (rps_buckets.keys().min(), rps_buckets.keys().max()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => {
+            warn!("No successful requests, the request rate chart is skipped");
+            return None;
+        }
+    };
+    let length = max - min + 1;
+    // the first and the last seconds may be incomplete
+    let trim = match config.trim_edges {
+        TrimEdges::Auto if length < TrimEdges::AUTO_MIN_RUN => 0,
+        TrimEdges::Auto => 1,
+        TrimEdges::Seconds(trim) => trim,
+    };
+    let (start, end) = if length > 2 * trim {
+        (min + trim, max - trim)
+    } else {
+        warn!(
+            "The run is too short to trim {}s at each edge, the stats cover all {}s of it",
+            trim, length
+        );
+        (min, max)
+    };
+
+    let mut x = vec![];
+    let mut y = vec![];
+    for i in min..=max {
+        x.push((i - min) as f64);
+        y.push(*rps_buckets.get(&i).unwrap_or(&0) as f64);
+    }
+    let window = &y[(start - min) as usize..=(end - min) as usize];
+    let avg = window.iter().sum::<f64>() / window.len() as f64;
+    let deviation: f64 = window.iter().map(|v| (avg - v) * (avg - v)).sum();
+    let rate = Rate {
         avg,
-        (deviation / data_points_count).sqrt()
-    );
+        std_dev: (deviation / window.len() as f64).sqrt(),
+        window: (start, end),
+        run: (min, max),
+    };
 
-    let line_plot = line_plot::<u64, u64>(x, y, None);
-    let mut figure = Figure::new();
-    figure.add_plot(line_plot.clone());
-    figure.add_plot(line_plot);
-    figure.save(
-        format!("./request_rate_{}.png", config.name).as_str(),
-        config.get_python_path(),
-    );
+    if length == 1 {
+        warn!(
+            "All the requests completed within a single second, the request rate chart is skipped"
+        );
+        return Some(rate);
+    }
+
+    let mut chart = Chart::new(
+        Plot::Rate.file_name(&config.name),
+        "Time, s",
+        "Requests per second (successes only)",
+    )
+    .line(None, x, y);
+    // the borders are halfway between the excluded and the included seconds
+    if start > min {
+        chart = chart.shade(0., (start - min) as f64 - 0.5);
+    }
+    if end < max {
+        chart = chart.shade((end - min) as f64 + 0.5, (max - min) as f64);
+    }
+    render(config, figures, Plot::Rate, &chart);
+
+    Some(rate)
 }
 
-fn build_latency_histogram(config: &ModelConfig, mut latencies: Vec<TaskStats>) {
-    println!("Latencies:");
+/// Throughput (successes only) and p99 latency on the same timeline,
+/// which shows when and how the service is saturated.
+fn build_combined_graph(config: &ModelConfig, figures: &Figures, seconds: &[SecondStats]) {
+    if seconds.len() < 2 {
+        info!("The run took a single second, the combined chart is skipped");
+        return;
+    }
+    let x: Vec<f64> = seconds.iter().map(|s| s.second as f64).collect();
+    let rps = seconds
+        .iter()
+        .map(|s| (s.completed - s.failed) as f64)
+        .collect();
+    let p99 = seconds
+        .iter()
+        .map(|s| s.p99.map_or(f64::NAN, |v| v * 1000.))
+        .collect();
+    let chart = Chart::new(
+        Plot::Combined.file_name(&config.name),
+        "Time, s",
+        "Requests per second (successes only)",
+    )
+    .line(Some("RPS"), x.clone(), rps)
+    .line_y2("p99", "p99 latency, ms", x, p99);
+    render(config, figures, Plot::Combined, &chart);
+}
+
+/// Returns the effective binning, if there were any successful requests.
+fn build_latency_histogram(
+    config: &ModelConfig,
+    figures: &Figures,
+    overheads: &Overheads,
+) -> Option<Binning> {
+    if overheads.is_empty() {
+        warn!("No successful requests, the latency histogram and percentiles are skipped");
+        return None;
+    }
 
-    latencies.sort_by(|a, b| a.overhead.partial_cmp(&b.overhead).unwrap());
     let mut percentiles_x = vec![];
     let mut percentiles_y = vec![];
-    let printed_percentiles = vec![0, 5000, 9000, 9500, 9900, 9990, 9999, 10000];
 
     for p in 0..=10000 {
-        let stats =
-            &latencies[((p as f64 / 10000. * latencies.len() as f64) as i32 - 1).max(0) as usize];
-        let value = stats.overhead;
-        if printed_percentiles.contains(&p) {
-            println!(
-                "{} - {}",
-                format!("p{:.3}", p as f64 / 100.),
-                format!("{:.3} ms", value * 1000.),
-            );
-        }
+        let value = percentile(config, overheads, p as f64 / 10000.);
         percentiles_x.push(p as f64 / 100.);
         percentiles_y.push(value * 1000.);
     }
 
-    let mut figure = Figure::new();
-    let x = latencies.iter().map(|v| v.overhead * 1000.).collect();
-    let plot = histogram::<f64>(x, None);
-    figure.add_plot(plot);
-
-    figure.save(
-        format!("./latency_histogram_{}.png", config.name).as_str(),
-        config.get_python_path(),
+    let range = config.hist_range.map(|(min, max)| (min as f64, max as f64));
+    let mut binning = Binning::auto(overheads.len(), |i| overheads.get(i) * 1000., range);
+    if let Some(bins) = config.hist_bins {
+        binning.bins = bins;
+    }
+    info!(
+        "Histogram: {} bins within [{:.3}, {:.3}] ms",
+        binning.bins, binning.min, binning.max
     );
+    let chart = Chart::new(
+        Plot::Histogram.file_name(&config.name),
+        "Latency, ms",
+        "Count",
+    )
+    .histogram(overheads.iter().map(|v| v * 1000.), &binning);
+    render(config, figures, Plot::Histogram, &chart);
 
-    let line_plot = line_plot::<f64, f64>(percentiles_x, percentiles_y, None);
-    let mut figure = Figure::new();
-    figure.add_plot(line_plot.clone());
-    figure.add_plot(line_plot);
-    figure.save(
-        format!("./latency_percentiles_{}.png", config.name).as_str(),
-        config.get_python_path(),
-    );
+    let chart = Chart::new(
+        Plot::Percentiles.file_name(&config.name),
+        "Percentile",
+        "Latency, ms",
+    )
+    .line(None, percentiles_x, percentiles_y);
+    render(config, figures, Plot::Percentiles, &chart);
+
+    build_log_percentiles(config, figures, overheads);
+    build_latency_cdf(config, figures, overheads);
+
+    Some(binning)
 }
 
-fn build_latency_timeline(config: &ModelConfig, mut latencies: Vec<TaskStats>) {
-    latencies.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+// the points of the CDF at most, evenly spaced by rank
+const MAX_CDF_POINTS: usize = 10_000;
+
+/// The empirical CDF: the share of the successful requests up to each latency. The timeout is drawn
+/// as a vertical reference line, and the percentiles of the summary are marked, but p0 and p100,
+/// which are the ends of the curve.
+fn build_latency_cdf(config: &ModelConfig, figures: &Figures, overheads: &Overheads) {
+    let n = overheads.len();
+    let mut ranks: Vec<usize> = (0..n).step_by((n / MAX_CDF_POINTS).max(1)).collect();
+    if ranks.last() != Some(&(n - 1)) {
+        ranks.push(n - 1);
+    }
+    let x = ranks.iter().map(|i| overheads.get(*i) * 1000.).collect();
+    let y = ranks.iter().map(|i| (i + 1) as f64 / n as f64).collect();
+
+    let marked: Vec<f64> = PRINTED_PERCENTILES
+        .iter()
+        .copied()
+        .filter(|p| *p > 0. && *p < 100.)
+        .collect();
+    let (marked_x, marked_y): (Vec<f64>, Vec<f64>) = marked
+        .iter()
+        .map(|p| (percentile(config, overheads, p / 100.) * 1000., p / 100.))
+        .unzip();
+    let timeout = config.timeout as f64;
+    let mut chart = Chart::new(
+        Plot::Cdf.file_name(&config.name),
+        "Latency, ms",
+        "Share of requests",
+    )
+    .line(None, x, y)
+    .line(
+        Some(&format!("timeout {} ms", config.timeout)),
+        vec![timeout, timeout],
+        vec![0., 1.],
+    )
+    .points(Some("percentiles"), marked_x.clone(), marked_y.clone());
+    for ((x, y), p) in marked_x.into_iter().zip(marked_y).zip(marked) {
+        chart = chart.annotate(x, y, format!("p{}", p));
+    }
+    render(config, figures, Plot::Cdf, &chart);
+}
 
-    let mut timeline_x = vec![];
-    let mut p50_y = vec![];
-    let mut p90_y = vec![];
-    let mut p99_y = vec![];
+/// HdrHistogram-style percentile plot: X-axis is `log10(1 / (1 - p))`,
+/// so every "nine" (90%, 99%, 99.9%, ...) gets the same width.
+fn build_log_percentiles(config: &ModelConfig, figures: &Figures, overheads: &Overheads) {
+    // p100 is at infinity, so it's placed at the resolution of the sample, i.e. 1 - p = 1 / len
+    let max_x = (overheads.len() as f64).log10();
+    let mut x = vec![];
+    let mut y = vec![];
+    for i in 0..(max_x * 100.) as usize {
+        let log_x = i as f64 / 100.;
+        x.push(log_x);
+        y.push(percentile(config, overheads, 1. - 10f64.powf(-log_x)) * 1000.);
+    }
+    x.push(max_x);
+    y.push(percentile(config, overheads, 1.) * 1000.);
 
-    let mut start = latencies[0].start_time;
-    let mut current_x = 0;
-    let mut next_second_latency_batch: Vec<f64> = vec![];
+    let ticks = (0..=max_x.floor() as usize)
+        .map(|nines| {
+            let label = format!(
+                "{:.*}%",
+                nines.saturating_sub(2),
+                100. * (1. - 10f64.powi(-(nines as i32)))
+            );
+            (nines as f64, label)
+        })
+        .collect();
 
-    for (i, task) in latencies.iter().enumerate() {
-        let moment = task.start_time;
-        if moment.duration_since(start).as_secs_f64() >= 1. || i == latencies.len() - 1 {
-            timeline_x.push(current_x);
-            current_x += 1;
+    let chart = Chart::new(
+        Plot::LogPercentiles.file_name(&config.name),
+        "Percentile",
+        "Latency, ms",
+    )
+    .x_ticks(ticks)
+    .line(None, x, y);
+    render(config, figures, Plot::LogPercentiles, &chart);
+}
 
-            next_second_latency_batch.sort_by(|a, b| a.partial_cmp(&b).unwrap());
-            let batch_size = next_second_latency_batch.len();
-            p50_y.push(next_second_latency_batch[batch_size / 2 - 1] * 1000.);
-            p90_y.push(next_second_latency_batch[batch_size * 9 / 10 - 1] * 1000.);
-            p99_y.push(next_second_latency_batch[batch_size * 99 / 100 - 1] * 1000.);
+// the points of the scatter at most, more of them are evenly picked
+const MAX_SCATTER_POINTS: usize = 1_000_000;
 
-            start = moment;
-        } else {
-            next_second_latency_batch.push(task.overhead);
+/// The latency of each kept record by its completion, the failed ones in a color of their own.
+/// The raw samples show what the percentiles of a second smear out, e.g. periodic spikes or bands.
+/// The more points, the smaller and the more translucent they are, so the dense areas stand out.
+fn build_latency_scatter(config: &ModelConfig, figures: &Figures, samples: &[TaskStats]) {
+    let first = match samples.iter().map(|s| s.start_time).min() {
+        Some(first) => first,
+        None => {
+            warn!("No requests were completed, the latency scatter is skipped");
+            return;
         }
+    };
+    let step = samples.len().div_ceil(MAX_SCATTER_POINTS);
+    let (mut successes, mut failures) = ((vec![], vec![]), (vec![], vec![]));
+    for task in samples.iter().step_by(step) {
+        let (x, y) = if task.success() {
+            &mut successes
+        } else {
+            &mut failures
+        };
+        x.push(task.completion_time.duration_since(first).as_secs_f64());
+        y.push(task.overhead * 1000.);
     }
+    // ~4 pt and 80% opaque for a thousand points, down to 1 pt and 5% for a million
+    let scale = (samples.len() / step) as f64 / 1000.;
+    let size = (4. / scale.powf(0.2)).clamp(1., 4.);
+    let alpha = (0.8 / scale.sqrt()).clamp(0.05, 0.8);
 
-    let mut figure = Figure::new();
-    let p50_plot = line_plot::<u64, f64>(timeline_x.clone(), p50_y, None);
-    let p90_plot = line_plot::<u64, f64>(timeline_x.clone(), p90_y, None);
-    let p99_plot = line_plot::<u64, f64>(timeline_x, p99_y, None);
-    figure.add_plot(p50_plot);
-    figure.add_plot(p90_plot);
-    figure.add_plot(p99_plot);
-    figure.save(
-        format!("./latency_timeline_{}.png", config.name).as_str(),
-        config.get_python_path(),
+    let mut chart = Chart::new(
+        Plot::Scatter.file_name(&config.name),
+        "Completion, s",
+        "Latency, ms",
     );
+    if !successes.0.is_empty() {
+        chart = chart.scatter(Some("succeeded"), successes.0, successes.1, size, alpha);
+    }
+    if !failures.0.is_empty() {
+        chart = chart.scatter(Some("failed"), failures.0, failures.1, size, alpha);
+    }
+    render(config, figures, Plot::Scatter, &chart);
+}
+
+// the columns of the heatmap at most, the seconds of a longer run are merged into wider ones
+const MAX_HEATMAP_COLUMNS: usize = 1000;
+
+/// The latencies of the successful requests by the second of their completion and by their log bucket,
+/// from the rows of the seconds counted by the aggregator, so it covers all the requests of a run of any length.
+/// The bands and the shifts of the modes show up, which the percentiles of a second smear out.
+fn build_latency_heatmap(config: &ModelConfig, figures: &Figures, heatmap: &[[u32; LOG_BUCKETS]]) {
+    // only the range of the buckets with any latencies
+    let lowest = heatmap
+        .iter()
+        .filter_map(|row| row.iter().position(|count| *count > 0))
+        .min();
+    let highest = heatmap
+        .iter()
+        .filter_map(|row| row.iter().rposition(|count| *count > 0))
+        .max();
+    let (lowest, highest) = match (lowest, highest) {
+        (Some(lowest), Some(highest)) => (lowest, highest),
+        _ => {
+            warn!("No successful requests, the latency heatmap is skipped");
+            return;
+        }
+    };
+    let width = heatmap.len().div_ceil(MAX_HEATMAP_COLUMNS);
+    let counts: Vec<Vec<u64>> = heatmap
+        .chunks(width)
+        .map(|seconds| {
+            (lowest..=highest)
+                .map(|bucket| seconds.iter().map(|row| row[bucket] as u64).sum())
+                .collect()
+        })
+        .collect();
+    let x_edges = (0..=counts.len()).map(|i| (i * width) as f64).collect();
+    let y_edges = (lowest..=highest + 1)
+        .map(|bucket| stats::log_bucket_edge(bucket) * 1000.)
+        .collect();
+
+    let chart = Chart::new(
+        Plot::Heatmap.file_name(&config.name),
+        "Completion, s",
+        "Latency, ms",
+    )
+    .log_y()
+    .heatmap(x_edges, y_edges, counts, "Requests");
+    render(config, figures, Plot::Heatmap, &chart);
+}
+
+/// Renders a figure of the run, if it's selected by `--plots`.
+fn render(config: &ModelConfig, figures: &Figures, plot: Plot, chart: &Chart) {
+    if config.plots.contains(&plot) {
+        figures.render(chart);
+    }
+}
+
+/// `p` is within `[0, 1]`.
+fn percentile(config: &ModelConfig, overheads: &Overheads, p: f64) -> f64 {
+    overheads
+        .percentile(config.percentile_method, p)
+        .unwrap_or(f64::NAN)
+}
+
+// the records bucketed by the seconds of the timeline by a thread at once
+const TIMELINE_CHUNK: usize = 1 << 16;
+
+/// The overheads of the tasks started (or completed, see `TimelineBasis`) within each second of the run,
+/// and the name of the basis, `None` if there are none. `latencies` must be sorted by overhead,
+/// so the batches of the seconds are sorted as they are filled. The chunks of `latencies` are bucketed
+/// by the threads, and the buckets of a second are joined in the order of the chunks, so they stay sorted.
+fn latency_batches(
+    config: &ModelConfig,
+    latencies: &[TaskStats],
+) -> Option<(Vec<Vec<f64>>, &'static str)> {
+    let first = latencies.iter().map(|s| s.start_time).min()?;
+    // the origin is the first start in both cases, so the leading seconds may be empty by completion
+    let (moment, basis): (fn(&TaskStats) -> Instant, _) = match config.timeline_basis {
+        TimelineBasis::Start => (|s| s.start_time, "start"),
+        TimelineBasis::Completion => (|s| s.completion_time, "completion"),
+    };
+    let batches = latencies
+        .par_chunks(TIMELINE_CHUNK)
+        .map(|chunk| {
+            let mut batches: Vec<Vec<f64>> = vec![];
+            for task in chunk {
+                let second = moment(task).duration_since(first).as_secs() as usize;
+                if batches.len() <= second {
+                    batches.resize_with(second + 1, Vec::new);
+                }
+                batches[second].push(task.overhead);
+            }
+            batches
+        })
+        .reduce(Vec::new, |mut joined, batches| {
+            if joined.len() < batches.len() {
+                joined.resize_with(batches.len(), Vec::new);
+            }
+            for (joined, batch) in joined.iter_mut().zip(batches) {
+                joined.extend(batch);
+            }
+            joined
+        });
+    Some((batches, basis))
+}
+
+/// The percentiles `ps` of each sorted batch in ms, NaN for an empty one, i.e. a gap of a line.
+fn batch_percentiles<const N: usize>(
+    config: &ModelConfig,
+    batches: &[Vec<f64>],
+    ps: [f64; N],
+) -> Vec<[f64; N]> {
+    batches
+        .par_iter()
+        .map(|batch| {
+            ps.map(|p| {
+                config
+                    .percentile_method
+                    .percentile(batch, p)
+                    .map_or(f64::NAN, |v| v * 1000.)
+            })
+        })
+        .collect()
+}
+
+/// p50/p90/p99 of the tasks of each second of the run, see `latency_batches`.
+/// Seconds without successful tasks are rendered as gaps.
+fn build_latency_timeline(config: &ModelConfig, figures: &Figures, latencies: &[TaskStats]) {
+    let (batches, basis) = match latency_batches(config, latencies) {
+        Some(batches) => batches,
+        None => {
+            warn!("No successful requests, the latency timeline is skipped");
+            return;
+        }
+    };
+    if batches.len() < 2 {
+        info!(
+            "All the successful requests have the same second of {}, the latency timeline is skipped",
+            basis
+        );
+        return;
+    }
+
+    let percentiles = batch_percentiles(config, &batches, [0.5, 0.9, 0.99]);
+    let timeline_x: Vec<f64> = (0..batches.len()).map(|second| second as f64).collect();
+    let p50_y = percentiles.iter().map(|ps| ps[0]).collect();
+    let p90_y = percentiles.iter().map(|ps| ps[1]).collect();
+    let p99_y = percentiles.iter().map(|ps| ps[2]).collect();
+
+    let chart = Chart::new(
+        Plot::Timeline.file_name(&config.name),
+        &format!("Time of {}, s", basis),
+        "Latency, ms",
+    )
+    .line(Some("p50"), timeline_x.clone(), p50_y)
+    .line(Some("p90"), timeline_x.clone(), p90_y)
+    .line(Some("p99"), timeline_x, p99_y);
+    render(config, figures, Plot::Timeline, &chart);
+}
+
+// a box per second is readable up to this many of them
+const MAX_BOXES: usize = 300;
+// the outliers of a box on each side at most, the most extreme ones
+const MAX_OUTLIERS: usize = 10;
+
+/// A box per second of the run: the median, the quartiles, the whiskers at p1 and p99,
+/// and the most extreme outliers beyond them. The seconds are the ones of the timeline.
+fn build_latency_boxes(config: &ModelConfig, figures: &Figures, latencies: &[TaskStats]) {
+    let (batches, basis) = match latency_batches(config, latencies) {
+        Some(batches) => batches,
+        None => {
+            warn!("No successful requests, the latency box plot is skipped");
+            return;
+        }
+    };
+    if batches.len() > MAX_BOXES {
+        info!(
+            "The run has {} seconds, more than {} boxes, the latency box plot is skipped",
+            batches.len(),
+            MAX_BOXES
+        );
+        return;
+    }
+
+    let percentiles = batch_percentiles(config, &batches, [0.01, 0.25, 0.5, 0.75, 0.99]);
+    let (mut x, mut boxes) = (vec![], vec![]);
+    let (mut outliers_x, mut outliers_y) = (vec![], vec![]);
+    for (second, (batch, box_)) in batches.iter().zip(percentiles).enumerate() {
+        if batch.is_empty() {
+            continue;
+        }
+        x.push(second as f64);
+        boxes.push(box_);
+        let (low, high) = (box_[0] / 1000., box_[4] / 1000.);
+        let below = batch.iter().take_while(|v| **v < low).take(MAX_OUTLIERS);
+        let above = batch
+            .iter()
+            .rev()
+            .take_while(|v| **v > high)
+            .take(MAX_OUTLIERS);
+        for v in below.chain(above) {
+            outliers_x.push(second as f64);
+            outliers_y.push(v * 1000.);
+        }
+    }
+
+    let chart = Chart::new(
+        Plot::Boxes.file_name(&config.name),
+        &format!("Time of {}, s", basis),
+        "Latency, ms",
+    )
+    .boxes(x, boxes, 0.6)
+    .scatter(Some("outliers"), outliers_x, outliers_y, 3., 0.5);
+    render(config, figures, Plot::Boxes, &chart);
+}
+
+/// A box per class of the tasks, e.g. per cost of the latencies: the median, the quartiles and the whiskers
+/// at p1 and p99 of the overhead (the queueing and the scheduling) of all the successful tasks of the class.
+/// E.g. the small tasks queued behind the big ones of the sync model, which the pooled histogram leaves implied.
+fn build_latency_classes(
+    config: &ModelConfig,
+    figures: &Figures,
+    classes: &[(String, ClassStats)],
+) {
+    if !config.plots.contains(&Plot::Classes) {
+        return;
+    }
+    let classes: Vec<&(String, ClassStats)> = classes
+        .iter()
+        .filter(|(_, stats)| !stats.histogram.is_empty())
+        .collect();
+    if classes.len() < 2 {
+        info!(
+            "The successful requests have {} classes, the overhead by class is skipped",
+            classes.len()
+        );
+        return;
+    }
+
+    let x: Vec<f64> = (0..classes.len()).map(|i| i as f64).collect();
+    let boxes = classes
+        .iter()
+        .map(|(_, stats)| {
+            [0.01, 0.25, 0.5, 0.75, 0.99]
+                .map(|q| stats.histogram.value_at_quantile(q) as f64 / 1000.)
+        })
+        .collect();
+    let x_ticks = classes
+        .iter()
+        .zip(&x)
+        .map(|((class, stats), x)| (*x, format!("{} ({})", class, stats.histogram.len())))
+        .collect();
+
+    let chart = Chart::new(
+        Plot::Classes.file_name(&config.name),
+        "Class of the tasks (successful ones)",
+        "Overhead, ms",
+    )
+    .x_ticks(x_ticks)
+    .boxes(x, boxes, 0.6);
+    render(config, figures, Plot::Classes, &chart);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+
+    /// The config of a command line of whitespace-separated arguments, e.g. `--name t ... async`.
+    fn config(args: &str) -> ModelConfig {
+        let args = iter::once("concurrency-demo-benchmarks").chain(args.split_whitespace());
+        ModelConfig::from_matches(&ModelConfig::cli().get_matches_from(args))
+    }
+
+    fn run_config(trim_edges: &str) -> ModelConfig {
+        config(&format!(
+            "--name t --rate 100 --num_req 1000 --latency 10ms --trim-edges {} async",
+            trim_edges
+        ))
+    }
+
+    /// The rate and the number of the charts of `buckets`, second -> successes.
+    fn rate_of(trim_edges: &str, buckets: &[(u64, u64)]) -> (Option<Rate>, usize) {
+        let figures = Figures::new(None);
+        let rate = build_rps_graph(
+            &run_config(trim_edges),
+            &figures,
+            buckets.iter().copied().collect(),
+        );
+        (rate, figures.requested())
+    }
+
+    #[test]
+    fn a_run_without_successes_has_no_rate() {
+        let (rate, charts) = rate_of("auto", &[]);
+        assert!(rate.is_none());
+        assert_eq!(charts, 0);
+    }
+
+    #[test]
+    fn a_1s_run_has_a_rate_but_no_chart() {
+        let (rate, charts) = rate_of("auto", &[(0, 100)]);
+        let rate = rate.expect("A second of successes");
+        assert_eq!((rate.avg, rate.std_dev), (100., 0.));
+        assert_eq!((rate.window, rate.run), ((0, 0), (0, 0)));
+        assert_eq!(charts, 0);
+    }
+
+    #[test]
+    fn a_2s_run_is_not_trimmed() {
+        let (rate, charts) = rate_of("auto", &[(0, 100), (1, 50)]);
+        let rate = rate.expect("Two seconds of successes");
+        assert_eq!((rate.avg, rate.std_dev), (75., 25.));
+        assert_eq!((rate.window, rate.run), ((0, 1), (0, 1)));
+        assert_eq!(charts, 1);
+    }
+
+    #[test]
+    fn a_3s_run_with_a_partial_last_second() {
+        let buckets = [(0, 100), (1, 100), (2, 40)];
+        // shorter than 10s, so all of it is covered
+        let (rate, charts) = rate_of("auto", &buckets);
+        let rate = rate.expect("Three seconds of successes");
+        assert_eq!(rate.avg, 80.);
+        assert_eq!((rate.window, rate.run), ((0, 2), (0, 2)));
+        assert_eq!(charts, 1);
+        // a trimmed edge each leaves the middle second
+        let (rate, _) = rate_of("1s", &buckets);
+        let rate = rate.expect("Three seconds of successes");
+        assert_eq!((rate.avg, rate.std_dev), (100., 0.));
+        assert_eq!(rate.window, (1, 1));
+        // too short to trim 2s at each edge
+        let (rate, _) = rate_of("2s", &buckets);
+        assert_eq!(rate.expect("Three seconds of successes").window, (0, 2));
+    }
+
+    #[test]
+    fn a_10s_run_is_trimmed_by_a_second_at_each_edge() {
+        let mut buckets: Vec<(u64, u64)> = (0..10).map(|second| (second, 100)).collect();
+        // the partial first and last seconds
+        buckets[0].1 = 30;
+        buckets[9].1 = 60;
+        let (rate, charts) = rate_of("auto", &buckets);
+        let rate = rate.expect("Ten seconds of successes");
+        assert_eq!((rate.avg, rate.std_dev), (100., 0.));
+        assert_eq!((rate.window, rate.run), ((1, 8), (0, 9)));
+        assert_eq!(charts, 1);
+    }
+
+    #[test]
+    fn a_second_without_successes_counts_as_zero() {
+        let (rate, _) = rate_of("0s", &[(3, 100), (5, 100)]);
+        let rate = rate.expect("Two seconds of successes");
+        assert!((rate.avg - 200. / 3.).abs() < 1e-9);
+        assert_eq!((rate.window, rate.run), ((3, 5), (3, 5)));
+    }
+
+    /// Task `k` starts at `300 ms * k`, it's served for 500 ms with an overhead of `k` ms.
+    fn staggered_tasks(n: u32) -> Vec<TaskStats> {
+        let first = Instant::now();
+        (0..n)
+            .map(|k| {
+                let start = first + Duration::from_millis(300) * k;
+                let service = Duration::from_millis(500);
+                let completion = start + service + Duration::from_millis(k as u64);
+                TaskStats::new(Duration::from_secs(1), start, start, service, completion)
+            })
+            .collect()
+    }
+
+    fn timeline_config(basis: &str) -> ModelConfig {
+        config(&format!(
+            "--name t --rate 100 --num_req 1000 --latency 10ms --timeline-basis {} async",
+            basis
+        ))
+    }
+
+    /// The overheads of each batch, rounded to ms.
+    fn batches_ms(config: &ModelConfig, tasks: &[TaskStats]) -> Vec<Vec<u64>> {
+        let (batches, _) = latency_batches(config, tasks).expect("There are tasks");
+        batches
+            .iter()
+            .map(|batch| batch.iter().map(|v| (v * 1000.).round() as u64).collect())
+            .collect()
+    }
+
+    #[test]
+    fn the_latencies_are_batched_by_the_second_of_their_start() {
+        let config = timeline_config("start");
+        let tasks = staggered_tasks(10);
+        // the starts are at 0, 0.3, 0.6, 0.9, 1.2, ... 2.7 s
+        assert_eq!(
+            batches_ms(&config, &tasks),
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]
+        );
+        let (batches, basis) = latency_batches(&config, &tasks).expect("There are tasks");
+        assert_eq!(basis, "start");
+        let percentiles = batch_percentiles(&config, &batches, [0.5, 0.99]);
+        let rounded: Vec<[u64; 2]> = percentiles
+            .iter()
+            .map(|ps| ps.map(|p| p.round() as u64))
+            .collect();
+        assert_eq!(rounded, vec![[1, 3], [5, 6], [8, 9]]);
+    }
+
+    #[test]
+    fn the_latencies_are_batched_by_the_second_of_their_completion() {
+        // the completions are at 0.5, 0.801, 1.102, ... 3.209 s since the first start
+        assert_eq!(
+            batches_ms(&timeline_config("completion"), &staggered_tasks(10)),
+            vec![vec![0, 1], vec![2, 3, 4], vec![5, 6, 7, 8], vec![9]]
+        );
+    }
+
+    #[test]
+    fn a_second_without_tasks_is_a_gap_of_the_timeline() {
+        let config = timeline_config("start");
+        let mut tasks = staggered_tasks(2);
+        // the 0-ms-overhead task starts at 0 s, the 1-ms one at 3 s, the order doesn't matter
+        tasks[1].start_time = tasks[0].start_time + Duration::from_secs(3);
+        tasks.reverse();
+        let (batches, _) = latency_batches(&config, &tasks).expect("There are tasks");
+        assert_eq!(batches.len(), 4);
+        let p50: Vec<f64> = batch_percentiles(&config, &batches, [0.5])
+            .iter()
+            .map(|ps| ps[0])
+            .collect();
+        assert!(p50[1].is_nan() && p50[2].is_nan());
+        assert_eq!(p50[0].round(), 0.);
+        assert_eq!(p50[3].round(), 1.);
+
+        let figures = Figures::new(None);
+        build_latency_timeline(&config, &figures, &tasks);
+        assert_eq!(figures.requested(), 1);
+    }
+
+    #[test]
+    fn a_timeline_of_a_single_second_or_none_is_skipped() {
+        let config = timeline_config("start");
+        assert!(latency_batches(&config, &[]).is_none());
+        let figures = Figures::new(None);
+        build_latency_timeline(&config, &figures, &[]);
+        build_latency_timeline(&config, &figures, &staggered_tasks(4));
+        assert_eq!(figures.requested(), 0);
+    }
+
+    fn aggregator_of(config: &ModelConfig, start: Instant) -> Arc<StreamingAggregator> {
+        Arc::new(StreamingAggregator::new(
+            start,
+            Clock::Precise,
+            config.percentile_method,
+            config.max_samples,
+            config.seed,
+        ))
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn the_sync_workers_exit_once_the_source_is_drained() {
+        // zero-cost tasks, so the workers keep up with the sends and no time is spent in the tasks
+        let config = config("--name t --rate 1000 --num_req 1000 --latency 0ms sync --threads 4");
+        let start = Instant::now();
+        let aggregator = aggregator_of(&config, start);
+        let workers = Workers {
+            cores: vec![None; 4],
+            sleep: Sleep::Os,
+        };
+        let source = TaskSource::new(&config, &aggregator);
+        let send_phase = sync_execution(
+            &config,
+            &workers,
+            None,
+            None,
+            source,
+            aggregator.clone(),
+            &Interrupt::default(),
+        )
+        .await;
+        let drain_phase = start.elapsed() - send_phase;
+        // the refills of 10 per 10 ms take about a second to send all the tasks. The workers
+        // don't wait for anything once the channel is closed, e.g. for a second to pass, so the
+        // drain phase is a fraction of it, even on a loaded machine.
+        assert!(
+            drain_phase < send_phase,
+            "drained in {:?}, sent in {:?}",
+            drain_phase,
+            send_phase
+        );
+        let results = aggregator.finish();
+        assert_eq!((results.sent, results.completed), (1000, 1000));
+    }
+
+    #[test]
+    fn a_simulated_run_of_zero_cost_tasks_has_no_drain_phase() {
+        for mode in ["sync --threads 4", "async"].iter() {
+            let config = config(&format!(
+                "--name t --rate 1000 --num_req 2500 --latency 0ms --engine des {}",
+                mode
+            ));
+            let start = Instant::now();
+            let aggregator = aggregator_of(&config, start);
+            let (send_phase, elapsed) =
+                des::run(&config, &aggregator, start, &Interrupt::default());
+            // the last of the refills of 10 per 10 ms is sent at 2.49 s, and completed at once
+            assert_eq!(send_phase, Duration::from_millis(2490));
+            assert_eq!(elapsed, send_phase);
+            let results = aggregator.finish();
+            assert_eq!((results.sent, results.completed), (2500, 2500));
+        }
+    }
+
+    #[test]
+    fn a_negative_overhead_is_clamped_to_zero() {
+        let timeout = Duration::from_secs(1);
+        let start = Instant::now();
+        let service = Duration::from_millis(10);
+        // the clock says the service took longer than the whole request
+        let stats = TaskStats::new(timeout, start, start, service, start + service / 2);
+        assert_eq!(stats.overhead, 0.);
+        assert!(stats.clamped && stats.success());
+        let stats = TaskStats::new(
+            timeout,
+            start,
+            start,
+            service,
+            start + service + Duration::from_millis(1),
+        );
+        assert!((stats.overhead - 0.001).abs() < 1e-9);
+        assert!(!stats.clamped);
+    }
+
+    #[test]
+    fn no_negative_overhead_reaches_the_stats() {
+        let config = config("--name t --rate 100 --num_req 100 --latency 10ms async");
+        let start = Instant::now();
+        let aggregator = aggregator_of(&config, start);
+        let timeout = Duration::from_secs(1);
+        let service = Duration::from_millis(10);
+        for i in 0..100u32 {
+            let task_start = start + Duration::from_millis(10) * i;
+            // every other task completes "before" its service is over
+            let completion = if i % 2 == 0 {
+                task_start + service - Duration::from_micros(100)
+            } else {
+                task_start + service + Duration::from_micros(u64::from(i))
+            };
+            let stats = TaskStats::new(timeout, task_start, task_start, service, completion);
+            aggregator.record_completion(&stats, None);
+        }
+        let results = aggregator.finish();
+        assert_eq!((results.completed, results.clamped), (100, 50));
+        assert!(results.samples.iter().all(|s| s.overhead >= 0.));
+        assert_eq!(results.histogram.min(), 0);
+        assert!(results
+            .seconds
+            .iter()
+            .flat_map(|s| vec![s.p50, s.p90, s.p99, s.max])
+            .flatten()
+            .all(|p| p >= 0.));
+    }
+
+    #[test]
+    fn every_validated_option_accepts_and_rejects_its_values() {
+        // (the arguments before the option, the option, a valid value, an invalid one)
+        let cases = [
+            ("", "--rate", "2.5k", "2.5"),
+            ("", "--num_req", "10M", "0"),
+            ("", "--latency", "20ms*9,30s", "20ms*x"),
+            ("", "--timeout", "1s", "1 fortnight"),
+            ("", "--target", "cmd:./work.sh", "ftp"),
+            (
+                "",
+                "--url",
+                "http://localhost:8080/health",
+                "ftp://localhost",
+            ),
+            ("", "--success-status", "2xx,304", "6xx"),
+            ("", "--max-processes", "256", "1.5"),
+            ("", "--drop", "1%", "101%"),
+            ("", "--file-size", "64k", "64g"),
+            ("", "--hist-bins", "50", "fifty"),
+            ("", "--hist-range", "0:500ms", "500ms:0"),
+            ("", "--trim-edges", "1s", "1500ms"),
+            ("", "--tag", "branch=feature-x", "branch=a/b"),
+            ("", "--max-samples", "1_000_000", "1e6"),
+            ("--spill-dir /tmp", "--spill-chunk", "1_000_000", "_"),
+            ("", "--repeat", "5", "5x"),
+            ("", "--checkpoint-every", "60s", "0s"),
+            ("", "--apdex", "50ms", "fast"),
+            ("", "--assert-p50", "10ms", "10 parsecs"),
+            ("", "--assert-p99", "250ms", "p99"),
+            ("", "--assert-errors", "0.5", "100.5"),
+            ("--apdex 50ms", "--assert-apdex", "0.9", "1.1"),
+            (
+                "",
+                "--otel-endpoint",
+                "http://localhost:4317",
+                "localhost:4317",
+            ),
+            (
+                "--otel-endpoint http://localhost:4317",
+                "--trace-sample",
+                "1%",
+                "1%%",
+            ),
+            ("", "--coarse-clock", "full=1ms", "full=0ms"),
+            ("", "--background-load", "4", "0"),
+            ("", "--plots", "histogram,timeline", "histogram,pie"),
+            ("async", "--max-in-flight", "1000", "1k1"),
+            ("sync", "--threads", "100", "0"),
+            (
+                "sync --threads 4",
+                "--cancellable-work",
+                "granularity=50ms",
+                "granularity=",
+            ),
+            ("sync --threads 4", "--worker-nice", "10", "20"),
+            (
+                "sync --threads 4 --pin-threads",
+                "--cores",
+                "0,2,4-6",
+                "6-4",
+            ),
+            ("sync --threads 4", "--stats-batch", "64", "64.5"),
+            ("sync --threads 4", "--stats-flush", "10ms", "0ms"),
+            ("sweep-threads", "--threads", "1,2,4,8", "1,,2"),
+            (
+                "sweep-concurrency",
+                "--max-in-flight",
+                "100,1000",
+                "100;1000",
+            ),
+            ("sweep-rate --to 5000 --step 250", "--from", "100", "1.5k1"),
+            ("sweep-rate --from 100 --step 250", "--to", "5000", "5 k"),
+            ("sweep-rate --from 100 --to 5000", "--step", "x2", "x1"),
+            (
+                "sweep-rate --from 100 --to 5000 --step x2",
+                "--duration",
+                "10s",
+                "0",
+            ),
+            (
+                "sweep-rate --from 100 --to 5000 --step x2",
+                "--threads",
+                "100",
+                "0x64",
+            ),
+            (
+                "sweep-rate --from 100 --to 5000 --step x2",
+                "--max-p99",
+                "250ms",
+                "250mss",
+            ),
+            (
+                "history results.db",
+                "--tag",
+                "branch=feature-x",
+                "=feature-x",
+            ),
+            ("history results.db --name t", "--last", "10", "ten"),
+        ];
+        for (before, option, valid, invalid) in cases.iter() {
+            let args = |value: &'static str| {
+                iter::once("concurrency-demo-benchmarks")
+                    .chain(before.split_whitespace())
+                    .chain(vec![*option, value])
+                    .collect::<Vec<_>>()
+            };
+            if let Err(e) = ModelConfig::cli().try_get_matches_from(args(valid)) {
+                panic!("{} {} is rejected: {}", option, valid, e);
+            }
+            match ModelConfig::cli().try_get_matches_from(args(invalid)) {
+                Ok(_) => panic!("{} {} is accepted", option, invalid),
+                Err(e) => {
+                    assert_eq!(e.kind, ErrorKind::ValueValidation, "{} {}", option, invalid);
+                    assert!(
+                        e.to_string().contains(&format!("`{}` is not", invalid)),
+                        "{}",
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Simulates a run of `config` in virtual time. Returns its results and its (send, drain) phases.
+    fn simulate(config: &ModelConfig) -> (RunResults, (Duration, Duration)) {
+        let start = Instant::now();
+        let aggregator = aggregator_of(config, start);
+        let (send_phase, elapsed) = des::run(config, &aggregator, start, &Interrupt::default());
+        (aggregator.finish(), (send_phase, elapsed - send_phase))
+    }
+
+    /// Reports the results of a run as `main` does, but the figures aren't rendered and the summary
+    /// is written to a buffer. Returns the summary, its text and the number of the requested figures.
+    fn report(
+        config: &ModelConfig,
+        mut results: RunResults,
+        phases: (Duration, Duration),
+    ) -> (Summary, String, usize) {
+        let figures = Figures::new(None);
+        let successes = sort_records(&mut results.samples);
+        let overheads = Overheads::Records(&results.samples[..successes]);
+        let (_, rate) = build_figures(config, &figures, &results, successes, &overheads);
+        let summary = Summary::new(config, &overheads, &results, rate, phases, false, &[]);
+        let mut out = termcolor::Buffer::no_color();
+        summary
+            .write(&mut out)
+            .expect("Failed to write the summary");
+        let text = String::from_utf8(out.into_inner()).expect("The summary is UTF-8");
+        (summary, text, figures.requested())
+    }
+
+    #[test]
+    fn a_run_without_tasks_is_reported() {
+        let config = config("--name t --rate 100 --num_req 100 --latency 10ms async");
+        let results = aggregator_of(&config, Instant::now()).finish();
+        let (summary, text, charts) = report(&config, results, Default::default());
+        assert_eq!((summary.total(), summary.failed()), (0, 0));
+        assert!(summary.rate().is_none());
+        assert!(summary.percentiles().iter().all(|(_, v)| v.is_nan()));
+        assert!(
+            text.contains("Latencies: n/a (no successful requests)"),
+            "{}",
+            text
+        );
+        assert_eq!(charts, 0);
+    }
+
+    #[test]
+    fn a_run_of_failures_only_is_reported() {
+        let config = config(
+            "--name t --rate 100 --num_req 200 --latency 2s --timeout 1s --engine des async",
+        );
+        let (results, phases) = simulate(&config);
+        let (summary, _, _) = report(&config, results, phases);
+        assert_eq!((summary.total(), summary.failed()), (200, 200));
+        assert!(summary.rate().is_none());
+        assert!(summary.percentiles().iter().all(|(_, v)| v.is_nan()));
+    }
+
+    #[test]
+    fn a_run_of_a_single_task_is_reported() {
+        let config =
+            config("--name t --rate 100 --num_req 1 --latency 10ms --engine des sync --threads 1");
+        let (results, phases) = simulate(&config);
+        let (summary, text, _) = report(&config, results, phases);
+        assert_eq!((summary.total(), summary.failed()), (1, 0));
+        let rate = summary.rate().expect("A successful task");
+        assert_eq!((rate.avg, rate.window, rate.run), (1., (0, 0), (0, 0)));
+        // a simulated task has no overhead
+        assert!(summary.percentiles().iter().all(|(_, v)| *v == 0.));
+        assert!(
+            text.contains("Send phase: 0.000s, drain phase: 0.010s"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn a_run_of_no_time_is_reported() {
+        // a refill of 10 sends every task at once, and they take no time
+        let config = config("--name t --rate 1000 --num_req 10 --latency 0ms --engine des async");
+        let (results, phases) = simulate(&config);
+        assert_eq!(phases, (Duration::from_secs(0), Duration::from_secs(0)));
+        let (summary, text, _) = report(&config, results, phases);
+        assert_eq!((summary.total(), summary.failed()), (10, 0));
+        let rate = summary.rate().expect("Successful tasks");
+        assert_eq!((rate.avg, rate.std_dev, rate.window), (10., 0., (0, 0)));
+        assert_eq!(summary.phases(), phases);
+        assert!(
+            text.contains("Send phase: 0.000s, drain phase: 0.000s"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn an_all_failing_run_reports_its_failures_instead_of_the_latencies() {
+        let config = config(
+            "--name t --rate 100 --num_req 200 --latency 2s --timeout 1s --engine des async",
+        );
+        let (results, phases) = simulate(&config);
+        let (_, text, charts) = report(&config, results, phases);
+        assert!(
+            text.contains("Latencies: n/a (no successful requests)"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("p50.000"), "{}", text);
+        assert!(
+            text.contains(
+                "Timed out (measured latency, queueing included, > 1000 ms): 200 of 200 \
+                 - slow backend: 200, overloaded queue: 0"
+            ),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("Failed latencies: p0 - 2000.000 ms, p50 - 2000.000 ms"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains(
+                "Most common cause: slow backend, the service time alone exceeds the timeout"
+            ),
+            "{}",
+            text
+        );
+        assert!(!text.contains("Avg rate"), "{}", text);
+        // the latency figures and the rate are skipped, the scatter shows the failures,
+        // and the combined chart no throughput
+        assert_eq!(charts, 2);
+    }
+
+    #[test]
+    fn an_overloaded_queue_is_the_most_common_cause() {
+        // the first task is too slow, the ones behind it wait for the only worker too long
+        let config = config(
+            "--name t --rate 100 --num_req 200 --latency 2s,900ms*3 --timeout 1s --engine des sync --threads 1",
+        );
+        let (results, phases) = simulate(&config);
+        let (_, text, _) = report(&config, results, phases);
+        assert!(
+            text.contains("200 of 200 - slow backend: 50, overloaded queue: 150"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("Most common cause: overloaded queue, the requests waited too long before being served"),
+            "{}",
+            text
+        );
+    }
+
+    /// The CSV of `--export-timeseries` of a simulated run of `config`, and the offsets of the starts
+    /// of its sampled records from the first one, in the order of their completion.
+    fn timeseries_of(config: &ModelConfig) -> (Vec<u8>, Vec<Duration>) {
+        let (results, _) = simulate(config);
+        let path = env::temp_dir().join(format!(
+            "cdb-timeseries-{}-{}-{}.csv",
+            config.name,
+            config.seed,
+            std::process::id()
+        ));
+        // the metadata goes first, the start of a simulated run is its virtual one
+        let metadata = RunMetadata::new(config, SystemTime::UNIX_EPOCH);
+        export::write_timeseries(&path, &results.seconds, &metadata)
+            .expect("Failed to write the timeseries");
+        let csv = std::fs::read(&path).expect("Failed to read the timeseries");
+        let _ = std::fs::remove_file(&path);
+        let mut samples = results.samples;
+        samples.sort_by_key(|s| s.completion_time);
+        let first = samples.iter().map(|s| s.start_time).min();
+        let starts = samples
+            .iter()
+            .map(|s| s.start_time - first.expect("There are samples"))
+            .collect();
+        (csv, starts)
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_run() {
+        let args =
+            "--name seeded --rate 1000 --num_req 5000 --latency 1ms,5ms*3,50ms --max-samples 500 \
+                    --engine des";
+        let run = |seed: u64| {
+            timeseries_of(&config(&format!(
+                "{} --seed {} sync --threads 4",
+                args, seed
+            )))
+        };
+        let (csv, samples) = run(42);
+        let (same_csv, same_samples) = run(42);
+        assert!(csv == same_csv, "The CSVs of the same seed differ");
+        assert_eq!(samples, same_samples);
+        assert_eq!(samples.len(), 500);
+        // the reservoir of another seed keeps other records
+        let (_, other_samples) = run(43);
+        assert_ne!(samples, other_samples);
+    }
+
+    /// A trial of `--repeat` of the summary of a simulated run, as the sweep reads it back.
+    fn trial_of(value: usize, summary: &Summary) -> sweep::Trial {
+        let (send, drain) = summary.phases();
+        let run = store::Run {
+            name: format!("repeat_{}", value),
+            start_time: String::new(),
+            mode: "sync-4".to_string(),
+            rate: 500,
+            completed: summary.total() as i64,
+            failed: summary.failed() as i64,
+            percentiles: summary.percentiles().to_vec(),
+            avg_rate: summary.rate().map(|rate| rate.avg),
+            duration: (send + drain).as_secs_f64(),
+            interrupted: false,
+            tags: vec![],
+        };
+        sweep::Trial { value, run }
+    }
+
+    #[test]
+    fn the_repeats_of_a_fixed_seed_do_not_spread() {
+        let config = config(
+            "--name repeat --rate 500 --num_req 2000 --latency 1ms,5ms*3,50ms --timeout 20ms \
+             --max-samples 300 --seed 42 --engine des sync --threads 4",
+        );
+        let trials: Vec<sweep::Trial> = (1..=3)
+            .map(|value| {
+                let (results, phases) = simulate(&config);
+                trial_of(value, &report(&config, results, phases).0)
+            })
+            .collect();
+        let aggregate = repeat::Aggregate::new(&trials);
+        assert_eq!(aggregate.repeats, 3);
+        assert!(!aggregate.percentiles.is_empty());
+        for (p, spread) in &aggregate.percentiles {
+            assert_eq!(spread.std_dev, 0., "p{}", p);
+            assert_eq!(Some(spread.mean), trials[0].run.percentile(*p), "p{}", p);
+        }
+        assert_eq!(aggregate.achieved_rate.std_dev, 0.);
+        // the 50ms tasks time out
+        assert!(aggregate.error_rate.mean > 0.);
+        assert_eq!(aggregate.error_rate.std_dev, 0.);
+    }
+
+    #[test]
+    fn counts_have_suffixes_of_thousands_millions_and_billions() {
+        let cases = [
+            ("1", 1),
+            ("1_000_000", 1_000_000),
+            ("500k", 500_000),
+            ("500K", 500_000),
+            ("10M", 10_000_000),
+            ("2G", 2_000_000_000),
+            ("2.5k", 2_500),
+            (".5k", 500),
+            ("1.000001M", 1_000_001),
+            ("1_5k", 15_000),
+        ];
+        for (s, n) in cases.iter() {
+            assert_eq!(ModelConfig::parse_count(s), Some(*n), "{}", s);
+        }
+    }
+
+    #[test]
+    fn counts_reject_the_other_cases_overflows_and_garbage() {
+        let max = usize::MAX.to_string();
+        let cases = [
+            // millis and the lowercase billions aren't counts
+            "10m", "2g", "", "_", "k", "0", "0k", "-5", "+5",
+            // a fraction must be a whole number of the unit
+            "1.5", "1.0005k", "1.", "10x", "10kb", "10k ", " 10", "1e3", "1,000",
+        ];
+        for s in cases.iter() {
+            assert_eq!(ModelConfig::parse_count(s), None, "{:?}", s);
+        }
+        assert_eq!(ModelConfig::parse_count(&max), Some(usize::MAX));
+        for s in [
+            format!("{}0", max),
+            format!("{}k", max),
+            format!("{}G", usize::MAX / 1_000_000),
+        ]
+        .iter()
+        {
+            assert_eq!(ModelConfig::parse_count(s), None, "{}", s);
+        }
+    }
+
+    // it's timing, which a loaded machine skews: `cargo test -- --ignored simulation`
+    #[ignore]
+    #[tokio::test(threaded_scheduler)]
+    async fn the_simulation_predicts_the_percentiles_of_a_real_run() {
+        // the real sleeps overshoot by the slack of the OS timer and the wakeups of the workers
+        const TOLERANCE: f64 = 0.1;
+        const MIN_TOLERANCE_MS: f64 = 2.;
+        let args = "--name t --rate 1000 --num_req 1000 --latency 5ms*3,20ms --seed 42";
+        let mode = "sync --threads 50";
+        let real = config(&format!("{} {}", args, mode));
+        let start = Instant::now();
+        let aggregator = aggregator_of(&real, start);
+        let workers = Workers {
+            cores: vec![None; 50],
+            sleep: Sleep::Os,
+        };
+        let source = TaskSource::new(&real, &aggregator);
+        let send_phase = sync_execution(
+            &real,
+            &workers,
+            None,
+            None,
+            source,
+            aggregator.clone(),
+            &Interrupt::default(),
+        )
+        .await;
+        let phases = (send_phase, start.elapsed() - send_phase);
+        let (real, _, _) = report(&real, aggregator.finish(), phases);
+
+        let simulated = config(&format!("{} --engine des {}", args, mode));
+        let (results, phases) = simulate(&simulated);
+        let (simulated, _, _) = report(&simulated, results, phases);
+
+        assert_eq!(real.total(), simulated.total());
+        assert!(!simulated.percentiles().is_empty());
+        for ((p, real), (_, simulated)) in real.percentiles().iter().zip(simulated.percentiles()) {
+            let tolerance = (simulated * TOLERANCE).max(MIN_TOLERANCE_MS);
+            assert!(
+                (real - simulated).abs() <= tolerance,
+                "p{}: {:.3} ms real, {:.3} ms simulated",
+                p,
+                real,
+                simulated
+            );
+        }
+    }
+
+    #[test]
+    fn the_parallel_post_processing_is_the_serial_one() {
+        let config = config("--name t --rate 10000 --num_req 150000 --latency 10ms async");
+        let mut rng = stats::rng(7, "records");
+        let first = Instant::now();
+        // more than two chunks of the timeline, with ties of the overheads and a failure in 10
+        let mut tasks: Vec<TaskStats> = (0..150_000u32)
+            .map(|i| {
+                let start = first + Duration::from_micros(100) * i;
+                let service = Duration::from_millis(rng.gen_range(0..50));
+                let completion = if i % 10 == 0 {
+                    start + Duration::from_secs(2)
+                } else {
+                    start + service + Duration::from_millis(rng.gen_range(0..20))
+                };
+                TaskStats::new(Duration::from_secs(1), start, start, service, completion)
+            })
+            .collect();
+        let mut serial = tasks.clone();
+        serial.sort_by(|a, b| {
+            a.failure
+                .is_some()
+                .cmp(&b.failure.is_some())
+                .then_with(|| a.overhead.partial_cmp(&b.overhead).unwrap())
+        });
+        let successes = sort_records(&mut tasks);
+        assert_eq!(successes, serial.partition_point(TaskStats::success));
+        assert_eq!(successes, 135_000);
+        let overheads = |tasks: &[TaskStats]| tasks.iter().map(|t| t.overhead).collect::<Vec<_>>();
+        assert!(overheads(&tasks) == overheads(&serial));
+
+        let mut serial_batches: Vec<Vec<f64>> = vec![];
+        for task in &serial[..successes] {
+            let second = task.start_time.duration_since(first).as_secs() as usize;
+            if serial_batches.len() <= second {
+                serial_batches.resize_with(second + 1, Vec::new);
+            }
+            serial_batches[second].push(task.overhead);
+        }
+        let (batches, _) = latency_batches(&config, &tasks[..successes]).expect("There are tasks");
+        assert_eq!(batches.len(), 15);
+        assert!(batches == serial_batches);
+
+        let ps = [0.5, 0.9, 0.99];
+        let serial_percentiles: Vec<[f64; 3]> = serial_batches
+            .iter()
+            .map(|batch| ps.map(|p| config.percentile_method.percentile(batch, p).unwrap() * 1000.))
+            .collect();
+        assert!(batch_percentiles(&config, &batches, ps) == serial_percentiles);
+    }
 }
@@ -0,0 +1,240 @@
+use crate::aggregator::SecondStats;
+use crate::checkpoint::Checkpoint;
+use crate::metadata::RunMetadata;
+use crate::report::{Chart, Figures};
+use crate::store;
+use crate::ModelConfig;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::{info, warn};
+
+const PRINTED_PERCENTILES: [f64; 8] = [0., 50., 90., 95., 99., 99.9, 99.99, 100.];
+
+/// A percentile of the per-second stats, s.
+type Percentile = fn(&SecondStats) -> Option<f64>;
+
+/// A run of the merged dataset.
+#[derive(Serialize)]
+struct Session {
+    path: String,
+    start_time: String,
+    /// The first second of the session in the dataset.
+    first_second: u64,
+    completed: usize,
+    failed: usize,
+    /// s
+    elapsed: f64,
+}
+
+/// A checkpoint of the whole dataset, so it can be merged, resumed or compared in turn,
+/// with the sessions it's made of.
+#[derive(Serialize)]
+struct Merged<'a> {
+    #[serde(flatten)]
+    checkpoint: &'a Checkpoint,
+    sessions: &'a [Session],
+}
+
+/// Merges the checkpoints at `paths`, the sessions of a run in order, into `merged_{name}.json` in `out`,
+/// with the timeline and the rate charts, the sessions are marked on them. The percentiles are recomputed
+/// from the merged histogram. The runs must have the same latencies, mode and target, unless `force` is set.
+pub fn run(paths: &[&str], out: &Path, force: bool, figures: &Figures) -> io::Result<()> {
+    let mut runs = Vec::with_capacity(paths.len());
+    for path in paths {
+        runs.push(read(Path::new(path))?);
+    }
+    let first = &runs[0].metadata.config;
+    for (path, run) in paths.iter().zip(&runs).skip(1) {
+        if let Some(reason) = incompatibility(first, &run.metadata.config) {
+            if !force {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{} can't be merged with {}: {}. Use --force to merge them anyway",
+                        path, paths[0], reason
+                    ),
+                ));
+            }
+            warn!("{} is merged with {}, though {}", path, paths[0], reason);
+        }
+    }
+
+    let mut sessions = Vec::with_capacity(runs.len());
+    let mut runs = runs.into_iter();
+    let mut merged = runs.next().expect("There is a run at least");
+    sessions.push(session(paths[0], &merged, 0));
+    for (path, run) in paths.iter().skip(1).zip(runs) {
+        let first_second = merged.state.seconds.last().map_or(0, |s| s.second + 1);
+        sessions.push(session(path, &run, first_second));
+        merged.elapsed += run.elapsed;
+        merged.metadata.interrupted |= run.metadata.interrupted;
+        merged.state.append(run.state);
+    }
+
+    fs::create_dir_all(out)?;
+    let name = &merged.metadata.config.name;
+    let path = out.join(format!("merged_{}.json", name));
+    let json = serde_json::to_string(&Merged {
+        checkpoint: &merged,
+        sessions: &sessions,
+    })
+    .expect("Checkpoint is serializable");
+    fs::write(&path, json)?;
+    info!("The merged dataset is written to {}", path.display());
+    print(&merged, &sessions);
+    for chart in charts(&merged, &sessions, out) {
+        figures.render(&chart);
+    }
+    Ok(())
+}
+
+/// A checkpoint, the metadata of a run is rejected, as it has no results.
+fn read(path: &Path) -> io::Result<Checkpoint> {
+    Checkpoint::read(path).map_err(|e| {
+        let reason = if RunMetadata::read(path).is_ok() {
+            "it's the metadata of a run, which has no results, merge the checkpoints of --checkpoint-every instead"
+                .to_string()
+        } else {
+            e.to_string()
+        };
+        io::Error::new(e.kind(), format!("{}: {}", path.display(), reason))
+    })
+}
+
+fn session(path: &str, run: &Checkpoint, first_second: u64) -> Session {
+    Session {
+        path: path.to_string(),
+        start_time: run.metadata.start_time.clone(),
+        first_second,
+        completed: run.state.completed,
+        failed: run.state.failed,
+        elapsed: run.elapsed,
+    }
+}
+
+/// Why the runs are of different scenarios, if they are, e.g. `the modes differ: sync-50 vs async`.
+fn incompatibility(a: &ModelConfig, b: &ModelConfig) -> Option<String> {
+    if a.latency_distribution != b.latency_distribution {
+        return Some(format!(
+            "the latencies differ: {} vs {}",
+            latencies(&a.latency_distribution),
+            latencies(&b.latency_distribution)
+        ));
+    }
+    let (mode_a, mode_b) = (store::mode(a), store::mode(b));
+    if mode_a != mode_b {
+        return Some(format!("the modes differ: {} vs {}", mode_a, mode_b));
+    }
+    let (target_a, target_b) = (format!("{:?}", a.target), format!("{:?}", b.target));
+    if target_a != target_b {
+        return Some(format!("the targets differ: {} vs {}", target_a, target_b));
+    }
+    None
+}
+
+/// The latencies as `--latency`, e.g. `20ms*9,30000ms`.
+fn latencies(values: &[u64]) -> String {
+    let mut groups: Vec<(u64, usize)> = vec![];
+    for value in values {
+        match groups.last_mut() {
+            Some((last, count)) if last == value => *count += 1,
+            _ => groups.push((*value, 1)),
+        }
+    }
+    groups
+        .iter()
+        .map(|(value, count)| match count {
+            1 => format!("{}ms", value),
+            _ => format!("{}ms*{}", value, count),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn print(merged: &Checkpoint, sessions: &[Session]) {
+    let state = &merged.state;
+    println!(
+        "{} sessions of {}, {:.3}s:",
+        sessions.len(),
+        merged.metadata.config.name,
+        merged.elapsed
+    );
+    for (i, session) in sessions.iter().enumerate() {
+        println!(
+            "  {}. {} started at {}: {} requests, {} failed, {:.3}s",
+            i + 1,
+            session.path,
+            session.start_time,
+            session.completed,
+            session.failed,
+            session.elapsed
+        );
+    }
+    let histogram = state.histogram();
+    println!("Latencies:");
+    if histogram.is_empty() {
+        println!("No successful requests");
+    } else {
+        for p in &PRINTED_PERCENTILES {
+            println!(
+                "p{:.3} - {:.3} ms",
+                p,
+                histogram.value_at_quantile(p / 100.) as f64 / 1000.
+            );
+        }
+    }
+    println!(
+        "Requests: {}, failed: {} ({:.2}%), achieved rate: {:.3}",
+        state.completed,
+        state.failed,
+        100. * state.failed as f64 / state.completed.max(1) as f64,
+        (state.completed - state.failed) as f64 / merged.elapsed.max(f64::EPSILON)
+    );
+}
+
+/// The per-second p50, p90 and p99, and the rate of the successes, with a line at the start
+/// of every session but the first one.
+fn charts(merged: &Checkpoint, sessions: &[Session], out: &Path) -> Vec<Chart> {
+    let seconds = &merged.state.seconds;
+    let name = &merged.metadata.config.name;
+    let file_name = |stem: &str| {
+        out.join(format!("{}_{}.png", stem, name))
+            .to_string_lossy()
+            .into_owned()
+    };
+    let x: Vec<f64> = seconds.iter().map(|s| s.second as f64).collect();
+    let ms = |v: Option<f64>| v.map_or(f64::NAN, |v| v * 1000.);
+    let mut timeline = Chart::new(file_name("latency_timeline"), "Time, s", "Latency, ms");
+    let mut max_latency: f64 = 0.;
+    let percentiles: [(&str, Percentile); 3] =
+        [("p50", |s| s.p50), ("p90", |s| s.p90), ("p99", |s| s.p99)];
+    for (label, p) in &percentiles {
+        let y: Vec<f64> = seconds.iter().map(|s| ms(p(s))).collect();
+        max_latency = y
+            .iter()
+            .cloned()
+            .filter(|v| !v.is_nan())
+            .fold(max_latency, f64::max);
+        timeline = timeline.line(Some(label), x.clone(), y);
+    }
+    let rps: Vec<f64> = seconds
+        .iter()
+        .map(|s| (s.completed - s.failed) as f64)
+        .collect();
+    let max_rps = rps.iter().cloned().fold(0., f64::max);
+    let mut rate = Chart::new(
+        file_name("request_rate"),
+        "Time, s",
+        "Requests per second (successes only)",
+    )
+    .line(Some("RPS"), x, rps);
+    for (i, session) in sessions.iter().enumerate().skip(1) {
+        let label = if i == 1 { Some("session start") } else { None };
+        let at = session.first_second as f64;
+        timeline = timeline.line(label, vec![at, at], vec![0., max_latency]);
+        rate = rate.line(label, vec![at, at], vec![0., max_rps]);
+    }
+    vec![timeline, rate]
+}
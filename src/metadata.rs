@@ -0,0 +1,142 @@
+use crate::report::Binning;
+use crate::ModelConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Everything needed to tell which parameters and which machine produced a set of results.
+/// Written as `meta_{name}.json` next to the figures.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunMetadata {
+    pub command_line: Vec<String>,
+    pub config: ModelConfig,
+    pub version: String,
+    pub seed: Option<u64>,
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    /// RFC 3339, UTC.
+    pub start_time: String,
+    /// The effective binning of the latency histogram, once it's built.
+    pub histogram: Option<Binning>,
+    /// Set if the run was stopped by Ctrl-C before all the requests were sent.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// The intervals of the run (seconds since the start), when the load generator was behind schedule.
+    #[serde(default)]
+    pub generator_limited: Vec<(f64, f64)>,
+    /// The core of each sync worker of `--pin-threads`, by its index.
+    #[serde(default)]
+    pub pinned_cores: Option<Vec<usize>>,
+}
+
+impl RunMetadata {
+    pub fn new(config: &ModelConfig, start_time: SystemTime) -> Self {
+        Self {
+            command_line: env::args().collect(),
+            config: config.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: Some(config.seed),
+            hostname: hostname::get()
+                .map(|h| h.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            cpus: num_cpus::get(),
+            start_time: humantime::format_rfc3339_millis(start_time).to_string(),
+            histogram: None,
+            interrupted: false,
+            generator_limited: vec![],
+            pinned_cores: None,
+        }
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Path::new(".").join(format!("meta_{}.json", name))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("Metadata is serializable");
+        fs::write(path, json)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Prints the metadata of two runs side by side, differing values are marked with `*`.
+pub fn compare(a: &Path, b: &Path) -> io::Result<()> {
+    let mut rows: BTreeMap<String, (String, String)> = BTreeMap::new();
+    for (key, value) in flatten(serde_json::to_value(RunMetadata::read(a)?)?) {
+        rows.entry(key).or_default().0 = value;
+    }
+    for (key, value) in flatten(serde_json::to_value(RunMetadata::read(b)?)?) {
+        rows.entry(key).or_default().1 = value;
+    }
+
+    let key_width = rows.keys().map(|k| k.len()).max().unwrap_or(0);
+    let value_width = rows.values().map(|(a, _)| a.len()).max().unwrap_or(0);
+    println!(
+        "  {:<kw$}  {:<vw$}  {}",
+        "",
+        a.to_string_lossy(),
+        b.to_string_lossy(),
+        kw = key_width,
+        vw = value_width
+    );
+    for (key, (a, b)) in rows {
+        println!(
+            "{} {:<kw$}  {:<vw$}  {}",
+            if a == b { ' ' } else { '*' },
+            key,
+            a,
+            b,
+            kw = key_width,
+            vw = value_width
+        );
+    }
+    Ok(())
+}
+
+/// `{"config": {"name": "x"}}` -> `[("config.name", "x")]`.
+fn flatten(value: Value) -> Vec<(String, String)> {
+    fn visit(prefix: String, value: Value, out: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let key = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    visit(key, value, out);
+                }
+            }
+            Value::Array(values) => {
+                let values: Vec<String> = values
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::String(s) => s,
+                        v => v.to_string(),
+                    })
+                    .collect();
+                out.push((prefix, values.join(" ")))
+            }
+            Value::String(s) => out.push((prefix, s)),
+            Value::Null => out.push((prefix, "-".to_string())),
+            value => out.push((prefix, value.to_string())),
+        }
+    }
+
+    let mut out = vec![];
+    visit(String::new(), value, &mut out);
+    out
+}
@@ -0,0 +1,206 @@
+use crate::{Failure, TaskStats};
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use opentelemetry::sdk::trace::{self, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::{Span, StatusCode, TraceContextExt, Tracer as _};
+use opentelemetry::{global, Context, KeyValue};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, SystemTime};
+use tracing::{info, warn};
+
+// the sampled tasks wait for the exporter in a queue of this size, the rest are dropped
+const QUEUE_SIZE: usize = 65_536;
+
+/// What a sampled task has taken.
+struct TaskSpan {
+    index: usize,
+    cost: u64,
+    /// The sync worker, which has served the task.
+    worker: Option<usize>,
+    start: Instant,
+    service_start: Instant,
+    completion: Instant,
+    failure: Option<Failure>,
+}
+
+/// Records a span of the sampled tasks. It's cloned by the workers or the tasks,
+/// the spans are exported by a thread of their own, so a task only enqueues its span.
+#[derive(Clone)]
+pub struct TaskTracer {
+    sender: Sender<TaskSpan>,
+    /// The traced share of the tasks, within [0, 1].
+    share: f64,
+    seed: u64,
+    dropped: Arc<AtomicU64>,
+}
+
+impl TaskTracer {
+    /// Whether the `index`-th task is traced, by a hash of the index and the seed,
+    /// so the choice needs no shared state and is the same in both modes.
+    fn sampled(&self, index: usize) -> bool {
+        // splitmix64
+        let mut x = self.seed ^ (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        (x as f64) < self.share * u64::MAX as f64
+    }
+
+    /// The `index`-th task of `cost` ms, does nothing if it's not sampled.
+    pub fn record(&self, index: usize, cost: u64, stats: &TaskStats) {
+        if !self.sampled(index) {
+            return;
+        }
+        let span = TaskSpan {
+            index,
+            cost,
+            worker: stats.worker,
+            start: stats.start_time,
+            service_start: stats.service_start_time,
+            completion: stats.completion_time,
+            failure: stats.failure,
+        };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(span) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Exports the spans of `--otel-endpoint` over OTLP/gRPC in batches. The exporter runs
+/// on a runtime of its own, the spans are flushed when it's stopped.
+pub struct Exporter {
+    tracer: TaskTracer,
+    endpoint: String,
+    /// Returns the number of the exported spans.
+    thread: JoinHandle<u64>,
+}
+
+impl Exporter {
+    /// `share` is the traced share of the tasks, %, chosen by `seed`.
+    pub fn start(endpoint: &str, name: &str, share: f64, seed: u64) -> io::Result<Self> {
+        let (sender, receiver) = channel::bounded(QUEUE_SIZE);
+        let (started, result) = channel::bounded(1);
+        // the spans are timed by `Instant`s, which aren't wall clock
+        let anchor = (Instant::now(), SystemTime::now());
+        let config = trace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", "concurrency-demo-benchmarks"),
+            KeyValue::new("run.name", name.to_string()),
+        ]));
+        let pipeline = opentelemetry_otlp::new_pipeline()
+            .with_endpoint(endpoint)
+            .with_trace_config(config);
+        let thread = thread::Builder::new()
+            .name("otel".to_string())
+            .spawn(move || {
+                let runtime = match tokio1::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                        return 0;
+                    }
+                };
+                let _entered = runtime.enter();
+                let tracer = match pipeline
+                    .with_tonic()
+                    .install_batch(opentelemetry::runtime::Tokio)
+                {
+                    Ok(tracer) => tracer,
+                    Err(e) => {
+                        let _ = started.send(Err(io::Error::other(e)));
+                        return 0;
+                    }
+                };
+                let _ = started.send(Ok(()));
+                export(&tracer, receiver, anchor)
+            })?;
+        result
+            .recv()
+            .expect("The exporter thread failed to start")?;
+        let _ = global::set_error_handler(|e| warn!("Failed to export the traces: {}", e));
+        info!(
+            "Exporting the traces of {}% of the tasks to {}",
+            share, endpoint
+        );
+        Ok(Self {
+            tracer: TaskTracer {
+                sender,
+                share: share / 100.,
+                seed,
+                dropped: Arc::new(AtomicU64::new(0)),
+            },
+            endpoint: endpoint.to_string(),
+            thread,
+        })
+    }
+
+    pub fn tracer(&self) -> TaskTracer {
+        self.tracer.clone()
+    }
+
+    /// Blocks until the spans are flushed. The workers and the tasks must be done by then.
+    pub fn stop(self) {
+        let dropped = self.tracer.dropped.load(Ordering::Relaxed);
+        // the channel is closed, once the last tracer is gone
+        drop(self.tracer);
+        let exported = self.thread.join().expect("The exporter thread failed");
+        info!(
+            "Traced {} tasks to {}{}",
+            exported,
+            self.endpoint,
+            if dropped > 0 {
+                format!(", {} were dropped as the exporter fell behind", dropped)
+            } else {
+                String::new()
+            }
+        );
+    }
+}
+
+/// A span of a task with the children of its queue wait and its execution.
+/// Returns the number of the tasks, once the channel is closed and the spans are flushed.
+fn export(tracer: &Tracer, receiver: Receiver<TaskSpan>, anchor: (Instant, SystemTime)) -> u64 {
+    let wall = |instant: Instant| anchor.1 + instant.saturating_duration_since(anchor.0);
+    let mut exported = 0;
+    for task in receiver {
+        let mut attributes = vec![
+            KeyValue::new("task.index", task.index as i64),
+            KeyValue::new("task.cost_ms", task.cost as i64),
+            KeyValue::new("task.success", task.failure.is_none()),
+        ];
+        if let Some(worker) = task.worker {
+            attributes.push(KeyValue::new("worker.id", worker as i64));
+        }
+        let span = tracer
+            .span_builder("task")
+            .with_start_time(wall(task.start))
+            .with_attributes(attributes)
+            .start(tracer);
+        if let Some(failure) = task.failure {
+            span.set_status(StatusCode::Error, failure.name().to_string());
+        }
+        let cx = Context::current_with_span(span);
+        for (name, start, end) in &[
+            ("queue", task.start, task.service_start),
+            ("execution", task.service_start, task.completion),
+        ] {
+            tracer
+                .span_builder(name)
+                .with_parent_context(cx.clone())
+                .with_start_time(wall(*start))
+                .start(tracer)
+                .end_with_timestamp(wall(*end));
+        }
+        cx.span().end_with_timestamp(wall(task.completion));
+        exported += 1;
+    }
+    // flushes the batch
+    global::shutdown_tracer_provider();
+    exported
+}
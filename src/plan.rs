@@ -0,0 +1,291 @@
+use crate::affinity;
+use crate::checkpoint::Checkpoint;
+use crate::metadata::RunMetadata;
+use crate::priority;
+use crate::rate_limiter;
+use crate::source::TaskSchedule;
+use crate::spill;
+use crate::{Backend, Engine, Mode, ModelConfig};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// the percentiles of the nominal costs
+const PERCENTILES: [f64; 5] = [50., 90., 99., 99.9, 100.];
+// the costs of the first tasks of the schedule
+const SCHEDULE_SHOWN: usize = 10;
+
+/// The nominal costs of the tasks, i.e. the latencies in turn: (cost in ms, number of the tasks),
+/// sorted by the cost.
+fn costs(config: &ModelConfig) -> Vec<(u64, usize)> {
+    let latencies = &config.latency_distribution;
+    let mut counts = BTreeMap::new();
+    for (i, cost) in latencies.iter().enumerate() {
+        let count =
+            config.n_jobs / latencies.len() + usize::from(i < config.n_jobs % latencies.len());
+        if count > 0 {
+            *counts.entry(*cost).or_insert(0) += count;
+        }
+    }
+    counts.into_iter().collect()
+}
+
+/// The nearest rank percentile of the costs, `p` is within `[0, 100]`.
+fn percentile(costs: &[(u64, usize)], total: usize, p: f64) -> u64 {
+    let rank = ((p / 100. * total as f64 - 1e-9).ceil() as usize).max(1);
+    let mut seen = 0;
+    for (cost, count) in costs {
+        seen += count;
+        if seen >= rank {
+            return *cost;
+        }
+    }
+    costs.last().map_or(0, |(cost, _)| *cost)
+}
+
+/// Prints what the run would do, without running it: the schedule of the tasks, the rate limiter,
+/// the expected duration and concurrency, the workers, and the files it would write.
+/// The costs are the nominal ones, i.e. the overheads of the target and the queueing are left out.
+pub fn print(config: &ModelConfig) {
+    println!("Dry run of {}, nothing is executed", config.name);
+    let mut warnings = vec![];
+
+    println!("Tasks:");
+    let costs = costs(config);
+    if config.stdin_tasks {
+        println!(
+            "  read from stdin, as soon as they are written, their number and costs are unknown"
+        );
+    } else {
+        println!("  {} at {} rps", config.n_jobs, config.rps);
+        let (refill, interval) = rate_limiter::refill(config.rps);
+        println!("  rate limiter: a refill of {} per {} ms", refill, interval);
+        let schedule = TaskSchedule::new(&config.latency_distribution, config.n_jobs);
+        let how = if schedule.is_materialized() {
+            "expanded up front"
+        } else {
+            "cycled lazily"
+        };
+        let first: Vec<String> = schedule
+            .take(SCHEDULE_SHOWN)
+            .map(|cost| cost.to_string())
+            .collect();
+        println!(
+            "  schedule ({}): {} ms{}",
+            how,
+            first.join(", "),
+            if config.n_jobs > SCHEDULE_SHOWN {
+                format!(", the first {} of {}", SCHEDULE_SHOWN, config.n_jobs)
+            } else {
+                String::new()
+            }
+        );
+    }
+    let total: usize = costs.iter().map(|(_, count)| count).sum();
+    if total > 0 {
+        let mean = costs
+            .iter()
+            .map(|(cost, count)| *cost as f64 * *count as f64)
+            .sum::<f64>()
+            / total as f64;
+        println!(
+            "  nominal costs of {} latency values: mean {:.3} ms, {}",
+            config.latency_distribution.len(),
+            mean,
+            PERCENTILES
+                .iter()
+                .map(|p| format!("p{} {} ms", p, percentile(&costs, total, *p)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let timed_out: usize = costs
+            .iter()
+            .filter(|(cost, _)| *cost >= config.timeout)
+            .map(|(_, count)| count)
+            .sum();
+        if timed_out > 0 {
+            warnings.push(format!(
+                "{} of {} tasks ({:.2}%) cost at least the timeout of {} ms, they will fail",
+                timed_out,
+                total,
+                100. * timed_out as f64 / total as f64,
+                config.timeout
+            ));
+        }
+    }
+
+    println!("Workers:");
+    if config.engine == Engine::Des {
+        println!("  simulated by --engine des: the queueing in virtual time, nothing sleeps");
+    }
+    let (limit, what) = match (&config.mode, config.max_in_flight) {
+        (Mode::Sync(n_workers), _) => {
+            let queue = if config.stdin_tasks {
+                *n_workers
+            } else {
+                config.n_jobs
+            };
+            println!(
+                "  sync: {} threads, the tasks wait for them in a queue of {}",
+                n_workers, queue
+            );
+            if config.pin_threads {
+                if let Some(cores) = affinity::plan(*n_workers, config.cores.as_deref()) {
+                    let cores: Vec<String> = cores.iter().map(|core| core.to_string()).collect();
+                    println!("  pinned to the cores (by worker): {}", cores.join(", "));
+                }
+            }
+            if let Some(nice) = config.worker_nice {
+                println!("  at nice {}", nice);
+            }
+            if config.precise_sleep {
+                println!(
+                    "  precise sleeps: they spin for the end of each cost, calibrated at the start"
+                );
+            }
+            match config.stats_batch.filter(|_| config.engine == Engine::Real) {
+                Some(batch) => println!(
+                    "  the completions are delivered by {}, in batches of up to {}, flushed every {} ms",
+                    config.stats_channel.name(),
+                    batch.size,
+                    batch.flush_every
+                ),
+                None if config.engine == Engine::Real => println!(
+                    "  the completions are delivered by {}, one at a time",
+                    config.stats_channel.name()
+                ),
+                None => {}
+            }
+            match config.cancellable_work {
+                Some(granularity) => println!(
+                    "  cancellable work: timed out tasks are abandoned within {} ms",
+                    granularity
+                ),
+                None => {
+                    println!("  the timed out tasks keep the threads busy for their whole costs")
+                }
+            }
+            (Some(*n_workers), "threads")
+        }
+        (Mode::Async, Some(max_in_flight)) => {
+            println!(
+                "  async: a task per request, at most {} in flight, the others wait for them",
+                max_in_flight
+            );
+            (Some(max_in_flight), "tasks in flight")
+        }
+        (Mode::Async, None) => {
+            println!("  async: a task per request, unlimited");
+            (None, "tasks in flight")
+        }
+    };
+    if config.background_load > 0 {
+        println!(
+            "  background load: {} busy threads at nice {}",
+            config.background_load,
+            priority::BACKGROUND_NICE
+        );
+    }
+    println!(
+        "  target: {:?}, timeout: {} ms",
+        config.target, config.timeout
+    );
+
+    if !config.stdin_tasks && total > 0 {
+        // only the cancellable work leaves a task at the timeout
+        let occupancy = costs
+            .iter()
+            .map(|(cost, count)| {
+                let cost = match config.cancellable_work {
+                    Some(_) => (*cost).min(config.timeout),
+                    None => *cost,
+                };
+                cost as f64 * *count as f64
+            })
+            .sum::<f64>()
+            / total as f64;
+        // Little's law: the mean number in the system is the rate times the mean time in it
+        let concurrency = config.rps as f64 * occupancy / 1000.;
+        println!("Expected:");
+        println!(
+            "  concurrency: {:.1} {} (Little's law: {} rps x {:.3} ms)",
+            concurrency, what, config.rps, occupancy
+        );
+        let send = config.n_jobs as f64 / config.rps as f64;
+        let max_cost = costs
+            .last()
+            .map_or(0, |(cost, _)| match config.cancellable_work {
+                Some(_) => (*cost).min(config.timeout),
+                None => *cost,
+            }) as f64
+            / 1000.;
+        let served = match limit {
+            Some(limit) if concurrency > limit as f64 => {
+                let capacity = limit as f64 * 1000. / occupancy;
+                warnings.push(format!(
+                    "the service saturates: {:.1} {} are needed, but there are only {}, \
+                     so it serves {:.0} rps at most and the queue grows by {:.0} per second",
+                    concurrency,
+                    what,
+                    limit,
+                    capacity,
+                    config.rps as f64 - capacity
+                ));
+                config.n_jobs as f64 / capacity
+            }
+            _ => send,
+        };
+        let duration = served.max(send) + max_cost;
+        println!(
+            "  duration: {:.3}s, the send phase {:.3}s and the drain phase {:.3}s",
+            duration,
+            send,
+            duration - send
+        );
+    }
+
+    println!("Outputs:");
+    for file in outputs(config) {
+        println!("  {}", file);
+    }
+    for warning in &warnings {
+        println!("Warning: {}", warning);
+    }
+}
+
+/// The files the run would write, in the current directory, unless their paths are set.
+fn outputs(config: &ModelConfig) -> Vec<String> {
+    let name = &config.name;
+    let mut files: Vec<String> = config
+        .plots
+        .iter()
+        .map(|plot| {
+            let file = plot.file_name(name);
+            match config.backend {
+                Backend::Gnuplot => format!("{} (with its .dat and .gp)", file),
+                _ => file,
+            }
+        })
+        .collect();
+    files.push(RunMetadata::path(name).display().to_string());
+    if config.checkpoint_every.is_some() {
+        files.push(Checkpoint::path(name).display().to_string());
+    }
+    files.extend(config.export_timeseries.iter().cloned());
+    files.extend(config.chrome_trace.iter().cloned());
+    if let Some(path) = &config.sqlite {
+        files.push(format!("{} (appended)", path));
+    }
+    if config.profile {
+        files.push(format!("flamegraph_{}.svg", name));
+    }
+    if let Some(dir) = &config.spill_dir {
+        files.push(format!(
+            "{} (the spill of the records, {} bytes per request, removed after the run)",
+            Path::new(dir)
+                .join(format!("cdb_spill_{}_<pid>", name))
+                .display(),
+            spill::RECORD_SIZE
+        ));
+    }
+    files
+}
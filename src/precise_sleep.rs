@@ -0,0 +1,87 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the short sleeps of the calibration
+const ROUNDS: u32 = 20;
+const PROBE: Duration = Duration::from_millis(1);
+// the spin is never shorter, as the overshoot varies, nor longer, not to burn a core on a coarse timer
+const MIN_MARGIN: Duration = Duration::from_micros(100);
+const MAX_MARGIN: Duration = Duration::from_millis(5);
+// the costs of the check of the calibration, ms
+const CHECKED: [u64; 5] = [1, 2, 3, 4, 5];
+
+/// How a sync worker sleeps for the cost of a task.
+#[derive(Debug, Clone, Copy)]
+pub enum Sleep {
+    /// `thread::sleep`, which overshoots by the granularity of the OS timer.
+    Os,
+    /// Sleeps for the bulk of the duration, and spins for the last `margin`, yielding the core.
+    Precise { margin: Duration },
+}
+
+impl Sleep {
+    /// The margin is the largest overshoot of a short sleep, so the sleep itself never overshoots the deadline.
+    pub fn calibrate() -> Self {
+        let overshoot = (0..ROUNDS)
+            .map(|_| {
+                let start = Instant::now();
+                thread::sleep(PROBE);
+                start.elapsed().saturating_sub(PROBE)
+            })
+            .max()
+            .unwrap_or_default();
+        Sleep::Precise {
+            margin: overshoot.clamp(MIN_MARGIN, MAX_MARGIN),
+        }
+    }
+
+    pub fn sleep(self, duration: Duration) {
+        match self {
+            Sleep::Os => thread::sleep(duration),
+            Sleep::Precise { margin } => {
+                let deadline = Instant::now() + duration;
+                if duration > margin {
+                    thread::sleep(duration - margin);
+                }
+                while Instant::now() < deadline {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    /// (the mean, the max) of the relative error of the sleeps of 1 to 5 ms, e.g. `0.01` is 1%.
+    pub fn check(self) -> (f64, f64) {
+        let errors: Vec<f64> = CHECKED
+            .iter()
+            .map(|ms| {
+                let duration = Duration::from_millis(*ms);
+                let start = Instant::now();
+                self.sleep(duration);
+                let elapsed = start.elapsed().as_secs_f64();
+                (elapsed - duration.as_secs_f64()).abs() / duration.as_secs_f64()
+            })
+            .collect();
+        let max = errors.iter().cloned().fold(0., f64::max);
+        (errors.iter().sum::<f64>() / errors.len() as f64, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // it's timing, which a loaded machine skews: `cargo test -- --ignored precise`
+    #[ignore]
+    #[test]
+    fn precise_sleeps_of_1_to_5ms_are_within_10_percent() {
+        let sleep = Sleep::calibrate();
+        match sleep {
+            Sleep::Precise { margin } => assert!((MIN_MARGIN..=MAX_MARGIN).contains(&margin)),
+            Sleep::Os => unreachable!("The calibration is of the precise sleep"),
+        }
+        let (mean, max) = sleep.check();
+        assert!(mean <= max);
+        assert!(max < 0.1, "The sleeps are off by {:.2}% max", max * 100.);
+    }
+}
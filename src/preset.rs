@@ -0,0 +1,64 @@
+/// A curated scenario of `--preset`: the latencies, the rate and the duration of a run,
+/// the options of the command line override them.
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// See `--latency`.
+    pub latency: &'static str,
+    /// rps
+    pub rate: usize,
+    /// s, the number of the requests is the rate times the duration.
+    pub duration: usize,
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "slow-db",
+        description: "9 of 10 queries take 20 ms, the 10th one hangs for 30 s, e.g. on a lock: \
+                      the hanging ones take up the sync threads, the comparison of the article",
+        latency: "20ms*9,30s",
+        rate: 1000,
+        duration: 30,
+    },
+    Preset {
+        name: "spiky-cache",
+        description:
+            "98% of the requests are cache hits of 1 ms, the misses go to the origin for 200 ms",
+        latency: "1ms*49,200ms",
+        rate: 2000,
+        duration: 30,
+    },
+    Preset {
+        name: "fanout-backend",
+        description: "A request waits for the slowest of its fanned out calls: mostly 10 ms, \
+                      with a tail of 50 ms and 300 ms",
+        latency: "10ms*8,50ms,300ms",
+        rate: 1000,
+        duration: 30,
+    },
+    Preset {
+        name: "uniform-fast",
+        description: "Every request takes 10 ms, the baseline both modes keep up with",
+        latency: "10ms",
+        rate: 1000,
+        duration: 10,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+/// A row per preset with its values and its description.
+pub fn print() {
+    println!(
+        "{:<16}  {:<20}  {:>6}  {:>8}  description",
+        "preset", "latency", "rate", "duration"
+    );
+    for preset in PRESETS {
+        println!(
+            "{:<16}  {:<20}  {:>6}  {:>7}s  {}",
+            preset.name, preset.latency, preset.rate, preset.duration, preset.description
+        );
+    }
+}
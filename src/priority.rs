@@ -0,0 +1,82 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use tracing::{info, warn};
+
+/// The nice value of the busy threads of `--background-load`, the lowest priority.
+pub const BACKGROUND_NICE: i32 = 19;
+
+/// Sets the nice value of the current thread, on Linux every thread has one of its own.
+/// Lowering it, i.e. raising the priority, needs root or `CAP_SYS_NICE`.
+#[cfg(target_os = "linux")]
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: neither call has preconditions, `setpriority` of the id of a thread sets its own value
+    let result = unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, tid, nice)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// The priority of a thread can't be set apart from the one of its process.
+#[cfg(not(target_os = "linux"))]
+pub fn set_nice(_nice: i32) -> io::Result<()> {
+    Err(io::Error::other(
+        "the priorities of the threads are only supported on Linux",
+    ))
+}
+
+/// Checks the nice value can be set, on a thread of its own, so the value of the current one is kept.
+pub fn probe(nice: i32) -> io::Result<()> {
+    thread::spawn(move || set_nice(nice))
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("the probe panicked")))
+}
+
+/// The busy-spinning threads of `--background-load`, at the lowest priority,
+/// to model a service competing with background work for the CPU.
+pub struct BackgroundLoad {
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundLoad {
+    pub fn start(n_threads: usize) -> Self {
+        if let Err(e) = probe(BACKGROUND_NICE) {
+            warn!(
+                "Failed to lower the priority of the background load: {}, it competes as an equal",
+                e
+            );
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let threads = (0..n_threads)
+            .map(|_| {
+                let stop = stop.clone();
+                thread::spawn(move || {
+                    // the failure is reported by the probe
+                    set_nice(BACKGROUND_NICE).unwrap_or_default();
+                    while !stop.load(Ordering::Relaxed) {
+                        std::hint::spin_loop();
+                    }
+                })
+            })
+            .collect();
+        info!(
+            "Started {} busy threads of background load at nice {}",
+            n_threads, BACKGROUND_NICE
+        );
+        Self { stop, threads }
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads {
+            thread.join().expect("Background load failed");
+        }
+    }
+}
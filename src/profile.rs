@@ -0,0 +1,35 @@
+use pprof::ProfilerGuard;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use tracing::info;
+
+// the stacks are sampled this many times a second
+const FREQUENCY: i32 = 100;
+
+/// The CPU profiler of `--profile`: it samples the stacks of all the threads of the process,
+/// i.e. the generator, the workers and the runtime, by `SIGPROF`.
+pub struct Profiler {
+    guard: ProfilerGuard<'static>,
+}
+
+impl Profiler {
+    pub fn start() -> io::Result<Self> {
+        let guard = ProfilerGuard::new(FREQUENCY).map_err(other)?;
+        info!("Profiling the run at {} Hz", FREQUENCY);
+        Ok(Self { guard })
+    }
+
+    /// Stops sampling and writes the flamegraph of the samples to `path`, e.g. `flamegraph_{name}.svg`.
+    pub fn finish(self, path: &Path) -> io::Result<()> {
+        let report = self.guard.report().build().map_err(other)?;
+        drop(self.guard);
+        report.flamegraph(File::create(path)?).map_err(other)?;
+        info!("The flamegraph is written to {}", path.display());
+        Ok(())
+    }
+}
+
+fn other(e: pprof::Error) -> io::Error {
+    io::Error::other(e)
+}
@@ -0,0 +1,70 @@
+use crate::aggregator::Snapshot;
+use crate::console::Console;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::time::Duration;
+
+// while sending, the ETA is known from the configured rate
+const SEND_TEMPLATE: &str = "{prefix:>9} [{elapsed_precise}] [{bar:40}] {pos}/{len} ETA {msg}";
+// while draining, it's estimated from the completion rate
+const DRAIN_TEMPLATE: &str = "{prefix:>9} [{elapsed_precise}] [{bar:40}] {pos}/{len} ETA {eta}";
+
+/// A progress bar for the send and the drain phases of the run.
+/// Requests sent vs `n_jobs` while sending, completed vs sent while draining.
+pub struct Progress {
+    bar: ProgressBar,
+    console: Console,
+    n_jobs: usize,
+    rps: usize,
+    draining: bool,
+}
+
+impl Progress {
+    /// Returns `None` if stdout is not a TTY.
+    pub fn new(console: Console, n_jobs: usize, rps: usize) -> Option<Self> {
+        let bar = ProgressBar::with_draw_target(n_jobs as u64, ProgressDrawTarget::stdout());
+        if bar.is_hidden() {
+            return None;
+        }
+        bar.set_style(style(SEND_TEMPLATE));
+        bar.set_prefix("Sending");
+        console.attach(bar.clone());
+        Some(Self {
+            bar,
+            console,
+            n_jobs,
+            rps,
+            draining: false,
+        })
+    }
+
+    pub fn update(&mut self, snapshot: &Snapshot) {
+        if snapshot.sent < self.n_jobs {
+            let remaining = (self.n_jobs - snapshot.sent) as f64 / self.rps as f64;
+            self.bar.set_position(snapshot.sent as u64);
+            self.bar.set_message(
+                &humantime::format_duration(Duration::from_secs(remaining.ceil() as u64))
+                    .to_string(),
+            );
+        } else {
+            if !self.draining {
+                self.draining = true;
+                self.bar.set_style(style(DRAIN_TEMPLATE));
+                self.bar.set_prefix("Draining");
+                self.bar.set_length(snapshot.sent as u64);
+                self.bar.reset_eta();
+            }
+            self.bar.set_position(snapshot.completed as u64);
+        }
+    }
+
+    pub fn finish(&self) {
+        self.console.detach();
+        self.bar.finish_and_clear();
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(template)
+        .progress_chars("=> ")
+}
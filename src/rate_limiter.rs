@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+use tokio::time::delay_for;
+
+/// (the amount, the interval in ms) of the refills of the rate limiter: the finest interval of at least
+/// 10 ms, which `rps` divides, e.g. 1 per 10 ms for 100 rps, so the tasks are sent evenly.
+pub fn refill(rps: usize) -> (usize, u64) {
+    let mut duration_ms = 1000;
+    let mut refill = rps;
+    while duration_ms > 10 && refill.is_multiple_of(10) {
+        duration_ms /= 10;
+        refill /= 10;
+    }
+    (refill, duration_ms)
+}
+
+/// A leaky bucket of `refill` permits every `interval`. It starts full and holds a refill at most,
+/// so the permits missed by a late consumer aren't sent in a burst, i.e. the task `i` is sent
+/// at `interval * (i / refill)` at the earliest, the same as the arrivals of `--engine des`.
+/// It has a single consumer, the send loop, so it needs no locks.
+pub struct RateLimiter {
+    refill: usize,
+    interval: Duration,
+    /// The permits left of the current refill.
+    permits: usize,
+    /// When the next refill is due.
+    next_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(refill: usize, interval: Duration) -> Self {
+        assert!(
+            refill > 0 && interval > Duration::from_secs(0),
+            "A rate limiter needs a positive refill and interval"
+        );
+        Self {
+            refill,
+            interval,
+            permits: refill,
+            next_refill: Instant::now() + interval,
+        }
+    }
+
+    /// Waits for the next permit. A cancelled wait takes none.
+    pub async fn acquire_one(&mut self) {
+        if self.permits == 0 {
+            let now = Instant::now();
+            if self.next_refill > now {
+                delay_for(self.next_refill - now).await;
+            }
+            // the refills missed by a late consumer are dropped
+            let late = Instant::now().duration_since(self.next_refill);
+            let missed = (late.as_nanos() / self.interval.as_nanos()) as u32;
+            self.next_refill += self.interval * (missed + 1);
+            self.permits = self.refill;
+        }
+        self.permits -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // the module is shared with `benches/benchmarks_rate_limiter.rs`, which has no test harness,
+    // so the tests import within their bodies
+
+    #[test]
+    fn refills_at_the_finest_interval_of_10ms_or_more() {
+        assert_eq!(super::refill(100), (1, 10));
+        assert_eq!(super::refill(1_000), (10, 10));
+        assert_eq!(super::refill(100_000), (1_000, 10));
+        assert_eq!(super::refill(250), (25, 100));
+        assert_eq!(super::refill(7), (7, 1000));
+    }
+
+    #[test]
+    fn achieves_the_rate_within_the_tolerance() {
+        use std::time::{Duration, Instant};
+
+        // the achieved rate may be off the configured one by this fraction at most
+        const MAX_ERROR: f64 = 0.1;
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .expect("Failed to build the runtime");
+        for &rate in [1_000, 10_000].iter() {
+            let (refill, interval) = super::refill(rate);
+            let mut rate_limiter = super::RateLimiter::new(refill, Duration::from_millis(interval));
+            let start = Instant::now();
+            runtime.block_on(async {
+                for _ in 0..rate {
+                    rate_limiter.acquire_one().await;
+                }
+            });
+            // the first refill is there at the start
+            let achieved = (rate - refill) as f64 / start.elapsed().as_secs_f64();
+            let error = achieved / rate as f64 - 1.;
+            assert!(
+                error.abs() <= MAX_ERROR,
+                "{} permits/s achieved at {}/s, off by {:+.1}%",
+                achieved,
+                rate,
+                error * 100.
+            );
+        }
+    }
+}
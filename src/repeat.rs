@@ -0,0 +1,161 @@
+use crate::report::Chart;
+use crate::stats;
+use crate::sweep::Trial;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The mean and the standard deviation of a metric over the repeats.
+#[derive(Clone, Copy)]
+pub struct Spread {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl Spread {
+    fn of(values: &[f64]) -> Self {
+        let (mean, std_dev) = stats::mean_std_dev(values);
+        Self { mean, std_dev }
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        json!({"mean": self.mean, "std_dev": self.std_dev})
+    }
+}
+
+/// The summaries of the repeats of a run, aggregated.
+pub struct Aggregate {
+    pub repeats: usize,
+    /// (percentile, latency in ms) of the summary.
+    pub percentiles: Vec<(f64, Spread)>,
+    /// See `Run::achieved_rate`.
+    pub achieved_rate: Spread,
+    /// %
+    pub error_rate: Spread,
+}
+
+impl Aggregate {
+    /// The percentiles of the first repeat, which are the same for all of them.
+    pub fn new(trials: &[Trial]) -> Self {
+        let percentiles = trials
+            .first()
+            .map(|trial| trial.run.percentiles.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|(p, _)| {
+                let latencies: Vec<f64> = trials
+                    .iter()
+                    .filter_map(|trial| trial.run.percentile(*p))
+                    .collect();
+                (*p, Spread::of(&latencies))
+            })
+            .collect();
+        let rates: Vec<f64> = trials
+            .iter()
+            .filter_map(|trial| trial.run.achieved_rate())
+            .collect();
+        let errors: Vec<f64> = trials.iter().map(|trial| trial.run.error_rate()).collect();
+        Self {
+            repeats: trials.len(),
+            percentiles,
+            achieved_rate: Spread::of(&rates),
+            error_rate: Spread::of(&errors),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Repeats: {}", self.repeats);
+        for (p, latency) in &self.percentiles {
+            println!(
+                "p{:.3} - {:.3} ± {:.3} ms",
+                p, latency.mean, latency.std_dev
+            );
+        }
+        println!(
+            "Achieved rate: {:.3} ± {:.3}",
+            self.achieved_rate.mean, self.achieved_rate.std_dev
+        );
+        println!(
+            "Errors: {:.2}% ± {:.2}%",
+            self.error_rate.mean, self.error_rate.std_dev
+        );
+    }
+
+    /// A bar per percentile of its mean latency, with the standard deviation as the error bar.
+    /// The Y-axis is logarithmic, as the tail is orders of magnitude above the median.
+    pub fn chart(&self, file_name: String) -> Chart {
+        let x_ticks = self
+            .percentiles
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| (i as f64, format!("{}%", p)))
+            .collect();
+        let x = (0..self.percentiles.len()).map(|i| i as f64).collect();
+        let (y, errors) = self
+            .percentiles
+            .iter()
+            .map(|(_, latency)| (latency.mean, latency.std_dev))
+            .unzip();
+        Chart::new(file_name, "Percentile", "Latency, ms")
+            .x_ticks(x_ticks)
+            .log_y()
+            .bars(
+                &format!("mean of {} repeats", self.repeats),
+                x,
+                y,
+                errors,
+                0.8,
+            )
+    }
+
+    /// `repeats`: a section per repeat, with its seed, `aggregate`: the mean
+    /// and the standard deviation of every metric, and the `tags` of the run.
+    pub fn write_json(
+        &self,
+        path: &Path,
+        trials: &[Trial],
+        seeds: &[u64],
+        tags: &BTreeMap<String, String>,
+    ) -> io::Result<()> {
+        let repeats: Vec<serde_json::Value> = trials
+            .iter()
+            .map(|trial| {
+                json!({
+                    "repeat": trial.value,
+                    "seed": seeds[trial.value - 1],
+                    "start_time": trial.run.start_time,
+                    "completed": trial.run.completed,
+                    "failed": trial.run.failed,
+                    "achieved_rate": trial.run.achieved_rate(),
+                    "avg_rate": trial.run.avg_rate,
+                    "percentiles": trial
+                        .run
+                        .percentiles
+                        .iter()
+                        .map(|(p, latency)| json!({"percentile": p, "latency_ms": latency}))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let aggregate = json!({
+            "repeats": self.repeats,
+            "achieved_rate": self.achieved_rate.to_json(),
+            "error_rate": self.error_rate.to_json(),
+            "percentiles": self
+                .percentiles
+                .iter()
+                .map(|(p, latency)| json!({"percentile": p, "latency_ms": latency.to_json()}))
+                .collect::<Vec<_>>(),
+        });
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(
+            &mut out,
+            &json!({"repeats": repeats, "aggregate": aggregate, "tags": tags}),
+        )?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
@@ -0,0 +1,271 @@
+use crate::report::{format_value, Chart, Reporter, Series};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+const MAX_HORIZONTAL_TICKS: usize = 10;
+// the colors of the series, the same as the default ones of matplotlib
+const COLORS: [&str; 10] = [
+    "1f77b4", "ff7f0e", "2ca02c", "d62728", "9467bd", "8c564b", "e377c2", "7f7f7f", "bcbd22",
+    "17becf",
+];
+// the diameter of a point of `pointsize 1`, pt
+const POINT_SIZE: f64 = 6.;
+
+/// Renders charts with `gnuplot`: for every figure it writes the data file (`.dat`)
+/// and the script (`.gp`) next to the PNG, and then runs the script.
+pub struct GnuplotReporter {
+    gnuplot: PathBuf,
+}
+
+impl GnuplotReporter {
+    pub fn new(gnuplot: PathBuf) -> Self {
+        Self { gnuplot }
+    }
+
+    /// Every series is a separate data block, addressed by `index` in the script.
+    fn data(chart: &Chart) -> String {
+        let mut data = String::new();
+        for series in &chart.series {
+            match series {
+                Series::Line { x, y, .. } | Series::Points { x, y, .. } => {
+                    for (x, y) in x.iter().zip(y) {
+                        data.push_str(&format!("{} {}\n", format_value(*x), format_value(*y)));
+                    }
+                }
+                Series::Bars {
+                    x,
+                    y,
+                    errors,
+                    width,
+                    ..
+                } => {
+                    for ((x, y), error) in x.iter().zip(y).zip(errors) {
+                        data.push_str(&format!(
+                            "{} {} {} {}\n",
+                            format_value(*x),
+                            format_value(*y),
+                            format_value(*error),
+                            format_value(*width)
+                        ));
+                    }
+                }
+                Series::Boxes { x, boxes, width } => {
+                    for (x, [low, q1, median, q3, high]) in x.iter().zip(boxes) {
+                        data.push_str(&format!(
+                            "{} {} {} {} {} {} {}\n",
+                            format_value(*x),
+                            format_value(*low),
+                            format_value(*q1),
+                            format_value(*median),
+                            format_value(*q3),
+                            format_value(*high),
+                            format_value(*width)
+                        ));
+                    }
+                }
+                Series::Heatmap {
+                    x_edges,
+                    y_edges,
+                    counts,
+                    ..
+                } => {
+                    // a box per non-empty cell: its center, its edges and its count
+                    for (i, column) in counts.iter().enumerate() {
+                        for (j, count) in column.iter().enumerate().filter(|(_, c)| **c > 0) {
+                            data.push_str(&format!(
+                                "{} {} {} {} {} {} {}\n",
+                                format_value((x_edges[i] + x_edges[i + 1]) / 2.),
+                                format_value((y_edges[j] + y_edges[j + 1]) / 2.),
+                                format_value(x_edges[i]),
+                                format_value(x_edges[i + 1]),
+                                format_value(y_edges[j]),
+                                format_value(y_edges[j + 1]),
+                                count
+                            ));
+                        }
+                    }
+                }
+                Series::Histogram { edges, counts } => {
+                    for (i, count) in counts.iter().enumerate() {
+                        let center = (edges[i] + edges[i + 1]) / 2.;
+                        let width = edges[i + 1] - edges[i];
+                        data.push_str(&format!(
+                            "{} {} {}\n",
+                            format_value(center),
+                            format_value(width),
+                            count
+                        ));
+                    }
+                }
+            }
+            data.push_str("\n\n");
+        }
+        data
+    }
+
+    fn script(chart: &Chart, data_path: &str) -> String {
+        let mut script = String::new();
+        // translucent points need the alpha channel
+        let translucent = chart
+            .series
+            .iter()
+            .any(|s| matches!(s, Series::Points { alpha, .. } if *alpha < 1.));
+        script.push_str(if translucent {
+            "set terminal png truecolor size 640,480\n"
+        } else {
+            "set terminal png size 640,480\n"
+        });
+        script.push_str(&format!(
+            "set output {}\n",
+            gp_str(&chart.path.to_string_lossy())
+        ));
+        script.push_str(&format!("set xlabel {}\n", gp_str(&chart.x_label)));
+        script.push_str(&format!("set ylabel {}\n", gp_str(&chart.y_label)));
+        script.push_str("set style fill solid 0.5\n");
+        if chart.log_y {
+            script.push_str("set logscale y\n");
+        }
+        if !chart.x_ticks.is_empty() {
+            let ticks: Vec<String> = chart
+                .x_ticks
+                .iter()
+                // explicit tic labels are format strings in gnuplot
+                .map(|(x, label)| {
+                    format!("{} {}", gp_str(&label.replace('%', "%%")), format_value(*x))
+                })
+                .collect();
+            script.push_str(&format!("set xtics ({})\n", ticks.join(", ")));
+            // long lists of labels would overlap
+            if ticks.len() > MAX_HORIZONTAL_TICKS {
+                script.push_str("set xtics rotate by 45 right\n");
+            }
+        }
+        for (from, to) in &chart.shades {
+            script.push_str(&format!(
+                "set object rect from {}, graph 0 to {}, graph 1 behind fc rgb 'grey' fs transparent solid 0.2 noborder\n",
+                format_value(*from),
+                format_value(*to)
+            ));
+        }
+        for (x, y, text) in &chart.annotations {
+            script.push_str(&format!(
+                "set label {} at {}, {} offset 1,-1\n",
+                gp_str(text),
+                format_value(*x),
+                format_value(*y)
+            ));
+        }
+        for series in &chart.series {
+            if let Series::Heatmap { label, .. } = series {
+                script.push_str("set logscale cb\n");
+                script.push_str(&format!("set cblabel {}\n", gp_str(label)));
+            }
+        }
+        if let Some(y2_label) = &chart.y2_label {
+            script.push_str(&format!("set y2label {}\n", gp_str(y2_label)));
+            script.push_str("set ytics nomirror\n");
+            script.push_str("set y2tics\n");
+        }
+        if !chart.has_labels() {
+            script.push_str("unset key\n");
+        }
+        let plots: Vec<String> = chart
+            .series
+            .iter()
+            .enumerate()
+            .map(|(i, series)| match series {
+                Series::Line { label, y2, .. } => format!(
+                    "{} index {} using 1:2 axes {} with lines title {}",
+                    gp_str(data_path),
+                    i,
+                    if *y2 { "x1y2" } else { "x1y1" },
+                    gp_str(label.as_deref().unwrap_or(""))
+                ),
+                Series::Points {
+                    label, size, alpha, ..
+                } => format!(
+                    // the alpha of gnuplot is the transparency, from 00 (opaque) to ff
+                    "{} index {} using 1:2 with points pt 7 ps {} lc rgb '#{:02x}{}' title {}",
+                    gp_str(data_path),
+                    i,
+                    format_value(size / POINT_SIZE),
+                    ((1. - alpha.clamp(0., 1.)) * 255.).round() as u8,
+                    COLORS[i % COLORS.len()],
+                    gp_str(label.as_deref().unwrap_or(""))
+                ),
+                Series::Bars { label, .. } => format!(
+                    "{} index {} using 1:2:3:4 with boxerrorbars title {}",
+                    gp_str(data_path),
+                    i,
+                    gp_str(label)
+                ),
+                // the columns of candlesticks are x:box_min:whisker_min:whisker_max:box_high,
+                // the median is a degenerate box on top
+                Series::Boxes { .. } => format!(
+                    "{0} index {1} using 1:3:2:6:5:7 with candlesticks lc rgb '#{2}' whiskerbars notitle, \\\n     \
+                     {0} index {1} using 1:4:4:4:4:7 with candlesticks lc rgb 'black' notitle",
+                    gp_str(data_path),
+                    i,
+                    COLORS[i % COLORS.len()]
+                ),
+                Series::Heatmap { .. } => format!(
+                    "{} index {} using 1:2:3:4:5:6:7 with boxxyerror fs solid 1.0 noborder lc palette notitle",
+                    gp_str(data_path),
+                    i
+                ),
+                Series::Histogram { .. } => format!(
+                    "{} index {} using 1:3:2 with boxes notitle",
+                    gp_str(data_path),
+                    i
+                ),
+            })
+            .collect();
+        script.push_str(&format!("plot {}\n", plots.join(", \\\n     ")));
+        script
+    }
+}
+
+impl Reporter for GnuplotReporter {
+    fn render(&self, chart: &Chart) -> io::Result<()> {
+        let data_path = chart.path.with_extension("dat");
+        let script_path = chart.path.with_extension("gp");
+        fs::write(&data_path, Self::data(chart))?;
+        fs::write(
+            &script_path,
+            Self::script(chart, &data_path.to_string_lossy()),
+        )?;
+
+        let output = Command::new(&self.gnuplot).arg(&script_path).output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "gnuplot exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+}
+
+fn gp_str(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::{assert_golden, golden_charts};
+
+    #[test]
+    fn the_scripts_are_the_golden_ones() {
+        for chart in golden_charts() {
+            let data_path = chart.path.with_extension("dat");
+            let script = GnuplotReporter::script(&chart, &data_path.to_string_lossy());
+            let name = chart.path.with_extension("gp");
+            assert_golden(&name.file_name().unwrap().to_string_lossy(), &script);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+set terminal png size 640,480
+set output './golden_distribution.png'
+set xlabel 'Latency, ms'
+set ylabel 'Count'
+set style fill solid 0.5
+set xtics ('t0' 0, 't1' 1, 't2' 2, 't3' 3, 't4' 4, 't5' 5, 't6' 6, 't7' 7, 't8' 8, 't9' 9, 't10' 10, 't11' 11)
+set xtics rotate by 45 right
+set logscale cb
+set cblabel 'Requests'
+plot './golden_distribution.dat' index 0 using 1:3:2 with boxes notitle, \
+     './golden_distribution.dat' index 1 using 1:3:2:6:5:7 with candlesticks lc rgb '#ff7f0e' whiskerbars notitle, \
+     './golden_distribution.dat' index 1 using 1:4:4:4:4:7 with candlesticks lc rgb 'black' notitle, \
+     './golden_distribution.dat' index 2 using 1:2:3:4 with boxerrorbars title 'Rate', \
+     './golden_distribution.dat' index 3 using 1:2:3:4:5:6:7 with boxxyerror fs solid 1.0 noborder lc palette notitle
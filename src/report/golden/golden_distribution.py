@@ -0,0 +1,19 @@
+from math import nan
+import matplotlib
+matplotlib.use('agg')
+import matplotlib.pyplot as plt
+fig, ax = plt.subplots()
+ax.hist([0, 2, 4, 6], bins=[0, 2, 4, 6, 8], weights=[1, 2, 1, 0])
+ax.bxp([{'whislo': 1, 'q1': 2, 'med': 3, 'q3': 4, 'whishi': 5}, {'whislo': 2, 'q1': 3, 'med': 4, 'q3': 5, 'whishi': 6}], positions=[1, 2], widths=0.6, showfliers=False, manage_ticks=False)
+ax.bar([1, 2], [100, 200], width=0.8, yerr=[5, 10], capsize=2, color='C2', label='Rate')
+from matplotlib.colors import LogNorm
+mesh = ax.pcolormesh([0, 1, 2], [0, 10, 20], [[1, 3], [0, 10]], norm=LogNorm(), shading='flat')
+fig.colorbar(mesh, ax=ax, label='Requests')
+ax.set_xlabel('Latency, ms')
+ax.set_ylabel('Count')
+ax.set_xticks([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11])
+ax.set_xticklabels(['t0', 't1', 't2', 't3', 't4', 't5', 't6', 't7', 't8', 't9', 't10', 't11'], rotation=45, ha='right')
+fig.set_size_inches(6.4, 4.8)
+fig.tight_layout()
+ax.legend()
+fig.savefig('./golden_distribution.png')
@@ -0,0 +1,16 @@
+set terminal png truecolor size 640,480
+set output './golden_latency.png'
+set xlabel 'Time, s'
+set ylabel 'Latency, ms'
+set style fill solid 0.5
+set logscale y
+set xtics ('0%%' 0, 'it''s 1' 1)
+set object rect from 0, graph 0 to 0.5, graph 1 behind fc rgb 'grey' fs transparent solid 0.2 noborder
+set label 'p99: 20 ms' at 2, 20 offset 1,-1
+set y2label 'Requests/s'
+set ytics nomirror
+set y2tics
+plot './golden_latency.dat' index 0 using 1:2 axes x1y1 with lines title 'p50', \
+     './golden_latency.dat' index 1 using 1:2 with points pt 7 ps 1 lc rgb '#00ff7f0e' title 'p99', \
+     './golden_latency.dat' index 2 using 1:2 with points pt 7 ps 0.3333333333333333 lc rgb '#b32ca02c' title '', \
+     './golden_latency.dat' index 3 using 1:2 axes x1y2 with lines title 'Rate'
@@ -0,0 +1,21 @@
+from math import nan
+import matplotlib
+matplotlib.use('agg')
+import matplotlib.pyplot as plt
+fig, ax = plt.subplots()
+ax2 = ax.twinx()
+ax2.set_ylabel('Requests/s')
+ax.axvspan(0, 0.5, color='grey', alpha=0.2)
+ax.plot([0, 1, 2], [1.5, nan, 2], color='C0', label='p50')
+ax.scatter([0, 2], [10, 20], s=36, alpha=1, linewidths=0, color='C1', zorder=3, label='p99')
+ax.scatter([0.25, 1.75], [3, 4], s=4, alpha=0.3, linewidths=0, color='C2', zorder=3)
+ax2.plot([0, 1, 2], [100, 90, 110], color='C3', label='Rate')
+ax.annotate('p99: 20 ms', (2, 20), textcoords='offset points', xytext=(5, -10))
+ax.set_xlabel('Time, s')
+ax.set_ylabel('Latency, ms')
+ax.set_yscale('log')
+ax.set_xticks([0, 1])
+ax.set_xticklabels(['0%', 'it\'s 1'])
+lines = ax.get_lines() + ax2.get_lines()
+ax.legend(lines, [l.get_label() for l in lines])
+fig.savefig('./golden_latency.png')
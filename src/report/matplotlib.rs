@@ -0,0 +1,273 @@
+use crate::report::{format_value, Chart, Reporter, Series};
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const MAX_HORIZONTAL_TICKS: usize = 10;
+
+/// Renders charts by piping a generated script into `python3` with `matplotlib`.
+pub struct MatplotlibReporter {
+    python_path: String,
+}
+
+impl MatplotlibReporter {
+    pub fn new(python_path: String) -> Self {
+        Self { python_path }
+    }
+
+    /// Makes sure the interpreter runs and has `matplotlib`.
+    pub fn check(&self) -> Result<(), String> {
+        let output = Command::new(&self.python_path)
+            .arg("-c")
+            .arg("import matplotlib")
+            .output()
+            .map_err(|e| format!("Can't run `{}`: {}", self.python_path, e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{}` can't import matplotlib: {}",
+                self.python_path,
+                // the last line of the traceback, e.g. `ModuleNotFoundError: ...`
+                String::from_utf8_lossy(&output.stderr)
+                    .trim()
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+            ))
+        }
+    }
+
+    fn script(chart: &Chart) -> String {
+        let mut script = String::new();
+        script.push_str("from math import nan\n");
+        script.push_str("import matplotlib\n");
+        script.push_str("matplotlib.use('agg')\n");
+        script.push_str("import matplotlib.pyplot as plt\n");
+        script.push_str("fig, ax = plt.subplots()\n");
+        if let Some(y2_label) = &chart.y2_label {
+            script.push_str("ax2 = ax.twinx()\n");
+            script.push_str(&format!("ax2.set_ylabel({})\n", py_str(y2_label)));
+        }
+        for (from, to) in &chart.shades {
+            script.push_str(&format!(
+                "ax.axvspan({}, {}, color='grey', alpha=0.2)\n",
+                format_value(*from),
+                format_value(*to)
+            ));
+        }
+        for (i, series) in chart.series.iter().enumerate() {
+            match series {
+                Series::Line { label, x, y, y2 } => {
+                    // the color is explicit, as twin axes have independent color cycles
+                    script.push_str(&format!(
+                        "{}.plot({}, {}, color='C{}'",
+                        if *y2 { "ax2" } else { "ax" },
+                        py_list(x),
+                        py_list(y),
+                        i
+                    ));
+                    if let Some(label) = label {
+                        script.push_str(&format!(", label={}", py_str(label)));
+                    }
+                    script.push_str(")\n");
+                }
+                Series::Points {
+                    label,
+                    x,
+                    y,
+                    size,
+                    alpha,
+                } => {
+                    // the size of a marker is its area in pt^2, the edges would blur the small ones
+                    script.push_str(&format!(
+                        "ax.scatter({}, {}, s={}, alpha={}, linewidths=0, color='C{}', zorder=3",
+                        py_list(x),
+                        py_list(y),
+                        format_value(size * size),
+                        format_value(*alpha),
+                        i
+                    ));
+                    if let Some(label) = label {
+                        script.push_str(&format!(", label={}", py_str(label)));
+                    }
+                    script.push_str(")\n");
+                }
+                Series::Bars {
+                    label,
+                    x,
+                    y,
+                    errors,
+                    width,
+                } => {
+                    script.push_str(&format!(
+                        "ax.bar({}, {}, width={}, yerr={}, capsize=2, color='C{}', label={})\n",
+                        py_list(x),
+                        py_list(y),
+                        format_value(*width),
+                        py_list(errors),
+                        i,
+                        py_str(label)
+                    ));
+                }
+                Series::Boxes { x, boxes, width } => {
+                    // the stats are pre-computed, so the boxes are drawn as they are, without the fliers
+                    let stats: Vec<String> = boxes
+                        .iter()
+                        .map(|[low, q1, median, q3, high]| {
+                            format!(
+                                "{{'whislo': {}, 'q1': {}, 'med': {}, 'q3': {}, 'whishi': {}}}",
+                                format_value(*low),
+                                format_value(*q1),
+                                format_value(*median),
+                                format_value(*q3),
+                                format_value(*high)
+                            )
+                        })
+                        .collect();
+                    script.push_str(&format!(
+                        "ax.bxp([{}], positions={}, widths={}, showfliers=False, manage_ticks=False)\n",
+                        stats.join(", "),
+                        py_list(x),
+                        format_value(*width)
+                    ));
+                }
+                Series::Heatmap {
+                    x_edges,
+                    y_edges,
+                    counts,
+                    label,
+                } => {
+                    // the rows of the mesh are the ones of Y, the zero counts are masked by the log scale
+                    let rows: Vec<String> = (0..y_edges.len() - 1)
+                        .map(|j| {
+                            let row: Vec<f64> =
+                                counts.iter().map(|column| column[j] as f64).collect();
+                            py_list(&row)
+                        })
+                        .collect();
+                    script.push_str("from matplotlib.colors import LogNorm\n");
+                    script.push_str(&format!(
+                        "mesh = ax.pcolormesh({}, {}, [{}], norm=LogNorm(), shading='flat')\n",
+                        py_list(x_edges),
+                        py_list(y_edges),
+                        rows.join(", ")
+                    ));
+                    script.push_str(&format!(
+                        "fig.colorbar(mesh, ax=ax, label={})\n",
+                        py_str(label)
+                    ));
+                }
+                Series::Histogram { edges, counts } => {
+                    let counts: Vec<f64> = counts.iter().map(|c| *c as f64).collect();
+                    script.push_str(&format!(
+                        "ax.hist({}, bins={}, weights={})\n",
+                        py_list(&edges[..edges.len() - 1]),
+                        py_list(edges),
+                        py_list(&counts),
+                    ));
+                }
+            }
+        }
+        for (x, y, text) in &chart.annotations {
+            script.push_str(&format!(
+                "ax.annotate({}, ({}, {}), textcoords='offset points', xytext=(5, -10))\n",
+                py_str(text),
+                format_value(*x),
+                format_value(*y)
+            ));
+        }
+        script.push_str(&format!("ax.set_xlabel({})\n", py_str(&chart.x_label)));
+        script.push_str(&format!("ax.set_ylabel({})\n", py_str(&chart.y_label)));
+        if chart.log_y {
+            script.push_str("ax.set_yscale('log')\n");
+        }
+        if !chart.x_ticks.is_empty() {
+            let positions: Vec<f64> = chart.x_ticks.iter().map(|(x, _)| *x).collect();
+            let labels: Vec<String> = chart.x_ticks.iter().map(|(_, l)| py_str(l)).collect();
+            script.push_str(&format!("ax.set_xticks({})\n", py_list(&positions)));
+            script.push_str(&format!(
+                "ax.set_xticklabels([{}]{})\n",
+                labels.join(", "),
+                // long lists of labels would overlap
+                if labels.len() > MAX_HORIZONTAL_TICKS {
+                    ", rotation=45, ha='right'"
+                } else {
+                    ""
+                }
+            ));
+            if labels.len() > MAX_HORIZONTAL_TICKS {
+                script.push_str(&format!(
+                    "fig.set_size_inches({:.1}, 4.8)\n",
+                    (0.4 * labels.len() as f64).max(6.4)
+                ));
+                script.push_str("fig.tight_layout()\n");
+            }
+        }
+        if chart.y2_label.is_some() {
+            // a single legend for the lines of both axes
+            script.push_str("lines = ax.get_lines() + ax2.get_lines()\n");
+            script.push_str("ax.legend(lines, [l.get_label() for l in lines])\n");
+        } else if chart.has_labels() {
+            script.push_str("ax.legend()\n");
+        }
+        script.push_str(&format!(
+            "fig.savefig({})\n",
+            py_str(&chart.path.to_string_lossy())
+        ));
+        script
+    }
+}
+
+impl Reporter for MatplotlibReporter {
+    fn render(&self, chart: &Chart) -> io::Result<()> {
+        let mut python = Command::new(&self.python_path)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        python
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(Self::script(chart).as_bytes())?;
+        let output = python.wait_with_output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}: {}",
+                self.python_path,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+}
+
+fn py_list(values: &[f64]) -> String {
+    let values: Vec<String> = values.iter().map(|v| format_value(*v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn py_str(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::{assert_golden, golden_charts};
+
+    #[test]
+    fn the_scripts_are_the_golden_ones() {
+        for chart in golden_charts() {
+            let name = chart.path.with_extension("py");
+            assert_golden(
+                &name.file_name().unwrap().to_string_lossy(),
+                &MatplotlibReporter::script(&chart),
+            );
+        }
+    }
+}
@@ -0,0 +1,604 @@
+mod gnuplot;
+mod matplotlib;
+
+use crate::Backend;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+pub use gnuplot::GnuplotReporter;
+pub use matplotlib::MatplotlibReporter;
+
+/// A backend-neutral description of a figure.
+/// All the data is prepared up front, so every backend renders the same content.
+pub struct Chart {
+    /// The resulting PNG file.
+    pub path: PathBuf,
+    pub x_label: String,
+    pub y_label: String,
+    /// The label of the secondary (right) Y-axis, if any series is plotted against it.
+    pub y2_label: Option<String>,
+    /// Custom tick labels on the X-axis, if set.
+    pub x_ticks: Vec<(f64, String)>,
+    /// Shaded X-ranges, e.g. the data excluded from the stats.
+    pub shades: Vec<(f64, f64)>,
+    /// The Y-axis is logarithmic.
+    pub log_y: bool,
+    pub series: Vec<Series>,
+    /// (x, y, text) labels of points of the series, e.g. the percentiles.
+    pub annotations: Vec<(f64, f64, String)>,
+}
+
+pub enum Series {
+    Line {
+        label: Option<String>,
+        x: Vec<f64>,
+        y: Vec<f64>,
+        /// Plotted against the secondary Y-axis.
+        y2: bool,
+    },
+    /// Markers at the points, e.g. the percentiles on a curve, or the raw samples.
+    Points {
+        label: Option<String>,
+        x: Vec<f64>,
+        y: Vec<f64>,
+        /// The diameter of a marker, pt.
+        size: f64,
+        /// The opacity of a marker, so the dense areas of many points stand out.
+        alpha: f64,
+    },
+    /// Pre-computed bins: `counts[i]` is the number of values within `[edges[i], edges[i + 1])`.
+    Histogram { edges: Vec<f64>, counts: Vec<u64> },
+    /// The counts of the cells of a grid: `counts[i][j]` is within `[x_edges[i], x_edges[i + 1])`
+    /// and `[y_edges[j], y_edges[j + 1])`. The colors are log-scaled, as explained by the colorbar of `label`,
+    /// the empty cells are blank.
+    Heatmap {
+        x_edges: Vec<f64>,
+        y_edges: Vec<f64>,
+        counts: Vec<Vec<u64>>,
+        label: String,
+    },
+    /// Box plots of `width` centered at `x`: `boxes[i]` is the (low whisker, lower quartile,
+    /// median, upper quartile, high whisker) of the `i`-th one.
+    Boxes {
+        x: Vec<f64>,
+        boxes: Vec<[f64; 5]>,
+        width: f64,
+    },
+    /// Bars of `width` centered at `x`, with error bars of `±errors`.
+    Bars {
+        label: String,
+        x: Vec<f64>,
+        y: Vec<f64>,
+        errors: Vec<f64>,
+        width: f64,
+    },
+}
+
+/// `bins` equal bins within `[min, max]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binning {
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Binning {
+    // protects from degenerate bin widths on heavy-tailed distributions
+    const MAX_AUTO_BINS: usize = 500;
+
+    /// The Freedman–Diaconis rule: the bin width is `2 * IQR / n^(1/3)`.
+    /// Falls back to the Sturges' rule if the IQR is zero (e.g. most values are the same).
+    /// `value(i)` is the `i`-th smallest of `n` values, `n` must be positive.
+    pub fn auto(n: usize, value: impl Fn(usize) -> f64, range: Option<(f64, f64)>) -> Self {
+        let (min, max) = range.unwrap_or_else(|| (value(0), value(n - 1)));
+        let iqr = value((n as f64 * 0.75) as usize) - value((n as f64 * 0.25) as usize);
+        let n = n as f64;
+        let bins = if iqr > 0. && max > min {
+            ((max - min) / (2. * iqr / n.cbrt())).ceil() as usize
+        } else {
+            n.log2().ceil() as usize + 1
+        };
+        Self {
+            bins: bins.clamp(1, Self::MAX_AUTO_BINS),
+            min,
+            max,
+        }
+    }
+
+    fn width(&self) -> f64 {
+        if self.max > self.min {
+            (self.max - self.min) / self.bins as f64
+        } else {
+            1.
+        }
+    }
+}
+
+pub trait Reporter {
+    fn render(&self, chart: &Chart) -> io::Result<()>;
+}
+
+impl Chart {
+    pub fn new(file_name: String, x_label: &str, y_label: &str) -> Self {
+        Self {
+            path: Path::new(".").join(file_name),
+            x_label: x_label.to_string(),
+            y_label: y_label.to_string(),
+            y2_label: None,
+            x_ticks: vec![],
+            shades: vec![],
+            log_y: false,
+            series: vec![],
+            annotations: vec![],
+        }
+    }
+
+    pub fn x_ticks(mut self, ticks: Vec<(f64, String)>) -> Self {
+        self.x_ticks = ticks;
+        self
+    }
+
+    pub fn shade(mut self, from: f64, to: f64) -> Self {
+        self.shades.push((from, to));
+        self
+    }
+
+    pub fn log_y(mut self) -> Self {
+        self.log_y = true;
+        self
+    }
+
+    pub fn line(mut self, label: Option<&str>, x: Vec<f64>, y: Vec<f64>) -> Self {
+        self.series.push(Series::Line {
+            label: label.map(|s| s.to_string()),
+            x,
+            y,
+            y2: false,
+        });
+        self
+    }
+
+    pub fn points(self, label: Option<&str>, x: Vec<f64>, y: Vec<f64>) -> Self {
+        self.scatter(label, x, y, 6., 1.)
+    }
+
+    /// Small translucent markers, e.g. of many samples.
+    pub fn scatter(
+        mut self,
+        label: Option<&str>,
+        x: Vec<f64>,
+        y: Vec<f64>,
+        size: f64,
+        alpha: f64,
+    ) -> Self {
+        self.series.push(Series::Points {
+            label: label.map(|s| s.to_string()),
+            x,
+            y,
+            size,
+            alpha,
+        });
+        self
+    }
+
+    /// Labels the point `(x, y)` with `text`.
+    pub fn annotate(mut self, x: f64, y: f64, text: String) -> Self {
+        self.annotations.push((x, y, text));
+        self
+    }
+
+    /// A line plotted against the secondary (right) Y-axis.
+    pub fn line_y2(mut self, label: &str, y2_label: &str, x: Vec<f64>, y: Vec<f64>) -> Self {
+        self.y2_label = Some(y2_label.to_string());
+        self.series.push(Series::Line {
+            label: Some(label.to_string()),
+            x,
+            y,
+            y2: true,
+        });
+        self
+    }
+
+    pub fn bars(
+        mut self,
+        label: &str,
+        x: Vec<f64>,
+        y: Vec<f64>,
+        errors: Vec<f64>,
+        width: f64,
+    ) -> Self {
+        self.series.push(Series::Bars {
+            label: label.to_string(),
+            x,
+            y,
+            errors,
+            width,
+        });
+        self
+    }
+
+    pub fn boxes(mut self, x: Vec<f64>, boxes: Vec<[f64; 5]>, width: f64) -> Self {
+        self.series.push(Series::Boxes { x, boxes, width });
+        self
+    }
+
+    pub fn heatmap(
+        mut self,
+        x_edges: Vec<f64>,
+        y_edges: Vec<f64>,
+        counts: Vec<Vec<u64>>,
+        label: &str,
+    ) -> Self {
+        self.series.push(Series::Heatmap {
+            x_edges,
+            y_edges,
+            counts,
+            label: label.to_string(),
+        });
+        self
+    }
+
+    /// Counts `values` into the bins, values outside of the binning range are ignored.
+    pub fn histogram(mut self, values: impl IntoIterator<Item = f64>, binning: &Binning) -> Self {
+        let width = binning.width();
+        let edges = (0..=binning.bins)
+            .map(|i| binning.min + width * i as f64)
+            .collect();
+        let mut counts = vec![0; binning.bins];
+        for v in values {
+            if v < binning.min || v > binning.max {
+                continue;
+            }
+            let bin = ((v - binning.min) / width) as usize;
+            // the last bin is closed, i.e. includes `max`
+            counts[bin.min(binning.bins - 1)] += 1;
+        }
+        self.series.push(Series::Histogram { edges, counts });
+        self
+    }
+
+    pub fn has_labels(&self) -> bool {
+        self.series.iter().any(|s| {
+            matches!(
+                s,
+                Series::Line { label: Some(_), .. }
+                    | Series::Points { label: Some(_), .. }
+                    | Series::Bars { .. }
+            )
+        })
+    }
+}
+
+/// Renders the figures of the run and keeps track of how many of them were generated.
+pub struct Figures {
+    /// `None` if plotting is disabled or not possible.
+    reporter: Option<Box<dyn Reporter>>,
+    // charts can be skipped if there is not enough data
+    requested: Cell<usize>,
+    rendered: Cell<usize>,
+    failed: Cell<usize>,
+    /// The files of the rendered charts.
+    paths: RefCell<Vec<PathBuf>>,
+}
+
+impl Figures {
+    pub fn new(reporter: Option<Box<dyn Reporter>>) -> Self {
+        Self {
+            reporter,
+            requested: Cell::new(0),
+            rendered: Cell::new(0),
+            failed: Cell::new(0),
+            paths: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn render(&self, chart: &Chart) {
+        self.requested.set(self.requested.get() + 1);
+        if let Some(reporter) = &self.reporter {
+            match reporter.render(chart) {
+                Ok(()) => {
+                    self.rendered.set(self.rendered.get() + 1);
+                    self.paths.borrow_mut().push(chart.path.clone());
+                }
+                Err(e) => {
+                    self.failed.set(self.failed.get() + 1);
+                    warn!("Failed to generate {}: {}", chart.path.display(), e);
+                }
+            }
+        }
+    }
+
+    pub fn requested(&self) -> usize {
+        self.requested.get()
+    }
+
+    pub fn rendered(&self) -> usize {
+        self.rendered.get()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed.get()
+    }
+
+    /// The chart of `file_name` is rendered, see `Chart::new`.
+    pub fn is_rendered(&self, file_name: &str) -> bool {
+        self.paths
+            .borrow()
+            .contains(&Path::new(".").join(file_name))
+    }
+}
+
+/// Creates the reporter for the configured backend. If it can't be used,
+/// falls back to the other one, or explains why no figures can be generated.
+/// If python is used, but `python_path` is not set, the discovered one is recorded in it.
+pub fn reporter(
+    backend: &Backend,
+    python_path: &mut Option<String>,
+) -> Result<Box<dyn Reporter>, String> {
+    match backend {
+        Backend::Matplotlib => matplotlib(python_path).or_else(|e| match gnuplot() {
+            Ok(reporter) => {
+                warn!("{} Falling back to gnuplot.", e);
+                Ok(reporter)
+            }
+            Err(_) => Err(e),
+        }),
+        Backend::Gnuplot => gnuplot().or_else(|e| match matplotlib(python_path) {
+            Ok(reporter) => {
+                warn!("{} Falling back to matplotlib.", e);
+                Ok(reporter)
+            }
+            Err(_) => Err(e),
+        }),
+    }
+}
+
+pub fn matplotlib(python_path: &mut Option<String>) -> Result<Box<dyn Reporter>, String> {
+    let reporter = match python_path {
+        Some(python_path) => {
+            let reporter = MatplotlibReporter::new(python_path.clone());
+            reporter.check().map(|_| reporter)
+        }
+        None => find_python().map(|found| {
+            *python_path = Some(found.clone());
+            MatplotlibReporter::new(found)
+        }),
+    };
+    match reporter {
+        Ok(reporter) => Ok(Box::new(reporter)),
+        Err(e) => Err(format!(
+            "{}. Install python3 with matplotlib (e.g. `pip3 install matplotlib`), \
+             use --python_path to specify it, or use `--backend gnuplot`.",
+            e
+        )),
+    }
+}
+
+/// Looks for `python3`, then `python` in `PATH`, and picks the first one which has `matplotlib`.
+fn find_python() -> Result<String, String> {
+    find_python_in(&env::var_os("PATH").unwrap_or_default())
+}
+
+/// Same as `find_python`, but in the directories of `paths`, listed the same way as in `PATH`.
+fn find_python_in(paths: &OsStr) -> Result<String, String> {
+    let mut rejected = vec![];
+    for name in &["python3", "python"] {
+        if let Some(path) = find_in(paths, name) {
+            let python_path = path.to_string_lossy().into_owned();
+            match MatplotlibReporter::new(python_path.clone()).check() {
+                Ok(()) => return Ok(python_path),
+                Err(e) => rejected.push(e),
+            }
+        }
+    }
+    if rejected.is_empty() {
+        Err("Neither `python3` nor `python` is found in PATH".to_string())
+    } else {
+        Err(rejected.join("; "))
+    }
+}
+
+pub fn gnuplot() -> Result<Box<dyn Reporter>, String> {
+    match find_in_path("gnuplot") {
+        Some(gnuplot) => Ok(Box::new(GnuplotReporter::new(gnuplot))),
+        None => Err(
+            "`gnuplot` is not found in PATH. Install it (e.g. `apt install gnuplot`) \
+             or use `--backend matplotlib`."
+                .to_string(),
+        ),
+    }
+}
+
+/// Looks up an executable in the directories listed in `PATH`.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    find_in(&env::var_os("PATH")?, binary)
+}
+
+/// Same as `find_in_path`, but in the directories of `paths`.
+fn find_in(paths: &OsStr, binary: &str) -> Option<PathBuf> {
+    env::split_paths(paths)
+        .flat_map(|dir| vec![dir.join(binary), dir.join(format!("{}.exe", binary))])
+        .find(|candidate| candidate.is_file())
+}
+
+/// Formats a value, so it can be parsed by both python (with `from math import nan`)
+/// and gnuplot. Missing values are rendered as gaps.
+fn format_value(v: f64) -> String {
+    if v.is_finite() {
+        format!("{}", v)
+    } else {
+        "nan".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::fs;
+
+    /// A directory of its own of a test, e.g. of the fake executables of a `PATH`. It's removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("cdb-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).expect("Failed to create a temporary directory");
+            Self(dir)
+        }
+
+        /// A shell script, which exits with `code`.
+        fn executable(&self, name: &str, code: i32) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, format!("#!/bin/sh\nexit {}\n", code))
+                .expect("Failed to write a fake executable");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                    .expect("Failed to make a fake executable");
+            }
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn paths(dirs: &[&Path]) -> OsString {
+        env::join_paths(dirs).expect("Valid directories")
+    }
+
+    #[test]
+    fn finds_an_executable_in_the_first_directory_which_has_it() {
+        let first = TempDir::new("find-first");
+        let second = TempDir::new("find-second");
+        let gnuplot = second.executable("gnuplot", 0);
+        let python3 = first.executable("python3", 0);
+        second.executable("python3", 0);
+        let paths = paths(&[&first.0, &second.0]);
+        assert_eq!(find_in(&paths, "gnuplot"), Some(gnuplot));
+        assert_eq!(find_in(&paths, "python3"), Some(python3));
+        assert_eq!(find_in(&paths, "python"), None);
+    }
+
+    #[test]
+    fn finds_nothing_in_a_path_without_the_executables() {
+        let empty = TempDir::new("find-none");
+        let paths = paths(&[&empty.0]);
+        assert_eq!(find_in(&paths, "gnuplot"), None);
+        assert_eq!(
+            find_python_in(&paths),
+            Err("Neither `python3` nor `python` is found in PATH".to_string())
+        );
+        assert_eq!(find_in(OsStr::new(""), "gnuplot"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn picks_the_first_python_which_imports_matplotlib() {
+        let dir = TempDir::new("find-python");
+        // the fake ones succeed with whatever they run, i.e. they "have" matplotlib
+        let python = dir.executable("python", 0);
+        let paths = paths(&[&dir.0]);
+        assert_eq!(
+            find_python_in(&paths),
+            Ok(python.to_string_lossy().into_owned())
+        );
+        let python3 = dir.executable("python3", 0);
+        assert_eq!(
+            find_python_in(&paths),
+            Ok(python3.to_string_lossy().into_owned())
+        );
+        // without matplotlib, it's skipped
+        dir.executable("python3", 1);
+        assert_eq!(
+            find_python_in(&paths),
+            Ok(python.to_string_lossy().into_owned())
+        );
+        dir.executable("python", 1);
+        let rejected = find_python_in(&paths).expect_err("Neither has matplotlib");
+        assert!(
+            rejected.contains("python3` can't import matplotlib"),
+            "{}",
+            rejected
+        );
+        assert!(
+            rejected.contains("python` can't import matplotlib"),
+            "{}",
+            rejected
+        );
+    }
+
+    /// The charts of the golden scripts of the backends, between them every kind of series and option.
+    pub(super) fn golden_charts() -> Vec<Chart> {
+        let latency = Chart::new("golden_latency.png".to_string(), "Time, s", "Latency, ms")
+            .x_ticks(vec![(0., "0%".to_string()), (1., "it's 1".to_string())])
+            .shade(0., 0.5)
+            .log_y()
+            .line(Some("p50"), vec![0., 1., 2.], vec![1.5, f64::NAN, 2.])
+            .points(Some("p99"), vec![0., 2.], vec![10., 20.])
+            .scatter(None, vec![0.25, 1.75], vec![3., 4.], 2., 0.3)
+            .annotate(2., 20., "p99: 20 ms".to_string())
+            .line_y2(
+                "Rate",
+                "Requests/s",
+                vec![0., 1., 2.],
+                vec![100., 90., 110.],
+            );
+        let distribution = Chart::new(
+            "golden_distribution.png".to_string(),
+            "Latency, ms",
+            "Count",
+        )
+        .x_ticks((0..12).map(|i| (i as f64, format!("t{}", i))).collect())
+        .histogram(
+            vec![1., 2., 2.5, 4., 9.],
+            &Binning {
+                bins: 4,
+                min: 0.,
+                max: 8.,
+            },
+        )
+        .boxes(
+            vec![1., 2.],
+            vec![[1., 2., 3., 4., 5.], [2., 3., 4., 5., 6.]],
+            0.6,
+        )
+        .bars("Rate", vec![1., 2.], vec![100., 200.], vec![5., 10.], 0.8)
+        .heatmap(
+            vec![0., 1., 2.],
+            vec![0., 10., 20.],
+            vec![vec![1, 0], vec![3, 10]],
+            "Requests",
+        );
+        vec![latency, distribution]
+    }
+
+    /// Compares `script` with the checked-in `src/report/golden/{name}`, or rewrites the file,
+    /// if `UPDATE_GOLDEN` is set, so the changes of the scripts are reviewed as the diffs of the files.
+    pub(super) fn assert_golden(name: &str, script: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/report/golden")
+            .join(name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, script).expect("Failed to write a golden script");
+        }
+        let golden = fs::read_to_string(&path).expect("Failed to read a golden script");
+        assert!(
+            script == golden,
+            "The script differs from {}, rerun with UPDATE_GOLDEN=1 to update it:\n{}",
+            path.display(),
+            script
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::aggregator::StreamingAggregator;
+use crate::interrupt::Interrupt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use tracing::warn;
+
+const CHECK_TICK: Duration = Duration::from_millis(100);
+// the generator is behind schedule, if it's late by more than this;
+// has to be well above the error of the rate limiter itself, which grows with the rate
+// (see `benches/benchmarks_rate_limiter.rs`, it prints the achieved rate for 1k, 10k and 100k per second)
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Compares the requests sent so far with the schedule (`rps` from the start),
+/// so a run which measures the load generator, rather than the model, doesn't go unnoticed.
+pub struct ScheduleMonitor {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<Vec<(f64, f64)>>,
+}
+
+impl ScheduleMonitor {
+    pub fn start(
+        aggregator: Arc<StreamingAggregator>,
+        start_time: Instant,
+        rps: usize,
+        n_jobs: usize,
+        interrupt: Interrupt,
+    ) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let handle = tokio::spawn(async move {
+            let max_deficit = (rps as f64 * MAX_LAG.as_secs_f64()).max(1.);
+            let mut intervals: Vec<(f64, f64)> = vec![];
+            let mut lagging = false;
+            let mut check_interval = interval(CHECK_TICK);
+            loop {
+                tokio::select! {
+                    _ = check_interval.tick() => {
+                        let elapsed = start_time.elapsed().as_secs_f64();
+                        let sent = aggregator.sent();
+                        // the schedule is over once everything is sent
+                        if sent >= n_jobs || interrupt.is_set() {
+                            break;
+                        }
+                        let intended = (rps as f64 * elapsed).min(n_jobs as f64);
+                        if intended - sent as f64 > max_deficit {
+                            match intervals.last_mut() {
+                                Some(last) if lagging => last.1 = elapsed,
+                                _ => {
+                                    warn!(
+                                        "The load generator is behind schedule: {} requests sent instead of {:.0} at {:.1}s",
+                                        sent, intended, elapsed
+                                    );
+                                    let since = elapsed - CHECK_TICK.as_secs_f64();
+                                    intervals.push((since.max(0.), elapsed));
+                                }
+                            }
+                            lagging = true;
+                        } else {
+                            lagging = false;
+                        }
+                    }
+                    _ = &mut stopped => break,
+                }
+            }
+            intervals
+        });
+        Self { stop, handle }
+    }
+
+    /// Returns the intervals of the run (in seconds since the start),
+    /// when the generator was behind schedule.
+    pub async fn stop(self) -> Vec<(f64, f64)> {
+        // the receiver is gone only if the monitor has already stopped
+        self.stop.send(()).unwrap_or_default();
+        self.handle.await.expect("Schedule monitor failed")
+    }
+}
@@ -0,0 +1,476 @@
+use crate::aggregator::{Batch, Sends, StreamingAggregator};
+use crate::allocations;
+use crate::clock::Clock;
+use crate::precise_sleep::Sleep;
+use crate::report;
+use crate::spill::{Overheads, Spill};
+use crate::stats::{self, PercentileMethod};
+use crate::suite;
+use crate::{measure_sleep, Mode, TaskStats, CALIBRATION_SLEEP};
+use rand::Rng;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, Instant};
+
+// a cost below it is off by more than its own value
+const MAX_SLEEP_OVERSHOOT: Duration = Duration::from_millis(5);
+// the error of a precise sleep of 1-5 ms
+const MAX_PRECISE_SLEEP_ERROR: f64 = 0.1;
+// the smoke runs: 2 seconds of 200 requests
+const SMOKE_RUN: [&str; 4] = ["--rate=100", "--num_req=200", "--latency=5ms", "--no-plots"];
+// the runs of the simulation check: 3 threads for 4 needed, so the queue grows for 2 seconds
+const SIMULATED_RUN: [&str; 6] = [
+    "--rate=200",
+    "--num_req=400",
+    "--latency=20ms",
+    "--no-plots",
+    "sync",
+    "--threads=3",
+];
+// the percentiles of the simulation are within this share of the ones of the real run
+const MAX_SIMULATION_ERROR: f64 = 0.05;
+// the tasks of the allocation check, after as many to warm up the buffers
+const ALLOCATION_TASKS: usize = 100_000;
+// the records of the spill check, in artificially tiny chunks, so they are merged in several passes
+const SPILL_RECORDS: usize = 200_000;
+const SPILL_CHUNK: usize = 256;
+// the memory of the spill check, the records alone take ~10 MB
+const MAX_SPILL_MEMORY: usize = 1 << 20;
+
+/// The outcome of a check: its name, whether it passed, and what was found.
+struct Check {
+    name: &'static str,
+    passed: bool,
+    /// It can't be done in this build, so it doesn't fail the rest.
+    skipped: bool,
+    details: String,
+}
+
+impl Check {
+    /// The checks of the allocations, which the allocator counts only with the `count-allocations` feature.
+    fn uncounted(name: &'static str) -> Self {
+        Self {
+            name,
+            passed: true,
+            skipped: true,
+            details: "the allocations are counted only with the `count-allocations` feature"
+                .to_string(),
+        }
+    }
+}
+
+/// Checks the environment: the plotting backends, the permission to write to the current directory,
+/// the timer, the allocations of the hot path, the spill of the records, and a short run of each mode. Prints a line per check and returns whether all passed.
+pub async fn run(python_path: Option<String>) -> bool {
+    let checks = vec![
+        plotting(python_path),
+        writable(Path::new(".")),
+        timer_resolution(),
+        sleep_granularity(&Mode::Sync(1)).await,
+        sleep_granularity(&Mode::Async).await,
+        precise_sleep(),
+        allocations(),
+        spill(),
+        smoke_run("sync", &["sync", "--threads=4"]),
+        smoke_run("async", &["async"]),
+        simulation(),
+    ];
+    for check in &checks {
+        println!(
+            "{}  {}: {}",
+            match (check.passed, check.skipped) {
+                (_, true) => "SKIP",
+                (true, false) => "PASS",
+                (false, false) => "FAIL",
+            },
+            check.name,
+            check.details
+        );
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    let skipped = checks.iter().filter(|check| check.skipped).count();
+    if failed == 0 && skipped > 0 {
+        println!(
+            "All {} checks passed, {} skipped",
+            checks.len() - skipped,
+            skipped
+        );
+    } else if failed == 0 {
+        println!("All {} checks passed", checks.len());
+    } else {
+        println!("{} of {} checks failed", failed, checks.len());
+    }
+    failed == 0
+}
+
+/// matplotlib, or gnuplot as the fallback.
+fn plotting(mut python_path: Option<String>) -> Check {
+    let name = "plotting";
+    match (report::matplotlib(&mut python_path), report::gnuplot()) {
+        (Ok(_), gnuplot) => Check {
+            name,
+            passed: true,
+            skipped: false,
+            details: format!(
+                "matplotlib of {}{}",
+                python_path.unwrap_or_default(),
+                if gnuplot.is_ok() { ", gnuplot too" } else { "" }
+            ),
+        },
+        (Err(e), Ok(_)) => Check {
+            name,
+            passed: true,
+            skipped: false,
+            details: format!("gnuplot, the fallback of matplotlib: {}", e),
+        },
+        (Err(matplotlib), Err(gnuplot)) => Check {
+            name,
+            passed: false,
+            skipped: false,
+            details: format!(
+                "no figures can be generated, a run writes only the summary and the metadata. {} {}",
+                matplotlib, gnuplot
+            ),
+        },
+    }
+}
+
+/// A file can be created and removed in `dir`, where the outputs are written.
+fn writable(dir: &Path) -> Check {
+    let path = dir.join(format!(".validate_{}", process::id()));
+    let result = fs::write(&path, b"validate").and_then(|_| fs::remove_file(&path));
+    Check {
+        name: "output directory",
+        passed: result.is_ok(),
+        skipped: false,
+        details: match result {
+            Ok(()) => format!("{} is writable", absolute(dir)),
+            Err(e) => format!("{} is not writable: {}", absolute(dir), e),
+        },
+    }
+}
+
+/// The smallest step of the clock, i.e. of the measured latencies.
+fn timer_resolution() -> Check {
+    const ROUNDS: usize = 100;
+    let resolution = (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            loop {
+                let elapsed = start.elapsed();
+                if elapsed > Duration::default() {
+                    break elapsed;
+                }
+            }
+        })
+        .min()
+        .expect("There are rounds");
+    Check {
+        name: "timer resolution",
+        passed: resolution < Duration::from_millis(1),
+        skipped: false,
+        details: format!("{:.3} µs", resolution.as_secs_f64() * 1e6),
+    }
+}
+
+/// How much longer than requested a short sleep of the mode takes, see `calibrate_timer`.
+async fn sleep_granularity(mode: &Mode) -> Check {
+    let (mean, max) = measure_sleep(mode).await;
+    let passed = mean < CALIBRATION_SLEEP + MAX_SLEEP_OVERSHOOT;
+    Check {
+        name: match mode {
+            Mode::Sync(_) => "sleep granularity (sync)",
+            Mode::Async => "sleep granularity (async)",
+        },
+        passed,
+        skipped: false,
+        details: format!(
+            "a {} ms sleep takes {:.3} ms on average, {:.3} ms max{}",
+            CALIBRATION_SLEEP.as_millis(),
+            mean.as_secs_f64() * 1000.,
+            max.as_secs_f64() * 1000.,
+            if passed {
+                String::new()
+            } else {
+                format!(
+                    ", the costs below {} ms are off by more than their values",
+                    MAX_SLEEP_OVERSHOOT.as_millis()
+                )
+            }
+        ),
+    }
+}
+
+/// The sleeps of 1-5 ms of `sync --precise-sleep` are within 10% of their durations.
+fn precise_sleep() -> Check {
+    let sleep = tokio::task::block_in_place(Sleep::calibrate);
+    let (mean, max) = tokio::task::block_in_place(|| sleep.check());
+    let margin = match sleep {
+        Sleep::Precise { margin } => margin,
+        Sleep::Os => Duration::default(),
+    };
+    Check {
+        name: "precise sleep",
+        passed: max < MAX_PRECISE_SLEEP_ERROR,
+        skipped: false,
+        details: format!(
+            "spinning for the last {:.3} ms, the sleeps of 1-5 ms are off by {:.2}% on average, {:.2}% max",
+            margin.as_secs_f64() * 1000.,
+            mean * 100.,
+            max * 100.
+        ),
+    }
+}
+
+/// The bookkeeping of a task of a sync worker, i.e. its events, its stats and their delivery in the batches
+/// of the default `--stats-batch`, allocates nothing, once the buffers are warmed up.
+fn allocations() -> Check {
+    let name = "allocations per task";
+    if !allocations::ENABLED {
+        return Check::uncounted(name);
+    }
+    let aggregator = StreamingAggregator::new(
+        Instant::now(),
+        Clock::Precise,
+        PercentileMethod::NearestRank,
+        None,
+        0,
+    )
+    .with_capacity(2 * ALLOCATION_TASKS, 2 * ALLOCATION_TASKS);
+    let mut batch = Batch::new(64, Duration::from_millis(10), None);
+    let mut sends = Sends::default();
+    let mut task = || {
+        let start = Instant::now();
+        aggregator.record_sent(&mut sends);
+        aggregator.record_started();
+        let completion = Instant::now();
+        let stats = TaskStats::new(Duration::MAX, start, start, completion - start, completion);
+        batch.push(&aggregator, stats.served_by(0), None);
+    };
+    (0..ALLOCATION_TASKS).for_each(|_| task());
+    let before = allocations::count_in_thread();
+    (0..ALLOCATION_TASKS).for_each(|_| task());
+    let allocated = allocations::count_in_thread() - before;
+    Check {
+        name,
+        passed: allocated == 0,
+        skipped: false,
+        details: format!(
+            "{} allocations in {} tasks of a sync worker, once warmed up",
+            allocated, ALLOCATION_TASKS
+        ),
+    }
+}
+
+/// The records spilled in tiny chunks and merged are sorted as in memory, so the percentiles are the same,
+/// and the memory of spilling and merging them stays bounded, regardless of their number.
+fn spill() -> Check {
+    let name = "spill";
+    let start = Instant::now();
+    // the same records for the spill and for the sort in memory, so only one of them is in memory at once
+    let records = || {
+        let mut rng = stats::rng(0, "spill");
+        let timeout = Duration::from_millis(10);
+        (0..SPILL_RECORDS).map(move |i| {
+            let service = Duration::from_micros(rng.gen_range(0..5_000));
+            let latency = service + Duration::from_micros(rng.gen_range(0..8_000));
+            let start = start + Duration::from_millis(i as u64);
+            TaskStats::new(timeout, start, start, service, start + latency)
+        })
+    };
+    let baseline = allocations::reset_peak();
+    let spilled =
+        Spill::new(&env::temp_dir(), "validate", start, SPILL_CHUNK).and_then(|mut spill| {
+            records().for_each(|stats| spill.push(&stats));
+            spill.finish()
+        });
+    let memory = allocations::peak() - baseline;
+    let overheads = match spilled {
+        Ok(merged) => Overheads::Spilled(merged),
+        Err(e) => {
+            return Check {
+                name,
+                passed: false,
+                skipped: false,
+                details: format!("failed to spill to {}: {}", absolute(&env::temp_dir()), e),
+            }
+        }
+    };
+
+    let mut sorted: Vec<TaskStats> = records().filter(TaskStats::success).collect();
+    sorted.sort_unstable_by(|a, b| a.overhead.partial_cmp(&b.overhead).unwrap());
+    let in_memory = Overheads::Records(&sorted);
+    let method = PercentileMethod::NearestRank;
+    let identical = overheads.iter().eq(in_memory.iter())
+        && [0., 0.5, 0.99, 0.999, 1.]
+            .iter()
+            .all(|p| overheads.percentile(method, *p) == in_memory.percentile(method, *p));
+    Check {
+        name,
+        passed: identical && (memory < MAX_SPILL_MEMORY || !allocations::ENABLED),
+        skipped: false,
+        details: format!(
+            "{} records in chunks of {}: {} overheads merged, {} the ones sorted in memory, {}",
+            SPILL_RECORDS,
+            SPILL_CHUNK,
+            overheads.len(),
+            if identical { "the same as" } else { "unlike" },
+            if allocations::ENABLED {
+                format!("{} KiB of memory at most", memory / 1024)
+            } else {
+                "the memory is counted only with the `count-allocations` feature".to_string()
+            }
+        ),
+    }
+}
+
+/// Runs the model with `args` as a child process in a temporary directory, which is removed then.
+/// The `CDB_` variables are left out, so only the built-in defaults apply.
+fn smoke_run(mode: &'static str, args: &[&str]) -> Check {
+    let name = match mode {
+        "sync" => "smoke run (sync)",
+        _ => "smoke run (async)",
+    };
+    let failed = |details: String| Check {
+        name,
+        passed: false,
+        skipped: false,
+        details,
+    };
+    let dir = env::temp_dir().join(format!("validate_{}_{}", mode, process::id()));
+    let mut command = match model(&dir) {
+        Ok(command) => command,
+        Err(e) => return failed(e),
+    };
+    command
+        .arg(format!("--name=validate_{}", mode))
+        .args(SMOKE_RUN)
+        .args(args);
+    let start = Instant::now();
+    let output = tokio::task::block_in_place(|| command.output());
+    let elapsed = start.elapsed();
+    fs::remove_dir_all(&dir).unwrap_or_default();
+    match output {
+        Ok(output) if output.status.success() => Check {
+            name,
+            passed: true,
+            skipped: false,
+            details: format!("200 requests at 100 rps in {:.3}s", elapsed.as_secs_f64()),
+        },
+        Ok(output) => failed(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .last()
+                .unwrap_or_default()
+        )),
+        Err(e) => failed(format!("failed to run: {}", e)),
+    }
+}
+
+/// The model run in `dir`, which is created, without the `CDB_` variables, so only the built-in defaults apply.
+fn model(dir: &Path) -> Result<Command, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    let mut command = Command::new(env::current_exe().map_err(|e| e.to_string())?);
+    command.current_dir(dir);
+    for (key, _) in env::vars().filter(|(key, _)| key.starts_with("CDB_")) {
+        command.env_remove(key);
+    }
+    Ok(command)
+}
+
+/// The p50 and p99 of `--engine des` are within 5% of the ones of the real sync engine,
+/// with precise sleeps, for a run where the queue grows.
+fn simulation() -> Check {
+    let name = "simulation (--engine des)";
+    let failed = |details: String| Check {
+        name,
+        passed: false,
+        skipped: false,
+        details,
+    };
+    let dir = env::temp_dir().join(format!("validate_des_{}", process::id()));
+    let database = dir.join("validate.db");
+    let mut runs = vec![];
+    // the simulated sleeps are exact
+    for (run, engine, sleep) in [
+        ("validate_real", "real", Some("--precise-sleep")),
+        ("validate_des", "des", None),
+    ] {
+        let mut command = match model(&dir) {
+            Ok(command) => command,
+            Err(e) => return failed(e),
+        };
+        command
+            .arg(format!("--name={}", run))
+            .arg(format!("--engine={}", engine))
+            .arg(format!("--sqlite={}", database.display()))
+            .arg("--quiet")
+            .args(SIMULATED_RUN)
+            .args(sleep)
+            .stdout(Stdio::null());
+        match suite::spawn(&mut command, &database, run) {
+            Ok((_, Some(run))) => runs.push(run),
+            Ok((status, None)) => {
+                fs::remove_dir_all(&dir).unwrap_or_default();
+                return failed(format!("the {} run failed: {}", engine, status));
+            }
+            Err(e) => {
+                fs::remove_dir_all(&dir).unwrap_or_default();
+                return failed(format!("failed to run the {} engine: {}", engine, e));
+            }
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap_or_default();
+    let percentiles: Vec<(f64, f64, f64)> = [50., 99.]
+        .iter()
+        .map(|p| {
+            (
+                *p,
+                runs[0].percentile(*p).unwrap_or(f64::NAN),
+                runs[1].percentile(*p).unwrap_or(f64::NAN),
+            )
+        })
+        .collect();
+    // NaN, i.e. a missing percentile, fails it too
+    let passed = percentiles
+        .iter()
+        .all(|(_, real, simulated)| (simulated - real).abs() <= MAX_SIMULATION_ERROR * real);
+    Check {
+        name,
+        passed,
+        skipped: false,
+        details: percentiles
+            .iter()
+            .map(|(p, real, simulated)| {
+                format!(
+                    "p{} {:.3} ms simulated vs {:.3} ms real",
+                    p, simulated, real
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+fn absolute(dir: &Path) -> String {
+    dir.canonicalize()
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_task_of_a_sync_worker_allocates_nothing_once_warmed_up() {
+        let check = allocations();
+        assert!(!check.skipped);
+        assert!(check.passed, "{}", check.details);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::aggregator::{Class, StreamingAggregator};
+use crate::interrupt::Interrupt;
+use crate::rate_limiter::{self, RateLimiter};
+use crate::{ModelConfig, Plot};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+// a pending read of stdin is interrupted this often to check for Ctrl-C
+const INTERRUPT_CHECK: Duration = Duration::from_millis(100);
+// the runs of up to this many tasks are expanded up front, i.e. 8 MiB of costs
+const MATERIALIZED_MAX: usize = 1 << 20;
+// the tasks are labeled by their costs, if the latencies have up to this many distinct ones
+const MAX_COST_CLASSES: usize = 10;
+
+/// The cost of a task, ms.
+pub type TaskCost = u64;
+
+/// The costs of the tasks of a run in the order they are sent: `n_jobs` of the latencies in turn.
+/// It's built once, so the send loop just takes the next one, without allocations or branches on the spec.
+/// Up to `MATERIALIZED_MAX` tasks are expanded up front, the larger runs cycle through the latencies lazily,
+/// the order is the same.
+pub enum TaskSchedule {
+    Materialized(vec::IntoIter<TaskCost>),
+    Lazy {
+        latencies: Vec<TaskCost>,
+        next: usize,
+        n_jobs: usize,
+    },
+}
+
+impl TaskSchedule {
+    pub fn new(latencies: &[u64], n_jobs: usize) -> Self {
+        if n_jobs <= MATERIALIZED_MAX {
+            let costs: Vec<TaskCost> = latencies.iter().copied().cycle().take(n_jobs).collect();
+            TaskSchedule::Materialized(costs.into_iter())
+        } else {
+            TaskSchedule::Lazy {
+                latencies: latencies.to_vec(),
+                next: 0,
+                n_jobs,
+            }
+        }
+    }
+
+    pub fn is_materialized(&self) -> bool {
+        matches!(self, TaskSchedule::Materialized(_))
+    }
+}
+
+impl Iterator for TaskSchedule {
+    type Item = TaskCost;
+
+    fn next(&mut self) -> Option<TaskCost> {
+        match self {
+            TaskSchedule::Materialized(costs) => costs.next(),
+            TaskSchedule::Lazy {
+                latencies,
+                next,
+                n_jobs,
+            } => {
+                if *next == *n_jobs {
+                    return None;
+                }
+                let cost = latencies[*next % latencies.len()];
+                *next += 1;
+                Some(cost)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            TaskSchedule::Materialized(costs) => costs.size_hint(),
+            TaskSchedule::Lazy { next, n_jobs, .. } => (n_jobs - next, Some(n_jobs - next)),
+        }
+    }
+}
+
+/// The classes of the tasks by their costs, labeled e.g. `10ms` and `2000ms`, so the overhead of each cost
+/// is charted on its own, e.g. of the small tasks queued behind the big ones. `None` unless `--plots` has
+/// `classes`, for a single cost, or for more than `MAX_COST_CLASSES` of them.
+pub fn cost_classes(
+    config: &ModelConfig,
+    aggregator: &StreamingAggregator,
+) -> Option<HashMap<TaskCost, Class>> {
+    let costs: HashSet<TaskCost> = config.latency_distribution.iter().copied().collect();
+    if !config.plots.contains(&Plot::Classes) || costs.len() < 2 || costs.len() > MAX_COST_CLASSES {
+        return None;
+    }
+    Some(
+        costs
+            .into_iter()
+            .map(|cost| (cost, aggregator.class(&format!("{}ms", cost))))
+            .collect(),
+    )
+}
+
+/// What a task to send is.
+pub struct TaskSpec {
+    pub cost: TaskCost,
+    /// The class of the task, which groups it in the summary.
+    pub class: Option<Class>,
+}
+
+/// Where the tasks come from.
+pub enum TaskSource {
+    /// The tasks of the schedule at the rate of the rate limiter.
+    Schedule {
+        rate_limiter: RateLimiter,
+        schedule: TaskSchedule,
+        /// The classes of the tasks by their costs, see `cost_classes`.
+        classes: Option<HashMap<TaskCost, Class>>,
+    },
+    /// A task per line of `--stdin-tasks`, as soon as it's read. It ends with stdin.
+    Stdin {
+        lines: Lines<BufReader<Stdin>>,
+        /// The number of the lines read.
+        read: usize,
+        /// The classes of the labels seen so far, a label is registered once.
+        classes: HashMap<String, Class>,
+        aggregator: Arc<StreamingAggregator>,
+    },
+}
+
+impl TaskSource {
+    /// The classes of the tasks are registered by `aggregator`.
+    pub fn new(config: &ModelConfig, aggregator: &Arc<StreamingAggregator>) -> Self {
+        if config.stdin_tasks {
+            info!("Reading the tasks from stdin");
+            TaskSource::Stdin {
+                lines: BufReader::new(tokio::io::stdin()).lines(),
+                read: 0,
+                classes: HashMap::new(),
+                aggregator: aggregator.clone(),
+            }
+        } else {
+            let (refill, duration_ms) = rate_limiter::refill(config.rps);
+            info!("Rate limit refill {} per {} ms", refill, duration_ms);
+            let rate_limiter = RateLimiter::new(refill, Duration::from_millis(duration_ms));
+            TaskSource::Schedule {
+                rate_limiter,
+                schedule: TaskSchedule::new(&config.latency_distribution, config.n_jobs),
+                classes: cost_classes(config, aggregator),
+            }
+        }
+    }
+
+    /// `None` once all the tasks are sent, or the run is interrupted.
+    pub async fn next(&mut self, interrupt: &Interrupt) -> Option<TaskSpec> {
+        match self {
+            TaskSource::Schedule {
+                rate_limiter,
+                schedule,
+                classes,
+            } => {
+                let cost = schedule.next()?;
+                rate_limiter.acquire_one().await;
+                if interrupt.is_set() {
+                    return None;
+                }
+                Some(TaskSpec {
+                    cost,
+                    class: classes.as_ref().map(|classes| classes[&cost]),
+                })
+            }
+            TaskSource::Stdin {
+                lines,
+                read,
+                classes,
+                aggregator,
+            } => loop {
+                if interrupt.is_set() {
+                    return None;
+                }
+                // a cancelled read keeps what it has read so far
+                let line = match timeout(INTERRUPT_CHECK, lines.next_line()).await {
+                    Err(_) => continue,
+                    Ok(Ok(Some(line))) => line,
+                    Ok(Ok(None)) => return None,
+                    Ok(Err(e)) => {
+                        warn!("Failed to read the tasks from stdin: {}", e);
+                        return None;
+                    }
+                };
+                *read += 1;
+                match parse_task(&line) {
+                    Some(Some((cost, class))) => {
+                        return Some(TaskSpec {
+                            cost,
+                            class: class.map(|class| intern(classes, class, aggregator)),
+                        })
+                    }
+                    // an empty line
+                    Some(None) => {}
+                    None => warn!(
+                        "Skipped line {} of stdin: `{}` is not a cost and an optional class, e.g. 20ms read",
+                        read, line
+                    ),
+                }
+            },
+        }
+    }
+}
+
+/// The class labeled `label`, it's registered by the aggregator once, as a run has a few of them.
+fn intern(
+    classes: &mut HashMap<String, Class>,
+    label: &str,
+    aggregator: &StreamingAggregator,
+) -> Class {
+    if let Some(class) = classes.get(label) {
+        return *class;
+    }
+    let class = aggregator.class(label);
+    classes.insert(label.to_string(), class);
+    class
+}
+
+/// A cost, e.g. `20ms` or `20` (ms), and an optional class label separated by whitespace,
+/// e.g. `20ms read`. `Some(None)` for an empty line.
+fn parse_task(line: &str) -> Option<Option<(TaskCost, Option<&str>)>> {
+    let mut words = line.split_whitespace();
+    let cost = match words.next() {
+        Some(cost) => ModelConfig::parse_latency(cost)?,
+        None => return Some(None),
+    };
+    let class = words.next();
+    if words.next().is_some() {
+        return None;
+    }
+    Some(Some((cost, class)))
+}
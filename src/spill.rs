@@ -0,0 +1,502 @@
+use crate::stats::PercentileMethod;
+use crate::{Failure, TaskStats};
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+/// The records of a chunk, unless `--spill-chunk` is set: ~40 MB in memory and on disk.
+pub const DEFAULT_CHUNK: usize = 1 << 20;
+/// The bytes of an encoded `TaskRecord`.
+pub const RECORD_SIZE: usize = 38;
+// the runs merged at once, each one is read through a buffer of its own
+const FAN_IN: usize = 64;
+// the bytes of an overhead in the merged file
+const OVERHEAD_SIZE: usize = 8;
+
+/// A task record as it's spilled to disk, the times are in ns since the start of the run.
+#[derive(Clone, Copy)]
+struct TaskRecord {
+    start: u64,
+    service_start: u64,
+    completion: u64,
+    overhead: f64,
+    failure: Option<Failure>,
+    worker: Option<u32>,
+    clamped: bool,
+}
+
+impl TaskRecord {
+    fn new(stats: &TaskStats, start: Instant) -> Self {
+        let nanos = |at: Instant| at.saturating_duration_since(start).as_nanos() as u64;
+        Self {
+            start: nanos(stats.start_time),
+            service_start: nanos(stats.service_start_time),
+            completion: nanos(stats.completion_time),
+            overhead: stats.overhead,
+            failure: stats.failure,
+            worker: stats.worker.map(|worker| worker as u32),
+            clamped: stats.clamped,
+        }
+    }
+
+    /// Little-endian: the times, the overhead, the failure (0 for none), the worker (`u32::MAX` for none), clamped.
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.start.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.service_start.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.completion.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.overhead.to_le_bytes());
+        bytes[32] = match self.failure {
+            None => 0,
+            Some(Failure::ServiceTime) => 1,
+            Some(Failure::Queueing) => 2,
+            Some(Failure::Io) => 3,
+        };
+        bytes[33..37].copy_from_slice(&self.worker.unwrap_or(u32::MAX).to_le_bytes());
+        bytes[37] = self.clamped as u8;
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Self {
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().expect("8 bytes"));
+        let worker = u32::from_le_bytes(bytes[33..37].try_into().expect("4 bytes"));
+        Self {
+            start: u64_at(0),
+            service_start: u64_at(8),
+            completion: u64_at(16),
+            overhead: f64::from_bits(u64_at(24)),
+            failure: match bytes[32] {
+                0 => None,
+                1 => Some(Failure::ServiceTime),
+                2 => Some(Failure::Queueing),
+                _ => Some(Failure::Io),
+            },
+            worker: if worker == u32::MAX {
+                None
+            } else {
+                Some(worker)
+            },
+            clamped: bytes[37] != 0,
+        }
+    }
+
+    /// The next record of `reader`, `None` at its end.
+    fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut bytes = [0; RECORD_SIZE];
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(Self::decode(&bytes))),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The successful records first, by overhead, as the records in memory are sorted.
+    fn order(&self, other: &Self) -> Ordering {
+        self.failure
+            .is_some()
+            .cmp(&other.failure.is_some())
+            .then_with(|| self.overhead.total_cmp(&other.overhead))
+    }
+}
+
+/// The directory of the spill of a run, it's removed with everything in it once dropped.
+struct Directory(PathBuf);
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        // nothing to do about it at this point, the files are temporary anyway
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes the records of the run to disk in chunks of `chunk` records. A chunk is sorted and written
+/// as a run of its own by a thread, while the next one is filled, so at most 3 chunks are in memory at once.
+pub struct Spill {
+    start: Instant,
+    chunk: usize,
+    buffer: Vec<TaskRecord>,
+    /// `None` once the writer has failed.
+    sender: Option<SyncSender<Vec<TaskRecord>>>,
+    writer: JoinHandle<io::Result<Vec<PathBuf>>>,
+    directory: Directory,
+}
+
+impl Spill {
+    /// Creates a directory of its own for the run `name` within `dir`.
+    /// The times of the records are relative to `start`.
+    pub fn new(dir: &Path, name: &str, start: Instant, chunk: usize) -> io::Result<Self> {
+        let path = dir.join(format!("cdb_spill_{}_{}", name, process::id()));
+        fs::create_dir_all(&path)?;
+        let directory = Directory(path.clone());
+        let (sender, receiver) = mpsc::sync_channel::<Vec<TaskRecord>>(1);
+        let writer = thread::Builder::new()
+            .name("spill".to_string())
+            .spawn(move || {
+                let mut runs = vec![];
+                for mut records in receiver {
+                    records.sort_unstable_by(TaskRecord::order);
+                    let run = path.join(format!("run_{}", runs.len()));
+                    write_run(&run, records.iter().copied())?;
+                    runs.push(run);
+                }
+                Ok(runs)
+            })?;
+        Ok(Self {
+            start,
+            chunk,
+            buffer: Vec::with_capacity(chunk),
+            sender: Some(sender),
+            writer,
+            directory,
+        })
+    }
+
+    pub fn push(&mut self, stats: &TaskStats) {
+        self.buffer.push(TaskRecord::new(stats, self.start));
+        if self.buffer.len() >= self.chunk {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let records = mem::replace(&mut self.buffer, Vec::with_capacity(self.chunk));
+        if records.is_empty() {
+            return;
+        }
+        // the error is reported by `finish`, the records are lost anyway
+        if let Some(sender) = &self.sender {
+            if sender.send(records).is_err() {
+                self.sender = None;
+            }
+        }
+    }
+
+    /// Writes the last chunk and merges the runs.
+    pub fn finish(mut self) -> io::Result<Merged> {
+        self.flush();
+        let Spill {
+            sender,
+            writer,
+            directory,
+            ..
+        } = self;
+        drop(sender);
+        let runs = writer.join().expect("The spill writer has panicked")?;
+        merge(directory, runs)
+    }
+}
+
+fn write_run(path: &Path, records: impl Iterator<Item = TaskRecord>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for record in records {
+        out.write_all(&record.encode())?;
+    }
+    out.flush()
+}
+
+/// The next record of a run being merged.
+struct Head(TaskRecord, usize);
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.order(&other.0)
+    }
+}
+
+/// Passes the records of the sorted `runs` to `output` in their order.
+fn merge_runs(
+    runs: &[PathBuf],
+    mut output: impl FnMut(TaskRecord) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut readers = runs
+        .iter()
+        .map(|run| File::open(run).map(BufReader::new))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heads = BinaryHeap::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = TaskRecord::read(reader)? {
+            heads.push(Reverse(Head(record, i)));
+        }
+    }
+    while let Some(Reverse(Head(record, i))) = heads.pop() {
+        output(record)?;
+        if let Some(next) = TaskRecord::read(&mut readers[i])? {
+            heads.push(Reverse(Head(next, i)));
+        }
+    }
+    Ok(())
+}
+
+/// Merges the runs `FAN_IN` at a time, until there are few enough of them,
+/// then merges the overheads of the successful records into a file of their own.
+fn merge(directory: Directory, mut runs: Vec<PathBuf>) -> io::Result<Merged> {
+    let mut pass = 0;
+    while runs.len() > FAN_IN {
+        let mut merged = Vec::with_capacity(runs.len() / FAN_IN + 1);
+        for (i, group) in runs.chunks(FAN_IN).enumerate() {
+            let path = directory.0.join(format!("merged_{}_{}", pass, i));
+            let mut out = BufWriter::new(File::create(&path)?);
+            merge_runs(group, |record| out.write_all(&record.encode()))?;
+            out.flush()?;
+            group.iter().try_for_each(fs::remove_file)?;
+            merged.push(path);
+        }
+        runs = merged;
+        pass += 1;
+    }
+
+    let path = directory.0.join("overheads");
+    let mut out = BufWriter::new(File::create(&path)?);
+    let mut len = 0;
+    merge_runs(&runs, |record| {
+        if record.failure.is_some() {
+            return Ok(());
+        }
+        len += 1;
+        out.write_all(&record.overhead.to_le_bytes())
+    })?;
+    out.flush()?;
+    runs.iter().try_for_each(fs::remove_file)?;
+    Ok(Merged {
+        file: RefCell::new(File::open(&path)?),
+        path,
+        len,
+        _directory: directory,
+    })
+}
+
+/// The overheads of the successful tasks of the run in ascending order, in a file of the spill,
+/// which is removed with it. They are read by their rank, so only the ones needed are in memory.
+pub struct Merged {
+    file: RefCell<File>,
+    path: PathBuf,
+    len: usize,
+    _directory: Directory,
+}
+
+impl Merged {
+    /// The `i`-th smallest overhead. The file is temporary, so failing to read it is not expected.
+    fn get(&self, i: usize) -> f64 {
+        let mut file = self.file.borrow_mut();
+        let mut bytes = [0; OVERHEAD_SIZE];
+        file.seek(SeekFrom::Start((i * OVERHEAD_SIZE) as u64))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .expect("Failed to read the merged spill");
+        f64::from_le_bytes(bytes)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = f64> {
+        let mut reader =
+            BufReader::new(File::open(&self.path).expect("Failed to read the merged spill"));
+        iter::from_fn(move || {
+            let mut bytes = [0; OVERHEAD_SIZE];
+            reader
+                .read_exact(&mut bytes)
+                .ok()
+                .map(|_| f64::from_le_bytes(bytes))
+        })
+        .take(self.len)
+    }
+}
+
+/// The overheads of the successful tasks in ascending order, which the percentiles, the histogram and Apdex
+/// come from: of the records in memory, or of all the records of the run, merged from the spill.
+pub enum Overheads<'a> {
+    Records(&'a [TaskStats]),
+    Spilled(Merged),
+}
+
+impl Overheads<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            Overheads::Records(records) => records.len(),
+            Overheads::Spilled(merged) => merged.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `i`-th smallest overhead.
+    pub fn get(&self, i: usize) -> f64 {
+        match self {
+            Overheads::Records(records) => records[i].overhead,
+            Overheads::Spilled(merged) => merged.get(i),
+        }
+    }
+
+    /// `p` is within `[0, 1]`, `None` if there are no overheads.
+    pub fn percentile(&self, method: PercentileMethod, p: f64) -> Option<f64> {
+        method.percentile_at(self.len(), p, |i| self.get(i))
+    }
+
+    /// The number of overheads up to `value`, found by a binary search.
+    pub fn count_at_most(&self, value: f64) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.get(middle) <= value {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = f64> + '_> {
+        match self {
+            Overheads::Records(records) => Box::new(records.iter().map(|s| s.overhead)),
+            Overheads::Spilled(merged) => Box::new(merged.iter()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats;
+    use rand::Rng;
+    use std::env;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// `n` records of ms overheads, so many of them are equal, and a failure of each kind in every 20 of them.
+    fn records(n: u32) -> Vec<TaskStats> {
+        let mut rng = stats::rng(1, "spill");
+        let first = Instant::now();
+        (0..n)
+            .map(|i| {
+                let start = first + Duration::from_micros(10) * i;
+                let service = match i % 20 {
+                    // a slow backend
+                    0 => TIMEOUT * 2,
+                    _ => Duration::from_millis(rng.gen_range(0..50)),
+                };
+                let completion = match i % 20 {
+                    // an overloaded queue
+                    1 => start + service + TIMEOUT + Duration::from_millis(1),
+                    _ => start + service + Duration::from_millis(rng.gen_range(0..10)),
+                };
+                let stats = TaskStats::new(TIMEOUT, start, start, service, completion);
+                match i % 20 {
+                    2 => stats.io_error(),
+                    3 => stats.served_by(i as usize),
+                    _ => stats,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_record_is_decoded_as_it_was_encoded() {
+        let start = Instant::now();
+        for stats in records(20) {
+            let record = TaskRecord::new(&stats, start);
+            let decoded = TaskRecord::decode(&record.encode());
+            assert_eq!(
+                (decoded.start, decoded.service_start, decoded.completion),
+                (record.start, record.service_start, record.completion)
+            );
+            assert_eq!(decoded.overhead.to_bits(), record.overhead.to_bits());
+            assert!(decoded.failure == record.failure);
+            assert_eq!(decoded.worker, record.worker);
+            assert_eq!(decoded.clamped, record.clamped);
+        }
+        let clamped = TaskRecord {
+            start: u64::MAX,
+            service_start: 0,
+            completion: 1,
+            overhead: f64::MIN_POSITIVE,
+            failure: Some(Failure::Queueing),
+            worker: Some(u32::MAX - 1),
+            clamped: true,
+        };
+        assert_eq!(
+            TaskRecord::decode(&clamped.encode()).encode(),
+            clamped.encode()
+        );
+    }
+
+    #[test]
+    fn the_failed_records_are_ordered_after_the_successful_ones() {
+        let record = |overhead: f64, failure: Option<Failure>| TaskRecord {
+            start: 0,
+            service_start: 0,
+            completion: 0,
+            overhead,
+            failure,
+            worker: None,
+            clamped: false,
+        };
+        let fast_failure = record(0., Some(Failure::Io));
+        let slow_success = record(1., None);
+        assert_eq!(slow_success.order(&fast_failure), Ordering::Less);
+        assert_eq!(fast_failure.order(&slow_success), Ordering::Greater);
+        assert_eq!(record(0., None).order(&slow_success), Ordering::Less);
+        assert_eq!(
+            record(2., Some(Failure::Queueing)).order(&fast_failure),
+            Ordering::Greater
+        );
+        assert_eq!(slow_success.order(&record(1., None)), Ordering::Equal);
+    }
+
+    #[test]
+    fn the_merge_of_more_runs_than_its_fan_in_is_the_sort_in_memory() {
+        // up to 5000 runs of a record, i.e. two passes of merges before the overheads are merged
+        let mut tasks = records(5000);
+        for chunk in 1..=4 {
+            let name = format!("test_chunk_{}", chunk);
+            let mut spill = Spill::new(&env::temp_dir(), &name, Instant::now(), chunk)
+                .expect("Failed to create the spill");
+            tasks.iter().for_each(|stats| spill.push(stats));
+            let spilled = Overheads::Spilled(spill.finish().expect("Failed to merge the spill"));
+
+            let successes = crate::sort_records(&mut tasks);
+            let records = Overheads::Records(&tasks[..successes]);
+            assert_eq!(spilled.len(), 4250);
+            assert_eq!(spilled.len(), records.len());
+            assert!(spilled.iter().eq(records.iter()), "chunk = {}", chunk);
+            for ms in [0, 1, 5, 25, 59, 60].iter() {
+                let value = *ms as f64 / 1000.;
+                assert_eq!(
+                    spilled.count_at_most(value),
+                    records.count_at_most(value),
+                    "chunk = {}, {} ms",
+                    chunk,
+                    ms
+                );
+            }
+            assert_eq!(
+                spilled.percentile(PercentileMethod::NearestRank, 0.99),
+                records.percentile(PercentileMethod::NearestRank, 0.99)
+            );
+        }
+    }
+}
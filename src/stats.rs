@@ -0,0 +1,262 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// How a percentile is picked from a sample.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PercentileMethod {
+    /// The smallest value, such that at least `p` of the sample is less or equal to it.
+    /// Always one of the observed values.
+    NearestRank,
+    /// Linear interpolation between the closest ranks, i.e. `(n - 1) * p`.
+    Linear,
+}
+
+impl PercentileMethod {
+    /// `sorted` must be sorted, `p` is within `[0, 1]`. `None` if the sample is empty.
+    pub fn percentile(self, sorted: &[f64], p: f64) -> Option<f64> {
+        self.percentile_by(sorted, p, |v| *v)
+    }
+
+    /// Same as `percentile`, but for a sample sorted by `value`.
+    pub fn percentile_by<T>(self, sorted: &[T], p: f64, value: impl Fn(&T) -> f64) -> Option<f64> {
+        self.percentile_at(sorted.len(), p, |i| value(&sorted[i]))
+    }
+
+    /// Same as `percentile`, but for a sorted sample of `n` values, which are read by their rank,
+    /// e.g. from a file, `value(i)` is the `i`-th smallest one.
+    pub fn percentile_at(self, n: usize, p: f64, value: impl Fn(usize) -> f64) -> Option<f64> {
+        if n == 0 {
+            return None;
+        }
+        match self {
+            PercentileMethod::NearestRank => {
+                // the epsilon protects from rounding up e.g. `0.07 * 100 = 7.000000000000001`
+                let rank = (p * n as f64 - 1e-9).ceil() as usize;
+                Some(value(rank.clamp(1, n) - 1))
+            }
+            PercentileMethod::Linear => {
+                let position = p * (n - 1) as f64;
+                let lower = value(position.floor() as usize);
+                let upper = value(position.ceil() as usize);
+                Some(lower + (upper - lower) * position.fract())
+            }
+        }
+    }
+}
+
+// the lower edge of the first log bucket, s, and the buckets of a decade
+const LOG_BUCKET_MIN: f64 = 1e-5;
+const LOG_BUCKETS_PER_DECADE: usize = 10;
+/// The log-scaled buckets of the latencies from 10 µs to 100 s, 10 per decade.
+pub const LOG_BUCKETS: usize = 7 * LOG_BUCKETS_PER_DECADE;
+
+/// The log bucket of a latency, s. The lower and the higher ones are counted in the first and the last bucket.
+pub fn log_bucket(latency: f64) -> usize {
+    if latency <= LOG_BUCKET_MIN {
+        return 0;
+    }
+    let bucket = (latency / LOG_BUCKET_MIN).log10() * LOG_BUCKETS_PER_DECADE as f64;
+    (bucket as usize).min(LOG_BUCKETS - 1)
+}
+
+/// The lower edge of a log bucket, s, `log_bucket_edge(LOG_BUCKETS)` is the upper edge of the last one.
+pub fn log_bucket_edge(bucket: usize) -> f64 {
+    LOG_BUCKET_MIN * 10f64.powf(bucket as f64 / LOG_BUCKETS_PER_DECADE as f64)
+}
+
+/// A uniform random sample of at most `capacity` items of a stream (Algorithm R),
+/// i.e. every item of the stream has the same chance to be in the sample.
+pub struct Reservoir<T> {
+    capacity: usize,
+    seen: usize,
+    items: Vec<T>,
+    rng: StdRng,
+}
+
+impl<T> Reservoir<T> {
+    /// `None` keeps every item.
+    pub fn new(capacity: Option<usize>, rng: StdRng) -> Self {
+        Self {
+            capacity: capacity.unwrap_or(usize::MAX),
+            seen: 0,
+            items: vec![],
+            rng,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            let i = self.rng.gen_range(0..self.seen);
+            if i < self.capacity {
+                self.items[i] = item;
+            }
+        }
+    }
+
+    /// Preallocates the items of `expected` pushes, so they don't reallocate.
+    pub fn reserve(&mut self, expected: usize) {
+        self.items.reserve_exact(expected.min(self.capacity));
+    }
+
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.items)
+    }
+}
+
+/// A reproducible random stream of a subsystem of the run, e.g. `rng(seed, "reservoir")`.
+/// Every subsystem has its own stream, so random choices added to one don't change the others.
+pub fn rng(seed: u64, subsystem: &str) -> StdRng {
+    // FNV-1a, as `DefaultHasher` is not guaranteed to be the same between releases
+    let hash = subsystem.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    StdRng::seed_from_u64(seed ^ hash)
+}
+
+/// The mean and the (population) standard deviation, `(0, 0)` if there are no values.
+pub fn mean_std_dev(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0., 0.);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let deviation: f64 = values.iter().map(|v| (mean - v) * (mean - v)).sum();
+    (mean, (deviation / values.len() as f64).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_to_hundred() -> Vec<f64> {
+        (1..=100).map(f64::from).collect()
+    }
+
+    #[test]
+    fn nearest_rank_picks_an_observed_value() {
+        let sorted = one_to_hundred();
+        let percentile = |p| PercentileMethod::NearestRank.percentile(&sorted, p);
+        assert_eq!(percentile(0.), Some(1.));
+        assert_eq!(percentile(0.5), Some(50.));
+        assert_eq!(percentile(0.07), Some(7.));
+        assert_eq!(percentile(0.99), Some(99.));
+        assert_eq!(percentile(1.), Some(100.));
+    }
+
+    #[test]
+    fn linear_interpolates_between_the_closest_ranks() {
+        let sorted = one_to_hundred();
+        let percentile = |p| {
+            PercentileMethod::Linear
+                .percentile(&sorted, p)
+                .expect("The sample isn't empty")
+        };
+        assert_eq!(percentile(0.), 1.);
+        // `(n - 1) * p` is 49.5 and 98.01, between the ranks
+        assert!((percentile(0.5) - 50.5).abs() < 1e-9);
+        assert!((percentile(0.99) - 99.01).abs() < 1e-9);
+        assert_eq!(percentile(1.), 100.);
+    }
+
+    #[test]
+    fn an_empty_sample_has_no_percentiles() {
+        for method in [PercentileMethod::NearestRank, PercentileMethod::Linear].iter() {
+            assert_eq!(method.percentile(&[], 0.5), None);
+            assert_eq!(
+                method.percentile_at(0, 0.99, |_| unreachable!("An empty sample is never read")),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn every_percentile_of_a_single_value_is_the_value() {
+        for method in [PercentileMethod::NearestRank, PercentileMethod::Linear].iter() {
+            for &p in [0., 0.5, 0.99, 1.].iter() {
+                assert_eq!(method.percentile(&[42.], p), Some(42.));
+            }
+        }
+    }
+
+    #[test]
+    fn percentile_at_reads_the_values_by_their_rank() {
+        let sorted = one_to_hundred();
+        for method in [PercentileMethod::NearestRank, PercentileMethod::Linear].iter() {
+            for &p in [0., 0.25, 0.5, 0.9, 0.99, 0.999, 1.].iter() {
+                assert_eq!(
+                    method.percentile_at(sorted.len(), p, |i| sorted[i]),
+                    method.percentile(&sorted, p)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_reservoir_samples_the_stream_uniformly() {
+        const STREAM: usize = 100_000;
+        const CAPACITY: usize = 10_000;
+        const BUCKETS: usize = 10;
+        let mut reservoir = Reservoir::new(Some(CAPACITY), rng(42, "reservoir"));
+        for i in 0..STREAM {
+            reservoir.push(i);
+        }
+        let sample = reservoir.take();
+        assert_eq!(sample.len(), CAPACITY);
+        // the items of every tenth of the stream are as likely to be kept
+        let mut counts = [0; BUCKETS];
+        for i in sample {
+            counts[i * BUCKETS / STREAM] += 1;
+        }
+        let expected = (CAPACITY / BUCKETS) as f64;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+        // the critical value of 9 degrees of freedom at p = 0.001
+        assert!(
+            chi_square < 27.88,
+            "{:?}, chi-square {}",
+            counts,
+            chi_square
+        );
+    }
+
+    #[test]
+    fn the_percentiles_of_a_reservoir_track_the_exact_ones() {
+        // a skewed stream of latencies, sorted, so a sample biased towards either end shows
+        let stream: Vec<f64> = (1..=200_000).map(|i| (i as f64 / 1000.).powi(2)).collect();
+        let mut reservoir = Reservoir::new(Some(20_000), rng(7, "reservoir"));
+        for latency in &stream {
+            reservoir.push(*latency);
+        }
+        let mut sample = reservoir.take();
+        sample.sort_by(|a, b| a.partial_cmp(b).expect("No NaNs"));
+        for &p in [0.5, 0.9, 0.99].iter() {
+            let exact = PercentileMethod::NearestRank
+                .percentile(&stream, p)
+                .expect("The stream isn't empty");
+            let sampled = PercentileMethod::NearestRank
+                .percentile(&sample, p)
+                .expect("The sample isn't empty");
+            assert!(
+                (sampled / exact - 1.).abs() < 0.02,
+                "p{}: {} sampled, {} exact",
+                p * 100.,
+                sampled,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn a_reservoir_without_a_capacity_keeps_every_item() {
+        let mut reservoir = Reservoir::new(None, rng(42, "reservoir"));
+        for i in 0..1000 {
+            reservoir.push(i);
+        }
+        assert_eq!(reservoir.take(), (0..1000).collect::<Vec<_>>());
+    }
+}
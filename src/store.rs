@@ -0,0 +1,420 @@
+use crate::aggregator::SecondStats;
+use crate::metadata::RunMetadata;
+use crate::summary::Summary;
+use crate::{Mode, ModelConfig};
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "sqlite")]
+/// The schema by its version (`PRAGMA user_version`), a database is migrated
+/// by the scripts past its version when it's opened.
+const MIGRATIONS: &[&str] = &[
+    // 1
+    "CREATE TABLE runs (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        start_time TEXT NOT NULL,
+        mode TEXT NOT NULL,
+        rate INTEGER NOT NULL,
+        num_req INTEGER NOT NULL,
+        completed INTEGER NOT NULL,
+        failed INTEGER NOT NULL,
+        avg_rate REAL,
+        send_phase_s REAL NOT NULL,
+        drain_phase_s REAL NOT NULL,
+        interrupted INTEGER NOT NULL,
+        version TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        metadata TEXT NOT NULL
+    );
+    CREATE INDEX runs_by_name ON runs (name, id);
+    CREATE TABLE percentiles (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        percentile REAL NOT NULL,
+        latency_ms REAL NOT NULL,
+        PRIMARY KEY (run_id, percentile)
+    );
+    CREATE TABLE timeseries (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        second INTEGER NOT NULL,
+        sent INTEGER NOT NULL,
+        completed INTEGER NOT NULL,
+        failed INTEGER NOT NULL,
+        p50_ms REAL,
+        p90_ms REAL,
+        p99_ms REAL,
+        max_ms REAL,
+        queue_depth_mean REAL NOT NULL,
+        in_flight INTEGER NOT NULL,
+        PRIMARY KEY (run_id, second)
+    );",
+    // 2
+    "CREATE TABLE tags (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (run_id, key)
+    );
+    CREATE INDEX tags_by_key ON tags (key, value);",
+];
+
+#[cfg(feature = "sqlite")]
+/// Opens the database of `--sqlite`, creating it, or migrating it to the current schema.
+fn open(path: &Path) -> io::Result<Connection> {
+    let mut connection = Connection::open(path).map_err(other)?;
+    let version = connection
+        .query_row("PRAGMA user_version", params![], |row| row.get::<_, i64>(0))
+        .map_err(other)? as usize;
+    if version > MIGRATIONS.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the schema version {} is newer than this build supports ({})",
+                version,
+                MIGRATIONS.len()
+            ),
+        ));
+    }
+    migrate(&mut connection, version).map_err(other)?;
+    Ok(connection)
+}
+
+#[cfg(feature = "sqlite")]
+fn migrate(connection: &mut Connection, version: usize) -> rusqlite::Result<()> {
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.execute_batch(&format!("PRAGMA user_version = {}", i + 1))?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+/// Appends the run to the database at `path`, with its per-second aggregates, if any.
+/// Returns the id of the run.
+pub fn append(
+    path: &Path,
+    metadata: &RunMetadata,
+    summary: &Summary,
+    seconds: Option<&[SecondStats]>,
+) -> io::Result<i64> {
+    let mut connection = open(path)?;
+    insert(&mut connection, metadata, summary, seconds).map_err(other)
+}
+
+#[cfg(feature = "sqlite")]
+fn insert(
+    connection: &mut Connection,
+    metadata: &RunMetadata,
+    summary: &Summary,
+    seconds: Option<&[SecondStats]>,
+) -> rusqlite::Result<i64> {
+    let transaction = connection.transaction()?;
+    let config = &metadata.config;
+    transaction.execute(
+        "INSERT INTO runs (name, start_time, mode, rate, num_req, completed, failed, avg_rate,
+            send_phase_s, drain_phase_s, interrupted, version, hostname, metadata)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            config.name,
+            metadata.start_time,
+            mode(config),
+            config.rps as i64,
+            config.n_jobs as i64,
+            summary.total() as i64,
+            summary.failed() as i64,
+            summary.rate().map(|rate| rate.avg),
+            summary.phases().0.as_secs_f64(),
+            summary.phases().1.as_secs_f64(),
+            metadata.interrupted,
+            metadata.version,
+            metadata.hostname,
+            serde_json::to_string(metadata).expect("Metadata is serializable"),
+        ],
+    )?;
+    let run_id = transaction.last_insert_rowid();
+    for (key, value) in &config.tags {
+        transaction.execute(
+            "INSERT INTO tags (run_id, key, value) VALUES (?1, ?2, ?3)",
+            params![run_id, key, value],
+        )?;
+    }
+    for (p, latency) in summary.percentiles().iter().filter(|(_, v)| !v.is_nan()) {
+        transaction.execute(
+            "INSERT INTO percentiles (run_id, percentile, latency_ms) VALUES (?1, ?2, ?3)",
+            params![run_id, p, latency],
+        )?;
+    }
+    let ms = |v: Option<f64>| v.map(|v| v * 1000.);
+    for s in seconds.unwrap_or_default() {
+        transaction.execute(
+            "INSERT INTO timeseries (run_id, second, sent, completed, failed, p50_ms, p90_ms, p99_ms,
+                max_ms, queue_depth_mean, in_flight)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                run_id,
+                s.second as i64,
+                s.sent as i64,
+                s.completed as i64,
+                s.failed as i64,
+                ms(s.p50),
+                ms(s.p90),
+                ms(s.p99),
+                ms(s.max),
+                s.queue_depth,
+                s.in_flight as i64,
+            ],
+        )?;
+    }
+    transaction.commit()?;
+    Ok(run_id)
+}
+
+/// Prints the last `last` runs named `name`, if it's set, with all the `tags`, the oldest first.
+/// Without a name, the runs of all the names are printed, with their names.
+#[cfg(feature = "sqlite")]
+pub fn history(
+    path: &Path,
+    name: Option<&str>,
+    last: usize,
+    tags: &[(String, String)],
+) -> io::Result<()> {
+    if !path.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+    }
+    let connection = open(path)?;
+    let runs = query_runs(&connection, name, last, tags).map_err(other)?;
+    if runs.is_empty() {
+        let mut filter: Vec<String> = name
+            .map(|name| format!("named `{}`", name))
+            .into_iter()
+            .collect();
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            filter.push(format!("tagged {}", tags.join(" ")));
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no runs {}", filter.join(" and ")),
+        ));
+    }
+    let width = name_width(&runs);
+    let name_column = |name: &str| match width {
+        Some(width) => format!("{:<width$}  ", name, width = width),
+        None => String::new(),
+    };
+    println!(
+        "{}{:<24}  {:<10}  {:>8}  {:>9}  {:>7}  {:>10}  {:>10}  {:>10}",
+        name_column("name"),
+        "start_time",
+        "mode",
+        "rate",
+        "completed",
+        "failed",
+        "p50_ms",
+        "p99_ms",
+        "avg_rate"
+    );
+    let format = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.3}", v));
+    for run in runs.iter().rev() {
+        println!(
+            "{}{:<24}  {:<10}  {:>8}  {:>9}  {:>6.2}%  {:>10}  {:>10}  {:>10}{}{}",
+            name_column(&run.name),
+            run.start_time,
+            run.mode,
+            run.rate,
+            run.completed,
+            run.error_rate(),
+            format(run.percentile(50.)),
+            format(run.percentile(99.)),
+            format(run.avg_rate),
+            if run.interrupted { "  (partial)" } else { "" },
+            run.tags
+                .iter()
+                .map(|(key, value)| format!("  {}={}", key, value))
+                .collect::<String>()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+/// The latest run named `name`, if any.
+pub fn latest(path: &Path, name: &str) -> io::Result<Option<Run>> {
+    let connection = open(path)?;
+    let mut runs = query_runs(&connection, Some(name), 1, &[]).map_err(other)?;
+    Ok(runs.pop())
+}
+
+/// A run of the database.
+#[derive(Serialize, Deserialize)]
+pub struct Run {
+    pub name: String,
+    pub start_time: String,
+    /// E.g. `sync-64`, `async-1000` or `async`.
+    pub mode: String,
+    pub rate: i64,
+    pub completed: i64,
+    pub failed: i64,
+    /// (percentile, latency in ms) of the summary.
+    pub percentiles: Vec<(f64, f64)>,
+    pub avg_rate: Option<f64>,
+    /// The send and the drain phases, s.
+    pub duration: f64,
+    pub interrupted: bool,
+    /// (key, value) of `--tag`, by the key.
+    pub tags: Vec<(String, String)>,
+}
+
+impl Run {
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        self.percentiles
+            .iter()
+            .find(|(pp, _)| *pp == p)
+            .map(|(_, latency)| *latency)
+    }
+
+    /// The successful requests per second of the whole run, unlike `avg_rate`,
+    /// it covers the drain phase and the edges, so it's accurate for short runs too.
+    pub fn achieved_rate(&self) -> Option<f64> {
+        Some((self.completed - self.failed) as f64 / self.duration).filter(|_| self.duration > 0.)
+    }
+
+    /// %
+    pub fn error_rate(&self) -> f64 {
+        100. * self.failed as f64 / self.completed.max(1) as f64
+    }
+}
+
+#[cfg(feature = "sqlite")]
+/// The last `last` runs named `name`, if it's set, with all the `tags`, the latest first.
+fn query_runs(
+    connection: &Connection,
+    name: Option<&str>,
+    last: usize,
+    tags: &[(String, String)],
+) -> rusqlite::Result<Vec<Run>> {
+    let last = last as i64;
+    let mut conditions = vec![];
+    let mut values: Vec<&dyn ToSql> = vec![];
+    if let Some(name) = &name {
+        conditions.push("name = ?");
+        values.push(name);
+    }
+    for (key, value) in tags {
+        conditions.push("id IN (SELECT run_id FROM tags WHERE key = ? AND value = ?)");
+        values.push(key);
+        values.push(value);
+    }
+    values.push(&last);
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let mut statement = connection.prepare(&format!(
+        "SELECT id, start_time, mode, rate, completed, failed, avg_rate, interrupted,
+            send_phase_s + drain_phase_s, name
+         FROM runs {} ORDER BY id DESC LIMIT ?",
+        filter
+    ))?;
+    let mut percentiles = connection.prepare(
+        "SELECT percentile, latency_ms FROM percentiles WHERE run_id = ?1 ORDER BY percentile",
+    )?;
+    let mut run_tags =
+        connection.prepare("SELECT key, value FROM tags WHERE run_id = ?1 ORDER BY key")?;
+    let runs = statement
+        .query_map(&values, |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                Run {
+                    name: row.get(9)?,
+                    start_time: row.get(1)?,
+                    mode: row.get(2)?,
+                    rate: row.get(3)?,
+                    completed: row.get(4)?,
+                    failed: row.get(5)?,
+                    percentiles: vec![],
+                    avg_rate: row.get(6)?,
+                    duration: row.get(8)?,
+                    interrupted: row.get(7)?,
+                    tags: vec![],
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut result = Vec::with_capacity(runs.len());
+    for (id, mut run) in runs {
+        run.percentiles = percentiles
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        run.tags = run_tags
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        result.push(run);
+    }
+    Ok(result)
+}
+
+#[cfg(feature = "sqlite")]
+/// The width of the column of the names, if the runs are of different ones.
+fn name_width(runs: &[Run]) -> Option<usize> {
+    let first = &runs.first()?.name;
+    if runs.iter().all(|run| &run.name == first) {
+        return None;
+    }
+    runs.iter().map(|run| run.name.len()).max()
+}
+
+/// E.g. `sync-64`, `async-1000` (max in flight) or `async`.
+pub fn mode(config: &ModelConfig) -> String {
+    match (&config.mode, config.max_in_flight) {
+        (Mode::Sync(n_workers), _) => format!("sync-{}", n_workers),
+        (Mode::Async, Some(max_in_flight)) => format!("async-{}", max_in_flight),
+        (Mode::Async, None) => "async".to_string(),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn other(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+// Without the `sqlite` feature, the options, which use the database, are rejected by their validation.
+
+#[cfg(not(feature = "sqlite"))]
+pub fn append(
+    _path: &Path,
+    _metadata: &RunMetadata,
+    _summary: &Summary,
+    _seconds: Option<&[SecondStats]>,
+) -> io::Result<i64> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn history(
+    _path: &Path,
+    _name: Option<&str>,
+    _last: usize,
+    _tags: &[(String, String)],
+) -> io::Result<()> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn latest(_path: &Path, _name: &str) -> io::Result<Option<Run>> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn disabled() -> io::Error {
+    io::Error::other("the binary is built without the `sqlite` feature")
+}
@@ -0,0 +1,243 @@
+use crate::interrupt::Interrupt;
+use crate::report::{Chart, Figures};
+use crate::store::{self, Run};
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use toml::value::Table;
+use toml::Value;
+use tracing::{info, warn};
+
+// the key of the list of the scenarios, the other keys are shared by all of them
+const SCENARIOS: &str = "scenario";
+// the results of every scenario are appended here, so they can be compared
+const DATABASE: &str = "suite.db";
+// the percentiles of the curves, p0 and p100 don't fit the scale
+const CURVE_PERCENTILES: [f64; 5] = [50., 90., 99., 99.9, 99.99];
+
+/// A run of the suite: the options shared by all the scenarios, overridden by its own.
+struct Scenario {
+    name: String,
+    options: Table,
+}
+
+/// Runs every scenario of the suite at `path` in turn, each one in a subdirectory of `dir`
+/// named after it, and compares their results. The first Ctrl-C stops the suite after
+/// the running scenario. Returns the names of the failed scenarios.
+pub fn run(path: &Path, dir: &Path, figures: &Figures) -> io::Result<Vec<String>> {
+    let scenarios = read(path)?;
+    fs::create_dir_all(dir)?;
+    // the scenarios run in their own directories
+    let dir = dir.canonicalize()?;
+    let database = dir.join(DATABASE);
+    let binary = env::current_exe()?;
+    let interrupt = Interrupt::install();
+    let mut failed = vec![];
+    let mut runs = vec![];
+    for (i, scenario) in scenarios.iter().enumerate() {
+        if interrupt.is_set() {
+            warn!(
+                "The suite is interrupted, {} scenarios were skipped",
+                scenarios.len() - i
+            );
+            break;
+        }
+        let scenario_dir = dir.join(&scenario.name);
+        fs::create_dir_all(&scenario_dir)?;
+        let mut options = scenario.options.clone();
+        options.insert(
+            "sqlite".to_string(),
+            Value::String(database.to_string_lossy().into_owned()),
+        );
+        let config = format!("{}.toml", scenario.name);
+        fs::write(
+            scenario_dir.join(&config),
+            toml::to_string(&options).map_err(io::Error::other)?,
+        )?;
+        info!(
+            "Running the scenario {} ({} of {}) in {}",
+            scenario.name,
+            i + 1,
+            scenarios.len(),
+            scenario_dir.display()
+        );
+        let (status, run) = spawn(
+            Command::new(&binary)
+                .arg("--config")
+                .arg(&config)
+                .current_dir(&scenario_dir),
+            &database,
+            &scenario.name,
+        )?;
+        if !status.success() {
+            warn!("The scenario {} failed: {}", scenario.name, status);
+            failed.push(scenario.name.clone());
+        }
+        match run {
+            Some(run) => runs.push((scenario.name.as_str(), run)),
+            None => warn!("No results of the scenario {}", scenario.name),
+        }
+    }
+    if runs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no scenario has completed",
+        ));
+    }
+    let figure = dir.join("percentiles.png");
+    figures.render(&chart(&figure, &runs));
+    let table = table(&runs);
+    print!("{}", table);
+    let report = dir.join("comparison.md");
+    write_report(&report, path, &table, &runs, &figure)?;
+    info!("The comparison is written to {}", report.display());
+    Ok(failed)
+}
+
+/// Runs the model as a child process, which appends its run named `name` to `database`.
+/// Returns its exit status and the run, if any, e.g. not if its options are invalid.
+pub fn spawn(
+    command: &mut Command,
+    database: &Path,
+    name: &str,
+) -> io::Result<(ExitStatus, Option<Run>)> {
+    // the database may have results of the name of a previous run
+    let previous = store::latest(database, name)?.map(|run| run.start_time);
+    let status = tokio::task::block_in_place(|| command.status())?;
+    // an interrupted run is appended too
+    let run =
+        store::latest(database, name)?.filter(|run| Some(&run.start_time) != previous.as_ref());
+    Ok((status, run))
+}
+
+/// The scenarios of `[[scenario]]`, each one with a unique `name`.
+fn read(path: &Path) -> io::Result<Vec<Scenario>> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), message),
+        )
+    };
+    let mut shared = match fs::read_to_string(path)?.parse::<Value>() {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => unreachable!("A TOML document is a table"),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+    let tables = match shared.remove(SCENARIOS) {
+        Some(Value::Array(tables)) if !tables.is_empty() => tables,
+        _ => {
+            return Err(invalid(format!(
+                "no scenarios, e.g. [[{}]] name = \"async\" mode = \"async\"",
+                SCENARIOS
+            )))
+        }
+    };
+    let mut scenarios: Vec<Scenario> = vec![];
+    for (i, table) in tables.into_iter().enumerate() {
+        let table = match table {
+            Value::Table(table) => table,
+            _ => return Err(invalid(format!("scenario {} is not a table", i + 1))),
+        };
+        let name = match table.get("name").and_then(Value::as_str) {
+            Some(name) => name.to_string(),
+            None => return Err(invalid(format!("scenario {} has no name", i + 1))),
+        };
+        if scenarios.iter().any(|scenario| scenario.name == name) {
+            return Err(invalid(format!("the name {} is used twice", name)));
+        }
+        let mut options = shared.clone();
+        options.extend(table);
+        scenarios.push(Scenario { name, options });
+    }
+    Ok(scenarios)
+}
+
+/// The latencies of the printed percentiles of every run, on the scale of the "nines".
+fn chart(path: &Path, runs: &[(&str, Run)]) -> Chart {
+    // p99 -> 2, p99.9 -> 3, etc.
+    let nines = |p: f64| -(1. - p / 100.).log10();
+    // an absolute path replaces the current directory
+    let mut chart = Chart::new(
+        path.to_string_lossy().into_owned(),
+        "Percentile",
+        "Latency, ms",
+    )
+    .x_ticks(
+        CURVE_PERCENTILES
+            .iter()
+            .map(|p| (nines(*p), format!("{}%", p)))
+            .collect(),
+    )
+    .log_y();
+    for (name, run) in runs {
+        let (x, y) = CURVE_PERCENTILES
+            .iter()
+            .filter_map(|p| run.percentile(*p).map(|latency| (nines(*p), latency)))
+            .unzip();
+        chart = chart.line(Some(name), x, y);
+    }
+    chart
+}
+
+/// A markdown table of the runs, e.g. `| async | async | 30000 | 0.968 | 1.237 | 0.00% | 1000.000 |`.
+fn table(runs: &[(&str, Run)]) -> String {
+    let format = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.3}", v));
+    let mut table = String::from(
+        "| scenario | mode | completed | p50, ms | p99, ms | errors | avg rate |\n\
+         |---|---|---:|---:|---:|---:|---:|\n",
+    );
+    for (name, run) in runs {
+        table.push_str(&format!(
+            "| {}{} | {} | {} | {} | {} | {:.2}% | {} |\n",
+            name,
+            if run.interrupted { " (partial)" } else { "" },
+            run.mode,
+            run.completed,
+            format(run.percentile(50.)),
+            format(run.percentile(99.)),
+            run.error_rate(),
+            format(run.avg_rate)
+        ));
+    }
+    table
+}
+
+fn write_report(
+    path: &Path,
+    suite: &Path,
+    table: &str,
+    runs: &[(&str, Run)],
+    figure: &Path,
+) -> io::Result<()> {
+    let mut out = fs::File::create(path)?;
+    writeln!(out, "# {}\n", suite.display())?;
+    writeln!(out, "{}", table)?;
+    if figure.exists() {
+        writeln!(out, "![percentiles](percentiles.png)\n")?;
+    }
+    // the lowest p99 with no errors is the best one
+    let best = runs
+        .iter()
+        .filter(|(_, run)| run.failed == 0)
+        .filter_map(|(name, run)| run.percentile(99.).map(|p99| (name, p99)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).expect("Latencies are not NaN"));
+    if let Some((name, p99)) = best {
+        writeln!(
+            out,
+            "The lowest p99 without errors: {} ({:.3} ms).\n",
+            name, p99
+        )?;
+    }
+    writeln!(out, "The outputs of every scenario are in its directory:\n")?;
+    for (name, run) in runs {
+        writeln!(
+            out,
+            "- [{}]({}/), started at {}",
+            name, name, run.start_time
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,652 @@
+use crate::aggregator::RunResults;
+use crate::clock::Calibration;
+use crate::collector::{self, StatsChannel};
+use crate::priority;
+use crate::spill::Overheads;
+use crate::{CoarseClockConfig, ModelConfig, StatsBatch, Target};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io;
+use std::time::Duration;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+pub const PRINTED_PERCENTILES: [f64; 8] = [0., 50., 90., 95., 99., 99.9, 99.99, 100.];
+const FAILURE_PERCENTILES: [f64; 5] = [0., 50., 90., 99., 100.];
+
+/// Limits checked against the results of the run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Max p50 latency, ms.
+    pub p50: Option<u64>,
+    /// Max p99 latency, ms.
+    pub p99: Option<u64>,
+    /// Max share of failed requests, %.
+    pub errors: Option<f64>,
+    /// Min Apdex score.
+    pub apdex: Option<f64>,
+}
+
+impl Thresholds {
+    pub fn is_empty(&self) -> bool {
+        self.p50.is_none() && self.p99.is_none() && self.errors.is_none() && self.apdex.is_none()
+    }
+}
+
+/// The rate of successful requests per second.
+pub struct Rate {
+    pub avg: f64,
+    pub std_dev: f64,
+    /// The seconds of the run covered by the average, inclusive.
+    pub window: (u64, u64),
+    /// The seconds of the run with successful requests, inclusive.
+    pub run: (u64, u64),
+}
+
+/// The tasks of a class.
+struct ClassSummary {
+    class: String,
+    total: usize,
+    failed: usize,
+    /// (p50, p99) in ms, if any succeeded.
+    percentiles: Option<(f64, f64)>,
+}
+
+/// The results of the run, printed once it's complete.
+pub struct Summary {
+    /// (percentile, latency in ms)
+    percentiles: Vec<(f64, f64)>,
+    rate: Option<Rate>,
+    /// The durations of the send and the drain phases.
+    phases: (Duration, Duration),
+    failed: usize,
+    /// Failed requests, which would be in time if they hadn't waited in the queue.
+    queueing_failures: usize,
+    /// Failed requests to the target, e.g. refused connections.
+    io_failures: usize,
+    total: usize,
+    /// ms
+    timeout: u64,
+    /// The number of negative overheads clamped to zero.
+    clamped: usize,
+    /// (T in ms, score)
+    apdex: Option<(u64, f64)>,
+    /// The number of kept per-task records, if sampling was applied.
+    sample_size: Option<usize>,
+    /// The number of the successful requests of `--spill-dir`, if the percentiles cover all of them.
+    spilled: Option<usize>,
+    /// (what, count, p50, p99, max in ms) of the setups of the requests to the target, if any,
+    /// e.g. `Connects`.
+    setups: Option<(&'static str, u64, f64, f64, f64)>,
+    /// The labeled tasks of `--stdin-tasks`.
+    classes: Vec<ClassSummary>,
+    /// (files, bytes per file, fsync) written by `--target file`, timed out requests included.
+    written: Option<(usize, usize, bool)>,
+    /// (percentile, latency in ms) of failed requests, queueing included, if all of them failed.
+    failure_latencies: Option<Vec<(f64, f64)>>,
+    /// (sent, planned) requests, if the run was interrupted.
+    partial: Option<(usize, usize)>,
+    /// How long the load generator was behind schedule, s.
+    generator_limited: f64,
+    /// The run was sampled by `--profile`.
+    profiled: bool,
+    /// (nice value of the sync workers, number of the busy threads of the background load).
+    priorities: (Option<i32>, usize),
+    /// The core of each sync worker of `--pin-threads`, by its index.
+    pinned: Option<Vec<usize>>,
+    /// The time of recording a completion on average, and the batches of the sync workers, if any.
+    collection: Option<(Duration, Option<StatsBatch>)>,
+    /// The channel of the sync workers, and the cost of each one, calibrated with zero-cost tasks.
+    channels: Option<(StatsChannel, Vec<(StatsChannel, Duration)>)>,
+    /// The timestamps of `--coarse-clock`, as calibrated before the run.
+    coarse_clock: Option<(CoarseClockConfig, Calibration)>,
+    /// The wall-clock time of the simulation of `--engine des`.
+    simulated: Option<Duration>,
+    /// (produced, skipped) figures, unless `--no-plots`.
+    plots: Option<(Vec<&'static str>, Vec<&'static str>)>,
+    thresholds: Thresholds,
+}
+
+impl Summary {
+    /// `overheads` are of the successful tasks of `results.samples`, or of all of them, if they were spilled.
+    /// If they don't cover all the tasks, percentiles and Apdex come from the HDR histogram instead.
+    pub fn new(
+        config: &ModelConfig,
+        overheads: &Overheads,
+        results: &RunResults,
+        rate: Option<Rate>,
+        phases: (Duration, Duration),
+        interrupted: bool,
+        generator_limited: &[(f64, f64)],
+    ) -> Self {
+        // the spill has all the records of this run, but not the ones of a resumed checkpoint
+        let spilled = match overheads {
+            Overheads::Spilled(_) => {
+                Some(overheads.len()).filter(|n| *n == results.completed - results.failed)
+            }
+            Overheads::Records(_) => None,
+        };
+        let sampled = results.is_sampled() && spilled.is_none();
+        let histogram = &results.histogram;
+        let percentiles = PRINTED_PERCENTILES
+            .iter()
+            .map(|p| {
+                let value = if !sampled {
+                    overheads.percentile(config.percentile_method, p / 100.)
+                } else if histogram.is_empty() {
+                    None
+                } else {
+                    Some(histogram.value_at_quantile(p / 100.) as f64 / 1_000_000.)
+                };
+                (*p, value.unwrap_or(f64::NAN) * 1000.)
+            })
+            .collect();
+        // failed requests are "frustrated"
+        let apdex = config.apdex.map(|t| {
+            let (satisfied, tolerating) = if sampled {
+                let t_us = t * 1000;
+                (
+                    histogram.count_between(0, t_us),
+                    histogram.count_between(t_us + 1, 4 * t_us),
+                )
+            } else {
+                let t_s = t as f64 / 1000.;
+                let satisfied = overheads.count_at_most(t_s);
+                (
+                    satisfied as u64,
+                    (overheads.count_at_most(4. * t_s) - satisfied) as u64,
+                )
+            };
+            let score =
+                (satisfied as f64 + tolerating as f64 / 2.) / results.completed.max(1) as f64;
+            (t, score)
+        });
+        Self {
+            percentiles,
+            rate,
+            phases,
+            failed: results.failed,
+            queueing_failures: results.queueing_failures,
+            io_failures: results.io_failures,
+            total: results.completed,
+            timeout: config.timeout,
+            clamped: results.clamped,
+            apdex,
+            setups: if results.setups.is_empty() {
+                None
+            } else {
+                let ms = |micros: u64| micros as f64 / 1000.;
+                Some((
+                    config.target.setup(),
+                    results.setups.len(),
+                    ms(results.setups.value_at_quantile(0.5)),
+                    ms(results.setups.value_at_quantile(0.99)),
+                    ms(results.setups.max()),
+                ))
+            },
+            classes: results
+                .classes
+                .iter()
+                .map(|(class, stats)| {
+                    let ms = |micros: u64| micros as f64 / 1000.;
+                    let percentiles = if stats.histogram.is_empty() {
+                        None
+                    } else {
+                        Some((
+                            ms(stats.histogram.value_at_quantile(0.5)),
+                            ms(stats.histogram.value_at_quantile(0.99)),
+                        ))
+                    };
+                    ClassSummary {
+                        class: class.to_string(),
+                        total: stats.completed,
+                        failed: stats.failed,
+                        percentiles,
+                    }
+                })
+                .collect(),
+            written: match &config.target {
+                Target::File { size, fsync, .. } => {
+                    Some((results.completed - results.io_failures, *size, *fsync))
+                }
+                _ => None,
+            },
+            sample_size: if results.is_sampled() {
+                Some(results.samples.len())
+            } else {
+                None
+            },
+            spilled,
+            failure_latencies: if overheads.is_empty() && results.failed > 0 {
+                Some(failure_latencies(config, results))
+            } else {
+                None
+            },
+            partial: if interrupted {
+                Some((results.sent, config.n_jobs))
+            } else {
+                None
+            },
+            generator_limited: generator_limited.iter().map(|(from, to)| to - from).sum(),
+            profiled: config.profile,
+            priorities: (config.worker_nice, config.background_load),
+            pinned: None,
+            collection: results
+                .collection
+                .map(|collection| (collection, config.stats_batch)),
+            channels: None,
+            coarse_clock: None,
+            simulated: None,
+            plots: None,
+            thresholds: config.thresholds.clone(),
+        }
+    }
+
+    /// Lists the figures of `--plots`, which were produced, and the ones skipped,
+    /// i.e. without enough data, or failed.
+    pub fn plots(mut self, produced: Vec<&'static str>, skipped: Vec<&'static str>) -> Self {
+        self.plots = Some((produced, skipped));
+        self
+    }
+
+    /// Lists the cores the sync workers were pinned to.
+    pub fn pinned(mut self, cores: Vec<usize>) -> Self {
+        self.pinned = Some(cores);
+        self
+    }
+
+    /// Compares the costs of the channels, which the sync workers could deliver their completions by.
+    pub fn stats_channels(
+        mut self,
+        channel: StatsChannel,
+        costs: Vec<(StatsChannel, Duration)>,
+    ) -> Self {
+        self.channels = Some((channel, costs));
+        self
+    }
+
+    /// The run was simulated in `wall` time, so the cost of collecting its stats is left out.
+    pub fn simulated(mut self, wall: Duration) -> Self {
+        self.simulated = Some(wall);
+        self.collection = None;
+        self
+    }
+
+    /// Reports the overhead and the lag of the coarse clock.
+    pub fn coarse_clock(mut self, coarse: CoarseClockConfig, calibration: Calibration) -> Self {
+        self.coarse_clock = Some((coarse, calibration));
+        self
+    }
+
+    #[cfg(any(test, feature = "sqlite"))]
+    /// (percentile, latency in ms), NaN if there were no successful requests.
+    pub fn percentiles(&self) -> &[(f64, f64)] {
+        &self.percentiles
+    }
+
+    #[cfg(any(test, feature = "sqlite"))]
+    pub fn rate(&self) -> Option<&Rate> {
+        self.rate.as_ref()
+    }
+
+    #[cfg(any(test, feature = "sqlite"))]
+    pub fn phases(&self) -> (Duration, Duration) {
+        self.phases
+    }
+
+    #[cfg(any(test, feature = "sqlite"))]
+    /// The number of the completed requests, the failed ones included.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    #[cfg(any(test, feature = "sqlite"))]
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    fn error_rate(&self) -> f64 {
+        100. * self.failed as f64 / self.total.max(1) as f64
+    }
+
+    /// The latency of an exact printed percentile, NaN if there were no successful requests.
+    fn latency(&self, p: f64) -> f64 {
+        self.percentiles
+            .iter()
+            .find(|(pp, _)| *pp == p)
+            .map(|(_, v)| *v)
+            .expect("Only printed percentiles can be checked")
+    }
+
+    /// Human-readable descriptions of the violated thresholds, e.g. `p99 312.412ms > 250ms`.
+    pub fn failures(&self) -> Vec<String> {
+        let mut failures = vec![];
+        for (p, limit) in &[(50., self.thresholds.p50), (99., self.thresholds.p99)] {
+            if let Some(limit) = limit {
+                let value = self.latency(*p);
+                if value.is_nan() {
+                    failures.push(format!("p{} n/a (no successful requests)", p));
+                } else if value > *limit as f64 {
+                    failures.push(format!("p{} {:.3}ms > {}ms", p, value, limit));
+                }
+            }
+        }
+        if let Some(limit) = self.thresholds.errors {
+            if self.error_rate() > limit {
+                failures.push(format!("errors {:.3}% > {}%", self.error_rate(), limit));
+            }
+        }
+        if let (Some(limit), Some((_, score))) = (self.thresholds.apdex, self.apdex) {
+            if score < limit {
+                failures.push(format!("apdex {:.3} < {}", score, limit));
+            }
+        }
+        failures
+    }
+
+    pub fn print(&self, color: ColorChoice) -> io::Result<()> {
+        self.write(&mut StandardStream::stdout(color))
+    }
+
+    pub fn write(&self, out: &mut impl WriteColor) -> io::Result<()> {
+        match self.partial {
+            // `--stdin-tasks` plans none
+            Some((sent, 0)) => writeln!(
+                out,
+                "PARTIAL RUN: interrupted after {} requests were sent",
+                sent
+            )?,
+            Some((sent, planned)) => writeln!(
+                out,
+                "PARTIAL RUN: interrupted after {} of {} requests were sent",
+                sent, planned
+            )?,
+            None => {}
+        }
+        if self.generator_limited > 0. {
+            // the results may describe the load generator rather than the model
+            write_checked(
+                out,
+                &format!(
+                    "CAVEAT: the load generator was behind schedule for {:.1}s of the run, the rate wasn't achieved",
+                    self.generator_limited
+                ),
+                Some(false),
+            )?;
+        }
+        if self.profiled {
+            write_checked(
+                out,
+                "CAVEAT: the run was sampled by a CPU profiler (--profile), the latencies include its overhead",
+                Some(false),
+            )?;
+        }
+        if self.failed == self.total {
+            writeln!(out, "Latencies: n/a (no successful requests)")?;
+        } else {
+            writeln!(out, "Latencies:")?;
+        }
+        for (p, value) in self.percentiles.iter().filter(|(_, v)| !v.is_nan()) {
+            let limit = if *p == 50. {
+                self.thresholds.p50
+            } else if *p == 99. {
+                self.thresholds.p99
+            } else {
+                None
+            };
+            write_checked(
+                out,
+                &format!("p{:.3} - {:.3} ms", p, value),
+                limit.map(|limit| *value <= limit as f64),
+            )?;
+        }
+        if let Some((what, count, p50, p99, max)) = self.setups {
+            writeln!(
+                out,
+                "{} (included in the latencies): {}, p50 - {:.3} ms, p99 - {:.3} ms, max - {:.3} ms",
+                what, count, p50, p99, max
+            )?;
+        }
+        for class in &self.classes {
+            let latencies = match class.percentiles {
+                Some((p50, p99)) => format!("p50 - {:.3} ms, p99 - {:.3} ms", p50, p99),
+                None => "latencies n/a".to_string(),
+            };
+            writeln!(
+                out,
+                "Class {}: {} requests, {} failed, {}",
+                class.class, class.total, class.failed, latencies
+            )?;
+        }
+        if let Some((files, size, fsync)) = self.written {
+            writeln!(
+                out,
+                "Written: {:.1} MiB in {} files of {} bytes{}",
+                (files * size) as f64 / (1024. * 1024.),
+                files,
+                size,
+                if fsync { ", fsync'ed" } else { "" }
+            )?;
+        }
+        match (self.sample_size, self.spilled) {
+            (Some(sample_size), Some(spilled)) => writeln!(
+                out,
+                "Spilled: the percentiles and their figures cover all {} successful requests, {} of {} requests are used for the timeline",
+                spilled, sample_size, self.total
+            )?,
+            (Some(sample_size), None) => writeln!(
+                out,
+                "Sampled: {} of {} requests are used for the figures",
+                sample_size, self.total
+            )?,
+            _ => {}
+        }
+        let timed_out = self.failed - self.io_failures;
+        if timed_out > 0 {
+            // success used to be decided by the nominal cost, which ignored the time in the queue
+            writeln!(
+                out,
+                "Timed out (measured latency, queueing included, > {} ms): {} of {} - slow backend: {}, overloaded queue: {}",
+                self.timeout,
+                timed_out,
+                self.total,
+                timed_out - self.queueing_failures,
+                self.queueing_failures
+            )?;
+        }
+        if self.io_failures > 0 {
+            writeln!(
+                out,
+                "Failed requests to the target: {} of {}",
+                self.io_failures, self.total
+            )?;
+        }
+        if let Some(failure_latencies) = &self.failure_latencies {
+            let percentiles: Vec<String> = failure_latencies
+                .iter()
+                .map(|(p, value)| format!("p{} - {:.3} ms", p, value))
+                .collect();
+            writeln!(out, "Failed latencies: {}", percentiles.join(", "))?;
+            let cause = if self.io_failures * 2 > self.failed {
+                "the requests to the target failed, see the log with -v"
+            } else if self.queueing_failures * 2 > self.failed {
+                "overloaded queue, the requests waited too long before being served"
+            } else {
+                "slow backend, the service time alone exceeds the timeout"
+            };
+            writeln!(out, "Most common cause: {}", cause)?;
+        }
+        if self.clamped > 0 {
+            writeln!(
+                out,
+                "Clamped negative overheads: {} of {} requests",
+                self.clamped, self.total
+            )?;
+        }
+        if let Some(rate) = &self.rate {
+            writeln!(
+                out,
+                "Avg rate: {:.3}, StdDev: {:.3} (over seconds {}-{} of {}-{})",
+                rate.avg, rate.std_dev, rate.window.0, rate.window.1, rate.run.0, rate.run.1
+            )?;
+        }
+        writeln!(
+            out,
+            "Send phase: {:.3}s, drain phase: {:.3}s",
+            self.phases.0.as_secs_f64(),
+            self.phases.1.as_secs_f64()
+        )?;
+        let mut priorities = vec![];
+        if let Some(nice) = self.priorities.0 {
+            priorities.push(format!("the workers at nice {}", nice));
+        }
+        if self.priorities.1 > 0 {
+            priorities.push(format!(
+                "{} busy background threads at nice {}",
+                self.priorities.1,
+                priority::BACKGROUND_NICE
+            ));
+        }
+        if !priorities.is_empty() {
+            writeln!(out, "Priorities: {}", priorities.join(", "))?;
+        }
+        if let Some(cores) = &self.pinned {
+            let cores: Vec<String> = cores.iter().map(|core| core.to_string()).collect();
+            writeln!(out, "Pinned cores (by worker): {}", cores.join(", "))?;
+        }
+        if let Some((collection, batch)) = &self.collection {
+            writeln!(
+                out,
+                "Stats collection: {:.3} µs per task, {}",
+                collection.as_secs_f64() * 1e6,
+                match batch {
+                    Some(batch) => format!(
+                        "in batches of up to {} completions, flushed every {} ms",
+                        batch.size, batch.flush_every
+                    ),
+                    None => "one completion at a time".to_string(),
+                }
+            )?;
+        }
+        if let Some((channel, costs)) = &self.channels {
+            writeln!(
+                out,
+                "Stats channels (calibrated with zero-cost tasks, {} in this run): {}",
+                channel.name(),
+                collector::format_costs(costs)
+            )?;
+        }
+        if let Some((coarse, calibration)) = &self.coarse_clock {
+            writeln!(
+                out,
+                "Coarse clock of {} ms ({}): {:.1} ns per timestamp vs {:.1} ns of Instant::now(), \
+                 lag {:.3} ms on average, {:.3} ms max",
+                coarse.resolution,
+                if coarse.full {
+                    "the latencies too"
+                } else {
+                    "the seconds of the events"
+                },
+                calibration.coarse_cost.as_secs_f64() * 1e9,
+                calibration.precise_cost.as_secs_f64() * 1e9,
+                calibration.mean_lag.as_secs_f64() * 1000.,
+                calibration.max_lag.as_secs_f64() * 1000.
+            )?;
+        }
+        if let Some(wall) = self.simulated {
+            writeln!(
+                out,
+                "Simulated (--engine des) in {:.3}s: the queueing of the tasks only, \
+                 without the overheads of the threads and the timers",
+                wall.as_secs_f64()
+            )?;
+        }
+        if let Some((produced, skipped)) = &self.plots {
+            let list = |plots: &[&str]| {
+                if plots.is_empty() {
+                    "none".to_string()
+                } else {
+                    plots.join(", ")
+                }
+            };
+            writeln!(
+                out,
+                "Plots: {} (skipped: {})",
+                list(produced),
+                list(skipped)
+            )?;
+        }
+        if let Some(limit) = self.thresholds.errors {
+            write_checked(
+                out,
+                &format!(
+                    "Errors: {} of {} ({:.3}%)",
+                    self.failed,
+                    self.total,
+                    self.error_rate()
+                ),
+                Some(self.error_rate() <= limit),
+            )?;
+        }
+        if let Some((t, score)) = self.apdex {
+            write_checked(
+                out,
+                &format!("Apdex (T = {} ms): {:.3}", t, score),
+                self.thresholds.apdex.map(|limit| score >= limit),
+            )?;
+        }
+        if !self.thresholds.is_empty() {
+            let failures = self.failures();
+            if failures.is_empty() {
+                write_checked(out, "PASSED", Some(true))?;
+            } else {
+                write_checked(
+                    out,
+                    &format!("FAILED: {}", failures.join(", ")),
+                    Some(false),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The full latencies (queueing included) of the failed requests of the sample.
+fn failure_latencies(config: &ModelConfig, results: &RunResults) -> Vec<(f64, f64)> {
+    let mut latencies: Vec<f64> = results
+        .samples
+        .iter()
+        .filter(|s| !s.success())
+        .map(|s| s.completion_time.duration_since(s.start_time).as_secs_f64())
+        .collect();
+    latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    FAILURE_PERCENTILES
+        .iter()
+        .map(|p| {
+            let value = config.percentile_method.percentile(&latencies, p / 100.);
+            (*p, value.unwrap_or(f64::NAN) * 1000.)
+        })
+        .collect()
+}
+
+/// Green if passed, red if failed, no color if not checked.
+fn write_checked(out: &mut impl WriteColor, line: &str, passed: Option<bool>) -> io::Result<()> {
+    match passed {
+        Some(passed) => {
+            let color = if passed { Color::Green } else { Color::Red };
+            out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+            write!(out, "{}", line)?;
+            out.reset()?;
+            writeln!(out)
+        }
+        None => writeln!(out, "{}", line),
+    }
+}
+
+/// Colors are used only if stdout is a TTY, and neither `--no-color` nor `NO_COLOR` is set.
+pub fn color_choice(no_color: bool) -> ColorChoice {
+    if no_color || env::var_os("NO_COLOR").is_some() || !atty::is(atty::Stream::Stdout) {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Auto
+    }
+}
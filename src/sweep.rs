@@ -0,0 +1,414 @@
+use crate::config_file;
+use crate::interrupt::Interrupt;
+use crate::report::{Chart, Figures};
+use crate::store::Run;
+use crate::suite;
+use crate::ModelConfig;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+/// A run of the model with a value of the swept parameter.
+pub struct Trial {
+    pub value: usize,
+    pub run: Run,
+}
+
+/// A parameter of the model, e.g. the number of threads, swept over its values:
+/// a trial per value, each one a run of the model with the options of the command line.
+pub struct Sweep<'a> {
+    /// E.g. `threads`, the trials are named `{name}_{parameter}_{value}`.
+    pub parameter: &'static str,
+    /// E.g. `Threads`.
+    pub label: &'static str,
+    pub config: &'a ModelConfig,
+    /// The knee is also the first trial with p99 above it, ms, see `knee`.
+    pub max_p99: Option<u64>,
+}
+
+/// A trial saved as `sweep_{parameter}_{name}/{trial}.json`, so the curve can be charted again
+/// after the fact, see `plot`.
+#[derive(Serialize, Deserialize)]
+struct SavedTrial {
+    /// Of the sweep.
+    name: String,
+    parameter: String,
+    /// The order of the trial in the sweep.
+    index: usize,
+    value: usize,
+    max_p99: Option<u64>,
+    run: Run,
+}
+
+impl Sweep<'_> {
+    /// Runs a trial per value in turn, `args` are the arguments of a value after the options,
+    /// e.g. `sync --threads=4`. The trials have the same seed, so the same schedule of the tasks,
+    /// and are appended to `--sqlite`, `sweep_{name}.db` by default.
+    /// The first Ctrl-C stops the sweep after the running trial, which is left out as a partial one.
+    /// Saves every trial to `sweep_{parameter}_{name}/`, then prints them and writes them
+    /// to `sweep_{parameter}_{name}.csv`.
+    pub fn run(
+        &self,
+        values: &[usize],
+        args: impl Fn(usize) -> Vec<String>,
+    ) -> io::Result<Vec<Trial>> {
+        let name = &self.config.name;
+        let database = self.config.sqlite.as_ref().map_or_else(
+            || PathBuf::from(format!("sweep_{}.db", name)),
+            PathBuf::from,
+        );
+        let binary = env::current_exe()?;
+        let command_line: Vec<String> = env::args().collect();
+        let (options, _) = config_file::split(&command_line);
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let interrupt = Interrupt::install();
+        let mut trials = vec![];
+        for (i, value) in values.iter().enumerate() {
+            if interrupt.is_set() {
+                warn!(
+                    "The sweep is interrupted, {} trials were skipped",
+                    values.len() - i
+                );
+                break;
+            }
+            let trial = format!("{}_{}_{}", name, self.parameter, value);
+            info!(
+                "Running the trial {} ({} of {})",
+                trial,
+                i + 1,
+                values.len()
+            );
+            // the later options override the ones of the command line
+            let (status, run) = suite::spawn(
+                Command::new(&binary)
+                    .args(options)
+                    .arg(format!("--name={}", trial))
+                    .arg(format!("--seed={}", self.config.seed))
+                    .arg(format!("--sqlite={}", database.display()))
+                    .args(args(*value)),
+                &database,
+                &trial,
+            )?;
+            match run {
+                Some(run) if !run.interrupted => {
+                    let saved = SavedTrial {
+                        name: name.clone(),
+                        parameter: self.parameter.to_string(),
+                        index: i,
+                        value: *value,
+                        max_p99: self.max_p99,
+                        run,
+                    };
+                    let json = serde_json::to_string_pretty(&saved).expect("Trial is serializable");
+                    fs::write(dir.join(format!("{}.json", trial)), json)?;
+                    trials.push(Trial {
+                        value: *value,
+                        run: saved.run,
+                    });
+                }
+                Some(_) => warn!("The trial {} is partial, it's left out", trial),
+                None => warn!("The trial {} failed: {}", trial, status),
+            }
+        }
+        if trials.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no trial has completed",
+            ));
+        }
+
+        print!("{}", self.table(&trials));
+        let csv_path = Path::new(".").join(self.file_name("csv"));
+        self.write_csv(&csv_path, &trials)?;
+        info!(
+            "{} trials are written to {}",
+            trials.len(),
+            csv_path.display()
+        );
+        Ok(trials)
+    }
+
+    /// E.g. `sweep_threads_{name}.png`.
+    pub fn file_name(&self, extension: &str) -> String {
+        format!(
+            "sweep_{}_{}.{}",
+            self.parameter, self.config.name, extension
+        )
+    }
+
+    /// The saved trials, e.g. `sweep_threads_{name}/`.
+    fn dir(&self) -> PathBuf {
+        Path::new(".").join(format!("sweep_{}_{}", self.parameter, self.config.name))
+    }
+
+    /// A row per trial, e.g. `4  1000  998.000  0.00%  20.968  21.237`,
+    /// the rate is left out of a sweep of the rate.
+    fn table(&self, trials: &[Trial]) -> String {
+        let format = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.3}", v));
+        let rate = |rate: &dyn std::fmt::Display| {
+            if self.parameter == "rate" {
+                String::new()
+            } else {
+                format!("  {:>8}", rate)
+            }
+        };
+        let mut table = format!(
+            "{:>10}{}  {:>10}  {:>7}  {:>10}  {:>10}\n",
+            self.parameter,
+            rate(&"rate"),
+            "achieved",
+            "failed",
+            "p50_ms",
+            "p99_ms"
+        );
+        for trial in trials {
+            table.push_str(&format!(
+                "{:>10}{}  {:>10}  {:>6.2}%  {:>10}  {:>10}\n",
+                trial.value,
+                rate(&trial.run.rate),
+                format(trial.run.achieved_rate()),
+                trial.run.error_rate(),
+                format(trial.run.percentile(50.)),
+                format(trial.run.percentile(99.))
+            ));
+        }
+        table
+    }
+
+    fn write_csv(&self, path: &Path, trials: &[Trial]) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        // the rate is a column of every sweep
+        let parameter = if self.parameter == "rate" {
+            String::new()
+        } else {
+            format!("{},", self.parameter)
+        };
+        writeln!(
+            out,
+            "{}name,rate,achieved_rate,avg_rate,completed,failed,p50_ms,p90_ms,p99_ms",
+            parameter
+        )?;
+        let format = |v: Option<f64>| v.map_or(String::new(), |v| format!("{:.3}", v));
+        for trial in trials {
+            writeln!(
+                out,
+                "{}{}_{}_{},{},{},{},{},{},{},{},{}",
+                if parameter.is_empty() {
+                    String::new()
+                } else {
+                    format!("{},", trial.value)
+                },
+                self.config.name,
+                self.parameter,
+                trial.value,
+                trial.run.rate,
+                format(trial.run.achieved_rate()),
+                format(trial.run.avg_rate),
+                trial.run.completed,
+                trial.run.failed,
+                format(trial.run.percentile(50.)),
+                format(trial.run.percentile(90.)),
+                format(trial.run.percentile(99.))
+            )?;
+        }
+        out.flush()
+    }
+
+    /// The throughput-latency curve of the trials, see `curve_chart`.
+    pub fn curve_chart(&self, trials: &[Trial]) -> Chart {
+        curve_chart(
+            self.parameter,
+            &self.config.name,
+            trials,
+            knee(self.parameter, trials, self.max_p99),
+        )
+    }
+
+    /// The achieved and the target rates, and p99 on the right axis, over the values.
+    /// The X-axis is log2, as the values usually double, e.g. 1,2,4,8.
+    pub fn rate_chart(&self, trials: &[Trial]) -> Chart {
+        let x: Vec<f64> = trials
+            .iter()
+            .map(|trial| (trial.value as f64).log2())
+            .collect();
+        let x_ticks = trials
+            .iter()
+            .zip(&x)
+            .map(|(trial, x)| (*x, trial.value.to_string()))
+            .collect();
+        let (rate_x, rate): (Vec<f64>, Vec<f64>) = trials
+            .iter()
+            .zip(&x)
+            .filter_map(|(trial, x)| trial.run.achieved_rate().map(|rate| (*x, rate)))
+            .unzip();
+        let (p99_x, p99) = trials
+            .iter()
+            .zip(&x)
+            .filter_map(|(trial, x)| trial.run.percentile(99.).map(|p99| (*x, p99)))
+            .unzip();
+        Chart::new(self.file_name("png"), self.label, "Rate, rps")
+            .x_ticks(x_ticks)
+            .line(
+                Some("target rate"),
+                x,
+                trials.iter().map(|trial| trial.run.rate as f64).collect(),
+            )
+            .line(Some("achieved rate"), rate_x, rate)
+            .line_y2("p99", "Latency, ms", p99_x, p99)
+    }
+}
+
+/// How the swept values grow, e.g. `250` or `x2`.
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Add(usize),
+    Multiply(f64),
+}
+
+impl Step {
+    /// `from`, `from` + step, etc. up to `to`, each one at least one more than the previous one.
+    pub fn values(self, from: usize, to: usize) -> Vec<usize> {
+        let mut values = vec![];
+        let mut value = from;
+        while value <= to {
+            values.push(value);
+            value = match self {
+                Step::Add(step) => value + step,
+                Step::Multiply(factor) => ((value as f64 * factor).round() as usize).max(value + 1),
+            };
+        }
+        values
+    }
+}
+
+/// Whether the run is past the saturation point, i.e. with p99 above `max_p99` (ms), if any,
+/// or the achieved rate below `MIN_ACHIEVED` of the target one.
+fn saturated(run: &Run, max_p99: Option<u64>) -> bool {
+    // of the target rate
+    const MIN_ACHIEVED: f64 = 0.95;
+    matches!((run.percentile(99.), max_p99), (Some(p99), Some(max)) if p99 > max as f64)
+        || run.achieved_rate().unwrap_or_default() < MIN_ACHIEVED * run.rate as f64
+}
+
+/// The knee of a sweep of the rate is the first trial past the saturation point, if any,
+/// the trials are sorted by their rates. The one of a sweep of the capacity, e.g. of the threads,
+/// is the first trial that keeps up with the rate, i.e. the least capacity that does.
+pub fn knee<'a>(parameter: &str, trials: &'a [Trial], max_p99: Option<u64>) -> Option<&'a Trial> {
+    trials
+        .iter()
+        .find(|trial| saturated(&trial.run, max_p99) == (parameter == "rate"))
+}
+
+/// E.g. `sweep_threads_curve_{name}.png`.
+fn curve_file_name(parameter: &str, name: &str) -> String {
+    format!("sweep_{}_curve_{}.png", parameter, name)
+}
+
+/// The closed throughput-latency curve: p99 over the achieved rate, a point per trial
+/// connected in the order of the sweep, and the knee, if any, annotated with its value.
+pub fn curve_chart(parameter: &str, name: &str, trials: &[Trial], knee: Option<&Trial>) -> Chart {
+    let point = |trial: &Trial| Some((trial.run.achieved_rate()?, trial.run.percentile(99.)?));
+    let (x, y): (Vec<f64>, Vec<f64>) = trials.iter().filter_map(point).unzip();
+    let mut chart = Chart::new(
+        curve_file_name(parameter, name),
+        "Achieved rate, rps",
+        "p99 latency, ms",
+    )
+    .line(Some("p99"), x.clone(), y.clone())
+    .points(None, x, y);
+    if let Some((knee, (x, y))) = knee.and_then(|knee| Some((knee, point(knee)?))) {
+        chart = chart.points(Some("knee"), vec![x], vec![y]).annotate(
+            x,
+            y,
+            format!("{}={}", parameter, knee.value),
+        );
+    }
+    chart
+}
+
+/// Charts the curve of a sweep again from its trials saved in `dir`, e.g. `sweep_threads_{name}/`,
+/// to `sweep_{parameter}_curve_{name}.png`. Returns the number of the trials.
+pub fn plot(dir: &Path, figures: &Figures) -> io::Result<usize> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut saved = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            let trial: SavedTrial =
+                serde_json::from_str(&fs::read_to_string(&path)?).map_err(invalid)?;
+            saved.push(trial);
+        }
+    }
+    let first = saved.first().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no trials, i.e. {trial}.json files",
+        )
+    })?;
+    let (name, parameter, max_p99) = (first.name.clone(), first.parameter.clone(), first.max_p99);
+    if let Some(other) = saved
+        .iter()
+        .find(|trial| trial.name != name || trial.parameter != parameter)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the trials of the sweeps of {} by {} and of {} by {} are mixed",
+                name, parameter, other.name, other.parameter
+            ),
+        ));
+    }
+    saved.sort_by_key(|trial| trial.index);
+    let trials: Vec<Trial> = saved
+        .into_iter()
+        .map(|trial| Trial {
+            value: trial.value,
+            run: trial.run,
+        })
+        .collect();
+
+    let knee = knee(&parameter, &trials, max_p99);
+    match knee {
+        Some(knee) => info!(
+            "The knee: {}={}, achieved {:.3} rps, p99 {:.3} ms",
+            parameter,
+            knee.value,
+            knee.run.achieved_rate().unwrap_or_default(),
+            knee.run.percentile(99.).unwrap_or_default()
+        ),
+        None => info!("No knee among the {} trials", trials.len()),
+    }
+    figures.render(&curve_chart(&parameter, &name, &trials, knee));
+    Ok(trials.len())
+}
+
+/// The throughput-latency curve: p50 and p99 over the achieved rate of every trial,
+/// and the knee, if any, as a vertical line.
+pub fn latency_chart(sweep: &Sweep, trials: &[Trial], knee: Option<&Trial>) -> Chart {
+    let points = |p: f64| -> (Vec<f64>, Vec<f64>) {
+        trials
+            .iter()
+            .filter_map(|trial| {
+                let latency = trial.run.percentile(p)?;
+                Some((trial.run.achieved_rate()?, latency))
+            })
+            .unzip()
+    };
+    let (p50_x, p50) = points(50.);
+    let (p99_x, p99) = points(99.);
+    let max = p99.iter().cloned().fold(0., f64::max);
+    let mut chart = Chart::new(sweep.file_name("png"), "Achieved rate, rps", "Latency, ms")
+        .line(Some("p50"), p50_x, p50)
+        .line(Some("p99"), p99_x, p99);
+    if let Some(rate) = knee.and_then(|knee| knee.run.achieved_rate()) {
+        chart = chart.line(Some("knee"), vec![rate, rate], vec![0., max]);
+    }
+    chart
+}
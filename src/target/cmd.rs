@@ -0,0 +1,105 @@
+use super::Served;
+use std::io::{self, Read};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+use tokio::sync::Semaphore;
+
+// stderr of a command is kept up to this many bytes for the error message, the rest is discarded
+const MAX_STDERR: u64 = 4096;
+
+/// The command of `--target cmd:...`, which is run per request. The request is described
+/// by the environment: `CDB_TASK` is its index, `CDB_COST_MS` is its latency (ms).
+/// stdout is discarded, a non-zero exit status fails the request.
+#[derive(Clone)]
+pub struct CommandTarget {
+    /// The program and its arguments.
+    command: Arc<Vec<String>>,
+    /// Limits the running processes of the async mode, the sync one is limited by the threads.
+    processes: Arc<Semaphore>,
+}
+
+impl CommandTarget {
+    pub fn new(command: &[String], max_processes: usize) -> Self {
+        Self {
+            command: Arc::new(command.to_vec()),
+            processes: Arc::new(Semaphore::new(max_processes)),
+        }
+    }
+
+    /// Blocks until the command exits. The setup is the spawn, the service time is the rest.
+    pub fn run(&self, task: usize, cost: u64) -> io::Result<Served> {
+        let start = Instant::now();
+        let mut child = std::process::Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .env("CDB_TASK", task.to_string())
+            .env("CDB_COST_MS", cost.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let spawned = Instant::now();
+        let mut stderr = vec![];
+        // the child is waited for in any case, so it's never left a zombie
+        let captured = match child.stderr.take() {
+            Some(mut pipe) => pipe
+                .by_ref()
+                .take(MAX_STDERR)
+                .read_to_end(&mut stderr)
+                .and_then(|_| io::copy(&mut pipe, &mut io::sink())),
+            None => Ok(0),
+        };
+        let status = child.wait()?;
+        captured?;
+        self.check(status, &stderr)?;
+        Ok(Served {
+            service_time: spawned.elapsed(),
+            setup: Some(spawned - start),
+        })
+    }
+
+    /// Waits for a permit to spawn the command, the wait is a part of the overhead.
+    pub async fn run_async(&self, task: usize, cost: u64) -> io::Result<Served> {
+        let _permit = self.processes.acquire().await;
+        let start = Instant::now();
+        let mut child = tokio::process::Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .env("CDB_TASK", task.to_string())
+            .env("CDB_COST_MS", cost.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let spawned = Instant::now();
+        let mut stderr = vec![];
+        let captured = match child.stderr.take() {
+            Some(mut pipe) => match (&mut pipe).take(MAX_STDERR).read_to_end(&mut stderr).await {
+                Ok(_) => tokio::io::copy(&mut pipe, &mut tokio::io::sink()).await,
+                Err(e) => Err(e),
+            },
+            None => Ok(0),
+        };
+        let status = child.await?;
+        captured?;
+        self.check(status, &stderr)?;
+        Ok(Served {
+            service_time: spawned.elapsed(),
+            setup: Some(spawned - start),
+        })
+    }
+
+    fn check(&self, status: ExitStatus, stderr: &[u8]) -> io::Result<()> {
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "`{}` exited with {}: {}",
+                self.command.join(" "),
+                status,
+                String::from_utf8_lossy(stderr).trim()
+            )))
+        }
+    }
+}
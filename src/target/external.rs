@@ -0,0 +1,123 @@
+use super::http::{self, Exchange};
+use super::Served;
+use hyper::Uri;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// `GET` of the URL of `--url`, an HTTP service outside of the process. The latencies of the model
+/// aren't sent, the service takes as long as it takes. The service time is the one the service
+/// reports by `Server-Timing`, if it does, otherwise the whole exchange but the connect.
+#[derive(Clone)]
+pub struct ExternalClient {
+    client: http::AsyncClient,
+    uri: Uri,
+    timeout: Duration,
+    /// The statuses of a successful request, as inclusive ranges.
+    success: Arc<Vec<(u16, u16)>>,
+}
+
+impl ExternalClient {
+    /// Resolves the host once, all the connections are established to the first address.
+    pub fn new(
+        url: &str,
+        keepalive: bool,
+        success: &[(u16, u16)],
+        timeout: Duration,
+    ) -> io::Result<Self> {
+        let uri: Uri = url.parse().expect("URL is validated");
+        let host = uri.host().expect("URL is validated");
+        let addr = (host, uri.port_u16().unwrap_or(80))
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} resolves to no address", host),
+                )
+            })?;
+        info!("Sending the requests to {} at {}", uri, addr);
+        Ok(Self {
+            client: http::AsyncClient::new(addr, keepalive, None),
+            uri,
+            timeout,
+            success: Arc::new(success.to_vec()),
+        })
+    }
+
+    /// A blocking client, its timeout limits the connect and every read and write.
+    pub fn sync_client(&self) -> SyncClient {
+        SyncClient {
+            client: http::SyncClient::external(
+                self.client.addr(),
+                self.uri.authority().expect("URL is validated").to_string(),
+                self.client.keepalive(),
+                self.timeout,
+            ),
+            path: self
+                .uri
+                .path_and_query()
+                .map_or_else(|| "/".to_string(), |path| path.to_string()),
+            timeout: self.timeout,
+            success: self.success.clone(),
+        }
+    }
+
+    pub async fn call(&self) -> io::Result<Served> {
+        let start = Instant::now();
+        let exchange = tokio::time::timeout(self.timeout, self.client.send(self.uri.clone()))
+            .await
+            .map_err(|_| timed_out(self.timeout))??;
+        served(&self.success, exchange, start.elapsed())
+    }
+}
+
+/// A client of a sync worker, which keeps a connection of its own with keep-alive.
+pub struct SyncClient {
+    client: http::SyncClient,
+    /// With the query, if any.
+    path: String,
+    timeout: Duration,
+    success: Arc<Vec<(u16, u16)>>,
+}
+
+impl SyncClient {
+    pub fn call(&mut self) -> io::Result<Served> {
+        let start = Instant::now();
+        let exchange = self.client.send(&self.path).map_err(|e| {
+            if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut {
+                timed_out(self.timeout)
+            } else {
+                e
+            }
+        })?;
+        served(&self.success, exchange, start.elapsed())
+    }
+}
+
+fn served(success: &[(u16, u16)], exchange: Exchange, elapsed: Duration) -> io::Result<Served> {
+    if !success
+        .iter()
+        .any(|(from, to)| (*from..=*to).contains(&exchange.status))
+    {
+        return Err(io::Error::other(format!(
+            "Unexpected status {}",
+            exchange.status
+        )));
+    }
+    Ok(Served {
+        service_time: exchange
+            .server_timing
+            .unwrap_or_else(|| elapsed - exchange.connect.unwrap_or_default()),
+        setup: exchange.connect,
+    })
+}
+
+fn timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("No response within {} ms", timeout.as_millis()),
+    )
+}
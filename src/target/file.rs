@@ -0,0 +1,111 @@
+use super::Served;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// The work of `--target file`: every request writes a file of the configured size,
+/// reads it back and removes it. The files of a run are kept in a directory of its own.
+#[derive(Clone)]
+pub struct FileTarget {
+    dir: Arc<PathBuf>,
+    /// The content of every file.
+    data: Arc<Vec<u8>>,
+    /// Every file is flushed to the disk before it's read back.
+    fsync: bool,
+}
+
+impl FileTarget {
+    /// Creates the directory of the run within `parent`, e.g. `/tmp/cdb_{name}_{pid}`.
+    pub fn start(parent: &Path, name: &str, size: usize, fsync: bool) -> io::Result<Self> {
+        let dir = parent.join(format!("cdb_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        info!(
+            "Writing files of {} bytes to {}{}",
+            size,
+            dir.display(),
+            if fsync { " with fsync" } else { "" }
+        );
+        Ok(Self {
+            dir: Arc::new(dir),
+            // not all zeros, in case the file system compresses them
+            data: Arc::new((0..size).map(|i| (i % 251) as u8).collect()),
+            fsync,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes the directory of the run with everything left in it, e.g. by failed requests.
+    pub fn stop(self) {
+        if let Err(e) = fs::remove_dir_all(self.dir.as_path()) {
+            warn!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+
+    fn path(&self, task: usize) -> PathBuf {
+        self.dir.join(format!("{}.bin", task))
+    }
+
+    /// Blocks on the file system calls.
+    pub fn run(&self, task: usize) -> io::Result<Served> {
+        let path = self.path(task);
+        let start = Instant::now();
+        {
+            let mut file = File::create(&path)?;
+            file.write_all(&self.data)?;
+            if self.fsync {
+                file.sync_all()?;
+            }
+        }
+        let read = fs::read(&path)?;
+        fs::remove_file(&path)?;
+        self.check(&read)?;
+        Ok(Served {
+            service_time: start.elapsed(),
+            setup: None,
+        })
+    }
+
+    /// The same, but with `tokio::fs`, i.e. every call is a blocking one on the blocking pool.
+    pub async fn run_async(&self, task: usize) -> io::Result<Served> {
+        let path = self.path(task);
+        let start = Instant::now();
+        {
+            let mut file = tokio::fs::File::create(&path).await?;
+            file.write_all(&self.data).await?;
+            // the writes are completed in the background, otherwise
+            file.flush().await?;
+            if self.fsync {
+                file.sync_all().await?;
+            }
+        }
+        let read = tokio::fs::read(&path).await?;
+        tokio::fs::remove_file(&path).await?;
+        self.check(&read)?;
+        Ok(Served {
+            service_time: start.elapsed(),
+            setup: None,
+        })
+    }
+
+    fn check(&self, read: &[u8]) -> io::Result<()> {
+        if read == self.data.as_slice() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Read back {} bytes, which differ from the written {}",
+                    read.len(),
+                    self.data.len()
+                ),
+            ))
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use super::tcp::{self, accept, SyncStream};
+use super::tls::{self, ClientConfig, TlsAcceptor, SERVER_NAME};
+use super::Served;
+use hyper::client::conn;
+use hyper::client::connect::dns::Name;
+use hyper::client::HttpConnector;
+use hyper::server::conn::Http;
+use hyper::service::{make_service_fn, service_fn, Service};
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use hyper_rustls::HttpsConnector;
+use std::convert::Infallible;
+use std::future::{self, Ready};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter::{self, Once};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::stream::StreamExt;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::delay_for;
+use tracing::{debug, info};
+
+// the server reports the time it has spent on a request, e.g. `Server-Timing: app;dur=20.104` (ms)
+const SERVER_TIMING: &str = "Server-Timing";
+
+/// An in-process HTTP/1.1 server of `--target http`: `GET /{cost}` sleeps `cost` ms and returns 200.
+/// With `--tls`, it's HTTPS.
+pub struct HttpServer {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    server: JoinHandle<hyper::Result<()>>,
+}
+
+impl HttpServer {
+    /// Listens on an ephemeral port of the loopback interface, so concurrent runs don't clash.
+    pub async fn start(tls: Option<TlsAcceptor>) -> io::Result<Self> {
+        if let Some(acceptor) = tls {
+            return Self::start_tls(acceptor).await;
+        }
+        let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+        let server = hyper::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .map_err(io::Error::other)?
+            .tcp_nodelay(true)
+            .serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(server.with_graceful_shutdown(async {
+            // a dropped sender stops it as well
+            let _ = stopped.await;
+        }));
+        info!("The HTTP server is listening on {}", addr);
+        Ok(Self {
+            addr,
+            shutdown,
+            server,
+        })
+    }
+
+    /// hyper's server has no TLS, so the connections are accepted and served one by one.
+    /// Unlike the plain one, it doesn't wait for the open connections on stop.
+    async fn start_tls(acceptor: TlsAcceptor) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel();
+        let connections = listener.map(|accepted| {
+            let stream = accepted?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        });
+        let server = tokio::spawn(async move {
+            accept(connections, stopped, |stream| {
+                tls::serve(&acceptor, stream, |stream| async {
+                    if let Err(e) = Http::new()
+                        .http1_only(true)
+                        .serve_connection(stream, service_fn(handle))
+                        .await
+                    {
+                        debug!("An HTTPS connection failed: {}", e);
+                    }
+                })
+            })
+            .await;
+            Ok(())
+        });
+        info!("The HTTPS server is listening on {}", addr);
+        Ok(Self {
+            addr,
+            shutdown,
+            server,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops accepting, and waits for the open connections to close.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        match self.server.await.expect("The HTTP server failed") {
+            Ok(()) => info!("The HTTP server on {} is stopped", self.addr),
+            Err(e) => debug!("The HTTP server on {} is stopped: {}", self.addr, e),
+        }
+    }
+}
+
+async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let cost = match request.uri().path().trim_start_matches('/').parse() {
+        Ok(cost) => cost,
+        Err(_) => {
+            let mut response = Response::new(Body::from("Expected GET /{cost in ms}"));
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return Ok(response);
+        }
+    };
+    let start = Instant::now();
+    delay_for(Duration::from_millis(cost)).await;
+    let mut response = Response::new(Body::empty());
+    response.headers_mut().insert(
+        SERVER_TIMING,
+        format!("app;dur={:.3}", start.elapsed().as_secs_f64() * 1000.)
+            .parse()
+            .expect("A valid header value"),
+    );
+    Ok(response)
+}
+
+/// `app;dur=20.104` -> 20.104 ms.
+fn parse_server_timing(value: &str) -> Option<Duration> {
+    let ms: f64 = value.split(';').find_map(|param| {
+        param
+            .trim()
+            .strip_prefix("dur=")
+            .and_then(|ms| ms.parse().ok())
+    })?;
+    Some(Duration::from_secs_f64(ms / 1000.)).filter(|_| ms >= 0.)
+}
+
+/// A response of a failed request is an error as well.
+fn check_status(status: u16) -> io::Result<()> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("The status is {}", status)))
+    }
+}
+
+fn missing_server_timing() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("No valid {} header", SERVER_TIMING),
+    )
+}
+
+/// What a request has got back.
+pub struct Exchange {
+    pub status: u16,
+    /// The time the server has spent on the request, if it has reported it.
+    pub server_timing: Option<Duration>,
+    /// The time of establishing a connection, if the request had to.
+    pub connect: Option<Duration>,
+}
+
+impl Exchange {
+    /// A request to the built-in server must succeed and report its time.
+    fn served(self) -> io::Result<Served> {
+        check_status(self.status)?;
+        Ok(Served {
+            service_time: self.server_timing.ok_or_else(missing_server_timing)?,
+            setup: self.connect,
+        })
+    }
+}
+
+/// A blocking HTTP/1.1 client of a sync worker over a raw socket. With keep-alive,
+/// the connection is reused by the requests of the worker, and re-established after a failure.
+/// The setup is the connect and the TLS handshake, if any.
+pub struct SyncClient {
+    addr: SocketAddr,
+    /// The `Host` header.
+    host: String,
+    keepalive: bool,
+    tls: Option<Arc<ClientConfig>>,
+    /// Of the connect and of every read and write, if any.
+    timeout: Option<Duration>,
+    stream: Option<BufReader<Box<dyn SyncStream>>>,
+}
+
+impl SyncClient {
+    pub fn new(addr: SocketAddr, keepalive: bool, tls: Option<Arc<ClientConfig>>) -> Self {
+        Self {
+            addr,
+            host: addr.to_string(),
+            keepalive,
+            tls,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    /// A client of another server than the built-in one, e.g. `host` is `example.com:8080`.
+    pub fn external(addr: SocketAddr, host: String, keepalive: bool, timeout: Duration) -> Self {
+        Self {
+            addr,
+            host,
+            keepalive,
+            tls: None,
+            timeout: Some(timeout),
+            stream: None,
+        }
+    }
+
+    /// A request of `cost` ms to the built-in server.
+    pub fn call(&mut self, cost: u64) -> io::Result<Served> {
+        self.send(&format!("/{}", cost))?.served()
+    }
+
+    /// `GET path`, the body of the response is read out and discarded.
+    pub fn send(&mut self, path: &str) -> io::Result<Exchange> {
+        let result = self.round_trip(path);
+        match &result {
+            Ok((_, true)) if self.keepalive => {}
+            // the state of the stream is unknown after a failure
+            _ => self.stream = None,
+        }
+        result.map(|(exchange, _)| exchange)
+    }
+
+    /// Also returns whether the connection can be reused.
+    fn round_trip(&mut self, path: &str) -> io::Result<(Exchange, bool)> {
+        let mut connect = None;
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let start = Instant::now();
+                let stream = tcp::connect(self.addr, self.tls.as_ref(), self.timeout)?;
+                connect = Some(start.elapsed());
+                self.stream.get_or_insert(BufReader::new(stream))
+            }
+        };
+        write!(
+            stream.get_mut(),
+            "GET {} HTTP/1.1\r\nHost: {}\r\n{}\r\n",
+            path,
+            self.host,
+            if self.keepalive {
+                ""
+            } else {
+                "Connection: close\r\n"
+            }
+        )?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line)?;
+        // e.g. `HTTP/1.1 200 OK`
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unexpected status line {:?}", line),
+                )
+            })?;
+        let mut server_timing = None;
+        let mut content_length = None;
+        let mut chunked = false;
+        // HTTP/1.0 closes the connection by default
+        let mut reusable = line.starts_with("HTTP/1.1");
+        loop {
+            line.clear();
+            if stream.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case(SERVER_TIMING) {
+                    server_timing = parse_server_timing(value);
+                } else if name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = value.parse().ok();
+                } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                } else if name.eq_ignore_ascii_case("Connection") {
+                    if value.eq_ignore_ascii_case("close") {
+                        reusable = false;
+                    } else if value.eq_ignore_ascii_case("keep-alive") {
+                        reusable = true;
+                    }
+                }
+            }
+        }
+        // the body is read out, so the connection can be reused
+        if status == 204 || status == 304 || status < 200 {
+            // no body
+        } else if chunked {
+            read_chunks(stream)?;
+        } else if let Some(content_length) = content_length {
+            io::copy(&mut stream.by_ref().take(content_length), &mut io::sink())?;
+        } else {
+            // the body ends with the connection
+            io::copy(stream, &mut io::sink())?;
+            reusable = false;
+        }
+
+        Ok((
+            Exchange {
+                status,
+                server_timing,
+                connect,
+            },
+            reusable,
+        ))
+    }
+}
+
+/// Reads out a body of `Transfer-Encoding: chunked`, the trailers included.
+fn read_chunks<R: BufRead>(stream: &mut R) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line)?;
+        // e.g. `1a;name=value`
+        let size = line
+            .split(';')
+            .next()
+            .and_then(|size| u64::from_str_radix(size.trim(), 16).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unexpected chunk size {:?}", line),
+                )
+            })?;
+        if size == 0 {
+            break;
+        }
+        // and the CRLF after the chunk
+        io::copy(&mut stream.by_ref().take(size + 2), &mut io::sink())?;
+    }
+    loop {
+        line.clear();
+        if stream.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// A client of the async tasks. With keep-alive, the connections are pooled by hyper's client,
+/// otherwise every request establishes a connection of its own.
+#[derive(Clone)]
+pub struct AsyncClient {
+    addr: SocketAddr,
+    keepalive: bool,
+    tls: Option<Arc<ClientConfig>>,
+    pool: Client<HttpsConnector<HttpConnector<Resolved>>>,
+}
+
+impl AsyncClient {
+    /// Every name of the requests' URIs is resolved to the IP of `addr`.
+    pub fn new(addr: SocketAddr, keepalive: bool, tls: Option<Arc<ClientConfig>>) -> Self {
+        let mut connector = HttpConnector::new_with_resolver(Resolved(addr.ip()));
+        connector.set_nodelay(true);
+        connector.enforce_http(false);
+        // the config is used only by `https` requests
+        let config = tls.clone().unwrap_or_else(|| Arc::new(ClientConfig::new()));
+        Self {
+            addr,
+            keepalive,
+            tls,
+            pool: Client::builder().build(HttpsConnector::from((connector, config))),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn keepalive(&self) -> bool {
+        self.keepalive
+    }
+
+    /// A blocking client to the same server.
+    pub fn sync_client(&self) -> SyncClient {
+        SyncClient::new(self.addr, self.keepalive, self.tls.clone())
+    }
+
+    /// A request of `cost` ms to the built-in server.
+    pub async fn call(&self, cost: u64) -> io::Result<Served> {
+        // the certificate is issued to a name, which resolves to the server
+        let uri: Uri = format!(
+            "{}://{}:{}/{}",
+            if self.tls.is_some() { "https" } else { "http" },
+            SERVER_NAME,
+            self.addr.port(),
+            cost
+        )
+        .parse()
+        .expect("A valid URI");
+        self.send(uri).await?.served()
+    }
+
+    /// `GET uri`, the body of the response is read out and discarded.
+    /// The pool's connects and TLS handshakes are a part of the latency of the requests,
+    /// which have waited for them, as the pool doesn't tell which ones they are.
+    pub async fn send(&self, uri: Uri) -> io::Result<Exchange> {
+        let (response, connect) = if self.keepalive {
+            (self.pool.get(uri).await.map_err(to_io)?, None)
+        } else {
+            let host = uri
+                .authority()
+                .map(|authority| authority.to_string())
+                .unwrap_or_else(|| self.addr.to_string());
+            let path = uri
+                .path_and_query()
+                .map(|path| path.as_str())
+                .unwrap_or("/");
+            let request = Request::get(path)
+                .header("Host", host)
+                .header("Connection", "close")
+                .body(Body::empty())
+                .expect("A valid request");
+            let start = Instant::now();
+            let stream = TcpStream::connect(self.addr).await?;
+            stream.set_nodelay(true)?;
+            match &self.tls {
+                Some(config) => {
+                    let stream = tls::handshake(config, stream).await?;
+                    let connect = start.elapsed();
+                    (send_once(stream, request).await?, Some(connect))
+                }
+                None => {
+                    let connect = start.elapsed();
+                    (send_once(stream, request).await?, Some(connect))
+                }
+            }
+        };
+        let status = response.status().as_u16();
+        let server_timing = response
+            .headers()
+            .get(SERVER_TIMING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_server_timing);
+        // the body is read out, so the connection can be reused
+        hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(to_io)?;
+        Ok(Exchange {
+            status,
+            server_timing,
+            connect,
+        })
+    }
+}
+
+/// Sends the request over a connection of its own.
+async fn send_once<S>(stream: S, request: Request<Body>) -> io::Result<Response<Body>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = conn::handshake(stream).await.map_err(to_io)?;
+    // it's done once the response is read
+    tokio::spawn(connection);
+    sender.send_request(request).await.map_err(to_io)
+}
+
+/// Resolves any name to the IP, where the server listens.
+#[derive(Clone)]
+pub struct Resolved(IpAddr);
+
+impl Service<Name> for Resolved {
+    type Response = Once<IpAddr>;
+    type Error = io::Error;
+    type Future = Ready<io::Result<Self::Response>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Name) -> Self::Future {
+        future::ready(Ok(iter::once(self.0)))
+    }
+}
+
+fn to_io(e: hyper::Error) -> io::Error {
+    io::Error::other(e)
+}
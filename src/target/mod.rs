@@ -0,0 +1,268 @@
+mod cmd;
+#[cfg(feature = "http")]
+mod external;
+mod file;
+#[cfg(feature = "http")]
+mod http;
+mod tcp;
+#[cfg_attr(not(feature = "http"), path = "tls_disabled.rs")]
+mod tls;
+mod udp;
+#[cfg(unix)]
+mod uds;
+
+use crate::{stats, ModelConfig, Target};
+use std::io;
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use cmd::CommandTarget;
+#[cfg(feature = "http")]
+pub use external::ExternalClient;
+pub use file::FileTarget;
+#[cfg(feature = "http")]
+pub use http::HttpServer;
+pub use tcp::EchoServer;
+pub use tls::Tls;
+pub use udp::UdpServer;
+#[cfg(unix)]
+pub use uds::UdsServer;
+
+/// What a request to a target has taken.
+pub struct Served {
+    /// The time the server has spent on the request, as reported by it.
+    pub service_time: Duration,
+    /// The time of establishing a connection or spawning a process, if the request had to.
+    pub setup: Option<Duration>,
+}
+
+/// What runs in-process along the model for `--target`, e.g. a built-in server.
+pub enum Server {
+    Tcp {
+        server: EchoServer,
+        tls: Option<Tls>,
+    },
+    #[cfg(feature = "http")]
+    Http {
+        server: HttpServer,
+        keepalive: bool,
+        tls: Option<Tls>,
+    },
+    #[cfg(unix)]
+    Uds(UdsServer),
+    Udp(UdpServer),
+    File(FileTarget),
+}
+
+impl Server {
+    /// `None` if the target has no server, e.g. the work is a sleep.
+    pub async fn start(config: &ModelConfig) -> io::Result<Option<Self>> {
+        Ok(match &config.target {
+            Target::Sleep | Target::Cmd { .. } | Target::Url { .. } => None,
+            Target::Tcp { tls } => {
+                let tls = if *tls {
+                    Some(Tls::self_signed()?)
+                } else {
+                    None
+                };
+                Some(Server::Tcp {
+                    server: EchoServer::start(tls.as_ref().map(|tls| tls.acceptor.clone())).await?,
+                    tls,
+                })
+            }
+            #[cfg(feature = "http")]
+            Target::Http { keepalive, tls } => {
+                let tls = if *tls {
+                    Some(Tls::self_signed()?)
+                } else {
+                    None
+                };
+                Some(Server::Http {
+                    server: HttpServer::start(tls.as_ref().map(|tls| tls.acceptor.clone())).await?,
+                    keepalive: *keepalive,
+                    tls,
+                })
+            }
+            Target::File { dir, size, fsync } => Some(Server::File(FileTarget::start(
+                Path::new(dir),
+                // tells the files of the run apart
+                &config.name,
+                *size,
+                *fsync,
+            )?)),
+            Target::Udp { drop } => Some(Server::Udp(
+                UdpServer::start(
+                    *drop,
+                    stats::rng(config.seed, "drop"),
+                    Duration::from_millis(config.timeout),
+                )
+                .await?,
+            )),
+            #[cfg(unix)]
+            Target::Uds { socket } => Some(Server::Uds(UdsServer::start(Path::new(socket)).await?)),
+            // it's rejected by the validation of the config
+            #[cfg(not(feature = "http"))]
+            Target::Http { .. } => {
+                return Err(io::Error::other(
+                    "the binary is built without the `http` feature",
+                ))
+            }
+            // it's rejected by the validation of the config
+            #[cfg(not(unix))]
+            Target::Uds { .. } => {
+                return Err(io::Error::other(
+                    "Unix domain sockets are not supported on this platform",
+                ))
+            }
+        })
+    }
+
+    /// The endpoint is cloned by the workers or the tasks.
+    pub fn endpoint(&self) -> Endpoint {
+        match self {
+            Server::Tcp { server, tls } => Endpoint::Tcp(tcp::AsyncClient::new(
+                server.addr(),
+                tls.as_ref().map(|tls| tls.client.clone()),
+            )),
+            #[cfg(feature = "http")]
+            Server::Http {
+                server,
+                keepalive,
+                tls,
+            } => Endpoint::Http(http::AsyncClient::new(
+                server.addr(),
+                *keepalive,
+                tls.as_ref().map(|tls| tls.client.clone()),
+            )),
+            #[cfg(unix)]
+            Server::Uds(server) => Endpoint::Uds(Arc::from(server.path())),
+            Server::Udp(server) => Endpoint::Udp(server.client()),
+            Server::File(files) => Endpoint::File(files.clone()),
+        }
+    }
+
+    /// The clients must be done by then.
+    pub async fn stop(self) {
+        match self {
+            Server::Tcp { server, .. } => server.stop().await,
+            #[cfg(feature = "http")]
+            Server::Http { server, .. } => server.stop().await,
+            #[cfg(unix)]
+            Server::Uds(server) => server.stop().await,
+            Server::Udp(server) => server.stop().await,
+            Server::File(files) => files.stop(),
+        }
+    }
+}
+
+/// Where the requests of the run are sent.
+#[derive(Clone)]
+pub enum Endpoint {
+    Tcp(tcp::AsyncClient),
+    #[cfg(feature = "http")]
+    Http(http::AsyncClient),
+    #[cfg(unix)]
+    Uds(Arc<Path>),
+    Udp(udp::AsyncClient),
+    File(FileTarget),
+    Cmd(CommandTarget),
+    #[cfg(feature = "http")]
+    External(ExternalClient),
+}
+
+impl Endpoint {
+    /// `None` if the work is a sleep. The server of the target, if any, must be started.
+    /// Fails if the host of `--url` can't be resolved.
+    pub fn new(config: &ModelConfig, server: Option<&Server>) -> io::Result<Option<Self>> {
+        Ok(match &config.target {
+            Target::Cmd {
+                command,
+                max_processes,
+            } => Some(Endpoint::Cmd(CommandTarget::new(command, *max_processes))),
+            #[cfg(feature = "http")]
+            Target::Url {
+                url,
+                keepalive,
+                success,
+            } => Some(Endpoint::External(ExternalClient::new(
+                url,
+                *keepalive,
+                success,
+                Duration::from_millis(config.timeout),
+            )?)),
+            // it's rejected by the validation of the config
+            #[cfg(not(feature = "http"))]
+            Target::Url { .. } => {
+                return Err(io::Error::other(
+                    "the binary is built without the `http` feature",
+                ))
+            }
+            _ => server.map(Server::endpoint),
+        })
+    }
+
+    /// A client of a sync worker, which keeps a connection of its own.
+    pub fn sync_client(&self) -> SyncClient {
+        match self {
+            Endpoint::Tcp(client) => SyncClient::Tcp(client.sync_client()),
+            #[cfg(feature = "http")]
+            Endpoint::Http(client) => SyncClient::Http(client.sync_client()),
+            #[cfg(unix)]
+            Endpoint::Uds(path) => SyncClient::Uds(uds::SyncClient::new(path.to_path_buf())),
+            Endpoint::Udp(client) => SyncClient::Udp(client.sync_client()),
+            Endpoint::File(files) => SyncClient::File(files.clone()),
+            Endpoint::Cmd(command) => SyncClient::Cmd(command.clone()),
+            #[cfg(feature = "http")]
+            Endpoint::External(client) => SyncClient::External(client.sync_client()),
+        }
+    }
+
+    /// Sends the request of the `task`-th task, of `cost` ms, from an async task.
+    pub async fn call(&self, task: usize, cost: u64) -> io::Result<Served> {
+        match self {
+            Endpoint::Tcp(client) => client.call(cost).await,
+            #[cfg(feature = "http")]
+            Endpoint::Http(client) => client.call(cost).await,
+            #[cfg(unix)]
+            Endpoint::Uds(path) => uds::call(path, cost).await,
+            Endpoint::Udp(client) => client.call(cost).await,
+            Endpoint::File(files) => files.run_async(task).await,
+            Endpoint::Cmd(command) => command.run_async(task, cost).await,
+            #[cfg(feature = "http")]
+            Endpoint::External(client) => client.call().await,
+        }
+    }
+}
+
+pub enum SyncClient {
+    Tcp(tcp::SyncClient),
+    #[cfg(feature = "http")]
+    Http(http::SyncClient),
+    #[cfg(unix)]
+    Uds(uds::SyncClient),
+    Udp(udp::SyncClient),
+    File(FileTarget),
+    Cmd(CommandTarget),
+    #[cfg(feature = "http")]
+    External(external::SyncClient),
+}
+
+impl SyncClient {
+    /// Sends the request of the `task`-th task, of `cost` ms, and blocks until the response.
+    pub fn call(&mut self, task: usize, cost: u64) -> io::Result<Served> {
+        match self {
+            SyncClient::Tcp(client) => client.call(cost),
+            #[cfg(feature = "http")]
+            SyncClient::Http(client) => client.call(cost),
+            #[cfg(unix)]
+            SyncClient::Uds(client) => client.call(cost),
+            SyncClient::Udp(client) => client.call(cost),
+            SyncClient::File(files) => files.run(task),
+            SyncClient::Cmd(command) => command.run(task, cost),
+            #[cfg(feature = "http")]
+            SyncClient::External(client) => client.call(),
+        }
+    }
+}
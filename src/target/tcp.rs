@@ -0,0 +1,265 @@
+use super::tls::{self, ClientConfig, TlsAcceptor};
+use super::Served;
+use std::io::{self, Read, Write};
+use std::net::{self, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::stream::{Stream, StreamExt};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::delay_for;
+use tracing::{info, warn};
+
+// e.g. out of file descriptors, it's retried after a pause instead of spinning
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+
+/// The cost of the task in ms, as a big-endian `u64`.
+type Request = [u8; 8];
+/// The echo of the request, followed by the time the server has spent on it in ns, as a big-endian `u64`.
+/// The client subtracts the latter, so the overhead includes the round trip.
+type Response = [u8; 16];
+
+/// An in-process TCP server of `--target tcp`: for every request it sleeps the requested cost
+/// and echoes the request back. A connection serves requests until the client closes it.
+/// With `--tls`, every connection starts with a TLS handshake.
+pub struct EchoServer {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    acceptor: JoinHandle<()>,
+}
+
+impl EchoServer {
+    /// Listens on an ephemeral port of the loopback interface, so concurrent runs don't clash.
+    pub async fn start(tls: Option<TlsAcceptor>) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel();
+        let connections = listener.map(|accepted| {
+            let stream = accepted?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        });
+        let acceptor = tokio::spawn(accept(connections, stopped, echoes(tls)));
+        info!("The echo server is listening on {}", addr);
+        Ok(Self {
+            addr,
+            shutdown,
+            acceptor,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops accepting and closes the listener. By then the clients are done,
+    /// so the connections are closed and their tasks have exited or are about to.
+    pub async fn stop(self) {
+        // the acceptor only exits on the signal
+        let _ = self.shutdown.send(());
+        self.acceptor.await.expect("The echo server failed");
+        info!("The echo server on {} is stopped", self.addr);
+    }
+}
+
+/// Hands the accepted connections to `serve` until `stopped`, the listener is dropped at the end.
+/// `serve` mustn't block the loop, i.e. it spawns a task per connection.
+pub(super) async fn accept<L, S, F>(mut listener: L, mut stopped: oneshot::Receiver<()>, serve: F)
+where
+    L: Stream<Item = io::Result<S>> + Unpin,
+    F: Fn(S),
+{
+    loop {
+        tokio::select! {
+            _ = &mut stopped => break,
+            accepted = listener.next() => match accepted {
+                Some(Ok(stream)) => serve(stream),
+                Some(Err(e)) => {
+                    warn!("The server failed to accept a connection: {}", e);
+                    delay_for(ACCEPT_BACKOFF).await;
+                }
+                None => break,
+            },
+        }
+    }
+}
+
+/// Spawns `echo` of an accepted connection, after the TLS handshake, if any.
+pub(super) fn echoes<S>(tls: Option<TlsAcceptor>) -> impl Fn(S)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    move |stream| match &tls {
+        Some(acceptor) => tls::serve(acceptor, stream, echo),
+        None => {
+            tokio::spawn(echo(stream));
+        }
+    }
+}
+
+async fn echo<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S) {
+    let mut request: Request = [0; 8];
+    // fails at the end of the stream, once the client has closed the connection
+    while stream.read_exact(&mut request).await.is_ok() {
+        let start = Instant::now();
+        delay_for(Duration::from_millis(u64::from_be_bytes(request))).await;
+        let mut response: Response = [0; 16];
+        response[..8].copy_from_slice(&request);
+        response[8..].copy_from_slice(&(start.elapsed().as_nanos() as u64).to_be_bytes());
+        if stream.write_all(&response).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// A blocking connection, either a plain or a TLS one.
+pub trait SyncStream: Read + Write + Send {}
+
+impl<S: Read + Write + Send> SyncStream for S {}
+
+/// Connects to `addr`, and completes the TLS handshake, if any, so it's a part of the setup.
+/// The `timeout`, if any, limits the connect and every read and write of the stream.
+pub(super) fn connect(
+    addr: SocketAddr,
+    tls: Option<&Arc<ClientConfig>>,
+    timeout: Option<Duration>,
+) -> io::Result<Box<dyn SyncStream>> {
+    let stream = match timeout {
+        Some(timeout) => {
+            let stream = net::TcpStream::connect_timeout(&addr, timeout)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            stream
+        }
+        None => net::TcpStream::connect(addr)?,
+    };
+    stream.set_nodelay(true)?;
+    Ok(match tls {
+        Some(config) => Box::new(tls::client(config, stream)?),
+        None => Box::new(stream),
+    })
+}
+
+/// A blocking client of a sync worker. The connection is reused by the requests of the worker,
+/// and re-established after a failure. The setup is the connect and the TLS handshake, if any.
+pub struct SyncClient {
+    addr: SocketAddr,
+    tls: Option<Arc<ClientConfig>>,
+    stream: Option<Box<dyn SyncStream>>,
+}
+
+impl SyncClient {
+    pub fn new(addr: SocketAddr, tls: Option<Arc<ClientConfig>>) -> Self {
+        Self {
+            addr,
+            tls,
+            stream: None,
+        }
+    }
+
+    /// Sends a request of `cost` ms and waits for the echo.
+    pub fn call(&mut self, cost: u64) -> io::Result<Served> {
+        let result = self.round_trip(cost);
+        if result.is_err() {
+            // the state of the stream is unknown
+            self.stream = None;
+        }
+        result
+    }
+
+    fn round_trip(&mut self, cost: u64) -> io::Result<Served> {
+        let mut setup = None;
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let start = Instant::now();
+                let stream = connect(self.addr, self.tls.as_ref(), None)?;
+                setup = Some(start.elapsed());
+                self.stream.get_or_insert(stream)
+            }
+        };
+        Ok(Served {
+            service_time: round_trip(stream, cost)?,
+            setup,
+        })
+    }
+}
+
+/// A client of the async tasks, which connects per request.
+#[derive(Clone)]
+pub struct AsyncClient {
+    addr: SocketAddr,
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl AsyncClient {
+    pub fn new(addr: SocketAddr, tls: Option<Arc<ClientConfig>>) -> Self {
+        Self { addr, tls }
+    }
+
+    /// A blocking client to the same server.
+    pub fn sync_client(&self) -> SyncClient {
+        SyncClient::new(self.addr, self.tls.clone())
+    }
+
+    /// Connects, sends a request of `cost` ms and waits for the echo.
+    pub async fn call(&self, cost: u64) -> io::Result<Served> {
+        let start = Instant::now();
+        let stream = TcpStream::connect(self.addr).await?;
+        stream.set_nodelay(true)?;
+        // the setup is the connect and the TLS handshake, if any
+        let (service_time, connect) = match &self.tls {
+            Some(config) => {
+                let mut stream = tls::handshake(config, stream).await?;
+                let connect = start.elapsed();
+                (async_round_trip(&mut stream, cost).await?, connect)
+            }
+            None => {
+                let mut stream = stream;
+                let connect = start.elapsed();
+                (async_round_trip(&mut stream, cost).await?, connect)
+            }
+        };
+        Ok(Served {
+            service_time,
+            setup: Some(connect),
+        })
+    }
+}
+
+/// Returns the time the server has spent on the request.
+pub(super) fn round_trip<S: Read + Write>(stream: &mut S, cost: u64) -> io::Result<Duration> {
+    let request = cost.to_be_bytes();
+    stream.write_all(&request)?;
+    let mut response: Response = [0; 16];
+    stream.read_exact(&mut response)?;
+    service_time(&request, &response)
+}
+
+/// Returns the time the server has spent on the request.
+pub(super) async fn async_round_trip<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    cost: u64,
+) -> io::Result<Duration> {
+    let request = cost.to_be_bytes();
+    stream.write_all(&request).await?;
+    let mut response: Response = [0; 16];
+    stream.read_exact(&mut response).await?;
+    service_time(&request, &response)
+}
+
+/// The time the server has spent on the request, if the response is its echo.
+fn service_time(request: &Request, response: &Response) -> io::Result<Duration> {
+    let mut nanos = [0; 8];
+    nanos.copy_from_slice(&response[8..]);
+    if request[..] == response[..8] {
+        Ok(Duration::from_nanos(u64::from_be_bytes(nanos)))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unexpected echo {:?} of {:?}", response, request),
+        ))
+    }
+}
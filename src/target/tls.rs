@@ -0,0 +1,92 @@
+pub use rustls::ClientConfig;
+use rustls::{
+    Certificate, ClientSession, NoClientAuth, PrivateKey, ServerConfig, Session, StreamOwned,
+};
+use std::future::Future;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+pub use tokio_rustls::TlsAcceptor;
+use tokio_rustls::{client, server, TlsConnector};
+use tracing::{debug, info};
+use webpki::DNSNameRef;
+
+/// The certificate is issued to this name, the clients connect to `127.0.0.1` by it.
+pub const SERVER_NAME: &str = "localhost";
+
+/// The TLS of `--tls`: a self-signed certificate generated at startup, which the clients trust.
+#[derive(Clone)]
+pub struct Tls {
+    pub acceptor: TlsAcceptor,
+    pub client: Arc<ClientConfig>,
+}
+
+impl Tls {
+    pub fn self_signed() -> io::Result<Self> {
+        let generated =
+            rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).map_err(other)?;
+        let certificate = Certificate(generated.serialize_der().map_err(other)?);
+
+        let mut server = ServerConfig::new(NoClientAuth::new());
+        server
+            .set_single_cert(
+                vec![certificate.clone()],
+                PrivateKey(generated.serialize_private_key_der()),
+            )
+            .map_err(other)?;
+        let mut client = ClientConfig::new();
+        client.root_store.add(&certificate).map_err(other)?;
+        info!("Generated a self-signed certificate for {}", SERVER_NAME);
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(server)),
+            client: Arc::new(client),
+        })
+    }
+}
+
+/// Completes the TLS handshake of a blocking connection, so it's a part of the setup.
+pub fn client(
+    config: &Arc<ClientConfig>,
+    mut stream: TcpStream,
+) -> io::Result<StreamOwned<ClientSession, TcpStream>> {
+    let mut session = ClientSession::new(config, server_name());
+    while session.is_handshaking() {
+        session.complete_io(&mut stream)?;
+    }
+    Ok(StreamOwned::new(session, stream))
+}
+
+/// Completes the TLS handshake of an async connection.
+pub async fn handshake<S>(config: &Arc<ClientConfig>, stream: S) -> io::Result<client::TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    TlsConnector::from(config.clone())
+        .connect(server_name(), stream)
+        .await
+}
+
+fn server_name() -> DNSNameRef<'static> {
+    DNSNameRef::try_from_ascii_str(SERVER_NAME).expect("A valid DNS name")
+}
+
+fn other<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::other(e)
+}
+
+/// Completes the TLS handshake of an accepted connection and serves it, in a task of its own.
+pub fn serve<S, F, Fut>(acceptor: &TlsAcceptor, stream: S, serve: F)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    F: FnOnce(server::TlsStream<S>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let handshake = acceptor.accept(stream);
+    tokio::spawn(async move {
+        match handshake.await {
+            Ok(stream) => serve(stream).await,
+            Err(e) => debug!("The TLS handshake failed: {}", e),
+        }
+    });
+}
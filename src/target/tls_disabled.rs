@@ -0,0 +1,42 @@
+use std::future::Future;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+// The stand-in of `tls.rs` without the `http` feature: `--tls` is rejected
+// by the validation of the options, so none of the types has a value.
+
+pub enum ClientConfig {}
+
+#[derive(Clone)]
+pub enum TlsAcceptor {}
+
+#[derive(Clone)]
+pub struct Tls {
+    pub acceptor: TlsAcceptor,
+    pub client: Arc<ClientConfig>,
+}
+
+impl Tls {
+    pub fn self_signed() -> io::Result<Self> {
+        Err(io::Error::other(
+            "the binary is built without the `http` feature",
+        ))
+    }
+}
+
+pub fn client(config: &Arc<ClientConfig>, _stream: TcpStream) -> io::Result<TcpStream> {
+    match **config {}
+}
+
+pub async fn handshake<S>(config: &Arc<ClientConfig>, _stream: S) -> io::Result<S> {
+    match **config {}
+}
+
+pub fn serve<S, F, Fut>(acceptor: &TlsAcceptor, _stream: S, _serve: F)
+where
+    F: FnOnce(S) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    match *acceptor {}
+}
@@ -0,0 +1,316 @@
+use super::Served;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::udp::{RecvHalf, SendHalf};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{delay_for, timeout};
+use tracing::{debug, info, warn};
+
+// e.g. a full socket buffer, it's retried after a pause instead of spinning
+const RECV_BACKOFF: Duration = Duration::from_millis(10);
+
+/// The requests waiting for a response by their ids.
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Duration>>>>;
+
+/// The id of the request and its cost in ms, as big-endian `u64`s.
+type Request = [u8; 16];
+/// The request, followed by the time the server has spent on it in ns, as a big-endian `u64`.
+type Response = [u8; 24];
+
+/// An in-process UDP server of `--target udp`: for every datagram it sleeps the requested cost
+/// and echoes it back, unless the datagram is dropped to model a lossy network.
+/// A lost datagram is never answered, so its request times out.
+/// The socket of the async clients lives as long as the server.
+pub struct UdpServer {
+    addr: SocketAddr,
+    client: AsyncClient,
+    shutdown: oneshot::Sender<()>,
+    /// Returns the number of the dropped datagrams.
+    receiver: JoinHandle<u64>,
+    client_shutdown: oneshot::Sender<()>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl UdpServer {
+    /// Listens on an ephemeral port of the loopback interface. `drop` is the share of the datagrams
+    /// to drop, %, chosen by `rng`. The clients wait for a response up to `timeout`.
+    pub async fn start(drop: f64, rng: StdRng, timeout: Duration) -> io::Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let addr = socket.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel();
+        let (recv, send) = socket.split();
+        let receiver = tokio::spawn(serve(recv, send, drop / 100., rng, stopped));
+        let (client_shutdown, client_stopped) = oneshot::channel();
+        let (client, dispatcher) = AsyncClient::start(addr, timeout, client_stopped).await?;
+        info!("The UDP echo server is listening on {}", addr);
+        Ok(Self {
+            addr,
+            client,
+            shutdown,
+            receiver,
+            client_shutdown,
+            dispatcher,
+        })
+    }
+
+    pub fn client(&self) -> AsyncClient {
+        self.client.clone()
+    }
+
+    /// Stops receiving. The responses to the requests in flight may still be sent.
+    pub async fn stop(self) {
+        let _ = self.client_shutdown.send(());
+        let _ = self.shutdown.send(());
+        self.dispatcher.await.expect("The UDP client failed");
+        let dropped = self.receiver.await.expect("The UDP echo server failed");
+        info!(
+            "The UDP echo server on {} is stopped, {} datagrams were dropped",
+            self.addr, dropped
+        );
+    }
+}
+
+async fn serve(
+    mut recv: RecvHalf,
+    send: SendHalf,
+    drop: f64,
+    mut rng: StdRng,
+    mut stopped: oneshot::Receiver<()>,
+) -> u64 {
+    let send = Arc::new(tokio::sync::Mutex::new(send));
+    let mut dropped = 0;
+    // larger than a request, so a larger datagram isn't truncated to one
+    let mut datagram = [0; 64];
+    loop {
+        tokio::select! {
+            _ = &mut stopped => break,
+            received = recv.recv_from(&mut datagram) => match received {
+                Ok((16, peer)) => {
+                    if drop > 0. && rng.gen_bool(drop) {
+                        dropped += 1;
+                        continue;
+                    }
+                    let mut request: Request = [0; 16];
+                    request.copy_from_slice(&datagram[..16]);
+                    tokio::spawn(echo(request, peer, send.clone()));
+                }
+                Ok((len, peer)) => debug!("Unexpected datagram of {} bytes from {}", len, peer),
+                Err(e) => {
+                    warn!("The UDP echo server failed to receive a datagram: {}", e);
+                    delay_for(RECV_BACKOFF).await;
+                }
+            },
+        }
+    }
+    dropped
+}
+
+async fn echo(request: Request, peer: SocketAddr, send: Arc<tokio::sync::Mutex<SendHalf>>) {
+    let start = Instant::now();
+    let mut cost = [0; 8];
+    cost.copy_from_slice(&request[8..]);
+    delay_for(Duration::from_millis(u64::from_be_bytes(cost))).await;
+    let mut response: Response = [0; 24];
+    response[..16].copy_from_slice(&request);
+    response[16..].copy_from_slice(&(start.elapsed().as_nanos() as u64).to_be_bytes());
+    if let Err(e) = send.lock().await.send_to(&response, &peer).await {
+        debug!("Failed to send a datagram to {}: {}", peer, e);
+    }
+}
+
+fn request(id: u64, cost: u64) -> Request {
+    let mut request = [0; 16];
+    request[..8].copy_from_slice(&id.to_be_bytes());
+    request[8..].copy_from_slice(&cost.to_be_bytes());
+    request
+}
+
+/// (id, the time the server has spent on the request).
+fn parse(response: &Response) -> (u64, Duration) {
+    let mut id = [0; 8];
+    id.copy_from_slice(&response[..8]);
+    let mut nanos = [0; 8];
+    nanos.copy_from_slice(&response[16..]);
+    (
+        u64::from_be_bytes(id),
+        Duration::from_nanos(u64::from_be_bytes(nanos)),
+    )
+}
+
+fn timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("No response within {} ms", timeout.as_millis()),
+    )
+}
+
+/// A blocking client of a sync worker with a socket of its own. A response, which comes
+/// after its request has timed out, is skipped by the next request.
+pub struct SyncClient {
+    addr: SocketAddr,
+    timeout: Duration,
+    socket: Option<std::net::UdpSocket>,
+    next_id: u64,
+}
+
+impl SyncClient {
+    pub fn new(addr: SocketAddr, timeout: Duration) -> Self {
+        Self {
+            addr,
+            timeout,
+            socket: None,
+            next_id: 0,
+        }
+    }
+
+    /// Sends a request of `cost` ms and waits for the echo up to the timeout.
+    pub fn call(&mut self, cost: u64) -> io::Result<Served> {
+        let timeout = self.timeout;
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            None => {
+                let socket = std::net::UdpSocket::bind("127.0.0.1:0")?;
+                socket.connect(self.addr)?;
+                self.socket.get_or_insert(socket)
+            }
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let deadline = Instant::now() + timeout;
+        socket.send(&request(id, cost))?;
+        let mut response: Response = [0; 24];
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| *remaining > Duration::from_micros(1))
+                .ok_or_else(|| timed_out(timeout))?;
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv(&mut response) {
+                Ok(24) => {
+                    let (received, service_time) = parse(&response);
+                    // otherwise, it's a late response to a timed out request
+                    if received == id {
+                        return Ok(Served {
+                            service_time,
+                            setup: None,
+                        });
+                    }
+                }
+                Ok(len) => debug!("Unexpected datagram of {} bytes", len),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Err(timed_out(timeout))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A client of the async tasks: they share a socket, and the responses, which may come
+/// in any order, are matched to the requests by their ids.
+#[derive(Clone)]
+pub struct AsyncClient {
+    addr: SocketAddr,
+    timeout: Duration,
+    send: Arc<tokio::sync::Mutex<SendHalf>>,
+    pending: Pending,
+    next_id: Arc<AtomicU64>,
+}
+
+impl AsyncClient {
+    /// Starts receiving the responses until `stopped`.
+    async fn start(
+        addr: SocketAddr,
+        timeout: Duration,
+        stopped: oneshot::Receiver<()>,
+    ) -> io::Result<(Self, JoinHandle<()>)> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        socket.connect(addr).await?;
+        let (recv, send) = socket.split();
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let receiver = tokio::spawn(dispatch(recv, pending.clone(), stopped));
+        Ok((
+            Self {
+                addr,
+                timeout,
+                send: Arc::new(tokio::sync::Mutex::new(send)),
+                pending,
+                next_id: Arc::new(AtomicU64::new(0)),
+            },
+            receiver,
+        ))
+    }
+
+    /// A blocking client to the same server.
+    pub fn sync_client(&self) -> SyncClient {
+        SyncClient::new(self.addr, self.timeout)
+    }
+
+    /// Sends a request of `cost` ms and waits for the echo up to the timeout.
+    pub async fn call(&self, cost: u64) -> io::Result<Served> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (responded, response) = oneshot::channel();
+        self.pending
+            .lock()
+            .expect("Poisoned UDP client lock")
+            .insert(id, responded);
+        if let Err(e) = self.send.lock().await.send(&request(id, cost)).await {
+            self.forget(id);
+            return Err(e);
+        }
+        match timeout(self.timeout, response).await {
+            Ok(Ok(service_time)) => Ok(Served {
+                service_time,
+                setup: None,
+            }),
+            Ok(Err(_)) => Err(io::Error::other("The UDP client is stopped")),
+            Err(_) => {
+                self.forget(id);
+                Err(timed_out(self.timeout))
+            }
+        }
+    }
+
+    /// Its response, if any, is discarded.
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .expect("Poisoned UDP client lock")
+            .remove(&id);
+    }
+}
+
+async fn dispatch(mut recv: RecvHalf, pending: Pending, mut stopped: oneshot::Receiver<()>) {
+    let mut response: Response = [0; 24];
+    loop {
+        tokio::select! {
+            _ = &mut stopped => break,
+            received = recv.recv(&mut response) => match received {
+                Ok(24) => {
+                    let (id, service_time) = parse(&response);
+                    let responded = pending.lock().expect("Poisoned UDP client lock").remove(&id);
+                    // otherwise, the request has timed out
+                    if let Some(responded) = responded {
+                        let _ = responded.send(service_time);
+                    }
+                }
+                Ok(len) => debug!("Unexpected datagram of {} bytes", len),
+                Err(e) => {
+                    warn!("The UDP client failed to receive a datagram: {}", e);
+                    delay_for(RECV_BACKOFF).await;
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::tcp::{accept, async_round_trip, echoes, round_trip};
+use super::Served;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// The echo server of `--target uds`, the same as the TCP one, but over a Unix domain socket.
+pub struct UdsServer {
+    path: PathBuf,
+    shutdown: oneshot::Sender<()>,
+    acceptor: JoinHandle<()>,
+}
+
+impl UdsServer {
+    /// A socket file left by a crashed run is replaced, unless another process listens on it.
+    /// Any other file is never touched.
+    pub async fn start(path: &Path) -> io::Result<Self> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            if net::UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another process", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (shutdown, stopped) = oneshot::channel();
+        let acceptor = tokio::spawn(accept(listener, stopped, echoes(None)));
+        info!("The echo server is listening on {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            shutdown,
+            acceptor,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops accepting, closes the listener and removes the socket file.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        self.acceptor.await.expect("The echo server failed");
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+        info!("The echo server on {} is stopped", self.path.display());
+    }
+}
+
+/// A blocking client of a sync worker. The connection is reused by the requests of the worker,
+/// and re-established after a failure.
+pub struct SyncClient {
+    path: PathBuf,
+    stream: Option<net::UnixStream>,
+}
+
+impl SyncClient {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, stream: None }
+    }
+
+    /// Sends a request of `cost` ms and waits for the echo.
+    pub fn call(&mut self, cost: u64) -> io::Result<Served> {
+        let mut connect = None;
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let start = Instant::now();
+                let stream = net::UnixStream::connect(&self.path)?;
+                connect = Some(start.elapsed());
+                self.stream.get_or_insert(stream)
+            }
+        };
+        let result = round_trip(stream, cost);
+        if result.is_err() {
+            // the state of the stream is unknown
+            self.stream = None;
+        }
+        Ok(Served {
+            service_time: result?,
+            setup: connect,
+        })
+    }
+}
+
+/// Connects, sends a request of `cost` ms and waits for the echo, i.e. a connection per request.
+pub async fn call(path: &Path, cost: u64) -> io::Result<Served> {
+    let start = Instant::now();
+    let mut stream = UnixStream::connect(path).await?;
+    let connect = start.elapsed();
+    Ok(Served {
+        service_time: async_round_trip(&mut stream, cost).await?,
+        setup: Some(connect),
+    })
+}